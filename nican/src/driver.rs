@@ -30,6 +30,10 @@ pub struct NiCan {
 unsafe impl Send for NiCan {}
 unsafe impl Sync for NiCan {}
 
+/// The [`NiCan`] which runs on the blocking threads of tokio.
+#[cfg(feature = "async")]
+pub type AsyncNiCan = rs_can::BlockingAdapter<NiCan>;
+
 impl NiCan {
     pub fn new(dll_path: Option<&str>) -> Result<Self, CanError> {
         let dll_path = dll_path.unwrap_or(r"Nican.dll");
@@ -397,7 +401,7 @@ mod tests {
     use crate::CanMessage;
     use std::time::Duration;
     use rs_can::{CanDevice, CanFrame, CanId};
-    // use rs_can::isotp::IsoTpAdapter;
+    use rs_can::isotp::{Address, IsoTp};
 
     #[ignore]   // device required
     #[test]
@@ -431,34 +435,30 @@ mod tests {
         Ok(())
     }
 
-    // #[ignore]   // device required
-    // #[test]
-    // fn isotp() -> anyhow::Result<()> {
-    //     let channel = "CAN0";
-    //     let mut driver = NiCan::new(None)?;
-    //     driver.open(channel, vec![], 500_000, true)?;
-    //
-    //     let mut adapter = IsoTpAdapter::new(driver.clone());
-    //     adapter.start(100);
-    //
-    //     let data = vec![0x02, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00];
-    //     let mut count = 0;
-    //     loop {
-    //         let mut msg = CanMessage::new(CanId::from(0x7DF), data.as_slice()).unwrap();
-    //         msg.set_channel(channel.into());
-    //         adapter.sender().send(msg)?;
-    //
-    //         std::thread::sleep(Duration::from_millis(100));
-    //
-    //         count += 1;
-    //         if count > 10 {
-    //             break;
-    //         }
-    //     }
-    //
-    //     adapter.stop();
-    //     driver.shutdown();
-    //
-    //     Ok(())
-    // }
+    #[ignore]   // device required
+    #[test]
+    fn isotp() -> anyhow::Result<()> {
+        let channel = "CAN0";
+        let mut driver = NiCan::new(None)?;
+        driver.open(channel, vec![], 500_000, true)?;
+
+        let address = Address::new(CanId::from(0x7E0), CanId::from(0x7E8));
+        let mut isotp = IsoTp::new(driver.clone(), channel.into(), address);
+        let mut count = 0;
+        loop {
+            isotp.transmit(&[0x10, 0x01])?;
+            if let Ok(response) = isotp.receive(100) {
+                println!("{:02X?}", response);
+            }
+
+            count += 1;
+            if count > 10 {
+                break;
+            }
+        }
+
+        driver.shutdown();
+
+        Ok(())
+    }
 }
@@ -0,0 +1,195 @@
+use std::{pin::Pin, task::{Context, Poll}, thread, time::{Duration, Instant}};
+use futures_core::Stream;
+use tokio::{sync::mpsc, task};
+use crate::{AsyncCanDevice, CanDevice, CanError, DeviceBuilder};
+
+/// The interval in milliseconds of polling the blocking device.
+const POLL_INTERVAL: u32 = 10;
+/// The count of frames buffered by stream.
+const STREAM_CAPACITY: usize = 1024;
+
+/// The adapter which runs a blocking [`CanDevice`] on the blocking threads of tokio,
+/// so the drivers without non-blocking API(ZLGCAN, NI-CAN and so on) can be used as [`AsyncCanDevice`].
+///
+/// ```no_run
+/// use rs_can::{AsyncCanDevice, BlockingAdapter, vcan::VirtualCan};
+///
+/// # async fn example() -> Result<(), rs_can::CanError> {
+/// let mut device = VirtualCan::new();
+/// device.init_channel("vcan0", None)?;
+///
+/// let device = BlockingAdapter::new(device);
+/// let frames = device.receive("vcan0".into(), Some(100)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BlockingAdapter<D> {
+    device: D,
+}
+
+impl<D> BlockingAdapter<D> {
+    pub fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    /// Get the blocking device.
+    #[inline]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+}
+
+impl<D: CanDevice> TryFrom<DeviceBuilder> for BlockingAdapter<D> {
+    type Error = CanError;
+
+    fn try_from(builder: DeviceBuilder) -> Result<Self, Self::Error> {
+        Ok(Self::new(builder.build()?))
+    }
+}
+
+impl<D> BlockingAdapter<D>
+where
+    D: CanDevice + Send + Sync + 'static,
+    D::Channel: Clone + Send + 'static,
+{
+    async fn blocking_receive(&self, channel: D::Channel, timeout: u32) -> Result<Vec<D::Frame>, CanError> {
+        let device = self.device.clone();
+        match task::spawn_blocking(move || device.receive(channel, Some(timeout))).await {
+            Ok(Ok(frames)) => Ok(frames),
+            Ok(Err(CanError::TimeoutError(_))) => Ok(vec![]),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(CanError::OperationError(e.to_string())),
+        }
+    }
+}
+
+impl<D> AsyncCanDevice for BlockingAdapter<D>
+where
+    D: CanDevice + Send + Sync + 'static,
+    D::Channel: Clone + Send + 'static,
+{
+    type Channel = D::Channel;
+    type Frame = D::Frame;
+    type Stream = BlockingStream<D::Frame>;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::Channel> {
+        self.device.opened_channels()
+    }
+
+    async fn transmit(&self, msg: Self::Frame, timeout: Option<u32>) -> Result<(), CanError> {
+        let device = self.device.clone();
+        task::spawn_blocking(move || device.transmit(msg, timeout))
+            .await
+            .map_err(|e| CanError::OperationError(e.to_string()))?
+    }
+
+    async fn receive(&self, channel: Self::Channel, timeout: Option<u32>) -> Result<Vec<Self::Frame>, CanError> {
+        let start = Instant::now();
+        loop {
+            let remain = match timeout {
+                Some(timeout) => Duration::from_millis(timeout as u64)
+                    .saturating_sub(start.elapsed())
+                    .as_millis() as u32,
+                None => POLL_INTERVAL,
+            };
+            let frames = self.blocking_receive(channel.clone(), remain.min(POLL_INTERVAL)).await?;
+            if !frames.is_empty() {
+                return Ok(frames);
+            }
+            if remain == 0 {
+                return Err(CanError::channel_timeout(channel));
+            }
+        }
+    }
+
+    fn stream(&self, channel: Self::Channel) -> Result<Self::Stream, CanError> {
+        let name = channel.to_string();
+        if !self.device.opened_channels().iter().any(|c| c.to_string() == name) {
+            return Err(CanError::channel_not_opened(name));
+        }
+
+        let (sender, receiver) = mpsc::channel(STREAM_CAPACITY);
+        let device = self.device.clone();
+        thread::spawn(move || {
+            while !sender.is_closed() {
+                match device.receive(channel.clone(), Some(POLL_INTERVAL)) {
+                    Ok(frames) => {
+                        for frame in frames {
+                            if sender.blocking_send(Ok(frame)).is_err() {
+                                return;
+                            }
+                        }
+                    },
+                    Err(CanError::TimeoutError(_)) => {},
+                    Err(e) => {
+                        let _ = sender.blocking_send(Err(e));
+                        return;
+                    },
+                }
+            }
+        });
+
+        Ok(BlockingStream { receiver })
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.device.shutdown()
+    }
+}
+
+/// The stream of frames received by a thread from blocking device,
+/// the thread is stopped after the stream dropped.
+#[derive(Debug)]
+pub struct BlockingStream<F> {
+    receiver: mpsc::Receiver<Result<F, CanError>>,
+}
+
+impl<F> Stream for BlockingStream<F> {
+    type Item = Result<F, CanError>;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future::poll_fn, pin::Pin};
+    use futures_core::Stream;
+    use crate::{AsyncCanDevice, CanError, CanFrame, CanId};
+    use crate::test_utils::{new_device, new_message};
+    use super::BlockingAdapter;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocking_adapter() -> anyhow::Result<()> {
+        let channel = "vbus-blocking";
+        let dev1 = BlockingAdapter::new(new_device(channel)?);
+        let dev2 = BlockingAdapter::new(new_device(channel)?);
+
+        assert!(matches!(dev2.receive(channel.into(), Some(20)).await, Err(CanError::TimeoutError(_))));
+        assert!(dev2.stream("vbus-unknown".into()).is_err());
+
+        let mut stream = dev2.stream(channel.into())?;
+        dev1.transmit(new_message(channel, 0x123, &[0x01, 0x02]), None).await?;
+        let frame = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+            .unwrap()?;
+        assert_eq!((frame.id(), frame.data()), (CanId::Standard(0x123), [0x01, 0x02].as_slice()));
+
+        // the frames of dev2 are consumed by the stream
+        let dev3 = BlockingAdapter::new(new_device(channel)?);
+        dev1.transmit(new_message(channel, 0x456, &[0x03]), Some(10)).await?;
+        let frames = dev3.receive(channel.into(), None).await?;
+        assert_eq!(frames[0].id(), CanId::Standard(0x456));
+
+        Ok(())
+    }
+}
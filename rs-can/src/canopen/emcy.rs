@@ -0,0 +1,87 @@
+use super::CanOpenError;
+
+/// The emergency message(EMCY).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Emergency {
+    pub error_code: u16,
+    /// the error register(object 0x1001)
+    pub error_register: u8,
+    /// the manufacturer specific error code
+    pub data: [u8; 5],
+}
+
+impl Emergency {
+    pub fn new(error_code: u16, error_register: u8) -> Self {
+        Self { error_code, error_register, data: Default::default() }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CanOpenError> {
+        if data.len() < 8 {
+            return Err(CanOpenError::InvalidParameter(format!("EMCY length {} is less than 8", data.len())));
+        }
+        let mut specific = [0x00; 5];
+        specific.copy_from_slice(&data[3..8]);
+        Ok(Self {
+            error_code: u16::from_le_bytes([data[0], data[1]]),
+            error_register: data[2],
+            data: specific,
+        })
+    }
+
+    pub fn encode(&self) -> [u8; 8] {
+        let code = self.error_code.to_le_bytes();
+        let mut data = [code[0], code[1], self.error_register, 0x00, 0x00, 0x00, 0x00, 0x00];
+        data[3..].copy_from_slice(&self.data);
+        data
+    }
+
+    /// The error is reset or no error.
+    #[inline]
+    pub fn is_reset(&self) -> bool {
+        self.error_code == 0x0000
+    }
+
+    /// The description of error code class.
+    pub fn description(&self) -> &'static str {
+        match self.error_code {
+            0x0000..=0x00FF => "error reset or no error",
+            0x1000..=0x10FF => "generic error",
+            0x2000..=0x2FFF => "current",
+            0x3000..=0x3FFF => "voltage",
+            0x4000..=0x4FFF => "temperature",
+            0x5000..=0x5FFF => "device hardware",
+            0x6000..=0x6FFF => "device software",
+            0x7000..=0x7FFF => "additional modules",
+            0x8110 => "CAN overrun(objects lost)",
+            0x8120 => "CAN in error passive mode",
+            0x8130 => "life guard error or heartbeat error",
+            0x8140 => "recovered from bus off",
+            0x8150 => "CAN-ID collision",
+            0x8210 => "PDO not processed due to length error",
+            0x8220 => "PDO length exceeded",
+            0x8000..=0x8FFF => "monitoring",
+            0x9000..=0x90FF => "external error",
+            0xF000..=0xF0FF => "additional functions",
+            0xFF00..=0xFFFF => "device specific",
+            _ => "unknown",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Emergency;
+
+    #[test]
+    fn test_emergency() -> anyhow::Result<()> {
+        let emergency = Emergency::decode(&[0x30, 0x81, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00])?;
+        assert_eq!(emergency, Emergency::new(0x8130, 0x11));
+        assert_eq!(emergency.description(), "life guard error or heartbeat error");
+        assert_eq!(Emergency::new(0x3210, 0x05).encode(), [0x10, 0x32, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(Emergency::new(0x8100, 0x11).description(), "monitoring");
+        assert!(Emergency::new(0x0000, 0x00).is_reset());
+        assert!(Emergency::decode(&[0x00; 4]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! The CANopen(CiA 301) services over any [`CanDevice`](crate::CanDevice).
+//!
+//! The [`SdoClient`] reads and writes the object dictionary of a node by expedited, segmented
+//! or block transfers, and the [`SdoServer`] serves an [`ObjectDictionary`] for testing the masters.
+//!
+//! The [`CanOpenNode`] runs the NMT, heartbeat, SYNC, EMCY and PDO services configured by the
+//! object dictionary in background, and notifies the events through [`CanOpenListener`].
+//!
+//! ```no_run
+//! use rs_can::{canopen::SdoClient, vcan::VirtualCan};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//!
+//! let mut client = SdoClient::new(device, "vcan0".into(), 0x05);
+//! // the device type
+//! let device_type = client.upload(0x1000, 0x00).unwrap();
+//! // the profile acceleration of drive
+//! client.download(0x6083, 0x00, &1000u32.to_le_bytes()).unwrap();
+//! let name = client.block_upload(0x1008, 0x00).unwrap();
+//! ```
+mod emcy;
+mod nmt;
+mod node;
+mod od;
+mod pdo;
+mod sdo;
+
+pub use emcy::*;
+pub use nmt::*;
+pub use node::*;
+pub use od::*;
+pub use pdo::*;
+pub use sdo::*;
+
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+use crate::CanError;
+
+#[derive(Debug, Clone, Error)]
+pub enum CanOpenError {
+    /// The SDO transfer is aborted by the server or the client.
+    #[error("RUST-CAN - CANopen SDO of {index:#06X}:{subindex:02X} is aborted: {}", display_code(*.code))]
+    SdoAborted { index: u16, subindex: u8, code: u32 },
+    /// The response is not matched with request.
+    #[error("RUST-CAN - CANopen invalid response: {0:02X?}")]
+    InvalidResponse(Vec<u8>),
+    /// The parameter of request is invalid.
+    #[error("RUST-CAN - CANopen invalid parameter: {0}")]
+    InvalidParameter(String),
+    /// The error of device.
+    #[error(transparent)]
+    Transport(#[from] CanError),
+}
+
+impl CanOpenError {
+    /// Get the abort code of SDO transfer.
+    #[inline]
+    pub fn abort_code(&self) -> Option<AbortCode> {
+        match self {
+            Self::SdoAborted { code, .. } => AbortCode::try_from(*code).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[inline]
+fn display_code(code: u32) -> String {
+    match AbortCode::try_from(code) {
+        Ok(v) => format!("{} ({:#010X})", v, code),
+        Err(_) => format!("{:#010X}", code),
+    }
+}
+
+/// The abort code of SDO transfer.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortCode {
+    ToggleBitNotAlternated = 0x0503_0000,
+    Timeout = 0x0504_0000,
+    InvalidCommand = 0x0504_0001,
+    InvalidBlockSize = 0x0504_0002,
+    InvalidSequenceNumber = 0x0504_0003,
+    CrcError = 0x0504_0004,
+    OutOfMemory = 0x0504_0005,
+    UnsupportedAccess = 0x0601_0000,
+    WriteOnly = 0x0601_0001,
+    ReadOnly = 0x0601_0002,
+    ObjectNotExist = 0x0602_0000,
+    NotMappable = 0x0604_0041,
+    PdoLengthExceeded = 0x0604_0042,
+    ParameterIncompatibility = 0x0604_0043,
+    InternalIncompatibility = 0x0604_0047,
+    HardwareError = 0x0606_0000,
+    LengthMismatch = 0x0607_0010,
+    LengthTooHigh = 0x0607_0012,
+    LengthTooLow = 0x0607_0013,
+    SubIndexNotExist = 0x0609_0011,
+    InvalidValue = 0x0609_0030,
+    ValueTooHigh = 0x0609_0031,
+    ValueTooLow = 0x0609_0032,
+    MaxLessThanMin = 0x0609_0036,
+    ResourceNotAvailable = 0x060A_0023,
+    GeneralError = 0x0800_0000,
+    DataNotStored = 0x0800_0020,
+    DataNotStoredLocalControl = 0x0800_0021,
+    DataNotStoredDeviceState = 0x0800_0022,
+    NoDataAvailable = 0x0800_0024,
+}
+
+impl TryFrom<u32> for AbortCode {
+    type Error = CanOpenError;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0x0503_0000 => Ok(Self::ToggleBitNotAlternated),
+            0x0504_0000 => Ok(Self::Timeout),
+            0x0504_0001 => Ok(Self::InvalidCommand),
+            0x0504_0002 => Ok(Self::InvalidBlockSize),
+            0x0504_0003 => Ok(Self::InvalidSequenceNumber),
+            0x0504_0004 => Ok(Self::CrcError),
+            0x0504_0005 => Ok(Self::OutOfMemory),
+            0x0601_0000 => Ok(Self::UnsupportedAccess),
+            0x0601_0001 => Ok(Self::WriteOnly),
+            0x0601_0002 => Ok(Self::ReadOnly),
+            0x0602_0000 => Ok(Self::ObjectNotExist),
+            0x0604_0041 => Ok(Self::NotMappable),
+            0x0604_0042 => Ok(Self::PdoLengthExceeded),
+            0x0604_0043 => Ok(Self::ParameterIncompatibility),
+            0x0604_0047 => Ok(Self::InternalIncompatibility),
+            0x0606_0000 => Ok(Self::HardwareError),
+            0x0607_0010 => Ok(Self::LengthMismatch),
+            0x0607_0012 => Ok(Self::LengthTooHigh),
+            0x0607_0013 => Ok(Self::LengthTooLow),
+            0x0609_0011 => Ok(Self::SubIndexNotExist),
+            0x0609_0030 => Ok(Self::InvalidValue),
+            0x0609_0031 => Ok(Self::ValueTooHigh),
+            0x0609_0032 => Ok(Self::ValueTooLow),
+            0x0609_0036 => Ok(Self::MaxLessThanMin),
+            0x060A_0023 => Ok(Self::ResourceNotAvailable),
+            0x0800_0000 => Ok(Self::GeneralError),
+            0x0800_0020 => Ok(Self::DataNotStored),
+            0x0800_0021 => Ok(Self::DataNotStoredLocalControl),
+            0x0800_0022 => Ok(Self::DataNotStoredDeviceState),
+            0x0800_0024 => Ok(Self::NoDataAvailable),
+            _ => Err(CanOpenError::InvalidParameter(format!("abort code {:#010X} is not supported", value))),
+        }
+    }
+}
+
+impl Display for AbortCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::ToggleBitNotAlternated => "toggle bit not alternated",
+            Self::Timeout => "SDO protocol timed out",
+            Self::InvalidCommand => "client/server command specifier not valid or unknown",
+            Self::InvalidBlockSize => "invalid block size",
+            Self::InvalidSequenceNumber => "invalid sequence number",
+            Self::CrcError => "CRC error",
+            Self::OutOfMemory => "out of memory",
+            Self::UnsupportedAccess => "unsupported access to an object",
+            Self::WriteOnly => "attempt to read a write only object",
+            Self::ReadOnly => "attempt to write a read only object",
+            Self::ObjectNotExist => "object does not exist in the object dictionary",
+            Self::NotMappable => "object cannot be mapped to the PDO",
+            Self::PdoLengthExceeded => "the number and length of the objects to be mapped would exceed PDO length",
+            Self::ParameterIncompatibility => "general parameter incompatibility reason",
+            Self::InternalIncompatibility => "general internal incompatibility in the device",
+            Self::HardwareError => "access failed due to a hardware error",
+            Self::LengthMismatch => "data type does not match, length of service parameter does not match",
+            Self::LengthTooHigh => "data type does not match, length of service parameter too high",
+            Self::LengthTooLow => "data type does not match, length of service parameter too low",
+            Self::SubIndexNotExist => "sub-index does not exist",
+            Self::InvalidValue => "invalid value for parameter",
+            Self::ValueTooHigh => "value of parameter written too high",
+            Self::ValueTooLow => "value of parameter written too low",
+            Self::MaxLessThanMin => "maximum value is less than minimum value",
+            Self::ResourceNotAvailable => "resource not available: SDO connection",
+            Self::GeneralError => "general error",
+            Self::DataNotStored => "data cannot be transferred or stored to the application",
+            Self::DataNotStoredLocalControl => "data cannot be transferred or stored to the application because of local control",
+            Self::DataNotStoredDeviceState => "data cannot be transferred or stored to the application because of the present device state",
+            Self::NoDataAvailable => "no data available",
+        };
+        f.pad(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CanError;
+    use super::{AbortCode, CanOpenError};
+
+    #[test]
+    fn test_abort_code() -> anyhow::Result<()> {
+        assert_eq!(AbortCode::try_from(0x0602_0000)?, AbortCode::ObjectNotExist);
+        assert!(AbortCode::try_from(0x1234_5678).is_err());
+
+        let err = CanOpenError::SdoAborted { index: 0x1000, subindex: 0x00, code: 0x0601_0002 };
+        assert_eq!(err.abort_code(), Some(AbortCode::ReadOnly));
+        assert_eq!(err.to_string(), "RUST-CAN - CANopen SDO of 0x1000:00 is aborted: attempt to write a read only object (0x06010002)");
+        let err = CanOpenError::SdoAborted { index: 0x1000, subindex: 0x00, code: 0x1234_5678 };
+        assert_eq!(err.to_string(), "RUST-CAN - CANopen SDO of 0x1000:00 is aborted: 0x12345678");
+        assert_eq!(CanOpenError::from(CanError::TimeoutError("SDO".into())).abort_code(), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use super::CanOpenError;
+
+/// The COB-ID of NMT module control.
+pub const NMT_COB_ID: u16 = 0x000;
+/// The default COB-ID of SYNC.
+pub const SYNC_COB_ID: u16 = 0x080;
+/// The base of default COB-ID of EMCY.
+pub const EMCY_BASE: u16 = 0x080;
+/// The base of COB-ID of heartbeat and boot-up.
+pub const HEARTBEAT_BASE: u16 = 0x700;
+
+/// The command of NMT module control, which is sent by the NMT master.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmtCommand {
+    Start = 0x01,
+    Stop = 0x02,
+    PreOperational = 0x80,
+    ResetNode = 0x81,
+    ResetCommunication = 0x82,
+}
+
+impl TryFrom<u8> for NmtCommand {
+    type Error = CanOpenError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Start),
+            0x02 => Ok(Self::Stop),
+            0x80 => Ok(Self::PreOperational),
+            0x81 => Ok(Self::ResetNode),
+            0x82 => Ok(Self::ResetCommunication),
+            _ => Err(CanOpenError::InvalidParameter(format!("NMT command {:#04X} is not supported", value))),
+        }
+    }
+}
+
+/// The NMT state of node, which is reported by heartbeat.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NmtState {
+    BootUp = 0x00,
+    Stopped = 0x04,
+    Operational = 0x05,
+    PreOperational = 0x7F,
+}
+
+impl TryFrom<u8> for NmtState {
+    type Error = CanOpenError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::BootUp),
+            0x04 => Ok(Self::Stopped),
+            0x05 => Ok(Self::Operational),
+            0x7F => Ok(Self::PreOperational),
+            _ => Err(CanOpenError::InvalidParameter(format!("NMT state {:#04X} is not supported", value))),
+        }
+    }
+}
+
+impl NmtState {
+    /// The state after the command is applied, the node enters pre-operational after reset.
+    #[inline]
+    pub fn apply(self, command: NmtCommand) -> Self {
+        match command {
+            NmtCommand::Start => Self::Operational,
+            NmtCommand::Stop => Self::Stopped,
+            NmtCommand::PreOperational
+            | NmtCommand::ResetNode
+            | NmtCommand::ResetCommunication => Self::PreOperational,
+        }
+    }
+}
@@ -0,0 +1,710 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{atomic::{AtomicBool, AtomicU8, Ordering}, mpsc, Arc, Mutex, MutexGuard}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+use crate::{CanDevice, CanError, CanFrame, CanId};
+use super::{
+    CanOpenError, Emergency, NmtCommand, NmtState, ObjectDictionary, Pdo, PdoKind, EMCY_BASE, HEARTBEAT_BASE, NMT_COB_ID,
+    SYNC_COB_ID,
+};
+
+/// The index of COB-ID SYNC, the SYNC is produced when bit 30 is set.
+pub const OD_SYNC_COB_ID: u16 = 0x1005;
+/// The index of communication cycle period in microseconds.
+pub const OD_SYNC_PERIOD: u16 = 0x1006;
+/// The index of COB-ID EMCY.
+pub const OD_EMCY_COB_ID: u16 = 0x1014;
+/// The index of consumer heartbeat time, the sub-index value is `node << 16 | time`(milliseconds).
+pub const OD_HEARTBEAT_CONSUMER: u16 = 0x1016;
+/// The index of producer heartbeat time in milliseconds.
+pub const OD_HEARTBEAT_PRODUCER: u16 = 0x1017;
+/// The index of synchronous counter overflow value, the SYNC carries the counter when it is greater than 1.
+pub const OD_SYNC_OVERFLOW: u16 = 0x1019;
+/// The bit of COB-ID SYNC which enables the SYNC producer.
+const SYNC_PRODUCER: u32 = 1 << 30;
+/// The timeout in milliseconds of each polling by the background thread.
+const POLL_TIMEOUT: u32 = 10;
+
+/// The listener of CANopen node events, which is called in the thread of node.
+pub trait CanOpenListener: Send {
+    /// Callback when the state of node is changed by boot-up, heartbeat or NMT command of local node.
+    fn on_state_changed(&self, _node: u8, _state: NmtState) {}
+    /// Callback when the heartbeat of consumed node is not received in the consumer time.
+    fn on_heartbeat_timeout(&self, _node: u8) {}
+    /// Callback when the emergency message received.
+    fn on_emergency(&self, _node: u8, _emergency: &Emergency) {}
+    /// Callback when the SYNC received or produced, the `counter` is carried by SYNC.
+    fn on_sync(&self, _counter: Option<u8>) {}
+    /// Callback when the RPDO is written into the object dictionary.
+    fn on_rpdo(&self, _number: u16, _data: &[u8]) {}
+}
+
+/// The request from the handle of node running in background.
+#[derive(Debug)]
+enum Request {
+    Nmt(NmtCommand, u8),
+    Emergency(Emergency),
+    TriggerTpdo(u16),
+}
+
+#[derive(Default)]
+struct TpdoState {
+    last: Option<Instant>,
+    /// the count of SYNC since last transmission
+    syncs: u8,
+    triggered: bool,
+}
+
+/// The CANopen node runtime.
+///
+/// The node is the NMT master of the network, which keeps the state table by boot-up and heartbeat messages.
+/// The heartbeat producer, heartbeat consumers, SYNC producer and PDOs are configured by the object dictionary,
+/// and the configuration changed by SDO or application takes effect immediately.
+pub struct CanOpenNode<D: CanDevice> {
+    device: D,
+    channel: D::Channel,
+    node_id: u8,
+    od: Arc<Mutex<ObjectDictionary>>,
+    state: Arc<AtomicU8>,
+    nodes: Arc<Mutex<BTreeMap<u8, NmtState>>>,
+    listeners: Vec<Box<dyn CanOpenListener>>,
+    started: bool,
+    last_heartbeat: Instant,
+    next_sync: Option<Instant>,
+    sync_counter: u8,
+    /// the last heartbeat and timeout reported of consumed nodes
+    heartbeats: HashMap<u8, (Instant, bool)>,
+    tpdos: HashMap<u16, TpdoState>,
+    /// the synchronous RPDOs received, which are processed by next SYNC
+    rpdos: BTreeMap<u16, Vec<u8>>,
+    requests: Option<mpsc::Receiver<Request>>,
+}
+
+impl<D: CanDevice> CanOpenNode<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(device: D, channel: D::Channel, node_id: u8, od: ObjectDictionary) -> Self {
+        Self {
+            device,
+            channel,
+            node_id: node_id & 0x7F,
+            od: Arc::new(Mutex::new(od)),
+            state: Arc::new(AtomicU8::new(NmtState::BootUp as u8)),
+            nodes: Default::default(),
+            listeners: Default::default(),
+            started: false,
+            last_heartbeat: Instant::now(),
+            next_sync: None,
+            sync_counter: 0,
+            heartbeats: Default::default(),
+            tpdos: Default::default(),
+            rpdos: Default::default(),
+            requests: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn node_id(&self) -> u8 {
+        self.node_id
+    }
+
+    /// The object dictionary which is shared with the running node.
+    #[inline(always)]
+    pub fn od(&self) -> Arc<Mutex<ObjectDictionary>> {
+        Arc::clone(&self.od)
+    }
+
+    /// The NMT state of local node.
+    #[inline]
+    pub fn state(&self) -> NmtState {
+        load_state(&self.state)
+    }
+
+    /// The states of the remote nodes reported by boot-up and heartbeat.
+    pub fn nodes(&self) -> BTreeMap<u8, NmtState> {
+        lock_nodes(&self.nodes)
+    }
+
+    pub fn add_listener<L>(&mut self, listener: L) -> &mut Self
+    where
+        L: CanOpenListener + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Send the NMT command to the node, or all nodes when `node` is 0. The local node is included.
+    pub fn nmt(&mut self, command: NmtCommand, node: u8) -> Result<(), CanOpenError> {
+        self.send(CanId::Standard(NMT_COB_ID), &[command as u8, node])?;
+        if node == 0 || node == self.node_id {
+            self.apply_nmt(command)?;
+        }
+        Ok(())
+    }
+
+    /// Send the emergency message, which is not allowed in stopped state.
+    pub fn emergency(&mut self, emergency: &Emergency) -> Result<(), CanOpenError> {
+        if matches!(self.state(), NmtState::Stopped | NmtState::BootUp) {
+            return Err(CanOpenError::InvalidParameter(format!("EMCY is not allowed in {:?} state", self.state())));
+        }
+        let cob_id = self.lock_od()?
+            .get_unsigned(OD_EMCY_COB_ID, 0x00)
+            .map_or(EMCY_BASE + self.node_id as u16, |v| (v & 0x7FF) as u16);
+        self.send(CanId::Standard(cob_id), &emergency.encode())
+    }
+
+    /// Trigger the transmission of TPDO, the event-driven TPDO is transmitted after the inhibit time,
+    /// and the acyclic synchronous TPDO is transmitted by next SYNC.
+    pub fn trigger_tpdo(&mut self, number: u16) -> &mut Self {
+        self.tpdos.entry(number)
+            .or_default()
+            .triggered = true;
+        self
+    }
+
+    /// Process the messages and timers for `timeout` milliseconds, the boot-up is sent by first polling.
+    pub fn poll(&mut self, timeout: u32) -> Result<(), CanOpenError> {
+        if !self.started {
+            self.boot()?;
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        loop {
+            self.process_requests();
+            let wakeup = self.process_timers()?
+                .map_or(deadline, |v| v.min(deadline));
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+
+            let timeout = wakeup.saturating_duration_since(now).as_millis().clamp(1, u32::MAX as u128) as u32;
+            match self.device.receive(self.channel.clone(), Some(timeout)) {
+                Ok(frames) => frames.iter()
+                    .try_for_each(|frame| self.process_frame(frame))?,
+                Err(CanError::TimeoutError(_)) => {},
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Run the node in background until the handle is stopped or dropped.
+    pub fn spawn(mut self) -> CanOpenHandle
+    where
+        D: Send + 'static,
+        D::Channel: Send,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.requests = Some(receiver);
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+        let (od, state, nodes) = (self.od(), Arc::clone(&self.state), Arc::clone(&self.nodes));
+        let handle = thread::spawn(move || {
+            while flag.load(Ordering::Acquire) {
+                if let Err(e) = self.poll(POLL_TIMEOUT) {
+                    log::warn!("RUST-CAN - {} when CANopen node running", e);
+                    thread::sleep(Duration::from_millis(POLL_TIMEOUT as u64));
+                }
+            }
+        });
+
+        CanOpenHandle { running, od, state, nodes, sender, handle: Some(handle) }
+    }
+
+    fn boot(&mut self) -> Result<(), CanOpenError> {
+        self.started = true;
+        self.send(CanId::Standard(HEARTBEAT_BASE + self.node_id as u16), &[NmtState::BootUp as u8])?;
+        self.last_heartbeat = Instant::now();
+        self.next_sync = None;
+        self.tpdos.clear();
+        self.rpdos.clear();
+        self.set_state(NmtState::PreOperational);
+        Ok(())
+    }
+
+    fn apply_nmt(&mut self, command: NmtCommand) -> Result<(), CanOpenError> {
+        log::debug!("RUST-CAN - CANopen node {} NMT command: {:?}", self.node_id, command);
+        match command {
+            NmtCommand::ResetNode | NmtCommand::ResetCommunication => self.boot(),
+            _ => {
+                let state = self.state().apply(command);
+                self.set_state(state);
+                Ok(())
+            },
+        }
+    }
+
+    fn set_state(&mut self, state: NmtState) {
+        if self.state.swap(state as u8, Ordering::AcqRel) != state as u8 {
+            self.listeners.iter()
+                .for_each(|v| v.on_state_changed(self.node_id, state));
+        }
+    }
+
+    /// Process the requests from handle, the failed request is logged and the rest are continued.
+    fn process_requests(&mut self) {
+        let requests = match self.requests.as_ref() {
+            Some(v) => v.try_iter().collect::<Vec<_>>(),
+            None => return,
+        };
+        for request in requests {
+            let result = match &request {
+                Request::Nmt(command, node) => self.nmt(*command, *node),
+                Request::Emergency(emergency) => self.emergency(emergency),
+                Request::TriggerTpdo(number) => {
+                    self.trigger_tpdo(*number);
+                    Ok(())
+                },
+            };
+            if let Err(e) = result {
+                log::warn!("RUST-CAN - {} when CANopen node request: {:?} processing", e, request);
+            }
+        }
+    }
+
+    /// Process the heartbeat, SYNC and event timer, return the time of next timer.
+    fn process_timers(&mut self) -> Result<Option<Instant>, CanOpenError> {
+        let (heartbeat, sync, consumers, tpdos) = {
+            let od = self.lock_od()?;
+            let heartbeat = od.get_unsigned(OD_HEARTBEAT_PRODUCER, 0x00).unwrap_or_default();
+            let sync = od.get_unsigned(OD_SYNC_COB_ID, 0x00)
+                .filter(|v| v & SYNC_PRODUCER != 0)
+                .zip(od.get_unsigned(OD_SYNC_PERIOD, 0x00).filter(|&v| v > 0));
+            let consumers = od.keys(OD_HEARTBEAT_CONSUMER..=OD_HEARTBEAT_CONSUMER)
+                .filter(|&(_, subindex)| subindex > 0)
+                .filter_map(|(index, subindex)| od.get_unsigned(index, subindex))
+                .map(|v| (((v >> 16) & 0x7F) as u8, v & 0xFFFF))
+                .filter(|&(node, time)| node > 0 && time > 0)
+                .collect::<Vec<_>>();
+            let tpdos = if self.state() == NmtState::Operational { Pdo::list(&od, PdoKind::Transmit) } else { Vec::new() };
+            (heartbeat, sync, consumers, tpdos)
+        };
+
+        let now = Instant::now();
+        let mut wakeup: Option<Instant> = None;
+        let mut schedule = |time: Instant| wakeup = Some(wakeup.map_or(time, |v| v.min(time)));
+
+        if heartbeat > 0 {
+            let period = Duration::from_millis(heartbeat as u64);
+            if now >= self.last_heartbeat + period {
+                self.send(CanId::Standard(HEARTBEAT_BASE + self.node_id as u16), &[self.state() as u8])?;
+                self.last_heartbeat = now;
+            }
+            schedule(self.last_heartbeat + period);
+        }
+
+        match sync {
+            Some((cob_id, period)) if !matches!(self.state(), NmtState::Stopped) => {
+                let period = Duration::from_micros(period as u64);
+                let next = *self.next_sync.get_or_insert(now + period);
+                if now >= next {
+                    self.produce_sync(CanId::Standard((cob_id & 0x7FF) as u16))?;
+                    self.next_sync = Some(now + period);
+                }
+                schedule(self.next_sync.unwrap_or(now + period));
+            },
+            _ => self.next_sync = None,
+        }
+
+        for (node, time) in consumers {
+            if let Some((last, timeout)) = self.heartbeats.get_mut(&node) {
+                let expired = *last + Duration::from_millis(time as u64);
+                if *timeout {
+                    continue;
+                }
+                if now >= expired {
+                    *timeout = true;
+                    log::warn!("RUST-CAN - CANopen heartbeat of node {} is timeout", node);
+                    self.listeners.iter()
+                        .for_each(|v| v.on_heartbeat_timeout(node));
+                }
+                else {
+                    schedule(expired);
+                }
+            }
+        }
+
+        for pdo in tpdos.iter().filter(|v| !v.is_synchronous()) {
+            let state = self.tpdos.entry(pdo.number).or_default();
+            let inhibit = state.last
+                .map_or(now, |v| v + Duration::from_micros(pdo.inhibit_time as u64 * 100));
+            let timer = (pdo.event_timer > 0)
+                .then(|| state.last.map_or(now, |v| v + Duration::from_millis(pdo.event_timer as u64)));
+            let due = if state.triggered { Some(inhibit) } else { timer.map(|v| v.max(inhibit)) };
+            match due {
+                Some(due) if now >= due => {
+                    self.transmit_pdo(pdo)?;
+                    if pdo.event_timer > 0 {
+                        schedule(now + Duration::from_millis(pdo.event_timer as u64));
+                    }
+                },
+                Some(due) => schedule(due),
+                None => {},
+            }
+        }
+
+        Ok(wakeup)
+    }
+
+    fn process_frame(&mut self, frame: &D::Frame) -> Result<(), CanOpenError> {
+        if frame.is_remote() || frame.is_error_frame() {
+            return Ok(());
+        }
+
+        let id = frame.id();
+        let data = frame.data();
+        let sync_id = self.lock_od()?
+            .get_unsigned(OD_SYNC_COB_ID, 0x00)
+            .map_or(SYNC_COB_ID, |v| (v & 0x7FF) as u16);
+        match id {
+            CanId::Standard(NMT_COB_ID) => {
+                if data.len() < 2 || (data[1] != 0 && data[1] != self.node_id) {
+                    return Ok(());
+                }
+                match NmtCommand::try_from(data[0]) {
+                    Ok(command) => self.apply_nmt(command)?,
+                    Err(e) => log::warn!("{}", e),
+                }
+            },
+            CanId::Standard(v) if v == sync_id => {
+                self.listeners.iter()
+                    .for_each(|l| l.on_sync(data.first().copied()));
+                self.process_sync()?;
+            },
+            CanId::Standard(v @ 0x081..=0x0FF) => {
+                match Emergency::decode(data) {
+                    Ok(emergency) => self.listeners.iter()
+                        .for_each(|l| l.on_emergency((v - EMCY_BASE) as u8, &emergency)),
+                    Err(e) => log::warn!("{}", e),
+                }
+            },
+            CanId::Standard(v @ 0x701..=0x77F) => {
+                let node = (v - HEARTBEAT_BASE) as u8;
+                match data.first().map(|&v| NmtState::try_from(v & 0x7F)) {
+                    Some(Ok(state)) => self.update_node(node, state),
+                    Some(Err(e)) => log::warn!("{}", e),
+                    None => {},
+                }
+            },
+            _ => self.process_rpdo(id, data)?,
+        }
+
+        Ok(())
+    }
+
+    fn update_node(&mut self, node: u8, state: NmtState) {
+        self.heartbeats.insert(node, (Instant::now(), false));
+        let changed = match self.nodes.lock() {
+            Ok(mut v) => v.insert(node, state) != Some(state),
+            Err(_) => false,
+        };
+        if changed {
+            self.listeners.iter()
+                .for_each(|v| v.on_state_changed(node, state));
+        }
+    }
+
+    fn produce_sync(&mut self, cob_id: CanId) -> Result<(), CanOpenError> {
+        let overflow = self.lock_od()?
+            .get_unsigned(OD_SYNC_OVERFLOW, 0x00)
+            .unwrap_or_default() as u8;
+        let counter = if overflow > 1 {
+            self.sync_counter = if self.sync_counter >= overflow { 1 } else { self.sync_counter + 1 };
+            Some(self.sync_counter)
+        }
+        else {
+            None
+        };
+        match counter {
+            Some(counter) => self.send(cob_id, &[counter])?,
+            None => self.send(cob_id, &[])?,
+        }
+
+        self.listeners.iter()
+            .for_each(|v| v.on_sync(counter));
+        self.process_sync()
+    }
+
+    /// Process the synchronous RPDOs received and transmit the synchronous TPDOs.
+    fn process_sync(&mut self) -> Result<(), CanOpenError> {
+        if self.state() != NmtState::Operational {
+            return Ok(());
+        }
+
+        let rpdos = std::mem::take(&mut self.rpdos);
+        for (number, data) in rpdos {
+            let pdo = Pdo::from_od(&*self.lock_od()?, PdoKind::Receive, number);
+            if let Some(pdo) = pdo {
+                self.write_rpdo(&pdo, &data)?;
+            }
+        }
+
+        let tpdos = Pdo::list(&*self.lock_od()?, PdoKind::Transmit);
+        for pdo in tpdos.iter().filter(|v| v.is_synchronous()) {
+            let state = self.tpdos.entry(pdo.number).or_default();
+            let transmit = if pdo.transmission_type == 0 {
+                state.triggered
+            }
+            else {
+                state.syncs = state.syncs.saturating_add(1);
+                state.syncs >= pdo.transmission_type
+            };
+            if transmit {
+                self.transmit_pdo(pdo)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_rpdo(&mut self, id: CanId, data: &[u8]) -> Result<(), CanOpenError> {
+        if self.state() != NmtState::Operational {
+            return Ok(());
+        }
+        let pdo = Pdo::list(&*self.lock_od()?, PdoKind::Receive)
+            .into_iter()
+            .find(|v| v.cob_id == id);
+        match pdo {
+            Some(pdo) if pdo.is_synchronous() => {
+                self.rpdos.insert(pdo.number, data.to_vec());
+                Ok(())
+            },
+            Some(pdo) => self.write_rpdo(&pdo, data),
+            None => Ok(()),
+        }
+    }
+
+    fn write_rpdo(&mut self, pdo: &Pdo, data: &[u8]) -> Result<(), CanOpenError> {
+        let result = pdo.decode(&mut *self.lock_od()?, data);
+        match result {
+            Ok(()) => self.listeners.iter()
+                .for_each(|v| v.on_rpdo(pdo.number, data)),
+            Err(code) => log::warn!("RUST-CAN - CANopen RPDO {} is not processed: {}", pdo.number, code),
+        }
+        Ok(())
+    }
+
+    fn transmit_pdo(&mut self, pdo: &Pdo) -> Result<(), CanOpenError> {
+        let state = self.tpdos.entry(pdo.number).or_default();
+        state.last = Some(Instant::now());
+        state.syncs = 0;
+        state.triggered = false;
+
+        let data = pdo.encode(&*self.lock_od()?)
+            .map_err(|code| CanOpenError::InvalidParameter(format!("TPDO {} is not transmitted: {}", pdo.number, code)))?;
+        self.send(pdo.cob_id, &data)
+    }
+
+    fn send(&self, id: CanId, data: &[u8]) -> Result<(), CanOpenError> {
+        let mut frame = D::Frame::new(id, data)
+            .ok_or(CanError::other_error("can't create CANopen frame"))?;
+        frame.set_channel(self.channel.clone());
+        self.device.transmit(frame, None)
+            .map_err(CanOpenError::from)
+    }
+
+    #[inline]
+    fn lock_od(&self) -> Result<MutexGuard<'_, ObjectDictionary>, CanOpenError> {
+        self.od.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()).into())
+    }
+}
+
+/// The handle of the node running in background.
+pub struct CanOpenHandle {
+    running: Arc<AtomicBool>,
+    od: Arc<Mutex<ObjectDictionary>>,
+    state: Arc<AtomicU8>,
+    nodes: Arc<Mutex<BTreeMap<u8, NmtState>>>,
+    sender: mpsc::Sender<Request>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CanOpenHandle {
+    #[inline(always)]
+    pub fn od(&self) -> Arc<Mutex<ObjectDictionary>> {
+        Arc::clone(&self.od)
+    }
+
+    /// The NMT state of local node.
+    #[inline]
+    pub fn state(&self) -> NmtState {
+        load_state(&self.state)
+    }
+
+    /// The states of the remote nodes reported by boot-up and heartbeat.
+    pub fn nodes(&self) -> BTreeMap<u8, NmtState> {
+        lock_nodes(&self.nodes)
+    }
+
+    /// Send the NMT command to the node, or all nodes when `node` is 0.
+    #[inline]
+    pub fn nmt(&self, command: NmtCommand, node: u8) -> Result<(), CanOpenError> {
+        self.request(Request::Nmt(command, node))
+    }
+
+    #[inline]
+    pub fn emergency(&self, emergency: Emergency) -> Result<(), CanOpenError> {
+        self.request(Request::Emergency(emergency))
+    }
+
+    #[inline]
+    pub fn trigger_tpdo(&self, number: u16) -> Result<(), CanOpenError> {
+        self.request(Request::TriggerTpdo(number))
+    }
+
+    /// Stop the node and wait for the thread exited.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - CANopen node thread panicked");
+            }
+        }
+    }
+
+    #[inline]
+    fn request(&self, request: Request) -> Result<(), CanOpenError> {
+        self.sender.send(request)
+            .map_err(|_| CanError::OperationError("CANopen node is stopped".into()).into())
+    }
+}
+
+impl Drop for CanOpenHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[inline]
+fn load_state(state: &AtomicU8) -> NmtState {
+    NmtState::try_from(state.load(Ordering::Acquire))
+        .unwrap_or(NmtState::BootUp)
+}
+
+#[inline]
+fn lock_nodes(nodes: &Mutex<BTreeMap<u8, NmtState>>) -> BTreeMap<u8, NmtState> {
+    nodes.lock()
+        .map(|v| v.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+    use crate::canopen::{AccessType, Emergency, NmtCommand, NmtState, ObjectDictionary, PdoMapping, EVENT_DRIVEN_PROFILE};
+    use crate::test_utils::new_device;
+    use super::{CanOpenListener, CanOpenNode, OD_HEARTBEAT_CONSUMER, OD_HEARTBEAT_PRODUCER, OD_SYNC_COB_ID, OD_SYNC_PERIOD};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Event {
+        State(u8, NmtState),
+        Timeout(u8),
+        Emergency(u8, Emergency),
+        Sync,
+        Rpdo(u16, Vec<u8>),
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Event>>>);
+
+    impl Recorder {
+        fn contains(&self, event: &Event) -> bool {
+            self.0.lock().unwrap().contains(event)
+        }
+    }
+
+    impl CanOpenListener for Recorder {
+        fn on_state_changed(&self, node: u8, state: NmtState) {
+            self.0.lock().unwrap().push(Event::State(node, state));
+        }
+        fn on_heartbeat_timeout(&self, node: u8) {
+            self.0.lock().unwrap().push(Event::Timeout(node));
+        }
+        fn on_emergency(&self, node: u8, emergency: &Emergency) {
+            self.0.lock().unwrap().push(Event::Emergency(node, *emergency));
+        }
+        fn on_sync(&self, _counter: Option<u8>) {
+            self.0.lock().unwrap().push(Event::Sync);
+        }
+        fn on_rpdo(&self, number: u16, data: &[u8]) {
+            self.0.lock().unwrap().push(Event::Rpdo(number, data.to_vec()));
+        }
+    }
+
+    fn wait_until<F: Fn() -> bool>(condition: F) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        false
+    }
+
+    #[test]
+    fn test_node() -> anyhow::Result<()> {
+        let channel = "canopen-node";
+        let mut od = ObjectDictionary::new();
+        od.add(OD_SYNC_COB_ID, 0x00, AccessType::ReadWrite, &0x4000_0080u32.to_le_bytes())
+            .add(OD_SYNC_PERIOD, 0x00, AccessType::ReadWrite, &50_000u32.to_le_bytes())
+            .add(OD_HEARTBEAT_CONSUMER, 0x00, AccessType::Const, &[0x01])
+            .add(OD_HEARTBEAT_CONSUMER, 0x01, AccessType::ReadWrite, &0x0005_00C8u32.to_le_bytes())
+            .add(0x2000, 0x00, AccessType::ReadWrite, &[0x00; 4])
+            .add(0x2001, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add(0x2002, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add_rpdo(1, 0x185, EVENT_DRIVEN_PROFILE, &[PdoMapping::new(0x2000, 0x00, 32)])
+            .add_rpdo(2, 0x285, 1, &[PdoMapping::new(0x2001, 0x00, 16)])
+            .add_tpdo(1, 0x205, EVENT_DRIVEN_PROFILE, 0, &[PdoMapping::new(0x2002, 0x00, 16)]);
+        let recorder = Recorder::default();
+        let mut master = CanOpenNode::new(new_device(channel)?, channel.into(), 0x01, od);
+        master.add_listener(recorder.clone());
+        let master = master.spawn();
+
+        let mut od = ObjectDictionary::new();
+        od.add(OD_HEARTBEAT_PRODUCER, 0x00, AccessType::ReadWrite, &50u16.to_le_bytes())
+            .add(0x6040, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add(0x6041, 0x00, AccessType::ReadOnly, &0x0237u16.to_le_bytes())
+            .add(0x6064, 0x00, AccessType::ReadOnly, &[0x00; 4])
+            .add_rpdo(1, 0x205, EVENT_DRIVEN_PROFILE, &[PdoMapping::new(0x6040, 0x00, 16)])
+            .add_tpdo(1, 0x185, EVENT_DRIVEN_PROFILE, 20, &[PdoMapping::new(0x6064, 0x00, 32)])
+            .add_tpdo(2, 0x285, 1, 0, &[PdoMapping::new(0x6041, 0x00, 16)]);
+        let mut slave = CanOpenNode::new(new_device(channel)?, channel.into(), 0x05, od).spawn();
+
+        assert!(wait_until(|| master.nodes().get(&0x05) == Some(&NmtState::PreOperational)));
+        assert!(recorder.contains(&Event::State(0x05, NmtState::BootUp)));
+        assert_eq!(master.state(), NmtState::PreOperational);
+
+        // start all nodes
+        master.nmt(NmtCommand::Start, 0)?;
+        assert!(wait_until(|| master.nodes().get(&0x05) == Some(&NmtState::Operational)));
+        assert_eq!((master.state(), slave.state()), (NmtState::Operational, NmtState::Operational));
+        assert!(recorder.contains(&Event::State(0x01, NmtState::Operational)));
+
+        // the event-driven TPDO of slave
+        assert_eq!(slave.od().lock().unwrap().set(0x6064, 0x00, &12345i32.to_le_bytes()), Ok(()));
+        assert!(wait_until(|| master.od().lock().unwrap().get(0x2000, 0x00) == Some(&12345i32.to_le_bytes()[..])));
+        assert!(recorder.contains(&Event::Rpdo(1, 12345i32.to_le_bytes().to_vec())));
+        // the synchronous TPDO of slave is processed by next SYNC of master
+        assert!(wait_until(|| master.od().lock().unwrap().get_unsigned(0x2001, 0x00) == Some(0x0237)));
+        assert!(recorder.contains(&Event::Sync));
+
+        // the TPDO triggered by application
+        assert_eq!(master.od().lock().unwrap().set(0x2002, 0x00, &0x000Fu16.to_le_bytes()), Ok(()));
+        master.trigger_tpdo(1)?;
+        assert!(wait_until(|| slave.od().lock().unwrap().get_unsigned(0x6040, 0x00) == Some(0x000F)));
+
+        let emergency = Emergency::new(0x8130, 0x11);
+        slave.emergency(emergency)?;
+        assert!(wait_until(|| recorder.contains(&Event::Emergency(0x05, emergency))));
+
+        master.nmt(NmtCommand::Stop, 0x05)?;
+        assert!(wait_until(|| slave.state() == NmtState::Stopped));
+        assert!(wait_until(|| master.nodes().get(&0x05) == Some(&NmtState::Stopped)));
+        // the heartbeat is lost
+        slave.stop();
+        assert!(wait_until(|| recorder.contains(&Event::Timeout(0x05))));
+        assert!(slave.emergency(emergency).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+use std::{collections::BTreeMap, ops::RangeInclusive};
+use super::AbortCode;
+
+/// The access type of object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessType {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+    /// read only and the value is never changed
+    Const,
+}
+
+impl AccessType {
+    #[inline]
+    pub fn is_readable(self) -> bool {
+        !matches!(self, Self::WriteOnly)
+    }
+
+    #[inline]
+    pub fn is_writable(self) -> bool {
+        matches!(self, Self::WriteOnly | Self::ReadWrite)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Object {
+    access: AccessType,
+    data: Vec<u8>,
+    /// the length of data is changed by writing, such as the domain and visible string
+    variable: bool,
+}
+
+/// The object dictionary of node, which is addressed by index and sub-index.
+///
+/// The value of object is the little-endian bytes. The length of basic types is checked when written,
+/// and the objects added by [`ObjectDictionary::add_domain`] accept any length.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectDictionary {
+    objects: BTreeMap<(u16, u8), Object>,
+}
+
+impl ObjectDictionary {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add the object whose length is fixed, the object added is replaced.
+    pub fn add(&mut self, index: u16, subindex: u8, access: AccessType, data: &[u8]) -> &mut Self {
+        self.objects.insert((index, subindex), Object { access, data: data.to_vec(), variable: false });
+        self
+    }
+
+    /// Add the object whose length is variable, the object added is replaced.
+    pub fn add_domain(&mut self, index: u16, subindex: u8, access: AccessType, data: &[u8]) -> &mut Self {
+        self.objects.insert((index, subindex), Object { access, data: data.to_vec(), variable: true });
+        self
+    }
+
+    #[inline]
+    pub fn contains(&self, index: u16, subindex: u8) -> bool {
+        self.objects.contains_key(&(index, subindex))
+    }
+
+    /// Get the value of object without access checking.
+    #[inline]
+    pub fn get(&self, index: u16, subindex: u8) -> Option<&[u8]> {
+        self.objects.get(&(index, subindex))
+            .map(|v| v.data.as_slice())
+    }
+
+    /// Get the value of unsigned object which is not longer than 4 bytes.
+    pub fn get_unsigned(&self, index: u16, subindex: u8) -> Option<u32> {
+        let data = self.get(index, subindex)?;
+        if data.len() > 4 {
+            return None;
+        }
+        let mut value = [0x00; 4];
+        value[..data.len()].copy_from_slice(data);
+        Some(u32::from_le_bytes(value))
+    }
+
+    /// The index and sub-index of the objects in the range of index.
+    pub fn keys(&self, indexes: RangeInclusive<u16>) -> impl Iterator<Item = (u16, u8)> + '_ {
+        self.objects.range((*indexes.start(), 0x00)..=(*indexes.end(), 0xFF))
+            .map(|(k, _)| *k)
+    }
+
+    /// Set the value of object by the application without access checking.
+    pub fn set(&mut self, index: u16, subindex: u8, data: &[u8]) -> Result<(), AbortCode> {
+        let object = self.object_mut(index, subindex)?;
+        check_length(object, data)?;
+        object.data = data.to_vec();
+        Ok(())
+    }
+
+    #[inline]
+    pub fn access(&self, index: u16, subindex: u8) -> Option<AccessType> {
+        self.objects.get(&(index, subindex))
+            .map(|v| v.access)
+    }
+
+    /// Check the object is existed and accessible by SDO reading or writing.
+    pub fn check_access(&self, index: u16, subindex: u8, write: bool) -> Result<(), AbortCode> {
+        let access = self.access(index, subindex)
+            .ok_or(self.missing(index))?;
+        match (write, access.is_writable(), access.is_readable()) {
+            (true, false, _) => Err(AbortCode::ReadOnly),
+            (false, _, false) => Err(AbortCode::WriteOnly),
+            _ => Ok(()),
+        }
+    }
+
+    /// Read the object by SDO.
+    pub fn read(&self, index: u16, subindex: u8) -> Result<&[u8], AbortCode> {
+        self.check_access(index, subindex, false)?;
+        self.get(index, subindex)
+            .ok_or(AbortCode::ObjectNotExist)
+    }
+
+    /// Write the object by SDO.
+    pub fn write(&mut self, index: u16, subindex: u8, data: &[u8]) -> Result<(), AbortCode> {
+        self.check_access(index, subindex, true)?;
+        self.set(index, subindex, data)
+    }
+
+    fn object_mut(&mut self, index: u16, subindex: u8) -> Result<&mut Object, AbortCode> {
+        let code = self.missing(index);
+        self.objects.get_mut(&(index, subindex))
+            .ok_or(code)
+    }
+
+    /// The abort code of object not found.
+    #[inline]
+    fn missing(&self, index: u16) -> AbortCode {
+        if self.objects.range((index, 0x00)..=(index, 0xFF)).next().is_some() {
+            AbortCode::SubIndexNotExist
+        }
+        else {
+            AbortCode::ObjectNotExist
+        }
+    }
+}
+
+#[inline]
+fn check_length(object: &Object, data: &[u8]) -> Result<(), AbortCode> {
+    if object.variable || data.len() == object.data.len() {
+        Ok(())
+    }
+    else if data.len() > object.data.len() {
+        Err(AbortCode::LengthTooHigh)
+    }
+    else {
+        Err(AbortCode::LengthTooLow)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canopen::AbortCode;
+    use super::{AccessType, ObjectDictionary};
+
+    #[test]
+    fn test_object_dictionary() {
+        let mut od = ObjectDictionary::new();
+        od.add(0x1000, 0x00, AccessType::Const, &0x0002_0192u32.to_le_bytes())
+            .add(0x1017, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add(0x1F51, 0x01, AccessType::WriteOnly, &[0x00])
+            .add_domain(0x1008, 0x00, AccessType::ReadOnly, b"drive");
+
+        assert_eq!(od.read(0x1000, 0x00), Ok(&[0x92, 0x01, 0x02, 0x00][..]));
+        assert_eq!(od.read(0x1F51, 0x01), Err(AbortCode::WriteOnly));
+        assert_eq!(od.read(0x1F51, 0x02), Err(AbortCode::SubIndexNotExist));
+        assert_eq!(od.read(0x2000, 0x00), Err(AbortCode::ObjectNotExist));
+
+        assert_eq!(od.write(0x1000, 0x00, &[0x00; 4]), Err(AbortCode::ReadOnly));
+        assert_eq!(od.write(0x1017, 0x00, &[0x00; 4]), Err(AbortCode::LengthTooHigh));
+        assert_eq!(od.write(0x1017, 0x00, &[0x00]), Err(AbortCode::LengthTooLow));
+        assert_eq!(od.write(0x1017, 0x00, &1000u16.to_le_bytes()), Ok(()));
+        assert_eq!(od.get(0x1017, 0x00), Some(&[0xE8, 0x03][..]));
+        assert_eq!(od.get_unsigned(0x1017, 0x00), Some(1000));
+        assert_eq!(od.keys(0x1001..=0x1020).collect::<Vec<_>>(), [(0x1008, 0x00), (0x1017, 0x00)]);
+
+        // the application changes the read only object
+        assert_eq!(od.set(0x1008, 0x00, b"servo drive"), Ok(()));
+        assert_eq!(od.read(0x1008, 0x00), Ok(&b"servo drive"[..]));
+        assert_eq!(od.access(0x1F51, 0x01), Some(AccessType::WriteOnly));
+        assert_eq!(od.check_access(0x1F51, 0x01, true), Ok(()));
+        assert_eq!(od.check_access(0x1008, 0x00, true), Err(AbortCode::ReadOnly));
+    }
+}
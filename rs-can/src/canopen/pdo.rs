@@ -0,0 +1,247 @@
+use crate::CanId;
+use super::{AbortCode, AccessType, ObjectDictionary};
+
+/// The index of the first RPDO communication parameter.
+pub const RPDO_COMMUNICATION: u16 = 0x1400;
+/// The index of the first RPDO mapping parameter.
+pub const RPDO_MAPPING: u16 = 0x1600;
+/// The index of the first TPDO communication parameter.
+pub const TPDO_COMMUNICATION: u16 = 0x1800;
+/// The index of the first TPDO mapping parameter.
+pub const TPDO_MAPPING: u16 = 0x1A00;
+/// The max count of PDOs in each direction.
+pub const MAX_PDO_COUNT: u16 = 512;
+/// The max length of PDO data in bytes.
+pub const MAX_PDO_LENGTH: usize = 8;
+/// The transmission type of event-driven PDO(manufacturer specific).
+pub const EVENT_DRIVEN_MANUFACTURER: u8 = 0xFE;
+/// The transmission type of event-driven PDO(device and application profile specific).
+pub const EVENT_DRIVEN_PROFILE: u8 = 0xFF;
+/// The bit of COB-ID which disables the PDO.
+const COB_ID_INVALID: u32 = 1 << 31;
+/// The bit of COB-ID which selects the 29 bits identifier.
+const COB_ID_EXTENDED: u32 = 1 << 29;
+
+/// The direction of PDO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PdoKind {
+    Receive,
+    Transmit,
+}
+
+impl PdoKind {
+    #[inline]
+    fn communication(self) -> u16 {
+        match self {
+            Self::Receive => RPDO_COMMUNICATION,
+            Self::Transmit => TPDO_COMMUNICATION,
+        }
+    }
+
+    #[inline]
+    fn mapping(self) -> u16 {
+        match self {
+            Self::Receive => RPDO_MAPPING,
+            Self::Transmit => TPDO_MAPPING,
+        }
+    }
+}
+
+/// The object mapped into PDO, only the objects of whole bytes are supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdoMapping {
+    pub index: u16,
+    pub subindex: u8,
+    /// the length of object in bits
+    pub bits: u8,
+}
+
+impl PdoMapping {
+    pub fn new(index: u16, subindex: u8, bits: u8) -> Self {
+        Self { index, subindex, bits }
+    }
+
+    #[inline]
+    pub fn from_raw(raw: u32) -> Self {
+        Self::new((raw >> 16) as u16, (raw >> 8) as u8, raw as u8)
+    }
+
+    #[inline]
+    pub fn as_raw(self) -> u32 {
+        (self.index as u32) << 16 | (self.subindex as u32) << 8 | self.bits as u32
+    }
+}
+
+/// The PDO configured by the communication and mapping parameters of object dictionary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pdo {
+    /// the number of PDO which starts from 1
+    pub number: u16,
+    pub cob_id: CanId,
+    pub transmission_type: u8,
+    /// the minimum interval of transmission in 100μs
+    pub inhibit_time: u16,
+    /// the interval of event-driven transmission in milliseconds
+    pub event_timer: u16,
+    pub mappings: Vec<PdoMapping>,
+}
+
+impl Pdo {
+    /// Get the PDO from object dictionary, `None` is returned when it is not existed or disabled.
+    pub fn from_od(od: &ObjectDictionary, kind: PdoKind, number: u16) -> Option<Self> {
+        if !(1..=MAX_PDO_COUNT).contains(&number) {
+            return None;
+        }
+        let communication = kind.communication() + number - 1;
+        let cob_id = od.get_unsigned(communication, 0x01)?;
+        if cob_id & COB_ID_INVALID != 0 {
+            return None;
+        }
+        let mapping = kind.mapping() + number - 1;
+        let count = od.get_unsigned(mapping, 0x00).unwrap_or_default() as u8;
+        let mappings = (1..=count)
+            .filter_map(|i| od.get_unsigned(mapping, i))
+            .map(PdoMapping::from_raw)
+            .collect();
+
+        Some(Self {
+            number,
+            cob_id: if cob_id & COB_ID_EXTENDED != 0 { CanId::Extended(cob_id & 0x1FFF_FFFF) } else { CanId::Standard((cob_id & 0x7FF) as u16) },
+            transmission_type: od.get_unsigned(communication, 0x02).unwrap_or(EVENT_DRIVEN_PROFILE as u32) as u8,
+            inhibit_time: od.get_unsigned(communication, 0x03).unwrap_or_default() as u16,
+            event_timer: od.get_unsigned(communication, 0x05).unwrap_or_default() as u16,
+            mappings,
+        })
+    }
+
+    /// Get all PDOs enabled in object dictionary.
+    pub fn list(od: &ObjectDictionary, kind: PdoKind) -> Vec<Self> {
+        let first = kind.communication();
+        od.keys(first..=first + MAX_PDO_COUNT - 1)
+            .filter(|&(_, subindex)| subindex == 0x01)
+            .filter_map(|(index, _)| Self::from_od(od, kind, index - first + 1))
+            .collect()
+    }
+
+    /// The PDO is transmitted or processed by SYNC.
+    #[inline]
+    pub fn is_synchronous(&self) -> bool {
+        self.transmission_type <= 240
+    }
+
+    /// Pack the values of mapped objects.
+    pub fn encode(&self, od: &ObjectDictionary) -> Result<Vec<u8>, AbortCode> {
+        let mut data = Vec::with_capacity(MAX_PDO_LENGTH);
+        for mapping in &self.mappings {
+            let length = mapping_length(mapping)?;
+            let value = od.get(mapping.index, mapping.subindex)
+                .ok_or(AbortCode::ObjectNotExist)?;
+            if value.len() < length {
+                return Err(AbortCode::LengthMismatch);
+            }
+            data.extend(&value[..length]);
+        }
+        if data.len() > MAX_PDO_LENGTH {
+            return Err(AbortCode::PdoLengthExceeded);
+        }
+
+        Ok(data)
+    }
+
+    /// Write the data into mapped objects.
+    pub fn decode(&self, od: &mut ObjectDictionary, data: &[u8]) -> Result<(), AbortCode> {
+        let mut offset = 0;
+        for mapping in &self.mappings {
+            let length = mapping_length(mapping)?;
+            let value = data.get(offset..offset + length)
+                .ok_or(AbortCode::LengthTooLow)?;
+            od.set(mapping.index, mapping.subindex, value)?;
+            offset += length;
+        }
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn mapping_length(mapping: &PdoMapping) -> Result<usize, AbortCode> {
+    if mapping.bits & 0x07 != 0 {
+        return Err(AbortCode::NotMappable);
+    }
+    Ok(mapping.bits as usize / 8)
+}
+
+impl ObjectDictionary {
+    /// Add the communication and mapping parameters of RPDO, the `number` starts from 1.
+    pub fn add_rpdo(&mut self, number: u16, cob_id: u32, transmission_type: u8, mappings: &[PdoMapping]) -> &mut Self {
+        self.add_pdo(PdoKind::Receive, number, cob_id, transmission_type, 0, mappings)
+    }
+
+    /// Add the communication and mapping parameters of TPDO, the `number` starts from 1.
+    pub fn add_tpdo(
+        &mut self,
+        number: u16,
+        cob_id: u32,
+        transmission_type: u8,
+        event_timer: u16,
+        mappings: &[PdoMapping],
+    ) -> &mut Self {
+        self.add_pdo(PdoKind::Transmit, number, cob_id, transmission_type, event_timer, mappings)
+    }
+
+    fn add_pdo(
+        &mut self,
+        kind: PdoKind,
+        number: u16,
+        cob_id: u32,
+        transmission_type: u8,
+        event_timer: u16,
+        mappings: &[PdoMapping],
+    ) -> &mut Self {
+        let communication = kind.communication() + number.clamp(1, MAX_PDO_COUNT) - 1;
+        let mapping = kind.mapping() + number.clamp(1, MAX_PDO_COUNT) - 1;
+        self.add(communication, 0x00, AccessType::Const, &[0x05])
+            .add(communication, 0x01, AccessType::ReadWrite, &cob_id.to_le_bytes())
+            .add(communication, 0x02, AccessType::ReadWrite, &[transmission_type])
+            .add(communication, 0x03, AccessType::ReadWrite, &[0x00; 2])
+            .add(communication, 0x05, AccessType::ReadWrite, &event_timer.to_le_bytes())
+            .add(mapping, 0x00, AccessType::ReadWrite, &[mappings.len() as u8]);
+        for (i, v) in mappings.iter().enumerate() {
+            self.add(mapping, i as u8 + 1, AccessType::ReadWrite, &v.as_raw().to_le_bytes());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{canopen::{AbortCode, AccessType, ObjectDictionary}, CanId};
+    use super::{Pdo, PdoKind, PdoMapping, EVENT_DRIVEN_PROFILE};
+
+    #[test]
+    fn test_pdo() {
+        let mut od = ObjectDictionary::new();
+        od.add(0x6040, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add(0x6060, 0x00, AccessType::ReadWrite, &[0x00])
+            .add(0x6064, 0x00, AccessType::ReadOnly, &(-1000i32).to_le_bytes())
+            .add_rpdo(1, 0x205, EVENT_DRIVEN_PROFILE, &[PdoMapping::new(0x6040, 0x00, 16), PdoMapping::new(0x6060, 0x00, 8)])
+            .add_tpdo(1, 0x185, 1, 0, &[PdoMapping::new(0x6064, 0x00, 32)])
+            .add_tpdo(2, 0x8000_0285, EVENT_DRIVEN_PROFILE, 100, &[])
+            .add_tpdo(3, 0x2000_0385, EVENT_DRIVEN_PROFILE, 100, &[PdoMapping::new(0x6060, 0x00, 4)]);
+
+        assert_eq!(PdoMapping::from_raw(0x6064_0020), PdoMapping::new(0x6064, 0x00, 32));
+        let rpdo = Pdo::from_od(&od, PdoKind::Receive, 1).unwrap();
+        assert_eq!((rpdo.cob_id, rpdo.is_synchronous()), (CanId::Standard(0x205), false));
+        assert_eq!(rpdo.decode(&mut od, &[0x0F, 0x00, 0x01]), Ok(()));
+        assert_eq!((od.get_unsigned(0x6040, 0x00), od.get_unsigned(0x6060, 0x00)), (Some(0x0F), Some(0x01)));
+        assert_eq!(rpdo.decode(&mut od, &[0x0F, 0x00]), Err(AbortCode::LengthTooLow));
+
+        // the TPDO 2 is disabled
+        let tpdos = Pdo::list(&od, PdoKind::Transmit);
+        assert_eq!(tpdos.iter().map(|v| v.number).collect::<Vec<_>>(), [1, 3]);
+        assert!(tpdos[0].is_synchronous());
+        assert_eq!(tpdos[0].encode(&od), Ok((-1000i32).to_le_bytes().to_vec()));
+        assert_eq!(tpdos[1].cob_id, CanId::Extended(0x385));
+        assert_eq!(tpdos[1].encode(&od), Err(AbortCode::NotMappable));
+    }
+}
@@ -0,0 +1,326 @@
+use std::time::{Duration, Instant};
+use crate::{canopen::{AbortCode, CanOpenError}, CanDevice, CanError};
+use super::{
+    abort_frame, initiate_data, initiate_frame, multiplexer, sdo_crc, segment_frame, segments, SdoCobId, SdoConfig, SdoLink,
+    BLOCK_ACK, BLOCK_END, BLOCK_INITIATE, BLOCK_START, CCS_BLOCK_DOWNLOAD, CCS_BLOCK_UPLOAD, CCS_DOWNLOAD_SEGMENT,
+    CCS_INITIATE_DOWNLOAD, CCS_INITIATE_UPLOAD, CCS_UPLOAD_SEGMENT, CS_ABORT, MAX_BLOCK_SIZE, SCS_BLOCK_DOWNLOAD,
+    SCS_BLOCK_UPLOAD, SCS_DOWNLOAD_SEGMENT, SCS_INITIATE_DOWNLOAD, SCS_INITIATE_UPLOAD, SCS_UPLOAD_SEGMENT, SEGMENT_SIZE,
+};
+
+/// The SDO client which reads(uploads) and writes(downloads) the object dictionary of a server.
+///
+/// The transfer is aborted with [`AbortCode::Timeout`] when no response received in the timeout,
+/// and with [`AbortCode::InvalidCommand`] when the response is unexpected.
+pub struct SdoClient<D: CanDevice> {
+    link: SdoLink<D>,
+    cob_id: SdoCobId,
+    config: SdoConfig,
+}
+
+impl<D: CanDevice> SdoClient<D>
+where
+    D::Channel: Clone,
+{
+    /// Create the client of the default SDO channel of node.
+    pub fn new(device: D, channel: D::Channel, node: u8) -> Self {
+        Self {
+            link: SdoLink::new(device, channel),
+            cob_id: SdoCobId::from_node(node),
+            config: Default::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn device(&self) -> &D {
+        &self.link.device
+    }
+
+    #[inline(always)]
+    pub fn cob_id(&self) -> &SdoCobId {
+        &self.cob_id
+    }
+
+    /// Override the COB-IDs of SDO channel.
+    pub fn set_cob_id(&mut self, cob_id: SdoCobId) -> &mut Self {
+        self.cob_id = cob_id;
+        self
+    }
+
+    #[inline(always)]
+    pub fn config(&self) -> &SdoConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: SdoConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Read the object by expedited or segmented transfer, which is selected by the server.
+    pub fn upload(&mut self, index: u16, subindex: u8) -> Result<Vec<u8>, CanOpenError> {
+        let response = self.request(index, subindex, &initiate_frame(CCS_INITIATE_UPLOAD << 5, index, subindex, [0x00; 4]))?;
+        self.expect_initiate(index, subindex, &response, SCS_INITIATE_UPLOAD)?;
+
+        let command = response[0];
+        if command & 0x02 != 0 {
+            let length = if command & 0x01 != 0 { 4 - (command >> 2 & 0x03) as usize } else { 4 };
+            return Ok(response[4..4 + length].to_vec());
+        }
+
+        let size = (command & 0x01 != 0).then(|| initiate_data(&response) as usize);
+        self.check_upload_size(index, subindex, size.unwrap_or_default())?;
+        // the buffer grows with the segments received instead of the size indicated
+        let mut data = Vec::new();
+        let mut toggle = 0;
+        loop {
+            let response = self.request(index, subindex, &segment_frame(CCS_UPLOAD_SEGMENT << 5 | toggle << 4, &[]))?;
+            self.expect(index, subindex, &response, SCS_UPLOAD_SEGMENT)?;
+            if response[0] >> 4 & 0x01 != toggle {
+                return Err(self.abort(index, subindex, AbortCode::ToggleBitNotAlternated));
+            }
+            let unused = (response[0] >> 1 & 0x07) as usize;
+            data.extend(&response[1..8 - unused]);
+            self.check_upload_size(index, subindex, data.len())?;
+            if response[0] & 0x01 != 0 {
+                break;
+            }
+            toggle ^= 0x01;
+        }
+
+        self.check_size(index, subindex, size, data.len())?;
+        Ok(data)
+    }
+
+    /// Write the object by expedited transfer when the data is not longer than 4 bytes, otherwise by segmented transfer.
+    pub fn download(&mut self, index: u16, subindex: u8, data: &[u8]) -> Result<(), CanOpenError> {
+        let length = data.len();
+        if (1..=4).contains(&length) {
+            let command = CCS_INITIATE_DOWNLOAD << 5 | ((4 - length) as u8) << 2 | 0x03;
+            let mut value = [0x00; 4];
+            value[..length].copy_from_slice(data);
+            let response = self.request(index, subindex, &initiate_frame(command, index, subindex, value))?;
+            return self.expect_initiate(index, subindex, &response, SCS_INITIATE_DOWNLOAD);
+        }
+
+        let size = self.size(data)?;
+        let response = self.request(index, subindex, &initiate_frame(CCS_INITIATE_DOWNLOAD << 5 | 0x01, index, subindex, size))?;
+        self.expect_initiate(index, subindex, &response, SCS_INITIATE_DOWNLOAD)?;
+
+        let segments = segments(data);
+        let count = segments.len();
+        let mut toggle = 0;
+        for (i, segment) in segments.into_iter().enumerate() {
+            let last = (i + 1 == count) as u8;
+            let command = CCS_DOWNLOAD_SEGMENT << 5 | toggle << 4 | ((SEGMENT_SIZE - segment.len()) as u8) << 1 | last;
+            let response = self.request(index, subindex, &segment_frame(command, segment))?;
+            self.expect(index, subindex, &response, SCS_DOWNLOAD_SEGMENT)?;
+            if response[0] >> 4 & 0x01 != toggle {
+                return Err(self.abort(index, subindex, AbortCode::ToggleBitNotAlternated));
+            }
+            toggle ^= 0x01;
+        }
+
+        Ok(())
+    }
+
+    /// Read the object by block transfer.
+    pub fn block_upload(&mut self, index: u16, subindex: u8) -> Result<Vec<u8>, CanOpenError> {
+        let block_size = self.config.block_size;
+        let command = CCS_BLOCK_UPLOAD << 5 | (self.config.crc as u8) << 2 | BLOCK_INITIATE;
+        // the protocol switch threshold is not used
+        let response = self.request(index, subindex, &initiate_frame(command, index, subindex, [block_size, 0x00, 0x00, 0x00]))?;
+        self.expect_initiate(index, subindex, &response, SCS_BLOCK_UPLOAD)?;
+        if response[0] & 0x01 != BLOCK_INITIATE {
+            return Err(self.invalid(index, subindex, &response));
+        }
+        let crc = self.config.crc && response[0] & 0x04 != 0;
+        let size = (response[0] & 0x02 != 0).then(|| initiate_data(&response) as usize);
+        self.check_upload_size(index, subindex, size.unwrap_or_default())?;
+
+        self.send(&segment_frame(CCS_BLOCK_UPLOAD << 5 | BLOCK_START, &[]))?;
+        let mut data = Vec::new();
+        let mut sequence = 0;
+        loop {
+            let segment = self.response(index, subindex)?;
+            let number = segment[0] & 0x7F;
+            let last = segment[0] & 0x80 != 0;
+            let accepted = number == sequence + 1;
+            if accepted {
+                data.extend(&segment[1..]);
+                self.check_upload_size(index, subindex, data.len())?;
+                sequence = number;
+            }
+            if number >= block_size || last {
+                self.send(&segment_frame(CCS_BLOCK_UPLOAD << 5 | BLOCK_ACK, &[sequence, block_size]))?;
+                if last && accepted {
+                    break;
+                }
+                sequence = 0;
+            }
+        }
+
+        let response = self.response(index, subindex)?;
+        self.expect(index, subindex, &response, SCS_BLOCK_UPLOAD)?;
+        if response[0] & 0x03 != BLOCK_END {
+            return Err(self.invalid(index, subindex, &response));
+        }
+        let unused = (response[0] >> 2 & 0x07) as usize;
+        data.truncate(data.len().saturating_sub(unused));
+        if crc && sdo_crc(&data) != u16::from_le_bytes([response[1], response[2]]) {
+            return Err(self.abort(index, subindex, AbortCode::CrcError));
+        }
+        self.check_size(index, subindex, size, data.len())?;
+        self.send(&segment_frame(CCS_BLOCK_UPLOAD << 5 | BLOCK_END, &[]))?;
+
+        Ok(data)
+    }
+
+    /// Write the object by block transfer, the segments not acknowledged are retransmitted.
+    pub fn block_download(&mut self, index: u16, subindex: u8, data: &[u8]) -> Result<(), CanOpenError> {
+        let size = self.size(data)?;
+        let command = CCS_BLOCK_DOWNLOAD << 5 | (self.config.crc as u8) << 2 | 0x02 | BLOCK_INITIATE;
+        let response = self.request(index, subindex, &initiate_frame(command, index, subindex, size))?;
+        self.expect_initiate(index, subindex, &response, SCS_BLOCK_DOWNLOAD)?;
+        if response[0] & 0x03 != BLOCK_INITIATE {
+            return Err(self.invalid(index, subindex, &response));
+        }
+        let crc = self.config.crc && response[0] & 0x04 != 0;
+        let mut block_size = self.check_block_size(index, subindex, response[4])?;
+
+        let segments = segments(data);
+        let mut offset = 0;
+        while offset < segments.len() {
+            let count = (block_size as usize).min(segments.len() - offset);
+            for (i, segment) in segments[offset..offset + count].iter().enumerate() {
+                let last = if offset + i + 1 == segments.len() { 0x80 } else { 0x00 };
+                self.send(&segment_frame(last | (i + 1) as u8, segment))?;
+            }
+
+            let response = self.response(index, subindex)?;
+            self.expect(index, subindex, &response, SCS_BLOCK_DOWNLOAD)?;
+            if response[0] & 0x03 != BLOCK_ACK {
+                return Err(self.invalid(index, subindex, &response));
+            }
+            let acknowledged = response[1] as usize;
+            if acknowledged > count {
+                return Err(self.abort(index, subindex, AbortCode::InvalidSequenceNumber));
+            }
+            offset += acknowledged;
+            block_size = self.check_block_size(index, subindex, response[2])?;
+        }
+
+        let unused = segments.last()
+            .map_or(SEGMENT_SIZE, |v| SEGMENT_SIZE - v.len()) as u8;
+        let checksum = if crc { sdo_crc(data) } else { 0x0000 };
+        let response = self.request(index, subindex, &segment_frame(CCS_BLOCK_DOWNLOAD << 5 | unused << 2 | BLOCK_END, &checksum.to_le_bytes()))?;
+        self.expect(index, subindex, &response, SCS_BLOCK_DOWNLOAD)?;
+        if response[0] & 0x03 != BLOCK_END {
+            return Err(self.invalid(index, subindex, &response));
+        }
+
+        Ok(())
+    }
+
+    /// Abort the transfer of object.
+    pub fn abort_transfer(&self, index: u16, subindex: u8, code: AbortCode) -> Result<(), CanOpenError> {
+        self.send(&abort_frame(index, subindex, code as u32))
+    }
+
+    /// Send the request and wait for the response.
+    fn request(&mut self, index: u16, subindex: u8, request: &[u8; 8]) -> Result<[u8; 8], CanOpenError> {
+        self.send(request)?;
+        self.response(index, subindex)
+    }
+
+    /// Wait for the response, the transfer is aborted when timeout.
+    fn response(&mut self, index: u16, subindex: u8) -> Result<[u8; 8], CanOpenError> {
+        let deadline = Instant::now() + Duration::from_millis(self.config.timeout as u64);
+        let response = match self.link.recv(self.cob_id.response, deadline)? {
+            Some(v) => v,
+            None => {
+                self.abort_transfer(index, subindex, AbortCode::Timeout)?;
+                return Err(CanError::TimeoutError(format!("no SDO response of {:#06X}:{:02X}", index, subindex)).into());
+            },
+        };
+        if response[0] == CS_ABORT << 5 {
+            let code = initiate_data(&response);
+            return Err(CanOpenError::SdoAborted { index, subindex, code });
+        }
+
+        Ok(response)
+    }
+
+    #[inline]
+    fn send(&self, data: &[u8; 8]) -> Result<(), CanOpenError> {
+        self.link.send(self.cob_id.request, data, self.config.timeout)
+            .map_err(CanOpenError::from)
+    }
+
+    /// Check the server command specifier of response.
+    #[inline]
+    fn expect(&self, index: u16, subindex: u8, response: &[u8; 8], command: u8) -> Result<(), CanOpenError> {
+        if response[0] >> 5 != command {
+            return Err(self.invalid(index, subindex, response));
+        }
+        Ok(())
+    }
+
+    /// Check the server command specifier, index and sub-index of initiate response.
+    #[inline]
+    fn expect_initiate(&self, index: u16, subindex: u8, response: &[u8; 8], command: u8) -> Result<(), CanOpenError> {
+        self.expect(index, subindex, response, command)?;
+        if multiplexer(response) != (index, subindex) {
+            return Err(self.invalid(index, subindex, response));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_size(&self, index: u16, subindex: u8, size: Option<usize>, length: usize) -> Result<(), CanOpenError> {
+        if size.is_some_and(|v| v != length) {
+            return Err(self.abort(index, subindex, AbortCode::LengthMismatch));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_upload_size(&self, index: u16, subindex: u8, size: usize) -> Result<(), CanOpenError> {
+        if size > self.config.max_upload_size {
+            return Err(self.abort(index, subindex, AbortCode::OutOfMemory));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_block_size(&self, index: u16, subindex: u8, block_size: u8) -> Result<u8, CanOpenError> {
+        if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(self.abort(index, subindex, AbortCode::InvalidBlockSize));
+        }
+        Ok(block_size)
+    }
+
+    #[inline]
+    fn size(&self, data: &[u8]) -> Result<[u8; 4], CanOpenError> {
+        u32::try_from(data.len())
+            .map(u32::to_le_bytes)
+            .map_err(|_| CanOpenError::InvalidParameter(format!("SDO data length {} is out of range", data.len())))
+    }
+
+    /// Abort the transfer with unexpected response.
+    #[inline]
+    fn invalid(&self, index: u16, subindex: u8, response: &[u8; 8]) -> CanOpenError {
+        if let Err(e) = self.abort_transfer(index, subindex, AbortCode::InvalidCommand) {
+            return e;
+        }
+        CanOpenError::InvalidResponse(response.to_vec())
+    }
+
+    /// Abort the transfer and return the error of abort code.
+    #[inline]
+    fn abort(&self, index: u16, subindex: u8, code: AbortCode) -> CanOpenError {
+        if let Err(e) = self.abort_transfer(index, subindex, code) {
+            return e;
+        }
+        CanOpenError::SdoAborted { index, subindex, code: code as u32 }
+    }
+}
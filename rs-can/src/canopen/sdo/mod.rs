@@ -0,0 +1,247 @@
+mod client;
+mod server;
+
+pub use client::*;
+pub use server::*;
+
+use std::{collections::VecDeque, time::Instant};
+use derive_getters::Getters;
+use crate::{CanDevice, CanError, CanFrame, CanId, MAX_FRAME_SIZE};
+
+/// The base of default COB-ID of SDO request(client to server).
+pub const SDO_REQUEST_BASE: u16 = 0x600;
+/// The base of default COB-ID of SDO response(server to client).
+pub const SDO_RESPONSE_BASE: u16 = 0x580;
+/// The default timeout of SDO transfer in milliseconds.
+pub const DEFAULT_SDO_TIMEOUT: u32 = 1000;
+/// The max count of segments in a block.
+pub const MAX_BLOCK_SIZE: u8 = 127;
+/// The default max size of data uploaded by client.
+pub const DEFAULT_MAX_UPLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+// the client command specifiers
+const CCS_DOWNLOAD_SEGMENT: u8 = 0;
+const CCS_INITIATE_DOWNLOAD: u8 = 1;
+const CCS_INITIATE_UPLOAD: u8 = 2;
+const CCS_UPLOAD_SEGMENT: u8 = 3;
+const CCS_BLOCK_UPLOAD: u8 = 5;
+const CCS_BLOCK_DOWNLOAD: u8 = 6;
+// the server command specifiers
+const SCS_UPLOAD_SEGMENT: u8 = 0;
+const SCS_DOWNLOAD_SEGMENT: u8 = 1;
+const SCS_INITIATE_UPLOAD: u8 = 2;
+const SCS_INITIATE_DOWNLOAD: u8 = 3;
+const SCS_BLOCK_DOWNLOAD: u8 = 5;
+const SCS_BLOCK_UPLOAD: u8 = 6;
+/// The command specifier of abort transfer.
+const CS_ABORT: u8 = 4;
+// the sub-commands of block transfer
+const BLOCK_INITIATE: u8 = 0;
+const BLOCK_END: u8 = 1;
+const BLOCK_ACK: u8 = 2;
+const BLOCK_START: u8 = 3;
+/// The data length of segment.
+const SEGMENT_SIZE: usize = 7;
+
+/// The COB-IDs of SDO channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct SdoCobId {
+    /// the identifier of client to server
+    #[getter(copy)]
+    request: CanId,
+    /// the identifier of server to client
+    #[getter(copy)]
+    response: CanId,
+}
+
+impl SdoCobId {
+    pub fn new(request: CanId, response: CanId) -> Self {
+        Self { request, response }
+    }
+
+    /// The default SDO channel of node(0x600 + node and 0x580 + node).
+    #[inline]
+    pub fn from_node(node: u8) -> Self {
+        Self::new(
+            CanId::Standard(SDO_REQUEST_BASE + (node & 0x7F) as u16),
+            CanId::Standard(SDO_RESPONSE_BASE + (node & 0x7F) as u16),
+        )
+    }
+}
+
+/// The configuration of SDO transfer, the timeout is in milliseconds.
+#[derive(Debug, Clone, Getters)]
+pub struct SdoConfig {
+    /// the timeout of waiting for response, or the next request of server
+    #[getter(copy)]
+    timeout: u32,
+    /// the count of segments in a block which is requested by client or server
+    #[getter(copy)]
+    block_size: u8,
+    /// verify the data of block transfer by CRC
+    #[getter(copy)]
+    crc: bool,
+    /// the max size of data uploaded, the larger upload is aborted with `OutOfMemory`
+    #[getter(copy)]
+    max_upload_size: usize,
+}
+
+impl Default for SdoConfig {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_SDO_TIMEOUT,
+            block_size: MAX_BLOCK_SIZE,
+            crc: true,
+            max_upload_size: DEFAULT_MAX_UPLOAD_SIZE,
+        }
+    }
+}
+
+impl SdoConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_timeout(&mut self, timeout: u32) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the block size, which is clamped into 1..=127.
+    pub fn set_block_size(&mut self, block_size: u8) -> &mut Self {
+        self.block_size = block_size.clamp(1, MAX_BLOCK_SIZE);
+        self
+    }
+
+    pub fn set_crc(&mut self, crc: bool) -> &mut Self {
+        self.crc = crc;
+        self
+    }
+
+    pub fn set_max_upload_size(&mut self, size: usize) -> &mut Self {
+        self.max_upload_size = size;
+        self
+    }
+}
+
+/// The CRC of block transfer(CRC-16-CCITT, polynomial 0x1021 and initial value 0).
+pub fn sdo_crc(data: &[u8]) -> u16 {
+    data.iter()
+        .fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+                if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 }
+            })
+        })
+}
+
+/// The frame of SDO with index, sub-index and 4 bytes data.
+#[inline]
+fn initiate_frame(command: u8, index: u16, subindex: u8, data: [u8; 4]) -> [u8; 8] {
+    let index = index.to_le_bytes();
+    [command, index[0], index[1], subindex, data[0], data[1], data[2], data[3]]
+}
+
+#[inline]
+fn abort_frame(index: u16, subindex: u8, code: u32) -> [u8; 8] {
+    initiate_frame(CS_ABORT << 5, index, subindex, code.to_le_bytes())
+}
+
+/// The frame of segment with the data less than 7 bytes.
+#[inline]
+fn segment_frame(command: u8, data: &[u8]) -> [u8; 8] {
+    let mut frame = [0x00; 8];
+    frame[0] = command;
+    frame[1..=data.len()].copy_from_slice(data);
+    frame
+}
+
+/// Get the index and sub-index of frame.
+#[inline]
+fn multiplexer(frame: &[u8; 8]) -> (u16, u8) {
+    (u16::from_le_bytes([frame[1], frame[2]]), frame[3])
+}
+
+/// Get the 4 bytes data of initiate frame.
+#[inline]
+fn initiate_data(frame: &[u8; 8]) -> u32 {
+    u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]])
+}
+
+/// Split the data into the segments, an empty segment is returned when the data is empty.
+#[inline]
+fn segments(data: &[u8]) -> Vec<&[u8]> {
+    if data.is_empty() {
+        vec![data]
+    }
+    else {
+        data.chunks(SEGMENT_SIZE).collect()
+    }
+}
+
+/// The device channel which SDO frames are transmitted and received on.
+struct SdoLink<D: CanDevice> {
+    device: D,
+    channel: D::Channel,
+    pending: VecDeque<D::Frame>,
+}
+
+impl<D: CanDevice> SdoLink<D>
+where
+    D::Channel: Clone,
+{
+    fn new(device: D, channel: D::Channel) -> Self {
+        Self { device, channel, pending: Default::default() }
+    }
+
+    fn send(&self, id: CanId, data: &[u8; 8], timeout: u32) -> Result<(), CanError> {
+        let mut frame = D::Frame::new(id, data)
+            .ok_or(CanError::other_error("can't create SDO frame"))?;
+        frame.set_channel(self.channel.clone());
+        self.device.transmit(frame, Some(timeout))
+    }
+
+    /// Receive the frame with the identifier until deadline, the other frames are dropped.
+    fn recv(&mut self, id: CanId, deadline: Instant) -> Result<Option<[u8; 8]>, CanError> {
+        loop {
+            while let Some(frame) = self.pending.pop_front() {
+                if frame.id() != id || frame.is_remote() || frame.is_error_frame() || frame.data().is_empty() {
+                    continue;
+                }
+                let data = frame.data();
+                let length = data.len().min(MAX_FRAME_SIZE);
+                let mut result = [0x00; 8];
+                result[..length].copy_from_slice(&data[..length]);
+                return Ok(Some(result));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let timeout = (deadline - now).as_millis().clamp(1, u32::MAX as u128) as u32;
+            match self.device.receive(self.channel.clone(), Some(timeout)) {
+                Ok(frames) => self.pending.extend(frames),
+                Err(CanError::TimeoutError(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CanId;
+    use super::{sdo_crc, SdoCobId, SdoConfig};
+
+    #[test]
+    fn test_sdo() {
+        assert_eq!(sdo_crc(b"123456789"), 0x31C3);
+        assert_eq!(sdo_crc(&[]), 0x0000);
+
+        let cob_id = SdoCobId::from_node(0x05);
+        assert_eq!((cob_id.request(), cob_id.response()), (CanId::Standard(0x605), CanId::Standard(0x585)));
+        let mut config = SdoConfig::new();
+        config.set_block_size(0);
+        assert_eq!(config.block_size(), 1);
+    }
+}
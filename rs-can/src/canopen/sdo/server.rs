@@ -0,0 +1,557 @@
+use std::{mem, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+use crate::{canopen::{AbortCode, CanOpenError, ObjectDictionary}, CanDevice};
+use super::{
+    abort_frame, initiate_data, initiate_frame, multiplexer, sdo_crc, segment_frame, segments, SdoCobId, SdoConfig, SdoLink,
+    BLOCK_ACK, BLOCK_END, BLOCK_INITIATE, BLOCK_START, CCS_BLOCK_DOWNLOAD, CCS_BLOCK_UPLOAD, CCS_DOWNLOAD_SEGMENT,
+    CCS_INITIATE_DOWNLOAD, CCS_INITIATE_UPLOAD, CCS_UPLOAD_SEGMENT, CS_ABORT, MAX_BLOCK_SIZE, SCS_BLOCK_DOWNLOAD,
+    SCS_BLOCK_UPLOAD, SCS_DOWNLOAD_SEGMENT, SCS_INITIATE_DOWNLOAD, SCS_INITIATE_UPLOAD, SCS_UPLOAD_SEGMENT, SEGMENT_SIZE,
+};
+
+/// The transfer in progress.
+enum Transfer {
+    Idle,
+    Download {
+        size: Option<usize>,
+        toggle: u8,
+        data: Vec<u8>,
+    },
+    Upload {
+        toggle: u8,
+        data: Vec<u8>,
+        offset: usize,
+    },
+    BlockDownload {
+        crc: bool,
+        size: Option<usize>,
+        block_size: u8,
+        /// the sequence number of last segment received in order
+        sequence: u8,
+        /// the last segment is received, waiting for the end of transfer
+        finished: bool,
+        data: Vec<u8>,
+    },
+    BlockUpload {
+        crc: bool,
+        block_size: u8,
+        data: Vec<u8>,
+        /// the count of segments acknowledged
+        offset: usize,
+        /// the count of segments transmitted in current block
+        sent: usize,
+        started: bool,
+    },
+}
+
+/// The SDO server which serves an [`ObjectDictionary`] to the clients.
+pub struct SdoServer<D: CanDevice> {
+    link: SdoLink<D>,
+    cob_id: SdoCobId,
+    config: SdoConfig,
+    od: Arc<Mutex<ObjectDictionary>>,
+    transfer: Transfer,
+    /// the index and sub-index of transfer in progress
+    multiplexer: (u16, u8),
+    last_request: Instant,
+}
+
+impl<D: CanDevice> SdoServer<D>
+where
+    D::Channel: Clone,
+{
+    /// Create the server on the default SDO channel of node.
+    pub fn new(device: D, channel: D::Channel, node: u8, od: ObjectDictionary) -> Self {
+        Self {
+            link: SdoLink::new(device, channel),
+            cob_id: SdoCobId::from_node(node),
+            config: Default::default(),
+            od: Arc::new(Mutex::new(od)),
+            transfer: Transfer::Idle,
+            multiplexer: Default::default(),
+            last_request: Instant::now(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn cob_id(&self) -> &SdoCobId {
+        &self.cob_id
+    }
+
+    /// Override the COB-IDs of SDO channel.
+    pub fn set_cob_id(&mut self, cob_id: SdoCobId) -> &mut Self {
+        self.cob_id = cob_id;
+        self
+    }
+
+    #[inline(always)]
+    pub fn config(&self) -> &SdoConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: SdoConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// The object dictionary which is shared with the running server.
+    #[inline(always)]
+    pub fn od(&self) -> Arc<Mutex<ObjectDictionary>> {
+        Arc::clone(&self.od)
+    }
+
+    /// Serve a request, return `false` when no request received in `timeout` milliseconds.
+    ///
+    /// The transfer in progress is aborted when the next request is not received in the timeout of config.
+    pub fn serve(&mut self, timeout: u32) -> Result<bool, CanOpenError> {
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        let request = match self.link.recv(self.cob_id.request, deadline)? {
+            Some(v) => v,
+            None => {
+                let expired = self.last_request.elapsed() >= Duration::from_millis(self.config.timeout as u64);
+                if !matches!(self.transfer, Transfer::Idle) && expired {
+                    log::debug!("RUST-CAN - SDO transfer of {:#06X}:{:02X} is timeout", self.multiplexer.0, self.multiplexer.1);
+                    self.transfer = Transfer::Idle;
+                    self.send(&abort_frame(self.multiplexer.0, self.multiplexer.1, AbortCode::Timeout as u32))?;
+                }
+                return Ok(false);
+            },
+        };
+
+        self.last_request = Instant::now();
+        let responses = match self.process(&request) {
+            Ok(v) => v,
+            Err(code) => {
+                self.transfer = Transfer::Idle;
+                vec![abort_frame(self.multiplexer.0, self.multiplexer.1, code as u32)]
+            },
+        };
+        responses.iter()
+            .try_for_each(|v| self.send(v))?;
+
+        Ok(true)
+    }
+
+    /// Serve the requests in background until the handle is stopped or dropped.
+    pub fn spawn(mut self) -> SdoServerHandle
+    where
+        D: Send + 'static,
+        D::Channel: Send,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let od = self.od();
+        let flag = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            while flag.load(Ordering::Acquire) {
+                if let Err(e) = self.serve(10) {
+                    log::warn!("RUST-CAN - {} when SDO serving", e);
+                }
+            }
+        });
+
+        SdoServerHandle { running, od, handle: Some(handle) }
+    }
+
+    #[inline]
+    fn send(&self, data: &[u8; 8]) -> Result<(), CanOpenError> {
+        self.link.send(self.cob_id.response, data, self.config.timeout)
+            .map_err(CanOpenError::from)
+    }
+
+    #[inline]
+    fn lock_od(&self) -> Result<MutexGuard<'_, ObjectDictionary>, AbortCode> {
+        self.od.lock()
+            .map_err(|_| AbortCode::GeneralError)
+    }
+
+    /// Process the request and return the responses.
+    fn process(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let command = request[0];
+        if command == CS_ABORT << 5 {
+            log::debug!("RUST-CAN - SDO transfer is aborted by client: {:#010X}", initiate_data(request));
+            self.transfer = Transfer::Idle;
+            return Ok(Vec::new());
+        }
+        if matches!(self.transfer, Transfer::BlockDownload { finished: false, .. }) {
+            return self.block_segment(request);
+        }
+
+        match command >> 5 {
+            CCS_INITIATE_DOWNLOAD => self.initiate_download(request),
+            CCS_DOWNLOAD_SEGMENT => self.download_segment(request),
+            CCS_INITIATE_UPLOAD => self.initiate_upload(request),
+            CCS_UPLOAD_SEGMENT => self.upload_segment(request),
+            CCS_BLOCK_DOWNLOAD => match command & 0x01 {
+                BLOCK_INITIATE => self.initiate_block_download(request),
+                _ => self.end_block_download(request),
+            },
+            CCS_BLOCK_UPLOAD => match command & 0x03 {
+                BLOCK_INITIATE => self.initiate_block_upload(request),
+                BLOCK_START => self.start_block_upload(),
+                BLOCK_ACK => self.block_ack(request),
+                _ => self.end_block_upload(),
+            },
+            _ => Err(AbortCode::InvalidCommand),
+        }
+    }
+
+    fn initiate_download(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let (index, subindex) = multiplexer(request);
+        self.multiplexer = (index, subindex);
+        self.transfer = Transfer::Idle;
+        let command = request[0];
+        if command & 0x02 != 0 {
+            let length = if command & 0x01 != 0 { 4 - (command >> 2 & 0x03) as usize } else { 4 };
+            self.lock_od()?.write(index, subindex, &request[4..4 + length])?;
+        }
+        else {
+            self.lock_od()?.check_access(index, subindex, true)?;
+            let size = (command & 0x01 != 0).then(|| initiate_data(request) as usize);
+            self.transfer = Transfer::Download { size, toggle: 0, data: Vec::new() };
+        }
+
+        Ok(vec![initiate_frame(SCS_INITIATE_DOWNLOAD << 5, index, subindex, [0x00; 4])])
+    }
+
+    fn download_segment(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::Download { size, toggle, data } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        if request[0] >> 4 & 0x01 != *toggle {
+            return Err(AbortCode::ToggleBitNotAlternated);
+        }
+        let unused = (request[0] >> 1 & 0x07) as usize;
+        data.extend(&request[1..8 - unused]);
+        if size.is_some_and(|v| data.len() > v) {
+            return Err(AbortCode::LengthTooHigh);
+        }
+
+        let response = segment_frame(SCS_DOWNLOAD_SEGMENT << 5 | *toggle << 4, &[]);
+        *toggle ^= 0x01;
+        if request[0] & 0x01 != 0 {
+            if size.is_some_and(|v| data.len() != v) {
+                return Err(AbortCode::LengthMismatch);
+            }
+            let data = mem::take(data);
+            self.transfer = Transfer::Idle;
+            let (index, subindex) = self.multiplexer;
+            self.lock_od()?.write(index, subindex, &data)?;
+        }
+
+        Ok(vec![response])
+    }
+
+    fn initiate_upload(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let (index, subindex) = multiplexer(request);
+        self.multiplexer = (index, subindex);
+        self.transfer = Transfer::Idle;
+        let data = self.lock_od()?.read(index, subindex)?.to_vec();
+        let length = data.len();
+        if (1..=4).contains(&length) {
+            let command = SCS_INITIATE_UPLOAD << 5 | ((4 - length) as u8) << 2 | 0x03;
+            let mut value = [0x00; 4];
+            value[..length].copy_from_slice(&data);
+            return Ok(vec![initiate_frame(command, index, subindex, value)]);
+        }
+
+        let size = u32::try_from(length)
+            .map_err(|_| AbortCode::OutOfMemory)?;
+        self.transfer = Transfer::Upload { toggle: 0, data, offset: 0 };
+        Ok(vec![initiate_frame(SCS_INITIATE_UPLOAD << 5 | 0x01, index, subindex, size.to_le_bytes())])
+    }
+
+    fn upload_segment(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::Upload { toggle, data, offset } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        if request[0] >> 4 & 0x01 != *toggle {
+            return Err(AbortCode::ToggleBitNotAlternated);
+        }
+        let end = (*offset + SEGMENT_SIZE).min(data.len());
+        let segment = &data[*offset..end];
+        let last = end == data.len();
+        let command = SCS_UPLOAD_SEGMENT << 5 | *toggle << 4 | ((SEGMENT_SIZE - segment.len()) as u8) << 1 | last as u8;
+        let response = segment_frame(command, segment);
+        *offset = end;
+        *toggle ^= 0x01;
+        if last {
+            self.transfer = Transfer::Idle;
+        }
+
+        Ok(vec![response])
+    }
+
+    fn initiate_block_download(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let (index, subindex) = multiplexer(request);
+        self.multiplexer = (index, subindex);
+        self.transfer = Transfer::Idle;
+        self.lock_od()?.check_access(index, subindex, true)?;
+
+        let command = request[0];
+        let block_size = self.config.block_size;
+        self.transfer = Transfer::BlockDownload {
+            crc: self.config.crc && command & 0x04 != 0,
+            size: (command & 0x02 != 0).then(|| initiate_data(request) as usize),
+            block_size,
+            sequence: 0,
+            finished: false,
+            data: Vec::new(),
+        };
+        let command = SCS_BLOCK_DOWNLOAD << 5 | (self.config.crc as u8) << 2 | BLOCK_INITIATE;
+        Ok(vec![initiate_frame(command, index, subindex, [block_size, 0x00, 0x00, 0x00])])
+    }
+
+    /// Receive the segment of block download, the block is acknowledged by the last segment of block.
+    fn block_segment(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::BlockDownload { size, block_size, sequence, finished, data, .. } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        let number = request[0] & 0x7F;
+        let last = request[0] & 0x80 != 0;
+        if number == *sequence + 1 {
+            data.extend(&request[1..]);
+            if size.is_some_and(|v| data.len() > v.div_ceil(SEGMENT_SIZE) * SEGMENT_SIZE) {
+                return Err(AbortCode::LengthTooHigh);
+            }
+            *sequence = number;
+            *finished = last;
+        }
+        else {
+            log::debug!("RUST-CAN - SDO block segment {} is unexpected, {} is acknowledged", number, sequence);
+        }
+        if number < *block_size && !last {
+            return Ok(Vec::new());
+        }
+
+        let response = segment_frame(SCS_BLOCK_DOWNLOAD << 5 | BLOCK_ACK, &[*sequence, *block_size]);
+        *sequence = 0;
+        Ok(vec![response])
+    }
+
+    fn end_block_download(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::BlockDownload { crc, size, finished: true, data, .. } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        let unused = (request[0] >> 2 & 0x07) as usize;
+        data.truncate(data.len().saturating_sub(unused));
+        if *crc && sdo_crc(data) != u16::from_le_bytes([request[1], request[2]]) {
+            return Err(AbortCode::CrcError);
+        }
+        if size.is_some_and(|v| v != data.len()) {
+            return Err(AbortCode::LengthMismatch);
+        }
+
+        let data = mem::take(data);
+        self.transfer = Transfer::Idle;
+        let (index, subindex) = self.multiplexer;
+        self.lock_od()?.write(index, subindex, &data)?;
+        Ok(vec![segment_frame(SCS_BLOCK_DOWNLOAD << 5 | BLOCK_END, &[])])
+    }
+
+    fn initiate_block_upload(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let (index, subindex) = multiplexer(request);
+        self.multiplexer = (index, subindex);
+        self.transfer = Transfer::Idle;
+        let data = self.lock_od()?.read(index, subindex)?.to_vec();
+        let block_size = request[4];
+        if !(1..=MAX_BLOCK_SIZE).contains(&block_size) {
+            return Err(AbortCode::InvalidBlockSize);
+        }
+        let size = u32::try_from(data.len())
+            .map_err(|_| AbortCode::OutOfMemory)?;
+
+        self.transfer = Transfer::BlockUpload {
+            crc: self.config.crc && request[0] & 0x04 != 0,
+            block_size,
+            data,
+            offset: 0,
+            sent: 0,
+            started: false,
+        };
+        let command = SCS_BLOCK_UPLOAD << 5 | (self.config.crc as u8) << 2 | 0x02 | BLOCK_INITIATE;
+        Ok(vec![initiate_frame(command, index, subindex, size.to_le_bytes())])
+    }
+
+    fn start_block_upload(&mut self) -> Result<Vec<[u8; 8]>, AbortCode> {
+        match &mut self.transfer {
+            Transfer::BlockUpload { started, .. } if !*started => *started = true,
+            _ => return Err(AbortCode::InvalidCommand),
+        }
+        self.upload_block()
+    }
+
+    /// Transmit the next block, or the end of transfer when all segments are acknowledged.
+    fn upload_block(&mut self) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::BlockUpload { crc, block_size, data, offset, sent, .. } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        let segments = segments(data);
+        if *offset >= segments.len() {
+            let unused = segments.last()
+                .map_or(SEGMENT_SIZE, |v| SEGMENT_SIZE - v.len()) as u8;
+            let checksum = if *crc { sdo_crc(data) } else { 0x0000 };
+            *sent = 0;
+            return Ok(vec![segment_frame(SCS_BLOCK_UPLOAD << 5 | unused << 2 | BLOCK_END, &checksum.to_le_bytes())]);
+        }
+
+        let count = (*block_size as usize).min(segments.len() - *offset);
+        let frames = segments[*offset..*offset + count].iter()
+            .enumerate()
+            .map(|(i, segment)| {
+                let last = if *offset + i + 1 == segments.len() { 0x80 } else { 0x00 };
+                segment_frame(last | (i + 1) as u8, segment)
+            })
+            .collect();
+        *sent = count;
+        Ok(frames)
+    }
+
+    fn block_ack(&mut self, request: &[u8; 8]) -> Result<Vec<[u8; 8]>, AbortCode> {
+        let Transfer::BlockUpload { block_size, offset, sent, started: true, .. } = &mut self.transfer else {
+            return Err(AbortCode::InvalidCommand);
+        };
+        let acknowledged = request[1] as usize;
+        if acknowledged > *sent {
+            return Err(AbortCode::InvalidSequenceNumber);
+        }
+        if !(1..=MAX_BLOCK_SIZE).contains(&request[2]) {
+            return Err(AbortCode::InvalidBlockSize);
+        }
+        *offset += acknowledged;
+        *block_size = request[2];
+        self.upload_block()
+    }
+
+    fn end_block_upload(&mut self) -> Result<Vec<[u8; 8]>, AbortCode> {
+        match &self.transfer {
+            Transfer::BlockUpload { started: true, sent: 0, .. } => {
+                self.transfer = Transfer::Idle;
+                Ok(Vec::new())
+            },
+            _ => Err(AbortCode::InvalidCommand),
+        }
+    }
+}
+
+/// The handle of the server running in background.
+pub struct SdoServerHandle {
+    running: Arc<AtomicBool>,
+    od: Arc<Mutex<ObjectDictionary>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl SdoServerHandle {
+    #[inline(always)]
+    pub fn od(&self) -> Arc<Mutex<ObjectDictionary>> {
+        Arc::clone(&self.od)
+    }
+
+    /// Stop the server and wait for the request being served.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - SDO server thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for SdoServerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{canopen::{AbortCode, AccessType, CanOpenError, ObjectDictionary, SdoClient, SdoCobId, SdoConfig}, CanError, CanId};
+    use crate::test_utils::new_device;
+    use super::SdoServer;
+
+    fn new_od() -> ObjectDictionary {
+        let mut od = ObjectDictionary::new();
+        od.add(0x1000, 0x00, AccessType::Const, &0x0002_0192u32.to_le_bytes())
+            .add(0x1017, 0x00, AccessType::ReadWrite, &[0x00; 2])
+            .add(0x6083, 0x00, AccessType::ReadWrite, &[0x00; 4])
+            .add_domain(0x1008, 0x00, AccessType::Const, b"servo drive")
+            .add_domain(0x1F50, 0x01, AccessType::ReadWrite, &[]);
+        od
+    }
+
+    #[test]
+    fn test_transfer() -> anyhow::Result<()> {
+        let channel = "sdo-transfer";
+        let server = SdoServer::new(new_device(channel)?, channel.into(), 0x05, new_od());
+        let handle = server.spawn();
+        let mut client = SdoClient::new(new_device(channel)?, channel.into(), 0x05);
+
+        // expedited
+        assert_eq!(client.upload(0x1000, 0x00)?, [0x92, 0x01, 0x02, 0x00]);
+        client.download(0x6083, 0x00, &1000u32.to_le_bytes())?;
+        assert_eq!(client.upload(0x6083, 0x00)?, 1000u32.to_le_bytes());
+        // segmented
+        assert_eq!(client.upload(0x1008, 0x00)?, b"servo drive");
+        let program = (0..100).map(|v| v as u8).collect::<Vec<_>>();
+        client.download(0x1F50, 0x01, &program)?;
+        assert_eq!(client.upload(0x1F50, 0x01)?, program);
+        client.download(0x1F50, 0x01, &[])?;
+        assert_eq!(client.upload(0x1F50, 0x01)?, []);
+
+        // block
+        let program = (0..1000).map(|v| v as u8).collect::<Vec<_>>();
+        client.block_download(0x1F50, 0x01, &program)?;
+        assert_eq!(handle.od().lock().unwrap().get(0x1F50, 0x01), Some(program.as_slice()));
+        assert_eq!(client.block_upload(0x1F50, 0x01)?, program);
+        let mut config = SdoConfig::new();
+        config.set_block_size(4).set_crc(false);
+        client.set_config(config);
+        assert_eq!(client.block_upload(0x1008, 0x00)?, b"servo drive");
+        client.block_download(0x1F50, 0x01, b"short")?;
+        assert_eq!(client.block_upload(0x1F50, 0x01)?, b"short");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abort() -> anyhow::Result<()> {
+        let channel = "sdo-abort";
+        // the server with COB-IDs overridden
+        let cob_id = SdoCobId::new(CanId::Standard(0x640), CanId::Standard(0x5C0));
+        let mut server = SdoServer::new(new_device(channel)?, channel.into(), 0x05, new_od());
+        server.set_cob_id(cob_id);
+        let _handle = server.spawn();
+        let mut client = SdoClient::new(new_device(channel)?, channel.into(), 0x05);
+        let mut config = SdoConfig::new();
+        config.set_timeout(100);
+        client.set_config(config);
+
+        let err = client.upload(0x1000, 0x00).unwrap_err();
+        assert!(matches!(err, CanOpenError::Transport(CanError::TimeoutError(_))));
+
+        client.set_cob_id(cob_id);
+        let err = client.download(0x1000, 0x00, &[0x00; 4]).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::ReadOnly));
+        let err = client.upload(0x2000, 0x00).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::ObjectNotExist));
+        let err = client.download(0x1017, 0x00, &[0x00; 4]).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::LengthTooHigh));
+        let err = client.block_download(0x1008, 0x00, &[0x00; 20]).unwrap_err();
+        assert!(matches!(err, CanOpenError::SdoAborted { index: 0x1008, subindex: 0x00, code: 0x0601_0002 }));
+        let err = client.block_upload(0x1F50, 0x02).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::SubIndexNotExist));
+        // the server is still working after aborted
+        client.download(0x1017, 0x00, &100u16.to_le_bytes())?;
+        assert_eq!(client.upload(0x1017, 0x00)?, 100u16.to_le_bytes());
+
+        // the size indicated by server is larger than the max upload size
+        let mut config = SdoConfig::new();
+        config.set_max_upload_size(8);
+        client.set_config(config);
+        let err = client.upload(0x1008, 0x00).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::OutOfMemory));
+        let err = client.block_upload(0x1008, 0x00).unwrap_err();
+        assert_eq!(err.abort_code(), Some(AbortCode::OutOfMemory));
+        assert_eq!(client.upload(0x1017, 0x00)?, 100u16.to_le_bytes());
+
+        Ok(())
+    }
+}
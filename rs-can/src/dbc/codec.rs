@@ -0,0 +1,89 @@
+use super::ByteOrder;
+
+/// Get the bit at position(bit `n` is bit `n % 8` of byte `n / 8`).
+#[inline]
+fn bit(data: &[u8], pos: u32) -> Option<u64> {
+    data.get(pos as usize / 8)
+        .map(|v| ((v >> (pos % 8)) & 0x01) as u64)
+}
+
+/// Set the bit at position, `None` is returned when the position is out of data.
+#[inline]
+fn set_bit(data: &mut [u8], pos: u32, value: u64) -> Option<()> {
+    let byte = data.get_mut(pos as usize / 8)?;
+    let mask = 1 << (pos % 8);
+    if value & 0x01 == 0 { *byte &= !mask; } else { *byte |= mask; }
+    Some(())
+}
+
+/// The next bit position towards the LSB of Motorola signal.
+#[inline]
+fn motorola_next(pos: u32) -> u32 {
+    if pos & 0x07 == 0 { pos + 15 } else { pos - 1 }
+}
+
+/// Extract the raw bits of signal, `None` is returned when the signal is out of data.
+///
+/// The start bit is the LSB of Intel signal, and the MSB of Motorola signal.
+pub(crate) fn extract(data: &[u8], start_bit: u32, size: u32, order: ByteOrder) -> Option<u64> {
+    let mut value = 0;
+    match order {
+        ByteOrder::LittleEndian => {
+            for i in (0..size).rev() {
+                value = (value << 1) | bit(data, start_bit + i)?;
+            }
+        },
+        ByteOrder::BigEndian => {
+            let mut pos = start_bit;
+            for i in 0..size {
+                value = (value << 1) | bit(data, pos)?;
+                if i + 1 < size {
+                    pos = motorola_next(pos);
+                }
+            }
+        },
+    }
+
+    Some(value)
+}
+
+/// Insert the raw bits of signal, `None` is returned when the signal is out of data.
+///
+/// The data is not modified when the signal is out of data.
+pub(crate) fn insert(data: &mut [u8], start_bit: u32, size: u32, order: ByteOrder, raw: u64) -> Option<()> {
+    extract(data, start_bit, size, order)?;
+    match order {
+        ByteOrder::LittleEndian => {
+            for i in 0..size {
+                set_bit(data, start_bit + i, raw >> i)?;
+            }
+        },
+        ByteOrder::BigEndian => {
+            let mut pos = start_bit;
+            for i in (0..size).rev() {
+                set_bit(data, pos, raw >> i)?;
+                if i > 0 {
+                    pos = motorola_next(pos);
+                }
+            }
+        },
+    }
+
+    Some(())
+}
+
+/// The mask of signal size.
+#[inline]
+pub(crate) fn mask(size: u32) -> u64 {
+    if size >= 64 { u64::MAX } else { (1 << size) - 1 }
+}
+
+/// Interpret the raw bits as signed integer.
+#[inline]
+pub(crate) fn sign_extend(raw: u64, size: u32) -> i64 {
+    if size >= 64 {
+        return raw as i64;
+    }
+    let shift = 64 - size;
+    ((raw << shift) as i64) >> shift
+}
@@ -0,0 +1,807 @@
+//! The DBC(CAN database) file and signal decoding/encoding.
+//!
+//! ```no_run
+//! use rs_can::{dbc::Dbc, CanFrame, vcan::CanMessage};
+//!
+//! let dbc = Dbc::open("vehicle.dbc").unwrap();
+//! let frame = CanMessage::new(0x100, &[0x12, 0x34, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]).unwrap();
+//! let decoded = dbc.decode(&frame).unwrap();
+//! for signal in decoded.signals() {
+//!     println!("{} = {} {}", signal.name(), signal.value, signal.unit());
+//! }
+//!
+//! let values = [("Speed", 46.6), ("Rpm", 2000.)].into_iter().collect();
+//! let frame: CanMessage = dbc.encode("Engine", &values).unwrap();
+//! ```
+mod codec;
+mod parser;
+
+use std::{collections::HashMap, fmt, fs, path::Path};
+use derive_getters::Getters;
+use crate::{CanError, CanFrame, CanId, CanType, DEFAULT_PADDING, EFF_MASK, MAX_FRAME_SIZE, SFF_MASK};
+
+/// The flag of extended identifier in DBC.
+const EXTENDED_FLAG: u32 = 0x8000_0000;
+/// The attribute of signal initial value, the value is raw value.
+const START_VALUE: &str = "GenSigStartValue";
+
+/// The error of malformed DBC file, the line and column start from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, column: usize, message: &str) -> Self {
+        Self { line, column, message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DBC line {} column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The byte order of signal, `@0` is big endian(Motorola) and `@1` is little endian(Intel).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LittleEndian,
+    BigEndian,
+}
+
+/// The value type of signal, the IEEE float types are defined by `SIG_VALTYPE_`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    #[default]
+    Unsigned,
+    Signed,
+    /// 32 bits IEEE float
+    Float,
+    /// 64 bits IEEE double
+    Double,
+}
+
+/// The multiplexer indicator of signal.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Multiplexing {
+    #[default]
+    None,
+    /// `M`
+    Multiplexor,
+    /// `m<n>`
+    Multiplexed(u64),
+    /// `m<n>M`
+    MultiplexedMultiplexor(u64),
+}
+
+/// The extended multiplexing of signal defined by `SG_MUL_VAL_`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExtendedMultiplex {
+    /// the name of multiplexor signal
+    pub switch: String,
+    /// the inclusive ranges of multiplexor value
+    pub ranges: Vec<(u64, u64)>,
+}
+
+/// The object type of attribute.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeObject {
+    #[default]
+    Network,
+    Node,
+    Message,
+    Signal,
+    EnvironmentVariable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeType {
+    Int(i64, i64),
+    Hex(i64, i64),
+    Float(f64, f64),
+    String,
+    Enum(Vec<String>),
+}
+
+/// The attribute definition of `BA_DEF_`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub object: AttributeObject,
+    pub value_type: AttributeType,
+}
+
+/// The attribute value, the value of enum attribute is the index of enum.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl AttributeValue {
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(v) => Some(*v as f64),
+            Self::Float(v) => Some(*v),
+            Self::String(v) => v.parse().ok(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u32,
+    pub size: u32,
+    pub byte_order: ByteOrder,
+    pub value_type: ValueType,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub receivers: Vec<String>,
+    pub multiplexing: Multiplexing,
+    pub extended_multiplexing: Vec<ExtendedMultiplex>,
+    /// the value descriptions of `VAL_`
+    pub values: Vec<(i64, String)>,
+    pub comment: Option<String>,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+impl Signal {
+    #[inline]
+    pub fn is_multiplexor(&self) -> bool {
+        matches!(self.multiplexing, Multiplexing::Multiplexor | Multiplexing::MultiplexedMultiplexor(_))
+    }
+
+    /// Extract the raw bits from data, `None` is returned when the signal is out of data.
+    #[inline]
+    pub fn decode_raw(&self, data: &[u8]) -> Option<u64> {
+        codec::extract(data, self.start_bit, self.size, self.byte_order)
+    }
+
+    /// Convert the raw bits to physical value: `raw * factor + offset`.
+    pub fn to_physical(&self, raw: u64) -> f64 {
+        let value = match self.value_type {
+            ValueType::Unsigned => raw as f64,
+            ValueType::Signed => codec::sign_extend(raw, self.size) as f64,
+            ValueType::Float => f32::from_bits(raw as u32) as f64,
+            ValueType::Double => f64::from_bits(raw),
+        };
+        value * self.factor + self.offset
+    }
+
+    /// Convert the physical value to raw bits: `(value - offset) / factor`.
+    ///
+    /// The value is checked by `[min|max]` unless both of them are zero,
+    /// and the raw value is checked by the size of signal.
+    pub fn to_raw(&self, value: f64) -> Result<u64, CanError> {
+        if !value.is_finite() {
+            return Err(CanError::OtherError(format!("value {} of signal {} is not finite", value, self.name)));
+        }
+        if self.min != 0. || self.max != 0. {
+            let tolerance = |bound: f64| 1e-9 * bound.abs().max(1.);
+            if value < self.min - tolerance(self.min) || value > self.max + tolerance(self.max) {
+                return Err(CanError::OtherError(
+                    format!("value {} of signal {} is out of range [{}|{}]", value, self.name, self.min, self.max)
+                ));
+            }
+        }
+        if self.factor == 0. {
+            return Err(CanError::OtherError(format!("factor of signal {} is zero", self.name)));
+        }
+
+        let scaled = (value - self.offset) / self.factor;
+        let out_of_size = || CanError::OtherError(
+            format!("raw value {} of signal {} is out of {} bits", scaled, self.name, self.size)
+        );
+        match self.value_type {
+            ValueType::Unsigned => {
+                let raw = scaled.round();
+                if raw < 0. || raw > codec::mask(self.size) as f64 {
+                    return Err(out_of_size());
+                }
+                Ok(raw as u64)
+            },
+            ValueType::Signed => {
+                let raw = scaled.round();
+                let max = (codec::mask(self.size) >> 1) as f64;
+                if raw < -max - 1. || raw > max {
+                    return Err(out_of_size());
+                }
+                Ok(raw as i64 as u64 & codec::mask(self.size))
+            },
+            ValueType::Float => Ok((scaled as f32).to_bits() as u64),
+            ValueType::Double => Ok(scaled.to_bits()),
+        }
+    }
+
+    /// Get the raw start value of `GenSigStartValue`, the default is used when the signal has no attribute.
+    pub fn start_raw(&self, default: Option<&AttributeValue>) -> u64 {
+        let value = self.attributes.get(START_VALUE)
+            .or(default)
+            .and_then(AttributeValue::as_f64)
+            .unwrap_or_default();
+        match self.value_type {
+            ValueType::Unsigned | ValueType::Signed => value as i64 as u64 & codec::mask(self.size),
+            ValueType::Float => (value as f32).to_bits() as u64,
+            ValueType::Double => value.to_bits(),
+        }
+    }
+
+    /// Get the description of raw value in value table.
+    pub fn description(&self, raw: u64) -> Option<&str> {
+        let value = match self.value_type {
+            ValueType::Signed => codec::sign_extend(raw, self.size),
+            _ => raw as i64,
+        };
+        self.values.iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, d)| d.as_str())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Message {
+    /// the identifier in DBC, the bit 31 is set by extended identifiers
+    pub id: u32,
+    pub name: String,
+    /// the size of data in bytes
+    pub size: u32,
+    pub transmitter: String,
+    /// the transmitters of `BO_TX_BU_`
+    pub transmitters: Vec<String>,
+    pub signals: Vec<Signal>,
+    pub comment: Option<String>,
+    pub attributes: HashMap<String, AttributeValue>,
+}
+
+impl Message {
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        self.id & EXTENDED_FLAG != 0 || self.id & EFF_MASK > SFF_MASK
+    }
+
+    #[inline]
+    pub fn can_id(&self) -> CanId {
+        CanId::from_bits(self.id & EFF_MASK, Some(self.is_extended()))
+    }
+
+    #[inline]
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter()
+            .find(|s| s.name == name)
+    }
+
+    /// Decode the signals which are active by multiplexing, the signals out of data are skipped.
+    pub fn decode(&self, data: &[u8]) -> Vec<SignalValue<'_>> {
+        self.signals.iter()
+            .filter(|s| self.is_active(s, data, 0))
+            .filter_map(|signal| {
+                let raw = signal.decode_raw(data)?;
+                Some(SignalValue { signal, raw, value: signal.to_physical(raw) })
+            })
+            .collect()
+    }
+
+    /// Encode the physical values into data, see [`Dbc::encode`].
+    ///
+    /// The signal which is not given is encoded by the start value of signal attribute.
+    pub fn encode(&self, values: &HashMap<&str, f64>) -> Result<Vec<u8>, CanError> {
+        self.encode_with(values, None)
+    }
+
+    pub(crate) fn encode_with(
+        &self,
+        values: &HashMap<&str, f64>,
+        start_value: Option<&AttributeValue>,
+    ) -> Result<Vec<u8>, CanError> {
+        if let Some(name) = values.keys().find(|&&n| self.signal(n).is_none()) {
+            return Err(CanError::OtherError(format!("signal {} is not defined in message {}", name, self.name)));
+        }
+
+        let size = self.size as usize;
+        let length = match size {
+            ..=MAX_FRAME_SIZE => size,
+            _ => match crate::utils::can_dlc(size, CanType::CanFd) {
+                -1 => return Err(CanError::OtherError(format!("size {} of message {} is out of range", size, self.name))),
+                v => v as usize,
+            },
+        };
+        let mut data = vec![DEFAULT_PADDING; length];
+        // the multiplexors are encoded before the signals which are selected by them
+        let mut encoded = vec![false; self.signals.len()];
+        loop {
+            let mut progress = false;
+            for (i, signal) in self.signals.iter().enumerate() {
+                if encoded[i] || !self.is_switch(signal) {
+                    continue;
+                }
+                if self.switches(signal).iter()
+                    .all(|&n| self.signals.iter().position(|s| s.name == n).is_none_or(|j| encoded[j])) {
+                    self.encode_signal(signal, values, start_value, &mut data)?;
+                    encoded[i] = true;
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+        for (i, signal) in self.signals.iter().enumerate() {
+            if !encoded[i] {
+                self.encode_signal(signal, values, start_value, &mut data)?;
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn encode_signal(
+        &self,
+        signal: &Signal,
+        values: &HashMap<&str, f64>,
+        start_value: Option<&AttributeValue>,
+        data: &mut [u8],
+    ) -> Result<(), CanError> {
+        let value = values.get(signal.name.as_str());
+        if !self.is_active(signal, data, 0) {
+            return match value {
+                Some(_) => Err(CanError::OtherError(format!("signal {} is not selected by multiplexor", signal.name))),
+                None => Ok(()),
+            };
+        }
+
+        let raw = match value {
+            Some(&v) => signal.to_raw(v)?,
+            None => signal.start_raw(start_value),
+        };
+        codec::insert(data, signal.start_bit, signal.size, signal.byte_order, raw)
+            .ok_or(CanError::OtherError(format!("signal {} is out of message {}", signal.name, self.name)))
+    }
+
+    /// Whether the signal selects the other signals.
+    fn is_switch(&self, signal: &Signal) -> bool {
+        signal.is_multiplexor() || self.signals.iter()
+            .any(|s| s.extended_multiplexing.iter().any(|m| m.switch == signal.name))
+    }
+
+    /// The names of the signals which select the signal.
+    fn switches<'a>(&'a self, signal: &'a Signal) -> Vec<&'a str> {
+        if !signal.extended_multiplexing.is_empty() {
+            return signal.extended_multiplexing.iter()
+                .map(|m| m.switch.as_str())
+                .collect();
+        }
+
+        match signal.multiplexing {
+            Multiplexing::Multiplexed(_) | Multiplexing::MultiplexedMultiplexor(_) => self.signals.iter()
+                .filter(|s| s.multiplexing == Multiplexing::Multiplexor)
+                .map(|s| s.name.as_str())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the signal is selected by its multiplexor.
+    fn is_active(&self, signal: &Signal, data: &[u8], depth: usize) -> bool {
+        if depth > self.signals.len() {
+            return false;
+        }
+
+        let switch_value = |name: &str| self.signal(name)
+            .filter(|s| self.is_active(s, data, depth + 1))
+            .and_then(|s| s.decode_raw(data));
+        if !signal.extended_multiplexing.is_empty() {
+            return signal.extended_multiplexing.iter()
+                .all(|m| switch_value(&m.switch)
+                    .is_some_and(|v| m.ranges.iter().any(|(min, max)| (*min..=*max).contains(&v))));
+        }
+
+        match signal.multiplexing {
+            Multiplexing::Multiplexed(value) | Multiplexing::MultiplexedMultiplexor(value) => {
+                self.signals.iter()
+                    .find(|s| s.multiplexing == Multiplexing::Multiplexor)
+                    .and_then(|s| switch_value(&s.name))
+                    .is_some_and(|v| v == value)
+            },
+            _ => true,
+        }
+    }
+}
+
+/// The decoded value of signal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SignalValue<'a> {
+    pub signal: &'a Signal,
+    pub raw: u64,
+    /// the physical value
+    pub value: f64,
+}
+
+impl SignalValue<'_> {
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.signal.name
+    }
+
+    #[inline]
+    pub fn unit(&self) -> &str {
+        &self.signal.unit
+    }
+
+    /// The description of value in value table.
+    #[inline]
+    pub fn description(&self) -> Option<&str> {
+        self.signal.description(self.raw)
+    }
+}
+
+/// The decoded message of frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedMessage<'a> {
+    message: &'a Message,
+    signals: Vec<SignalValue<'a>>,
+}
+
+impl<'a> DecodedMessage<'a> {
+    #[inline(always)]
+    pub fn message(&self) -> &'a Message {
+        self.message
+    }
+
+    #[inline(always)]
+    pub fn signals(&self) -> &[SignalValue<'a>] {
+        &self.signals
+    }
+
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&SignalValue<'a>> {
+        self.signals.iter()
+            .find(|s| s.name() == name)
+    }
+
+    /// The physical values by signal names.
+    pub fn values(&self) -> HashMap<&'a str, f64> {
+        self.signals.iter()
+            .map(|s| (s.signal.name.as_str(), s.value))
+            .collect()
+    }
+}
+
+/// The CAN database.
+#[derive(Debug, Default, Clone, Getters)]
+pub struct Dbc {
+    version: String,
+    nodes: Vec<String>,
+    /// the value tables of `VAL_TABLE_`
+    value_tables: HashMap<String, Vec<(i64, String)>>,
+    messages: Vec<Message>,
+    /// the comment of network
+    comment: Option<String>,
+    node_comments: HashMap<String, String>,
+    attribute_definitions: Vec<AttributeDefinition>,
+    /// the default values of `BA_DEF_DEF_`
+    attribute_defaults: HashMap<String, AttributeValue>,
+    /// the attributes of network
+    attributes: HashMap<String, AttributeValue>,
+    node_attributes: HashMap<String, HashMap<String, AttributeValue>>,
+    #[getter(skip)]
+    index: HashMap<u32, usize>,
+}
+
+impl Dbc {
+    /// Parse the content of DBC file.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        parser::parse(text)
+    }
+
+    /// Read and parse the DBC file, the file is decoded as UTF-8 or Latin-1.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let bytes = fs::read(path)
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        let text = match String::from_utf8(bytes) {
+            Ok(v) => v,
+            Err(e) => e.into_bytes()
+                .into_iter()
+                .map(char::from)
+                .collect(),
+        };
+        Self::parse(&text)
+            .map_err(|e| CanError::OtherError(e.to_string()))
+    }
+
+    #[inline]
+    pub fn message_by_id(&self, id: CanId) -> Option<&Message> {
+        self.index.get(&message_key(id))
+            .map(|&i| &self.messages[i])
+    }
+
+    #[inline]
+    pub fn message_by_name(&self, name: &str) -> Option<&Message> {
+        self.messages.iter()
+            .find(|m| m.name == name)
+    }
+
+    /// Decode the signals of frame by the message which has the same identifier.
+    pub fn decode<F: CanFrame + ?Sized>(&self, frame: &F) -> Result<DecodedMessage<'_>, CanError> {
+        if frame.is_remote() || frame.is_error_frame() {
+            return Err(CanError::other_error("only the data frames can be decoded"));
+        }
+        let id = frame.id();
+        let message = self.message_by_id(id)
+            .ok_or(CanError::OtherError(format!("message {:#X} is not defined", id.into_bits())))?;
+
+        Ok(DecodedMessage { message, signals: message.decode(frame.data()) })
+    }
+
+    /// Encode the physical values into frame by the message name.
+    ///
+    /// The signals which are not given are encoded by `GenSigStartValue`(raw value),
+    /// the multiplexed signals are selected by the given(or start) value of multiplexor,
+    /// and the unused bits are filled with `DEFAULT_PADDING`.
+    /// The CAN FD frame is created when the message size is greater than 8.
+    pub fn encode<F: CanFrame>(&self, name: &str, values: &HashMap<&str, f64>) -> Result<F, CanError> {
+        let message = self.message_by_name(name)
+            .ok_or(CanError::OtherError(format!("message {} is not defined", name)))?;
+        self.encode_message(message, values)
+    }
+
+    /// Encode the physical values into frame by the message identifier, see [`Dbc::encode`].
+    pub fn encode_by_id<F: CanFrame>(&self, id: CanId, values: &HashMap<&str, f64>) -> Result<F, CanError> {
+        let message = self.message_by_id(id)
+            .ok_or(CanError::OtherError(format!("message {:#X} is not defined", id.into_bits())))?;
+        self.encode_message(message, values)
+    }
+
+    fn encode_message<F: CanFrame>(&self, message: &Message, values: &HashMap<&str, f64>) -> Result<F, CanError> {
+        let data = message.encode_with(values, self.attribute_defaults.get(START_VALUE))?;
+        F::new(message.can_id(), &data)
+            .ok_or(CanError::OtherError(format!("can't create frame of message {}", message.name)))
+    }
+
+    pub(crate) fn build_index(&mut self) {
+        self.index = self.messages.iter()
+            .enumerate()
+            .map(|(i, m)| (message_key(m.can_id()), i))
+            .collect();
+    }
+}
+
+#[inline]
+fn message_key(id: CanId) -> u32 {
+    match id {
+        CanId::Standard(v) => v as u32,
+        CanId::Extended(v) => v | EXTENDED_FLAG,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::{CanFrame, CanId, CanType, vcan::CanMessage};
+    use super::{AttributeValue, ByteOrder, Dbc, Multiplexing, ValueType};
+
+    const DBC: &str = r#"VERSION "1.0"
+
+NS_ :
+    NS_DESC_
+    CM_
+    BA_DEF_
+    BA_
+    VAL_
+    SIG_VALTYPE_
+    SG_MUL_VAL_
+
+BS_:
+
+BU_: ECU Tester
+
+VAL_TABLE_ OnOff 1 "On" 0 "Off" ;
+
+BO_ 256 Engine: 8 ECU
+ SG_ Speed : 7|16@0+ (0.01,0) [0|655.35] "km/h" Tester
+ SG_ Temp : 19|12@0- (0.1,-40) [-244.8|164.7] "degC" Tester
+ SG_ Rpm : 32|16@1+ (0.25,0) [0|16383.75] "rpm" Tester,ECU
+ SG_ Gear : 48|4@1- (1,0) [-8|7] "" Tester
+ SG_ Flag : 52|1@1+ (1,0) [0|1] "" Tester
+
+BO_ 257 Ieee: 8 ECU
+ SG_ Value : 0|32@1- (1,0) [-1E+038|1E+038] "" Tester
+
+BO_ 512 Mux: 8 ECU
+ SG_ Selector M : 0|8@1+ (1,0) [0|255] "" Tester
+ SG_ SigA m0 : 8|16@1+ (1,0) [0|65535] "" Tester
+ SG_ SigB m1 : 8|8@1+ (1,0) [0|255] "" Tester
+ SG_ SubSel m1M : 16|8@1+ (1,0) [0|255] "" Tester
+ SG_ SigC m2 : 24|8@1+ (1,0) [0|255] "" Tester
+
+BO_ 2566844926 Ext: 8 Vector__XXX
+ SG_ Value : 0|8@1+ (1,0) [0|255] "" Vector__XXX
+
+BO_TX_BU_ 256 : ECU,Tester;
+
+CM_ "network comment";
+CM_ BU_ ECU "engine control unit";
+CM_ BO_ 256 "engine status";
+CM_ SG_ 256 Speed "vehicle
+speed";
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 10000;
+BA_DEF_ SG_ "GenSigStartValue" FLOAT -1E+038 1E+038;
+BA_DEF_ "BusType" STRING ;
+BA_DEF_ BU_ "NodeLayer" ENUM "A","B";
+BA_DEF_DEF_ "GenMsgCycleTime" 100;
+BA_DEF_DEF_ "GenSigStartValue" 0;
+BA_ "BusType" "CAN";
+BA_ "GenMsgCycleTime" BO_ 256 20;
+BA_ "GenSigStartValue" SG_ 256 Rpm 3200;
+BA_ "NodeLayer" BU_ ECU 1;
+VAL_ 256 Flag 1 "On" 0 "Off" ;
+VAL_ 256 Gear -1 "Reverse" 0 "Neutral" ;
+SIG_VALTYPE_ 257 Value : 1;
+SG_MUL_VAL_ 512 SubSel Selector 1-1;
+SG_MUL_VAL_ 512 SigC SubSel 2-3, 5-5;
+"#;
+
+    #[test]
+    fn test_parse() -> anyhow::Result<()> {
+        let dbc = Dbc::parse(DBC)?;
+        assert_eq!(dbc.version(), "1.0");
+        assert_eq!(dbc.nodes(), &["ECU", "Tester"]);
+        assert_eq!(dbc.value_tables()["OnOff"].len(), 2);
+        assert_eq!(dbc.messages().len(), 4);
+        assert_eq!(dbc.comment().as_deref(), Some("network comment"));
+        assert_eq!(dbc.node_comments()["ECU"], "engine control unit");
+        assert_eq!(dbc.attribute_definitions().len(), 4);
+        assert_eq!(dbc.attribute_defaults()["GenMsgCycleTime"], AttributeValue::Int(100));
+        assert_eq!(dbc.attributes()["BusType"], AttributeValue::String("CAN".into()));
+        assert_eq!(dbc.node_attributes()["ECU"]["NodeLayer"], AttributeValue::Int(1));
+
+        let message = dbc.message_by_name("Engine").unwrap();
+        assert_eq!(message.can_id(), CanId::Standard(0x100));
+        assert_eq!(message.transmitters, ["ECU", "Tester"]);
+        assert_eq!(message.comment.as_deref(), Some("engine status"));
+        assert_eq!(message.attributes["GenMsgCycleTime"], AttributeValue::Int(20));
+        let speed = message.signal("Speed").unwrap();
+        assert_eq!(speed.byte_order, ByteOrder::BigEndian);
+        assert_eq!(speed.comment.as_deref(), Some("vehicle\nspeed"));
+        assert_eq!(speed.unit, "km/h");
+        let rpm = message.signal("Rpm").unwrap();
+        assert_eq!(rpm.receivers, ["Tester", "ECU"]);
+        assert_eq!(rpm.attributes["GenSigStartValue"], AttributeValue::Int(3200));
+        let gear = message.signal("Gear").unwrap();
+        assert_eq!(gear.value_type, ValueType::Signed);
+        assert_eq!(gear.min, -8.);
+
+        let message = dbc.message_by_id(CanId::Extended(0x18FE_F1FE)).unwrap();
+        assert_eq!(message.name, "Ext");
+        assert!(message.is_extended());
+        assert_eq!(dbc.message_by_name("Ieee").unwrap().signals[0].value_type, ValueType::Float);
+        let mux = dbc.message_by_name("Mux").unwrap();
+        assert_eq!(mux.signals[0].multiplexing, Multiplexing::Multiplexor);
+        assert_eq!(mux.signals[3].multiplexing, Multiplexing::MultiplexedMultiplexor(1));
+        assert_eq!(mux.signals[4].extended_multiplexing[0].ranges, [(2, 3), (5, 5)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode() -> anyhow::Result<()> {
+        let dbc = Dbc::parse(DBC)?;
+
+        let frame = CanMessage::new(0x100, &[0x12, 0x34, 0x0F, 0x9C, 0x40, 0x1F, 0x1E, 0x00]).unwrap();
+        let decoded = dbc.decode(&frame)?;
+        assert_eq!(decoded.message().name, "Engine");
+        let values = decoded.values();
+        assert!((values["Speed"] - 46.6).abs() < 1e-9);
+        assert!((values["Temp"] + 50.).abs() < 1e-9);
+        assert_eq!(values["Rpm"], 2000.);
+        assert_eq!(values["Gear"], -2.);
+        assert_eq!(decoded.get("Flag").unwrap().description(), Some("On"));
+        assert_eq!(decoded.get("Speed").unwrap().unit(), "km/h");
+
+        let frame = CanMessage::new(0x101, &[0x00, 0x00, 0xC0, 0x3F, 0, 0, 0, 0]).unwrap();
+        assert_eq!(dbc.decode(&frame)?.values()["Value"], 1.5);
+
+        let frame = CanMessage::new(0x200, &[0x01, 0xAA, 0x03, 0x55, 0, 0, 0, 0]).unwrap();
+        let values = dbc.decode(&frame)?.values();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values["SigB"], 170.);
+        assert_eq!(values["SigC"], 85.);
+        let frame = CanMessage::new(0x200, &[0x01, 0xAA, 0x04, 0x55, 0, 0, 0, 0]).unwrap();
+        let values = dbc.decode(&frame)?.values();
+        assert!(!values.contains_key("SigC"));
+        let frame = CanMessage::new(0x200, &[0x00, 0x34, 0x12, 0x55, 0, 0, 0, 0]).unwrap();
+        let values = dbc.decode(&frame)?.values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["SigA"], 4660.);
+
+        let frame = CanMessage::new(CanId::Extended(0x18FE_F1FE), &[0x7F]).unwrap();
+        assert_eq!(dbc.decode(&frame)?.values()["Value"], 127.);
+        // the signals out of data are skipped
+        let frame = CanMessage::new(0x100, &[0x12, 0x34]).unwrap();
+        assert_eq!(dbc.decode(&frame)?.signals().len(), 1);
+        let frame = CanMessage::new(0x300, &[]).unwrap();
+        assert!(dbc.decode(&frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode() -> anyhow::Result<()> {
+        let dbc = Dbc::parse(DBC)?;
+
+        let values: HashMap<_, _> = [("Speed", 46.6), ("Temp", -50.), ("Rpm", 2000.), ("Gear", -2.), ("Flag", 1.)]
+            .into_iter()
+            .collect();
+        let frame: CanMessage = dbc.encode("Engine", &values)?;
+        assert_eq!(frame.id(), CanId::Standard(0x100));
+        assert_eq!(frame.can_type(), CanType::Can);
+        // the unused bits are filled with padding
+        assert_eq!(frame.data(), &[0x12, 0x34, 0xAF, 0x9C, 0x40, 0x1F, 0xBE, 0xAA]);
+        let decoded = dbc.decode(&frame)?.values();
+        for (name, value) in &values {
+            assert!((decoded[name] - value).abs() < 1e-9);
+        }
+
+        // the start values are used
+        let values = [("Speed", 10.)].into_iter().collect();
+        let frame: CanMessage = dbc.encode("Engine", &values)?;
+        let decoded = dbc.decode(&frame)?.values();
+        assert_eq!(decoded["Rpm"], 800.);
+        assert_eq!(decoded["Gear"], 0.);
+        assert!((decoded["Temp"] + 40.).abs() < 1e-9);
+
+        assert!(dbc.encode::<CanMessage>("Engine", &[("Speed", 700.)].into_iter().collect()).is_err());
+        assert!(dbc.encode::<CanMessage>("Engine", &[("Gear", 8.)].into_iter().collect()).is_err());
+        assert!(dbc.encode::<CanMessage>("Engine", &[("Unknown", 1.)].into_iter().collect()).is_err());
+        assert!(dbc.encode::<CanMessage>("Unknown", &HashMap::new()).is_err());
+
+        let frame: CanMessage = dbc.encode("Ieee", &[("Value", 1.5)].into_iter().collect())?;
+        assert_eq!(frame.data(), &[0x00, 0x00, 0xC0, 0x3F, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        // the multiplexed signals are selected by multiplexor
+        let values = [("Selector", 1.), ("SigB", 170.), ("SubSel", 3.), ("SigC", 85.)].into_iter().collect();
+        let frame: CanMessage = dbc.encode("Mux", &values)?;
+        assert_eq!(frame.data(), &[0x01, 0xAA, 0x03, 0x55, 0xAA, 0xAA, 0xAA, 0xAA]);
+        let frame: CanMessage = dbc.encode("Mux", &[("SigA", 0x1234 as f64)].into_iter().collect())?;
+        assert_eq!(frame.data(), &[0x00, 0x34, 0x12, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        let values = [("Selector", 1.), ("SigA", 1.)].into_iter().collect();
+        assert!(dbc.encode::<CanMessage>("Mux", &values).is_err());
+        let values = [("Selector", 1.), ("SubSel", 4.), ("SigC", 1.)].into_iter().collect();
+        assert!(dbc.encode::<CanMessage>("Mux", &values).is_err());
+
+        let frame: CanMessage = dbc.encode_by_id(CanId::Extended(0x18FE_F1FE), &[("Value", 127.)].into_iter().collect())?;
+        assert!(frame.is_extended());
+        assert_eq!(frame.data(), &[0x7F, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        let dbc = Dbc::parse("BO_ 768 Fd: 10 ECU\n SG_ Last : 72|8@1+ (1,0) [0|0] \"\" ECU\n")?;
+        let frame: CanMessage = dbc.encode("Fd", &[("Last", 1.)].into_iter().collect())?;
+        assert_eq!(frame.can_type(), CanType::CanFd);
+        assert_eq!(frame.data().len(), 12);
+        assert_eq!(frame.data()[9], 0x01);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let err = Dbc::parse("BO_ 100 Msg: 8 Node\n SG_ S : 0|8@2+ (1,0) [0|1] \"\" Node\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 14));
+        let err = Dbc::parse("BO_ 100 Msg: 8 Node\n SG_ S : 0|8@1+ (1,0) [0|1 \"\" Node\n").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 28));
+        assert_eq!(err.to_string(), "DBC line 2 column 28: expected `]`, found string \"\"");
+        let err = Dbc::parse("CM_ BO_ 100 \"unknown message\";").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+        let err = Dbc::parse("VERSION \"1.0\n").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 9));
+    }
+}
@@ -0,0 +1,685 @@
+use super::{AttributeDefinition, AttributeObject, AttributeType, AttributeValue, ByteOrder, Dbc, ExtendedMultiplex, Message, Multiplexing, ParseError, Signal, ValueType};
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident,
+    Number,
+    Str,
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    #[inline]
+    fn is_punct(&self, c: char) -> bool {
+        self.kind == TokenKind::Punct(c)
+    }
+
+    #[inline]
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Ident && self.text == keyword
+    }
+
+    fn describe(&self) -> String {
+        match self.kind {
+            TokenKind::Str => format!("string \"{}\"", self.text),
+            _ => format!("`{}`", self.text),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, ParseError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let (mut line, mut column) = (1, 1);
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c == '\n' {
+            line += 1;
+            column = 1;
+            pos += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            column += 1;
+            pos += 1;
+            continue;
+        }
+        if c == '/' && chars.get(pos + 1) == Some(&'/') {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+            continue;
+        }
+
+        let (start_line, start_column, start) = (line, column, pos);
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            TokenKind::Ident
+        }
+        else if c.is_ascii_digit()
+            || ((c == '-' || c == '+' || c == '.') && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit())) {
+            pos += 1;
+            while pos < chars.len() {
+                let c = chars[pos];
+                let exponent = (c == '-' || c == '+') && matches!(chars[pos - 1], 'e' | 'E');
+                if c.is_ascii_alphanumeric() || c == '.' || exponent {
+                    pos += 1;
+                }
+                else {
+                    break;
+                }
+            }
+            TokenKind::Number
+        }
+        else if c == '"' {
+            // the string may contain new lines
+            let mut value = String::new();
+            pos += 1;
+            column += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err(ParseError::new(start_line, start_column, "unterminated string")),
+                    Some('"') => break,
+                    Some('\\') if pos + 1 < chars.len() => {
+                        value.push(chars[pos + 1]);
+                        pos += 1;
+                        column += 1;
+                    },
+                    Some('\n') => {
+                        value.push('\n');
+                        line += 1;
+                        column = 0;
+                    },
+                    Some(&c) => value.push(c),
+                }
+                pos += 1;
+                column += 1;
+            }
+            pos += 1;
+            column += 1;
+            tokens.push(Token { kind: TokenKind::Str, text: value, line: start_line, column: start_column });
+            continue;
+        }
+        else {
+            pos += 1;
+            TokenKind::Punct(c)
+        };
+
+        column += pos - start;
+        tokens.push(Token { kind, text: chars[start..pos].iter().collect(), line: start_line, column: start_column });
+    }
+
+    Ok(tokens)
+}
+
+pub(crate) fn parse(text: &str) -> Result<Dbc, ParseError> {
+    let tokens = tokenize(text)?;
+    Parser { tokens, pos: 0, dbc: Default::default() }.parse()
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    dbc: Dbc,
+}
+
+impl Parser {
+    fn parse(mut self) -> Result<Dbc, ParseError> {
+        while let Some(token) = self.peek().cloned() {
+            if token.kind != TokenKind::Ident {
+                return Err(self.unexpected(&token, "keyword"));
+            }
+            self.pos += 1;
+            match token.text.as_str() {
+                "VERSION" => self.dbc.version = self.expect_str()?,
+                "NS_" => self.parse_new_symbols(&token)?,
+                "BS_" => self.skip_line(&token),
+                "BU_" => self.parse_nodes(&token)?,
+                "VAL_TABLE_" => {
+                    let name = self.expect_ident()?;
+                    let table = self.parse_value_descriptions()?;
+                    self.dbc.value_tables.insert(name, table);
+                },
+                "BO_" => self.parse_message()?,
+                "SG_" => return Err(self.error_at(&token, "signal is not in a message")),
+                "BO_TX_BU_" => self.parse_message_transmitters()?,
+                "CM_" => self.parse_comment()?,
+                "BA_DEF_" => self.parse_attribute_definition()?,
+                "BA_DEF_DEF_" => {
+                    let name = self.expect_str()?;
+                    let value = self.parse_attribute_value()?;
+                    self.expect_punct(';')?;
+                    self.dbc.attribute_defaults.insert(name, value);
+                },
+                "BA_" => self.parse_attribute()?,
+                "VAL_" => self.parse_value_table()?,
+                "SIG_VALTYPE_" => self.parse_signal_value_type()?,
+                "SG_MUL_VAL_" => self.parse_extended_multiplex()?,
+                _ => self.skip_statement(),
+            }
+        }
+
+        self.dbc.build_index();
+        Ok(self.dbc)
+    }
+
+    /// `NS_ :` followed by the indented symbols.
+    fn parse_new_symbols(&mut self, keyword: &Token) -> Result<(), ParseError> {
+        self.expect_punct(':')?;
+        while let Some(token) = self.peek() {
+            if token.line == keyword.line || token.column > 1 {
+                self.pos += 1;
+            }
+            else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// `BU_: node1 node2 ...`
+    fn parse_nodes(&mut self, keyword: &Token) -> Result<(), ParseError> {
+        self.expect_punct(':')?;
+        while let Some(token) = self.peek() {
+            if token.line != keyword.line || token.kind != TokenKind::Ident {
+                break;
+            }
+            self.dbc.nodes.push(token.text.clone());
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    /// `BO_ <id> <name>: <size> <transmitter>` followed by signals.
+    fn parse_message(&mut self) -> Result<(), ParseError> {
+        let id = self.expect_u32()?;
+        let name = self.expect_ident()?;
+        self.expect_punct(':')?;
+        let size = self.expect_u32()?;
+        let transmitter = self.expect_ident()?;
+        let mut message = Message {
+            id,
+            name,
+            size,
+            transmitter,
+            ..Default::default()
+        };
+
+        while self.peek().is_some_and(|t| t.is_keyword("SG_")) {
+            self.pos += 1;
+            message.signals.push(self.parse_signal()?);
+        }
+        self.dbc.messages.push(message);
+
+        Ok(())
+    }
+
+    /// `SG_ <name> [M|m<n>|m<n>M] : <start>|<size>@<order><sign> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>`
+    fn parse_signal(&mut self) -> Result<Signal, ParseError> {
+        let name_token = self.expect(TokenKind::Ident, "signal name")?;
+        let mut multiplexing = Multiplexing::None;
+        if let Some(token) = self.peek().cloned() {
+            if token.kind == TokenKind::Ident {
+                self.pos += 1;
+                multiplexing = parse_multiplexing(&token.text)
+                    .ok_or(self.error_at(&token, &format!("invalid multiplexer indicator `{}`", token.text)))?;
+            }
+        }
+        self.expect_punct(':')?;
+        let start_bit = self.expect_u32()?;
+        self.expect_punct('|')?;
+        let size = self.expect_u32()?;
+        self.expect_punct('@')?;
+        let order = self.expect(TokenKind::Number, "byte order")?;
+        let byte_order = match order.text.as_str() {
+            "0" => ByteOrder::BigEndian,
+            "1" => ByteOrder::LittleEndian,
+            _ => return Err(self.error_at(&order, "byte order must be 0 or 1")),
+        };
+        let token = self.next_token("value type")?;
+        let sign = match token.kind {
+            TokenKind::Punct(c @ ('+' | '-')) => c,
+            _ => return Err(self.unexpected(&token, "`+` or `-`")),
+        };
+        let value_type = if sign == '-' { ValueType::Signed } else { ValueType::Unsigned };
+        if size == 0 || size > 64 {
+            return Err(self.error_at(&name_token, "signal size must be in 1..=64"));
+        }
+
+        self.expect_punct('(')?;
+        let factor = self.expect_f64()?;
+        self.expect_punct(',')?;
+        let offset = self.expect_f64()?;
+        self.expect_punct(')')?;
+        self.expect_punct('[')?;
+        let min = self.expect_f64()?;
+        self.expect_punct('|')?;
+        let max = self.expect_f64()?;
+        self.expect_punct(']')?;
+        let unit = self.expect_str()?;
+
+        let mut receivers = Vec::new();
+        let line = self.tokens[self.pos - 1].line;
+        while let Some(token) = self.peek().cloned() {
+            if token.line != line {
+                break;
+            }
+            match token.kind {
+                TokenKind::Ident => receivers.push(token.text),
+                TokenKind::Punct(',') => {},
+                _ => return Err(self.unexpected(&token, "receiver")),
+            }
+            self.pos += 1;
+        }
+
+        Ok(Signal {
+            name: name_token.text,
+            start_bit,
+            size,
+            byte_order,
+            value_type,
+            factor,
+            offset,
+            min,
+            max,
+            unit,
+            receivers,
+            multiplexing,
+            ..Default::default()
+        })
+    }
+
+    /// `BO_TX_BU_ <id> : <node>,<node> ;`
+    fn parse_message_transmitters(&mut self) -> Result<(), ParseError> {
+        let (id, message) = self.expect_message()?;
+        self.expect_punct(':')?;
+        let mut transmitters = Vec::new();
+        loop {
+            let token = self.next_token("`;`")?;
+            match token.kind {
+                TokenKind::Punct(';') => break,
+                TokenKind::Punct(',') => {},
+                TokenKind::Ident => transmitters.push(token.text),
+                _ => return Err(self.unexpected(&token, "transmitter")),
+            }
+        }
+        self.message_mut(id, message)?.transmitters = transmitters;
+        Ok(())
+    }
+
+    /// `CM_ [BU_ <node>|BO_ <id>|SG_ <id> <signal>|EV_ <name>] "<comment>" ;`
+    fn parse_comment(&mut self) -> Result<(), ParseError> {
+        let token = self.next_token("comment")?;
+        match token.kind {
+            TokenKind::Str => self.dbc.comment = Some(token.text),
+            TokenKind::Ident => match token.text.as_str() {
+                "BU_" => {
+                    let node = self.expect_ident()?;
+                    let comment = self.expect_str()?;
+                    self.dbc.node_comments.insert(node, comment);
+                },
+                "BO_" => {
+                    let (id, message) = self.expect_message()?;
+                    let comment = self.expect_str()?;
+                    self.message_mut(id, message)?.comment = Some(comment);
+                },
+                "SG_" => {
+                    let (id, message) = self.expect_message()?;
+                    let signal = self.expect(TokenKind::Ident, "signal name")?;
+                    let comment = self.expect_str()?;
+                    self.signal_mut(id, &message, &signal)?.comment = Some(comment);
+                },
+                "EV_" => {
+                    self.expect_ident()?;
+                    self.expect_str()?;
+                },
+                _ => return Err(self.unexpected(&token, "`BU_`, `BO_`, `SG_`, `EV_` or string")),
+            },
+            _ => return Err(self.unexpected(&token, "comment")),
+        }
+        self.expect_punct(';')
+    }
+
+    /// `BA_DEF_ [BU_|BO_|SG_|EV_] "<name>" <type> ... ;`
+    fn parse_attribute_definition(&mut self) -> Result<(), ParseError> {
+        let mut token = self.next_token("attribute name")?;
+        let object = match token.text.as_str() {
+            _ if token.kind == TokenKind::Str => AttributeObject::Network,
+            "BU_" => AttributeObject::Node,
+            "BO_" => AttributeObject::Message,
+            "SG_" => AttributeObject::Signal,
+            "EV_" => AttributeObject::EnvironmentVariable,
+            _ => return Err(self.unexpected(&token, "attribute object type or name")),
+        };
+        if object != AttributeObject::Network {
+            token = self.expect(TokenKind::Str, "attribute name")?;
+        }
+        let name = token.text;
+
+        let kind = self.expect(TokenKind::Ident, "attribute value type")?;
+        let value_type = match kind.text.as_str() {
+            "INT" => AttributeType::Int(self.expect_i64()?, self.expect_i64()?),
+            "HEX" => AttributeType::Hex(self.expect_i64()?, self.expect_i64()?),
+            "FLOAT" => AttributeType::Float(self.expect_f64()?, self.expect_f64()?),
+            "STRING" => AttributeType::String,
+            "ENUM" => {
+                let mut values = vec![self.expect_str()?];
+                while self.peek().is_some_and(|t| t.is_punct(',')) {
+                    self.pos += 1;
+                    values.push(self.expect_str()?);
+                }
+                AttributeType::Enum(values)
+            },
+            _ => return Err(self.unexpected(&kind, "`INT`, `HEX`, `FLOAT`, `STRING` or `ENUM`")),
+        };
+        self.expect_punct(';')?;
+        self.dbc.attribute_definitions.push(AttributeDefinition { name, object, value_type });
+
+        Ok(())
+    }
+
+    /// `BA_ "<name>" [BU_ <node>|BO_ <id>|SG_ <id> <signal>|EV_ <name>] <value> ;`
+    fn parse_attribute(&mut self) -> Result<(), ParseError> {
+        let name = self.expect_str()?;
+        let token = self.peek().cloned()
+            .ok_or(self.eof("attribute value"))?;
+        if token.kind == TokenKind::Ident {
+            self.pos += 1;
+            match token.text.as_str() {
+                "BU_" => {
+                    let node = self.expect_ident()?;
+                    let value = self.parse_attribute_value()?;
+                    self.dbc.node_attributes.entry(node).or_default().insert(name, value);
+                },
+                "BO_" => {
+                    let (id, message) = self.expect_message()?;
+                    let value = self.parse_attribute_value()?;
+                    self.message_mut(id, message)?.attributes.insert(name, value);
+                },
+                "SG_" => {
+                    let (id, message) = self.expect_message()?;
+                    let signal = self.expect(TokenKind::Ident, "signal name")?;
+                    let value = self.parse_attribute_value()?;
+                    self.signal_mut(id, &message, &signal)?.attributes.insert(name, value);
+                },
+                "EV_" => {
+                    self.expect_ident()?;
+                    self.parse_attribute_value()?;
+                },
+                _ => return Err(self.unexpected(&token, "`BU_`, `BO_`, `SG_`, `EV_` or value")),
+            }
+        }
+        else {
+            let value = self.parse_attribute_value()?;
+            self.dbc.attributes.insert(name, value);
+        }
+        self.expect_punct(';')
+    }
+
+    fn parse_attribute_value(&mut self) -> Result<AttributeValue, ParseError> {
+        let token = self.next_token("attribute value")?;
+        match token.kind {
+            TokenKind::Str => Ok(AttributeValue::String(token.text)),
+            TokenKind::Number => match token.text.parse::<i64>() {
+                Ok(v) => Ok(AttributeValue::Int(v)),
+                Err(_) => parse_f64(&token.text)
+                    .map(AttributeValue::Float)
+                    .ok_or(self.error_at(&token, &format!("invalid number `{}`", token.text))),
+            },
+            _ => Err(self.unexpected(&token, "attribute value")),
+        }
+    }
+
+    /// `VAL_ <id> <signal> <value> "<description>" ... ;`, the descriptions of environment variables are skipped.
+    fn parse_value_table(&mut self) -> Result<(), ParseError> {
+        let token = self.next_token("message ID")?;
+        if token.kind == TokenKind::Ident {
+            self.parse_value_descriptions()?;
+            return Ok(());
+        }
+        let (id, message) = (self.number_u32(&token)?, token);
+        let signal = self.expect(TokenKind::Ident, "signal name")?;
+        let values = self.parse_value_descriptions()?;
+        self.signal_mut(id, &message, &signal)?.values = values;
+        Ok(())
+    }
+
+    fn parse_value_descriptions(&mut self) -> Result<Vec<(i64, String)>, ParseError> {
+        let mut values = Vec::new();
+        loop {
+            let token = self.next_token("`;`")?;
+            match token.kind {
+                TokenKind::Punct(';') => break,
+                TokenKind::Number => {
+                    let value = self.number_i64(&token)?;
+                    values.push((value, self.expect_str()?));
+                },
+                _ => return Err(self.unexpected(&token, "value or `;`")),
+            }
+        }
+        Ok(values)
+    }
+
+    /// `SIG_VALTYPE_ <id> <signal> : <0|1|2> ;`
+    fn parse_signal_value_type(&mut self) -> Result<(), ParseError> {
+        let (id, message) = self.expect_message()?;
+        let signal = self.expect(TokenKind::Ident, "signal name")?;
+        if self.peek().is_some_and(|t| t.is_punct(':')) {
+            self.pos += 1;
+        }
+        let token = self.expect(TokenKind::Number, "value type")?;
+        let value_type = match token.text.as_str() {
+            "0" => None,
+            "1" => Some(ValueType::Float),
+            "2" => Some(ValueType::Double),
+            _ => return Err(self.error_at(&token, "value type must be 0, 1 or 2")),
+        };
+        self.expect_punct(';')?;
+
+        let (value_type, size) = match value_type {
+            Some(ValueType::Float) => (ValueType::Float, 32),
+            Some(v) => (v, 64),
+            None => return Ok(()),
+        };
+        let err = self.error_at(&token, &format!("the size of signal `{}` must be {} for IEEE float", signal.text, size));
+        let sig = self.signal_mut(id, &message, &signal)?;
+        if sig.size != size {
+            return Err(err);
+        }
+        sig.value_type = value_type;
+        Ok(())
+    }
+
+    /// `SG_MUL_VAL_ <id> <signal> <switch> <min>-<max>, ... ;`
+    fn parse_extended_multiplex(&mut self) -> Result<(), ParseError> {
+        let (id, message) = self.expect_message()?;
+        let signal = self.expect(TokenKind::Ident, "signal name")?;
+        let switch = self.expect_ident()?;
+        let mut ranges = Vec::new();
+        loop {
+            let token = self.next_token("`;`")?;
+            match token.kind {
+                TokenKind::Punct(';') => break,
+                TokenKind::Punct(',') => {},
+                TokenKind::Number => {
+                    // `0-3` is tokenized as `0` and `-3`
+                    let min = self.number_u64(&token)?;
+                    let next = self.next_token("range")?;
+                    let max = match next.kind {
+                        TokenKind::Number if next.text.starts_with('-') => {
+                            let mut next = next;
+                            next.text.remove(0);
+                            self.number_u64(&next)?
+                        },
+                        TokenKind::Punct('-') => {
+                            let next = self.expect(TokenKind::Number, "range maximum")?;
+                            self.number_u64(&next)?
+                        },
+                        _ => return Err(self.unexpected(&next, "`-`")),
+                    };
+                    ranges.push((min, max));
+                },
+                _ => return Err(self.unexpected(&token, "range or `;`")),
+            }
+        }
+        self.signal_mut(id, &message, &signal)?
+            .extended_multiplexing
+            .push(ExtendedMultiplex { switch, ranges });
+
+        Ok(())
+    }
+
+    fn skip_line(&mut self, keyword: &Token) {
+        while self.peek().is_some_and(|t| t.line == keyword.line) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip the unsupported statements until `;`.
+    fn skip_statement(&mut self) {
+        while let Some(token) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            if token.is_punct(';') {
+                break;
+            }
+        }
+    }
+
+    fn message_mut(&mut self, id: u32, token: Token) -> Result<&mut Message, ParseError> {
+        let err = self.error_at(&token, &format!("message {} is not defined", id));
+        self.dbc.messages.iter_mut()
+            .find(|m| m.id == id)
+            .ok_or(err)
+    }
+
+    fn signal_mut(&mut self, id: u32, message: &Token, signal: &Token) -> Result<&mut Signal, ParseError> {
+        let err = self.error_at(signal, &format!("signal `{}` is not defined in message {}", signal.text, id));
+        self.message_mut(id, message.clone())?
+            .signals
+            .iter_mut()
+            .find(|s| s.name == signal.text)
+            .ok_or(err)
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next_token(&mut self, expected: &str) -> Result<Token, ParseError> {
+        let token = self.tokens.get(self.pos)
+            .cloned()
+            .ok_or(self.eof(expected))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token, ParseError> {
+        let token = self.next_token(expected)?;
+        if token.kind != kind {
+            return Err(self.unexpected(&token, expected));
+        }
+        Ok(token)
+    }
+
+    #[inline]
+    fn expect_punct(&mut self, c: char) -> Result<(), ParseError> {
+        self.expect(TokenKind::Punct(c), &format!("`{}`", c))
+            .map(|_| ())
+    }
+
+    #[inline]
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        self.expect(TokenKind::Ident, "identifier")
+            .map(|t| t.text)
+    }
+
+    #[inline]
+    fn expect_str(&mut self) -> Result<String, ParseError> {
+        self.expect(TokenKind::Str, "string")
+            .map(|t| t.text)
+    }
+
+    fn expect_u32(&mut self) -> Result<u32, ParseError> {
+        let token = self.expect(TokenKind::Number, "number")?;
+        self.number_u32(&token)
+    }
+
+    fn expect_i64(&mut self) -> Result<i64, ParseError> {
+        let token = self.expect(TokenKind::Number, "number")?;
+        self.number_i64(&token)
+    }
+
+    fn expect_f64(&mut self) -> Result<f64, ParseError> {
+        let token = self.expect(TokenKind::Number, "number")?;
+        parse_f64(&token.text)
+            .ok_or(self.error_at(&token, &format!("invalid number `{}`", token.text)))
+    }
+
+    #[inline]
+    fn expect_message(&mut self) -> Result<(u32, Token), ParseError> {
+        let token = self.expect(TokenKind::Number, "message ID")?;
+        Ok((self.number_u32(&token)?, token))
+    }
+
+    fn number_u32(&self, token: &Token) -> Result<u32, ParseError> {
+        token.text.parse::<u32>()
+            .map_err(|_| self.error_at(token, &format!("invalid unsigned integer `{}`", token.text)))
+    }
+
+    fn number_u64(&self, token: &Token) -> Result<u64, ParseError> {
+        token.text.parse::<u64>()
+            .map_err(|_| self.error_at(token, &format!("invalid unsigned integer `{}`", token.text)))
+    }
+
+    fn number_i64(&self, token: &Token) -> Result<i64, ParseError> {
+        token.text.parse::<i64>()
+            .map_err(|_| self.error_at(token, &format!("invalid integer `{}`", token.text)))
+    }
+
+    #[inline]
+    fn error_at(&self, token: &Token, msg: &str) -> ParseError {
+        ParseError::new(token.line, token.column, msg)
+    }
+
+    #[inline]
+    fn unexpected(&self, token: &Token, expected: &str) -> ParseError {
+        self.error_at(token, &format!("expected {}, found {}", expected, token.describe()))
+    }
+
+    fn eof(&self, expected: &str) -> ParseError {
+        let (line, column) = match self.tokens.last() {
+            Some(v) => (v.line, v.column + v.text.chars().count()),
+            None => (1, 1),
+        };
+        ParseError::new(line, column, &format!("expected {}, found end of file", expected))
+    }
+}
+
+fn parse_multiplexing(text: &str) -> Option<Multiplexing> {
+    if text == "M" {
+        return Some(Multiplexing::Multiplexor);
+    }
+    let value = text.strip_prefix('m')?;
+    match value.strip_suffix('M') {
+        Some(v) => v.parse().ok().map(Multiplexing::MultiplexedMultiplexor),
+        None => value.parse().ok().map(Multiplexing::Multiplexed),
+    }
+}
+
+#[inline]
+fn parse_f64(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok()
+        .filter(|v| v.is_finite())
+}
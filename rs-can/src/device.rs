@@ -4,10 +4,7 @@ use serde::{Deserialize, Serialize};
 use crate::error::Error;
 use crate::frame::{Frame, Id};
 
-#[cfg(not(feature = "async"))]
 pub type CanResult<R, E> = Result<R, E>;
-#[cfg(feature = "async")]
-pub type CanResult<R, E> = impl std::future::Future<Output = Result<R, E>>;
 
 pub trait Listener<C, F: Frame>: Send {
     fn as_any(&self) -> &dyn Any;
@@ -36,6 +33,30 @@ pub trait Device: Clone + TryFrom<DeviceBuilder, Error = Error> {
     fn shutdown(&mut self);
 }
 
+/// The device which transmits and receives frames without blocking the runtime.
+///
+/// The `timeout` is in milliseconds, and `None` means waiting until the operation is done.
+#[cfg(feature = "async")]
+pub trait AsyncDevice: Send + Sync {
+    type Channel: Display + Send;
+    type Frame: Frame<Channel = Self::Channel>;
+    type Stream: futures_core::Stream<Item = Result<Self::Frame, Error>> + Send + Unpin;
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.opened_channels().is_empty()
+    }
+    /// get all channels that has opened
+    fn opened_channels(&self) -> Vec<Self::Channel>;
+    /// Transmit a CAN or CAN-FD Frame.
+    fn transmit(&self, msg: Self::Frame, timeout: Option<u32>) -> impl std::future::Future<Output = Result<(), Error>> + Send;
+    /// Receive CAN and CAN-FD Frames, at least one frame is returned when success.
+    fn receive(&self, channel: Self::Channel, timeout: Option<u32>) -> impl std::future::Future<Output = Result<Vec<Self::Frame>, Error>> + Send;
+    /// Get the stream of frames received from the channel.
+    fn stream(&self, channel: Self::Channel) -> Result<Self::Stream, Error>;
+    /// Close CAN device.
+    fn shutdown(&mut self);
+}
+
 #[derive(Debug, Default, Deserialize, Serialize, Getters)]
 pub struct ChannelConfig {
     #[getter(copy)]
@@ -0,0 +1,60 @@
+use crate::CanError;
+use super::{parse_hex_bytes, ImageBuilder};
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+/// Parse the Intel HEX records.
+pub(crate) fn parse(text: &str) -> Result<ImageBuilder, CanError> {
+    let mut builder = ImageBuilder::default();
+    let mut base = 0u32;
+    for (i, line) in text.lines().enumerate() {
+        let error = |msg: &str| CanError::OtherError(format!("HEX line {}: {}", i + 1, msg));
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record = line.strip_prefix(':')
+            .ok_or(error("record must start with `:`"))?;
+        let bytes = parse_hex_bytes(record)
+            .ok_or(error("invalid hex digits"))?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(error("invalid record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, v| sum.wrapping_add(*v)) != 0 {
+            return Err(error("checksum mismatched"));
+        }
+
+        let offset = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            DATA => builder.add(base.wrapping_add(offset), data)
+                .map_err(|e| error(&e))?,
+            END_OF_FILE => return Ok(builder),
+            EXTENDED_SEGMENT_ADDRESS | EXTENDED_LINEAR_ADDRESS => {
+                if data.len() != 2 {
+                    return Err(error("invalid extended address"));
+                }
+                let value = u16::from_be_bytes([data[0], data[1]]) as u32;
+                base = if bytes[3] == EXTENDED_SEGMENT_ADDRESS { value << 4 } else { value << 16 };
+            },
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => {
+                if data.len() != 4 {
+                    return Err(error("invalid start address"));
+                }
+                let address = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                builder.start_address = Some(match bytes[3] {
+                    START_SEGMENT_ADDRESS => (address >> 16 << 4) + (address & 0xFFFF),
+                    _ => address,
+                });
+            },
+            v => return Err(error(&format!("unknown record type {:02X}", v))),
+        }
+    }
+
+    Err(CanError::other_error("HEX end of file record is missing"))
+}
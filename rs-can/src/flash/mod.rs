@@ -0,0 +1,531 @@
+//! The ECU flash programming with Intel HEX and Motorola S-record images.
+//!
+//! The [`Flasher`] downloads each segment of [`Image`] by the standard UDS sequence:
+//! erase routine, `RequestDownload`, `TransferData`, `RequestTransferExit` and check routine.
+//! The programming session and security access are requested by the [`UdsClient`] before flashing.
+//!
+//! ```no_run
+//! use rs_can::{flash::{Flasher, Image}, isotp::{Address, IsoTp}, uds::{SessionType, UdsClient}, vcan::VirtualCan, CanId};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//! let address = Address::new(CanId::Standard(0x7E0), CanId::Standard(0x7E8));
+//!
+//! let mut client = UdsClient::new(IsoTp::new(device, "vcan0".into(), address));
+//! client.set_security_algorithm(|_level, seed| Ok(seed.iter().map(|v| !v).collect()));
+//! client.session_control(SessionType::Programming, false).unwrap();
+//! client.security_access(0x11).unwrap();
+//!
+//! let image = Image::open("application.hex").unwrap();
+//! let mut flasher = Flasher::new(client);
+//! flasher.set_progress(|p| println!("{}/{} bytes", p.transferred, p.total));
+//! if flasher.flash(&image).is_err() {
+//!     // continue from the first segment which is not finished
+//!     flasher.flash(&image).unwrap();
+//! }
+//! ```
+mod ihex;
+mod srec;
+
+use std::{collections::BTreeMap, fs, path::Path};
+use crate::{uds::{memory_bytes, RoutineControlType, UdsClient, UdsError}, CanDevice, CanError};
+
+/// The default `dataFormatIdentifier`, no compression and no encryption.
+pub const DEFAULT_DATA_FORMAT: u8 = 0x00;
+/// The default `addressAndLengthFormatIdentifier`, 4 bytes of memory size and 4 bytes of memory address.
+pub const DEFAULT_ADDRESS_FORMAT: u8 = 0x44;
+/// The `eraseMemory` routine identifier.
+pub const ERASE_MEMORY_ROUTINE: u16 = 0xFF00;
+/// The default routine identifier of checking memory.
+pub const CHECK_MEMORY_ROUTINE: u16 = 0x0202;
+/// The default count of retries of a `TransferData` request.
+pub const DEFAULT_RETRIES: u8 = 3;
+
+/// The checksum of segment data which is the option record of check routine.
+pub type Checksum = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+/// The callback of flashing progress.
+pub type ProgressCallback = Box<dyn FnMut(&Progress) + Send>;
+
+/// The continuous memory of image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// The records of image collected by parsers, the contiguous records are merged by [`ImageBuilder::build`].
+#[derive(Debug, Default)]
+pub(crate) struct ImageBuilder {
+    records: BTreeMap<u32, Vec<u8>>,
+    pub(crate) start_address: Option<u32>,
+}
+
+impl ImageBuilder {
+    pub(crate) fn add(&mut self, address: u32, data: &[u8]) -> Result<(), String> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = address as u64 + data.len() as u64;
+        if end > u32::MAX as u64 + 1 {
+            return Err(format!("data at {:#X} exceeds the address space", address));
+        }
+        let overlapped = self.records.range(..=(end - 1) as u32)
+            .next_back()
+            .is_some_and(|(&k, v)| k as u64 + v.len() as u64 > address as u64);
+        if overlapped {
+            return Err(format!("data at {:#X} is overlapped", address));
+        }
+
+        self.records.insert(address, data.to_vec());
+        Ok(())
+    }
+
+    pub(crate) fn build(self) -> Image {
+        let mut segments: Vec<Segment> = Vec::new();
+        for (address, data) in self.records {
+            match segments.last_mut() {
+                Some(v) if v.address as u64 + v.data.len() as u64 == address as u64 => v.data.extend(data),
+                _ => segments.push(Segment { address, data }),
+            }
+        }
+
+        Image { segments, start_address: self.start_address }
+    }
+}
+
+/// The firmware image which is divided into segments by the gaps of memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    segments: Vec<Segment>,
+    start_address: Option<u32>,
+}
+
+impl Image {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Self { segments, start_address: None }
+    }
+
+    /// Parse the Intel HEX file content.
+    #[inline]
+    pub fn from_ihex(text: &str) -> Result<Self, CanError> {
+        ihex::parse(text).map(ImageBuilder::build)
+    }
+
+    /// Parse the Motorola S-record(S19, S28 and S37) file content.
+    #[inline]
+    pub fn from_srec(text: &str) -> Result<Self, CanError> {
+        srec::parse(text).map(ImageBuilder::build)
+    }
+
+    /// Read and parse the image file, the format is detected by the first record.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        match text.trim_start().chars().next() {
+            Some(':') => Self::from_ihex(&text),
+            Some('S') => Self::from_srec(&text),
+            _ => Err(CanError::other_error("unknown image format")),
+        }
+    }
+
+    #[inline(always)]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// The entry point of start address record.
+    #[inline(always)]
+    pub fn start_address(&self) -> Option<u32> {
+        self.start_address
+    }
+
+    /// The total length of data.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.segments.iter()
+            .map(|v| v.data.len())
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The progress of flashing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// the index of segment in flashing
+    pub segment: usize,
+    pub segments: usize,
+    /// the transferred bytes of the whole image
+    pub transferred: usize,
+    pub total: usize,
+}
+
+/// The CRC-32(IEEE 802.3) of data, which is the default checksum of check routine.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFF_FFFFu32, |crc, &v| {
+        (0..8).fold(crc ^ v as u32, |crc, _| {
+            if crc & 0x01 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 }
+        })
+    })
+}
+
+/// The programmer of ECU which downloads the image by [`UdsClient`].
+///
+/// The segments finished are recorded, so the next [`Flasher::flash`] resumes from the failed segment.
+pub struct Flasher<D: CanDevice> {
+    client: UdsClient<D>,
+    data_format: u8,
+    address_format: u8,
+    erase_routine: Option<u16>,
+    check_routine: Option<u16>,
+    checksum: Checksum,
+    retries: u8,
+    progress: Option<ProgressCallback>,
+    checkpoint: usize,
+}
+
+impl<D: CanDevice> Flasher<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(client: UdsClient<D>) -> Self {
+        Self {
+            client,
+            data_format: DEFAULT_DATA_FORMAT,
+            address_format: DEFAULT_ADDRESS_FORMAT,
+            erase_routine: Some(ERASE_MEMORY_ROUTINE),
+            check_routine: Some(CHECK_MEMORY_ROUTINE),
+            checksum: Box::new(|data| crc32(data).to_be_bytes().to_vec()),
+            retries: DEFAULT_RETRIES,
+            progress: None,
+            checkpoint: 0,
+        }
+    }
+
+    #[inline(always)]
+    pub fn client(&self) -> &UdsClient<D> {
+        &self.client
+    }
+
+    #[inline(always)]
+    pub fn client_mut(&mut self) -> &mut UdsClient<D> {
+        &mut self.client
+    }
+
+    #[inline(always)]
+    pub fn into_client(self) -> UdsClient<D> {
+        self.client
+    }
+
+    /// Set the `dataFormatIdentifier` and `addressAndLengthFormatIdentifier` of `RequestDownload`.
+    pub fn set_format(&mut self, data_format: u8, address_format: u8) -> &mut Self {
+        self.data_format = data_format;
+        self.address_format = address_format;
+        self
+    }
+
+    /// Set the erase routine which is started with the address format, memory address and memory size,
+    /// the segment is not erased when it is `None`.
+    pub fn set_erase_routine(&mut self, id: Option<u16>) -> &mut Self {
+        self.erase_routine = id;
+        self
+    }
+
+    /// Set the check routine which is started with the checksum after the segment downloaded,
+    /// the segment is not checked when it is `None`.
+    pub fn set_check_routine(&mut self, id: Option<u16>) -> &mut Self {
+        self.check_routine = id;
+        self
+    }
+
+    pub fn set_checksum<F>(&mut self, checksum: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+    {
+        self.checksum = Box::new(checksum);
+        self
+    }
+
+    /// Set the count of retries when a `TransferData` request failed, the block is sent with the same counter.
+    pub fn set_retries(&mut self, retries: u8) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn set_progress<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: FnMut(&Progress) + Send + 'static,
+    {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// The count of segments finished by the last failed flashing.
+    #[inline(always)]
+    pub fn checkpoint(&self) -> usize {
+        self.checkpoint
+    }
+
+    /// Set the count of segments to skip, it should be reset when the image is changed.
+    pub fn set_checkpoint(&mut self, checkpoint: usize) -> &mut Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Flash the image, the segments finished by the last failed flashing are skipped.
+    pub fn flash(&mut self, image: &Image) -> Result<(), UdsError> {
+        let segments = image.segments();
+        if self.checkpoint > segments.len() {
+            self.checkpoint = 0;
+        }
+        let mut progress = Progress {
+            segment: self.checkpoint,
+            segments: segments.len(),
+            transferred: segments[..self.checkpoint].iter().map(|v| v.data.len()).sum(),
+            total: image.len(),
+        };
+
+        for (i, segment) in segments.iter().enumerate().skip(self.checkpoint) {
+            progress.segment = i;
+            self.flash_segment(segment, &mut progress)?;
+            self.checkpoint = i + 1;
+        }
+
+        self.checkpoint = 0;
+        Ok(())
+    }
+
+    fn flash_segment(&mut self, segment: &Segment, progress: &mut Progress) -> Result<(), UdsError> {
+        let size = u32::try_from(segment.data.len())
+            .map_err(|_| UdsError::InvalidParameter(format!("segment at {:#X} is too large", segment.address)))?;
+        let memory = memory_bytes(self.address_format, segment.address, size)?;
+
+        if let Some(id) = self.erase_routine {
+            let mut option = vec![self.address_format];
+            option.extend(&memory);
+            let status = self.client.routine_control(RoutineControlType::Start, id, &option)?;
+            check_status(status)?;
+        }
+
+        let max_length = self.client.request_download(self.data_format, self.address_format, segment.address, size)?;
+        // the service identifier and block sequence counter are included
+        let block_length = max_length.saturating_sub(2);
+        if block_length == 0 {
+            return Err(UdsError::InvalidParameter(format!("invalid maxNumberOfBlockLength {}", max_length)));
+        }
+        log::debug!("RUST-CAN - flash segment at {:#X} with {} bytes, block length {}", segment.address, size, block_length);
+
+        for (i, block) in segment.data.chunks(block_length).enumerate() {
+            // the counter starts from 1 and wraps to 0
+            let counter = (i + 1) as u8;
+            self.transfer_block(counter, block)?;
+            progress.transferred += block.len();
+            if let Some(callback) = self.progress.as_mut() {
+                callback(progress);
+            }
+        }
+
+        self.client.request_transfer_exit(&[])?;
+        if let Some(id) = self.check_routine {
+            let option = (self.checksum)(&segment.data);
+            let status = self.client.routine_control(RoutineControlType::Start, id, &option)?;
+            check_status(status)?;
+        }
+
+        Ok(())
+    }
+
+    fn transfer_block(&mut self, counter: u8, block: &[u8]) -> Result<(), UdsError> {
+        let mut retries = self.retries;
+        loop {
+            match self.client.transfer_data(counter, block) {
+                Ok(_) => return Ok(()),
+                Err(e) if retries > 0 => {
+                    log::warn!("RUST-CAN - transfer block {:#04X} failed: {}, retry", counter, e);
+                    retries -= 1;
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Check the routine status record, the first byte is zero when the routine is correct.
+#[inline]
+fn check_status(status: Vec<u8>) -> Result<(), UdsError> {
+    match status.first() {
+        Some(&v) if v != 0 => Err(UdsError::InvalidResponse(status)),
+        _ => Ok(()),
+    }
+}
+
+/// Parse the hex digits without separators.
+pub(crate) fn parse_hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if text.len() & 0x01 != 0 || !text.bytes().all(|v| v.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use crate::vcan::VirtualCan;
+    use crate::test_utils::new_isotp;
+    use crate::uds::{Ecu, ResponseCode, RoutineControlType, SessionType, UdsClient, UdsError, UdsServer};
+    use super::{crc32, Flasher, Image, Segment};
+
+    const HEX: &str = "\
+:020000040800F2
+:10000000000102030405060708090A0B0C0D0E0F78
+:0400100010111213A6
+:02002000AABB79
+:0400000508000101ED
+:00000001FF
+";
+
+    const SREC: &str = "\
+S00600004844521B
+S107100001020304DE
+S2060010040506DA
+S30808000000070809D7
+S70508000000F2
+";
+
+    fn new_ecu() -> Ecu {
+        let mut ecu = Ecu::new();
+        ecu.add_security_level(0x11, |seed| seed.iter().map(|v| !v).collect())
+            .add_routine(0xFF00, Some(0x11), |control, option| match control {
+                RoutineControlType::Start if option.len() == 9 && option[0] == 0x44 => Ok(vec![0x00]),
+                _ => Err(ResponseCode::RequestOutOfRange),
+            })
+            .add_routine(0x0202, Some(0x11), |_, option| Ok(vec![(option.len() != 4) as u8]))
+            // 5 bytes of data in single frame
+            .set_max_block_length(7);
+        ecu
+    }
+
+    fn new_flasher(channel: &str) -> anyhow::Result<Flasher<VirtualCan>> {
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        client.set_security_algorithm(|_, seed| Ok(seed.iter().map(|v| !v).collect()));
+        client.session_control(SessionType::Programming, false)?;
+        client.security_access(0x11)?;
+        Ok(Flasher::new(client))
+    }
+
+    #[test]
+    fn test_ihex() -> anyhow::Result<()> {
+        let image = Image::from_ihex(HEX)?;
+        assert_eq!(image.segments(), [
+            Segment { address: 0x0800_0000, data: (0..20).collect() },
+            Segment { address: 0x0800_0020, data: vec![0xAA, 0xBB] },
+        ]);
+        assert_eq!(image.start_address(), Some(0x0800_0101));
+        assert_eq!(image.len(), 22);
+
+        let err = Image::from_ihex(&HEX.replace(":02002000AABB79", ":02002000AABB78")).unwrap_err();
+        assert_eq!(err.to_string(), "RUST-CAN - other error: HEX line 4: checksum mismatched");
+        assert!(Image::from_ihex(&HEX.replace(":00000001FF\n", "")).is_err());
+        // overlapped with the first record
+        assert!(Image::from_ihex(&HEX.replace(":02002000AABB79", ":0200080000AA4C")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_srec() -> anyhow::Result<()> {
+        let image = Image::from_srec(SREC)?;
+        assert_eq!(image.segments(), [
+            Segment { address: 0x1000, data: vec![1, 2, 3, 4, 5, 6] },
+            Segment { address: 0x0800_0000, data: vec![7, 8, 9] },
+        ]);
+        assert_eq!(image.start_address(), Some(0x0800_0000));
+
+        assert!(Image::from_srec(&SREC.replace("S107100001020304DE", "S107100001020304DF")).is_err());
+        assert!(Image::from_srec(&SREC.replace("S107", "S407")).is_err());
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flash() -> anyhow::Result<()> {
+        let channel = "flash-download";
+        let server = UdsServer::new(new_isotp(channel, 0x7E8, 0x7E0)?, new_ecu());
+        let handle = server.spawn();
+
+        // more than 255 blocks, the counter is wrapped
+        let data = (0..1500).map(|v| v as u8).collect::<Vec<_>>();
+        let image = Image::new(vec![
+            Segment { address: 0x0800_0000, data: data.clone() },
+            Segment { address: 0x0801_0000, data: vec![0x55; 12] },
+        ]);
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let mut flasher = new_flasher(channel)?;
+        let records = Arc::clone(&progress);
+        flasher.set_progress(move |p| records.lock().unwrap().push(*p));
+        flasher.flash(&image)?;
+
+        let progress = progress.lock().unwrap();
+        assert_eq!(progress.len(), 300 + 3);
+        let last = progress.last().unwrap();
+        assert_eq!((last.segment, last.segments, last.transferred, last.total), (1, 2, 1512, 1512));
+        let ecu = handle.ecu();
+        let ecu = ecu.lock().unwrap();
+        assert_eq!(ecu.memory().get(&0x0800_0000), Some(&data));
+        assert_eq!(ecu.memory().get(&0x0801_0000), Some(&vec![0x55; 12]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume() -> anyhow::Result<()> {
+        let channel = "flash-resume";
+        let erased = Arc::new(Mutex::new(Vec::new()));
+        let records = Arc::clone(&erased);
+        let mut ecu = new_ecu();
+        ecu.add_routine(0xFF00, None, move |_, option| {
+            let address = u32::from_be_bytes([option[1], option[2], option[3], option[4]]);
+            let mut records = records.lock().unwrap();
+            records.push(address);
+            // the second segment is failed to erase at the first time
+            Ok(vec![(records.len() == 2) as u8])
+        });
+        let server = UdsServer::new(new_isotp(channel, 0x7E8, 0x7E0)?, ecu);
+        let handle = server.spawn();
+
+        let image = Image::new(vec![
+            Segment { address: 0x1000, data: vec![0x01; 20] },
+            Segment { address: 0x2000, data: vec![0x02; 20] },
+        ]);
+        let mut flasher = new_flasher(channel)?;
+        // the block is sent again with the same counter
+        handle.ecu().lock().unwrap().inject_nrc(0x36, 0x21, 2);
+        let err = flasher.flash(&image).unwrap_err();
+        assert!(matches!(err, UdsError::InvalidResponse(v) if v == [0x01]));
+        assert_eq!(flasher.checkpoint(), 1);
+
+        flasher.set_retries(0);
+        handle.ecu().lock().unwrap().inject_nrc(0x36, 0x72, 1);
+        let err = flasher.flash(&image).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::GeneralProgrammingFailure));
+        assert_eq!(flasher.checkpoint(), 1);
+
+        flasher.flash(&image)?;
+        assert_eq!(flasher.checkpoint(), 0);
+        assert_eq!(*erased.lock().unwrap(), [0x1000, 0x2000, 0x2000, 0x2000]);
+        let ecu = handle.ecu();
+        let ecu = ecu.lock().unwrap();
+        assert_eq!(ecu.memory().get(&0x1000), Some(&vec![0x01; 20]));
+        assert_eq!(ecu.memory().get(&0x2000), Some(&vec![0x02; 20]));
+
+        Ok(())
+    }
+}
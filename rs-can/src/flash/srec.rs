@@ -0,0 +1,49 @@
+use crate::CanError;
+use super::{parse_hex_bytes, ImageBuilder};
+
+/// Parse the Motorola S-records(S19, S28 and S37).
+pub(crate) fn parse(text: &str) -> Result<ImageBuilder, CanError> {
+    let mut builder = ImageBuilder::default();
+    for (i, line) in text.lines().enumerate() {
+        let error = |msg: &str| CanError::OtherError(format!("S-record line {}: {}", i + 1, msg));
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record_type = line.strip_prefix('S')
+            .and_then(|v| v.chars().next())
+            .ok_or(error("record must start with `S`"))?;
+        let bytes = line.get(2..)
+            .and_then(parse_hex_bytes)
+            .ok_or(error("invalid hex digits"))?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(error("invalid record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, v| sum.wrapping_add(*v)) != 0xFF {
+            return Err(error("checksum mismatched"));
+        }
+
+        let address_size = match record_type {
+            '0' | '1' | '5' | '9' => 2,
+            '2' | '6' | '8' => 3,
+            '3' | '7' => 4,
+            _ => return Err(error(&format!("unknown record type S{}", record_type))),
+        };
+        let body = &bytes[1..bytes.len() - 1];
+        if body.len() < address_size {
+            return Err(error("invalid address"));
+        }
+        let address = body[..address_size].iter()
+            .fold(0u32, |address, v| (address << 8) | *v as u32);
+        let data = &body[address_size..];
+        match record_type {
+            '1' | '2' | '3' => builder.add(address, data)
+                .map_err(|e| error(&e))?,
+            '7' | '8' | '9' => builder.start_address = Some(address),
+            // the header and the count of records
+            _ => {},
+        }
+    }
+
+    Ok(builder)
+}
@@ -1,7 +1,8 @@
 mod identifier;
 pub use identifier::*;
 
-use std::fmt::{Display, Formatter, Write};
+use std::fmt::{Display, Formatter};
+use crate::trace::asc;
 use crate::utils::can_dlc;
 
 #[repr(C)]
@@ -24,8 +25,8 @@ pub enum Direct {
 impl Display for Direct {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Transmit => f.write_str("Tx"),
-            Self::Receive => f.write_str("Rx"),
+            Self::Transmit => f.pad("Tx"),
+            Self::Receive => f.pad("Rx"),
         }
     }
 }
@@ -48,7 +49,7 @@ pub trait Frame: Send + Sync {
     where
         Self: Sized;
 
-    /// Prioritizes returning J1939Id if j1939 is true.
+    /// The J1939 identifier is parsed from the extended identifier by [`J1939Id`](crate::j1939::J1939Id).
     fn id(&self) -> Id;
 
     fn can_type(&self) -> Type;
@@ -106,63 +107,7 @@ pub trait Frame: Send + Sync {
 impl<T: Display> Display for dyn Frame<Channel = T> {
     /// Output Frame as `asc` String.
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let data_str = if self.is_remote() {
-            " ".to_owned()
-        } else {
-            self.data()
-                .iter()
-                .fold(String::new(), |mut out, &b| {
-                    let _ = write!(out, "{b:02x} ");
-                    out
-                })
-        };
-
-        match self.can_type() {
-            Type::Can => {
-                write!(f, "{:.3} {} {}{: <4} {} {} {} {}",
-                       self.timestamp() as f64 / 1000.,
-                       self.channel(),
-                       format!("{: >8x}", self.id().into_bits()),
-                       if self.is_extended() { "x" } else { "" },
-                       self.direct(),
-                       // if self.is_rx() { "Rx" } else { "Tx" },
-                       if self.is_remote() { "r" } else { "d" },
-                       format!("{: >2}", self.length()),
-                       data_str,
-                )
-            },
-            Type::CanFd => {
-                let mut flags = 1 << 12;
-                write!(f, "{:.3} CANFD {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
-                       self.timestamp() as f64 / 1000.,
-                       self.channel(),
-                       self.direct(),
-                       // if self.is_rx() { "Rx" } else { "Tx" },
-                       format!("{: >8x}", self.id().into_bits()),
-                       if self.is_bitrate_switch() {
-                           flags |= 1 << 13;
-                           1
-                       } else { 0 },
-                       if self.is_esi() {
-                           flags |= 1 << 14;
-                           1
-                       } else { 0 },
-                       format!("{: >2}", self.dlc()),
-                       format!("{: >2}", self.length()),
-                       data_str,
-                       format!("{: >8}", 0),       // message_duration
-                       format!("{: <4}", 0),       // message_length
-                       format!("{: >8x}", flags),
-                       format!("{: >8}", 0),       // crc
-                       format!("{: >8}", 0),       // bit_timing_conf_arb
-                       format!("{: >8}", 0),       // bit_timing_conf_data
-                       format!("{: >8}", 0),       // bit_timing_conf_ext_arb
-                       format!("{: >8}", 0),       // bit_timing_conf_ext_data
-                )
-            },
-            Type::CanXl => {    // TODO
-                write!(f, "CANXL Frame")
-            }
-        }
+        write!(f, "{:.6} ", self.timestamp() as f64 / 1000.)?;
+        asc::fmt_frame(f, self.channel(), self, &Default::default())
     }
 }
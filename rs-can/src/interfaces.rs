@@ -1,3 +1,4 @@
-pub const NI_CAN: &'static str = "nican";
-pub const SOCKETCAN: &'static str = "socketcan";
-pub const ZLGCAN: &'static str = "zlgcan";
+pub const NI_CAN: &str = "nican";
+pub const SOCKETCAN: &str = "socketcan";
+pub const ZLGCAN: &str = "zlgcan";
+pub const VIRTUAL: &str = "virtual";
@@ -0,0 +1,660 @@
+//! The ISO-TP(ISO 15765-2) transport layer over any [`CanDevice`].
+//!
+//! The transport is implemented in user space, so that it works the same on the devices
+//! without kernel ISO-TP support. The transmitting and receiving are half-duplex, and the frames
+//! which are not addressed to the transport are dropped while it is receiving.
+//!
+//! ```no_run
+//! use rs_can::{isotp::{Address, IsoTp}, vcan::VirtualCan, CanId};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//! let mut address = Address::new(CanId::Standard(0x7E0), CanId::Standard(0x7E8));
+//! address.set_functional_id(CanId::Standard(0x7DF));
+//!
+//! let mut isotp = IsoTp::new(device, "vcan0".into(), address);
+//! isotp.transmit(&[0x22, 0xF1, 0x90]).unwrap();
+//! let response = isotp.receive(1000).unwrap();
+//! ```
+mod pdu;
+
+pub use pdu::FlowStatus;
+
+use std::{collections::VecDeque, thread, time::{Duration, Instant}};
+use derive_getters::Getters;
+use crate::{CanDevice, CanError, CanFrame, CanId, CanType, DEFAULT_PADDING, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE};
+use self::pdu::{st_min_duration, Pdu, MAX_SHORT_LENGTH};
+
+/// The default timeout of N_As, N_Bs and N_Cr in milliseconds.
+pub const DEFAULT_TIMEOUT: u32 = 1000;
+/// The default max count of `FC.WAIT` accepted in a row(N_WFTmax).
+pub const DEFAULT_MAX_WAIT: u8 = 10;
+/// The default max length of message received.
+pub const DEFAULT_MAX_LENGTH: usize = 4 * 1024 * 1024;
+
+/// The addressing format of ISO-TP.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Addressing {
+    /// the addresses are carried by the identifiers only
+    #[default]
+    Normal,
+    /// the first byte of data is the target address,
+    /// `source` is expected in the received frames and `target` is written into the transmitted frames
+    Extended { source: u8, target: u8 },
+    /// the first byte of data is the address extension
+    Mixed(u8),
+}
+
+impl Addressing {
+    #[inline]
+    fn tx_prefix(&self) -> Option<u8> {
+        match self {
+            Self::Normal => None,
+            Self::Extended { target, .. } => Some(*target),
+            Self::Mixed(extension) => Some(*extension),
+        }
+    }
+
+    #[inline]
+    fn rx_prefix(&self) -> Option<u8> {
+        match self {
+            Self::Normal => None,
+            Self::Extended { source, .. } => Some(*source),
+            Self::Mixed(extension) => Some(*extension),
+        }
+    }
+
+    #[inline]
+    fn prefix_len(&self) -> usize {
+        match self {
+            Self::Normal => 0,
+            _ => 1,
+        }
+    }
+}
+
+/// The target type of message(N_TAtype).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetType {
+    Physical,
+    Functional,
+}
+
+/// The address information of transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Getters)]
+pub struct Address {
+    #[getter(copy)]
+    tx_id: CanId,
+    #[getter(copy)]
+    rx_id: CanId,
+    /// the functional requests are transmitted and accepted by this identifier
+    #[getter(copy)]
+    functional_id: Option<CanId>,
+    #[getter(copy)]
+    addressing: Addressing,
+}
+
+impl Address {
+    pub fn new(tx_id: CanId, rx_id: CanId) -> Self {
+        Self {
+            tx_id,
+            rx_id,
+            functional_id: None,
+            addressing: Default::default(),
+        }
+    }
+
+    pub fn set_functional_id(&mut self, id: CanId) -> &mut Self {
+        self.functional_id = Some(id);
+        self
+    }
+
+    pub fn set_addressing(&mut self, addressing: Addressing) -> &mut Self {
+        self.addressing = addressing;
+        self
+    }
+}
+
+/// The configuration of transport, the timeouts are in milliseconds.
+#[derive(Debug, Clone, Getters)]
+pub struct IsoTpConfig {
+    /// the block size sent by flow control frame
+    #[getter(copy)]
+    block_size: u8,
+    /// the STmin sent by flow control frame
+    #[getter(copy)]
+    st_min: u8,
+    #[getter(copy)]
+    n_as: u32,
+    #[getter(copy)]
+    n_bs: u32,
+    #[getter(copy)]
+    n_cr: u32,
+    #[getter(copy)]
+    max_wait: u8,
+    /// the frames are padded to full length when it is `Some`
+    #[getter(copy)]
+    padding: Option<u8>,
+    /// transmit CAN FD frames with 64 bytes(TX_DL)
+    #[getter(copy)]
+    can_fd: bool,
+    #[getter(copy)]
+    bitrate_switch: bool,
+    /// the max length of message received, the larger message is rejected by `FC.OVFLW`
+    #[getter(copy)]
+    max_length: usize,
+}
+
+impl Default for IsoTpConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 0,
+            st_min: 0,
+            n_as: DEFAULT_TIMEOUT,
+            n_bs: DEFAULT_TIMEOUT,
+            n_cr: DEFAULT_TIMEOUT,
+            max_wait: DEFAULT_MAX_WAIT,
+            padding: Some(DEFAULT_PADDING),
+            can_fd: false,
+            bitrate_switch: false,
+            max_length: DEFAULT_MAX_LENGTH,
+        }
+    }
+}
+
+impl IsoTpConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_block_size(&mut self, block_size: u8) -> &mut Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn set_st_min(&mut self, st_min: u8) -> &mut Self {
+        self.st_min = st_min;
+        self
+    }
+
+    /// Set the timeout of transmitting a frame.
+    pub fn set_n_as(&mut self, timeout: u32) -> &mut Self {
+        self.n_as = timeout;
+        self
+    }
+
+    /// Set the timeout of waiting for flow control frame.
+    pub fn set_n_bs(&mut self, timeout: u32) -> &mut Self {
+        self.n_bs = timeout;
+        self
+    }
+
+    /// Set the timeout of waiting for consecutive frame.
+    pub fn set_n_cr(&mut self, timeout: u32) -> &mut Self {
+        self.n_cr = timeout;
+        self
+    }
+
+    pub fn set_max_wait(&mut self, max_wait: u8) -> &mut Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    pub fn set_padding(&mut self, padding: Option<u8>) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn set_can_fd(&mut self, can_fd: bool) -> &mut Self {
+        self.can_fd = can_fd;
+        self
+    }
+
+    pub fn set_bitrate_switch(&mut self, bitrate_switch: bool) -> &mut Self {
+        self.bitrate_switch = bitrate_switch;
+        self
+    }
+
+    pub fn set_max_length(&mut self, max_length: usize) -> &mut Self {
+        self.max_length = max_length;
+        self
+    }
+}
+
+/// The result of receiving consecutive frames.
+enum Reception {
+    Complete(Vec<u8>),
+    /// the reception is interrupted by a new message
+    Restart(TargetType, Pdu),
+}
+
+/// The ISO-TP transport on a channel of device.
+pub struct IsoTp<D: CanDevice> {
+    device: D,
+    channel: D::Channel,
+    address: Address,
+    config: IsoTpConfig,
+    pending: VecDeque<D::Frame>,
+}
+
+impl<D: CanDevice> IsoTp<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(device: D, channel: D::Channel, address: Address) -> Self {
+        Self {
+            device,
+            channel,
+            address,
+            config: Default::default(),
+            pending: Default::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    #[inline(always)]
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    #[inline(always)]
+    pub fn config(&self) -> &IsoTpConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: IsoTpConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Transmit the message by physical addressing.
+    pub fn transmit(&mut self, data: &[u8]) -> Result<(), CanError> {
+        if data.is_empty() {
+            return Err(CanError::other_error("ISO-TP message is empty"));
+        }
+        if data.len() <= self.single_capacity() {
+            return self.send_pdu(self.address.tx_id, &Pdu::Single(data.to_vec()));
+        }
+        if data.len() > u32::MAX as usize {
+            return Err(CanError::OtherError(format!("ISO-TP message length {} is out of range", data.len())));
+        }
+
+        let frame_size = self.tx_dl() - self.address.addressing.prefix_len();
+        let first = frame_size - if data.len() > MAX_SHORT_LENGTH { 6 } else { 2 };
+        self.send_pdu(self.address.tx_id, &Pdu::First { length: data.len(), data: data[..first].to_vec() })?;
+
+        let mut chunks = data[first..].chunks(frame_size - 1).peekable();
+        let mut sequence = 1;
+        while chunks.peek().is_some() {
+            let (block_size, st_min) = self.wait_flow_control()?;
+            let count = if block_size == 0 { usize::MAX } else { block_size as usize };
+            for (i, chunk) in chunks.by_ref().take(count).enumerate() {
+                if i > 0 && !st_min.is_zero() {
+                    thread::sleep(st_min);
+                }
+                self.send_pdu(self.address.tx_id, &Pdu::Consecutive { sequence, data: chunk.to_vec() })?;
+                sequence = (sequence + 1) & 0x0F;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Transmit the message by functional addressing, only single frame is allowed.
+    pub fn transmit_functional(&mut self, data: &[u8]) -> Result<(), CanError> {
+        let id = self.address.functional_id
+            .ok_or(CanError::other_error("ISO-TP functional identifier is not set"))?;
+        let frame = self.single_frame(id, data)?;
+        self.send_frame(frame)
+    }
+
+    /// Receive a message, waiting at most `timeout` milliseconds for the start of message.
+    #[inline]
+    pub fn receive(&mut self, timeout: u32) -> Result<Vec<u8>, CanError> {
+        self.receive_with_target(timeout)
+            .map(|(_, data)| data)
+    }
+
+    /// Receive a message and the target type which it is addressed by.
+    pub fn receive_with_target(&mut self, timeout: u32) -> Result<(TargetType, Vec<u8>), CanError> {
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        let mut next = self.recv_pdu(deadline)?;
+        loop {
+            let (target, pdu) = next.take()
+                .ok_or(CanError::TimeoutError("no ISO-TP message received".into()))?;
+            match pdu {
+                Pdu::Single(data) => return Ok((target, data)),
+                Pdu::First { length, data } => {
+                    if length > self.config.max_length {
+                        self.send_pdu(self.address.tx_id, &Pdu::FlowControl { status: FlowStatus::Overflow, block_size: 0, st_min: 0 })?;
+                        return Err(CanError::OperationError(format!("ISO-TP message length {} is overflowed", length)));
+                    }
+                    match self.receive_consecutive(length, data)? {
+                        Reception::Complete(data) => return Ok((target, data)),
+                        Reception::Restart(target, pdu) => next = Some((target, pdu)),
+                    }
+                },
+                _ => next = self.recv_pdu(deadline)?,
+            }
+        }
+    }
+
+    fn receive_consecutive(&mut self, length: usize, data: Vec<u8>) -> Result<Reception, CanError> {
+        // the buffer grows with the consecutive frames received instead of the length announced
+        let mut message = data;
+        let block_size = self.config.block_size;
+        let mut sequence = 1;
+        while message.len() < length {
+            self.send_pdu(self.address.tx_id, &Pdu::FlowControl {
+                status: FlowStatus::ContinueToSend,
+                block_size,
+                st_min: self.config.st_min,
+            })?;
+
+            let mut count = 0;
+            while message.len() < length && (block_size == 0 || count < block_size) {
+                let deadline = Instant::now() + Duration::from_millis(self.config.n_cr as u64);
+                match self.recv_pdu(deadline)? {
+                    Some((_, Pdu::Consecutive { sequence: sn, data })) => {
+                        if sn != sequence {
+                            return Err(CanError::OperationError(
+                                format!("ISO-TP wrong sequence number {}, expected {}", sn, sequence)
+                            ));
+                        }
+                        let remaining = length - message.len();
+                        message.extend(&data[..data.len().min(remaining)]);
+                        sequence = (sequence + 1) & 0x0F;
+                        count += 1;
+                    },
+                    Some((TargetType::Physical, pdu @ (Pdu::Single(_) | Pdu::First { .. }))) => {
+                        log::warn!("RUST-CAN - ISO-TP reception is interrupted by a new message");
+                        return Ok(Reception::Restart(TargetType::Physical, pdu));
+                    },
+                    Some(_) => {},
+                    None => return Err(CanError::TimeoutError("N_Cr when waiting consecutive frame".into())),
+                }
+            }
+        }
+
+        Ok(Reception::Complete(message))
+    }
+
+    /// Wait for `FC.CTS`, return the block size and STmin.
+    fn wait_flow_control(&mut self) -> Result<(u8, Duration), CanError> {
+        let mut waits = 0;
+        loop {
+            let deadline = Instant::now() + Duration::from_millis(self.config.n_bs as u64);
+            let (status, block_size, st_min) = loop {
+                match self.recv_pdu(deadline)? {
+                    Some((TargetType::Physical, Pdu::FlowControl { status, block_size, st_min })) =>
+                        break (status, block_size, st_min),
+                    Some(_) => {},
+                    None => return Err(CanError::TimeoutError("N_Bs when waiting flow control frame".into())),
+                }
+            };
+
+            match status {
+                FlowStatus::ContinueToSend => return Ok((block_size, st_min_duration(st_min))),
+                FlowStatus::Wait => {
+                    waits += 1;
+                    if waits > self.config.max_wait {
+                        return Err(CanError::OperationError(format!("ISO-TP received FC.WAIT more than {} times", self.config.max_wait)));
+                    }
+                },
+                FlowStatus::Overflow => return Err(CanError::operation_error("ISO-TP message is overflowed by receiver")),
+            }
+        }
+    }
+
+    /// Receive the next N_PDU which is addressed to the transport before deadline.
+    fn recv_pdu(&mut self, deadline: Instant) -> Result<Option<(TargetType, Pdu)>, CanError> {
+        loop {
+            while let Some(frame) = self.pending.pop_front() {
+                if let Some(v) = self.accept(&frame) {
+                    return Ok(Some(v));
+                }
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let timeout = (deadline - now).as_millis().clamp(1, u32::MAX as u128) as u32;
+            match self.device.receive(self.channel.clone(), Some(timeout)) {
+                Ok(frames) => self.pending.extend(frames),
+                Err(CanError::TimeoutError(_)) => {},
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn accept(&self, frame: &D::Frame) -> Option<(TargetType, Pdu)> {
+        if frame.is_remote() || frame.is_error_frame() {
+            return None;
+        }
+
+        let id = frame.id();
+        let target = if id == self.address.rx_id {
+            TargetType::Physical
+        }
+        else if Some(id) == self.address.functional_id {
+            TargetType::Functional
+        }
+        else {
+            return None;
+        };
+
+        let data = frame.data();
+        let payload = match self.address.addressing.rx_prefix() {
+            Some(prefix) => match data.split_first() {
+                Some((&v, payload)) if v == prefix => payload,
+                _ => return None,
+            },
+            None => data,
+        };
+        match Pdu::decode(payload) {
+            Ok(pdu @ Pdu::Single(_)) => Some((target, pdu)),
+            Ok(pdu) if target == TargetType::Physical => Some((target, pdu)),
+            Ok(_) => None,
+            Err(e) => {
+                log::debug!("RUST-CAN - {}", e);
+                None
+            },
+        }
+    }
+
+    /// Create the single frame of message, which can be transmitted by device directly.
+    pub(crate) fn single_frame(&self, id: CanId, data: &[u8]) -> Result<D::Frame, CanError> {
+        if data.is_empty() || data.len() > self.single_capacity() {
+            return Err(CanError::OtherError(format!("ISO-TP message length {} is out of single frame", data.len())));
+        }
+
+        self.new_frame(id, &Pdu::Single(data.to_vec()))
+    }
+
+    #[inline]
+    fn send_pdu(&self, id: CanId, pdu: &Pdu) -> Result<(), CanError> {
+        let frame = self.new_frame(id, pdu)?;
+        self.send_frame(frame)
+    }
+
+    fn send_frame(&self, frame: D::Frame) -> Result<(), CanError> {
+        self.device.transmit(frame, Some(self.config.n_as))
+            .map_err(|e| match e {
+                CanError::TimeoutError(_) => CanError::TimeoutError("N_As when transmitting frame".into()),
+                e => e,
+            })
+    }
+
+    fn new_frame(&self, id: CanId, pdu: &Pdu) -> Result<D::Frame, CanError> {
+        let addressing = self.address.addressing;
+        let mut data = Vec::with_capacity(MAX_FD_FRAME_SIZE);
+        data.extend(addressing.tx_prefix());
+        data.extend(pdu.encode(addressing.prefix_len()));
+
+        let length = data.len();
+        if length > MAX_FRAME_SIZE {
+            let size = crate::utils::can_dlc(length, CanType::CanFd) as usize;
+            data.resize(size, self.config.padding.unwrap_or(DEFAULT_PADDING));
+        }
+        else if let Some(padding) = self.config.padding {
+            data.resize(MAX_FRAME_SIZE, padding);
+        }
+
+        let mut frame = D::Frame::new(id, &data)
+            .ok_or(CanError::other_error("can't create ISO-TP frame"))?;
+        frame.set_channel(self.channel.clone());
+        if self.config.can_fd {
+            frame.set_can_type(CanType::CanFd)
+                .set_bitrate_switch(self.config.bitrate_switch);
+        }
+
+        Ok(frame)
+    }
+
+    /// The max length of frame data(TX_DL).
+    #[inline]
+    fn tx_dl(&self) -> usize {
+        if self.config.can_fd { MAX_FD_FRAME_SIZE } else { MAX_FRAME_SIZE }
+    }
+
+    /// The max length of message which is transmitted by single frame.
+    #[inline]
+    fn single_capacity(&self) -> usize {
+        let prefix = self.address.addressing.prefix_len();
+        if self.config.can_fd { self.tx_dl() - prefix - 2 } else { self.tx_dl() - prefix - 1 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::{vcan::CanMessage, CanDevice, CanError, CanFrame, CanType};
+    use crate::test_utils::{new_device, new_isotp};
+    use super::{Addressing, IsoTpConfig, TargetType};
+
+    #[test]
+    fn test_transfer() -> anyhow::Result<()> {
+        let channel = "isotp-transfer";
+        let mut client = new_isotp(channel, 0x7E0, 0x7E8)?;
+        let mut server = new_isotp(channel, 0x7E8, 0x7E0)?;
+        let mut config = IsoTpConfig::new();
+        config.set_block_size(4)
+            .set_st_min(0xF5);
+        server.set_config(config);
+        let monitor = new_device(channel)?;
+
+        let request = (0..200).map(|v| v as u8).collect::<Vec<_>>();
+        let expected = request.clone();
+        let handle = thread::spawn(move || -> Result<(), CanError> {
+            let data = server.receive(1000)?;
+            assert_eq!(data, expected);
+            server.transmit(&[0x62, 0xF1, 0x90])
+        });
+
+        client.transmit(&request)?;
+        assert_eq!(client.receive(1000)?, [0x62, 0xF1, 0x90]);
+        handle.join().unwrap()?;
+
+        let frames = monitor.receive(channel.into(), Some(10))?;
+        // FF + 28 CF + 7 FC + SF
+        assert_eq!(frames.len(), 37);
+        assert!(frames.iter().all(|f| f.length() == 8 && f.can_type() == CanType::Can));
+        assert_eq!(frames[0].data(), &[0x10, 0xC8, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05]);
+        assert_eq!(frames[1].data(), &[0x30, 0x04, 0xF5, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]);
+        assert_eq!(frames[2].data()[0], 0x21);
+        assert_eq!(frames[36].data(), &[0x03, 0x62, 0xF1, 0x90, 0xAA, 0xAA, 0xAA, 0xAA]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fd_extended_addressing() -> anyhow::Result<()> {
+        let channel = "isotp-fd";
+        let mut config = IsoTpConfig::new();
+        config.set_can_fd(true)
+            .set_bitrate_switch(true);
+        let mut client = new_isotp(channel, 0x18DA_01F1, 0x18DA_F101)?;
+        client.address.set_addressing(Addressing::Extended { source: 0xF1, target: 0x01 });
+        client.set_config(config.clone());
+        let mut server = new_isotp(channel, 0x18DA_F101, 0x18DA_01F1)?;
+        server.address.set_addressing(Addressing::Extended { source: 0x01, target: 0xF1 });
+        server.set_config(config);
+        let monitor = new_device(channel)?;
+
+        // the escape sequence of first frame
+        let request = (0..5000).map(|v| v as u8).collect::<Vec<_>>();
+        let expected = request.clone();
+        let handle = thread::spawn(move || -> Result<(), CanError> {
+            assert_eq!(server.receive(1000)?, expected);
+            server.transmit(&[0x55; 20])
+        });
+
+        client.transmit(&request)?;
+        assert_eq!(client.receive(1000)?, [0x55; 20]);
+        handle.join().unwrap()?;
+
+        let frames = monitor.receive(channel.into(), Some(10))?;
+        assert!(frames.iter().all(|f| f.can_type() == CanType::CanFd && f.is_bitrate_switch()));
+        assert_eq!(&frames[0].data()[..7], &[0x01, 0x10, 0x00, 0x00, 0x00, 0x13, 0x88]);
+        assert_eq!(frames[0].length(), 64);
+        assert_eq!(frames[1].data(), &[0xF1, 0x30, 0x00, 0x00, 0xAA, 0xAA, 0xAA, 0xAA]);
+        // the single frame of CAN FD
+        let last = frames.last().unwrap();
+        assert_eq!(&last.data()[..3], &[0xF1, 0x00, 0x14]);
+        assert_eq!(last.length(), 24);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_functional() -> anyhow::Result<()> {
+        let channel = "isotp-functional";
+        let mut client = new_isotp(channel, 0x7E0, 0x7E8)?;
+        let mut server = new_isotp(channel, 0x7E8, 0x7E0)?;
+
+        client.transmit_functional(&[0x3E, 0x00])?;
+        assert_eq!(server.receive_with_target(100)?, (TargetType::Functional, vec![0x3E, 0x00]));
+        assert!(client.transmit_functional(&[0x00; 8]).is_err());
+        client.transmit(&[0x10, 0x03])?;
+        assert_eq!(server.receive_with_target(100)?, (TargetType::Physical, vec![0x10, 0x03]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> anyhow::Result<()> {
+        let channel = "isotp-errors";
+        let mut client = new_isotp(channel, 0x7E0, 0x7E8)?;
+        let mut config = IsoTpConfig::new();
+        config.set_n_bs(50);
+        client.set_config(config);
+
+        assert!(matches!(client.transmit(&[0x00; 20]), Err(CanError::TimeoutError(_))));
+        assert!(matches!(client.receive(10), Err(CanError::TimeoutError(_))));
+
+        let mut server = new_isotp(channel, 0x7E8, 0x7E0)?;
+        let mut config = IsoTpConfig::new();
+        config.set_max_length(10);
+        server.set_config(config);
+        let handle = thread::spawn(move || server.receive(1000));
+        assert!(matches!(client.transmit(&[0x00; 20]), Err(CanError::OperationError(_))));
+        assert!(handle.join().unwrap().is_err());
+
+        // the first frame with escape sequence announces 4GiB
+        let mut server = new_isotp(channel, 0x7E8, 0x7E0)?;
+        let monitor = new_device(channel)?;
+        let mut frame = CanMessage::new(0x7E0, &[0x10, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00]).unwrap();
+        frame.set_channel(channel.into());
+        monitor.transmit(frame, None)?;
+        assert!(matches!(server.receive(100), Err(CanError::OperationError(_))));
+        let frames = monitor.receive(channel.into(), Some(10))?;
+        assert_eq!(frames.last().unwrap().data()[0], 0x32);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+use crate::{CanError, MAX_FRAME_SIZE};
+
+/// The max length of message that the first frame carries by 12 bits.
+pub(crate) const MAX_SHORT_LENGTH: usize = 0x0FFF;
+
+/// The flow status of flow control frame.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowStatus {
+    ContinueToSend = 0x00,
+    Wait = 0x01,
+    Overflow = 0x02,
+}
+
+/// The network protocol data unit, the address byte is excluded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Pdu {
+    Single(Vec<u8>),
+    First { length: usize, data: Vec<u8> },
+    Consecutive { sequence: u8, data: Vec<u8> },
+    FlowControl { status: FlowStatus, block_size: u8, st_min: u8 },
+}
+
+impl Pdu {
+    /// Decode the payload of frame which the address byte is excluded.
+    pub(crate) fn decode(payload: &[u8]) -> Result<Self, CanError> {
+        let invalid = || CanError::OtherError(format!("invalid ISO-TP frame: {:02X?}", payload));
+        let pci = *payload.first().ok_or_else(invalid)?;
+        match pci >> 4 {
+            0x00 => {
+                let (length, offset) = match pci & 0x0F {
+                    0 => (*payload.get(1).ok_or_else(invalid)? as usize, 2),
+                    v => (v as usize, 1),
+                };
+                if length == 0 {
+                    return Err(invalid());
+                }
+                let data = payload.get(offset..offset + length).ok_or_else(invalid)?;
+                Ok(Self::Single(data.to_vec()))
+            },
+            0x01 => {
+                let length = (((pci & 0x0F) as usize) << 8) | *payload.get(1).ok_or_else(invalid)? as usize;
+                let (length, offset) = match length {
+                    0 => {
+                        let bytes = payload.get(2..6).ok_or_else(invalid)?;
+                        (u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize, 6)
+                    },
+                    v => (v, 2),
+                };
+                let data = &payload[offset..];
+                if length <= data.len() {
+                    return Err(invalid());
+                }
+                Ok(Self::First { length, data: data.to_vec() })
+            },
+            0x02 => Ok(Self::Consecutive { sequence: pci & 0x0F, data: payload[1..].to_vec() }),
+            0x03 => {
+                let status = match pci & 0x0F {
+                    0x00 => FlowStatus::ContinueToSend,
+                    0x01 => FlowStatus::Wait,
+                    0x02 => FlowStatus::Overflow,
+                    _ => return Err(invalid()),
+                };
+                let params = payload.get(1..3).ok_or_else(invalid)?;
+                Ok(Self::FlowControl { status, block_size: params[0], st_min: params[1] })
+            },
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Encode to payload without padding, `prefix` is the length of address byte.
+    ///
+    /// The single frame is escaped when it can't be held by a classic frame.
+    pub(crate) fn encode(&self, prefix: usize) -> Vec<u8> {
+        match self {
+            Self::Single(data) => {
+                let length = data.len();
+                let mut result = Vec::with_capacity(length + 2);
+                if prefix + 1 + length <= MAX_FRAME_SIZE {
+                    result.push(length as u8);
+                }
+                else {
+                    result.extend([0x00, length as u8]);
+                }
+                result.extend(data);
+                result
+            },
+            Self::First { length, data } => {
+                let mut result = Vec::with_capacity(data.len() + 6);
+                if *length > MAX_SHORT_LENGTH {
+                    result.extend([0x10, 0x00]);
+                    result.extend((*length as u32).to_be_bytes());
+                }
+                else {
+                    result.extend([0x10 | (*length >> 8) as u8, *length as u8]);
+                }
+                result.extend(data);
+                result
+            },
+            Self::Consecutive { sequence, data } => {
+                let mut result = Vec::with_capacity(data.len() + 1);
+                result.push(0x20 | (sequence & 0x0F));
+                result.extend(data);
+                result
+            },
+            Self::FlowControl { status, block_size, st_min } =>
+                vec![0x30 | *status as u8, *block_size, *st_min],
+        }
+    }
+}
+
+/// Convert STmin to duration, the reserved values are treated as 127ms.
+#[inline]
+pub(crate) fn st_min_duration(st_min: u8) -> Duration {
+    match st_min {
+        0x00..=0x7F => Duration::from_millis(st_min as u64),
+        0xF1..=0xF9 => Duration::from_micros((st_min - 0xF0) as u64 * 100),
+        _ => Duration::from_millis(0x7F),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{st_min_duration, FlowStatus, Pdu};
+
+    #[test]
+    fn test_codec() -> anyhow::Result<()> {
+        let pdu = Pdu::Single(vec![0x10, 0x01]);
+        assert_eq!(pdu.encode(0), [0x02, 0x10, 0x01]);
+        assert_eq!(Pdu::decode(&[0x02, 0x10, 0x01, 0xAA, 0xAA])?, pdu);
+        // escape sequence of CAN FD
+        let pdu = Pdu::Single(vec![0x55; 7]);
+        assert_eq!(pdu.encode(0)[0], 0x07);
+        assert_eq!(&pdu.encode(1)[..2], &[0x00, 0x07]);
+        assert_eq!(Pdu::decode(&pdu.encode(1))?, pdu);
+
+        let pdu = Pdu::First { length: 0x123, data: vec![0x01; 6] };
+        assert_eq!(&pdu.encode(0)[..2], &[0x11, 0x23]);
+        assert_eq!(Pdu::decode(&pdu.encode(0))?, pdu);
+        let pdu = Pdu::First { length: 0x1_0000, data: vec![0x01; 58] };
+        assert_eq!(&pdu.encode(0)[..6], &[0x10, 0x00, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(Pdu::decode(&pdu.encode(0))?, pdu);
+
+        let pdu = Pdu::Consecutive { sequence: 0x0F, data: vec![0x02; 7] };
+        assert_eq!(pdu.encode(0)[0], 0x2F);
+        assert_eq!(Pdu::decode(&pdu.encode(0))?, pdu);
+
+        let pdu = Pdu::FlowControl { status: FlowStatus::Wait, block_size: 8, st_min: 0xF5 };
+        assert_eq!(pdu.encode(0), [0x31, 0x08, 0xF5]);
+        assert_eq!(Pdu::decode(&pdu.encode(0))?, pdu);
+
+        assert!(Pdu::decode(&[]).is_err());
+        assert!(Pdu::decode(&[0x00, 0x00]).is_err());
+        assert!(Pdu::decode(&[0x05, 0x01]).is_err());
+        assert!(Pdu::decode(&[0x10, 0x05, 0x01, 0x02, 0x03, 0x04, 0x05]).is_err());
+        assert!(Pdu::decode(&[0x33, 0x00, 0x00]).is_err());
+        assert!(Pdu::decode(&[0x40]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_st_min() {
+        assert_eq!(st_min_duration(0x00), Duration::ZERO);
+        assert_eq!(st_min_duration(0x7F), Duration::from_millis(127));
+        assert_eq!(st_min_duration(0xF1), Duration::from_micros(100));
+        assert_eq!(st_min_duration(0xF9), Duration::from_micros(900));
+        assert_eq!(st_min_duration(0x80), Duration::from_millis(127));
+    }
+}
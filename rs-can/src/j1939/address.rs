@@ -0,0 +1,433 @@
+use std::{collections::BTreeMap, fmt::{Display, Formatter}, ops::RangeInclusive, time::{Duration, Instant}};
+use super::{J1939Message, NULL_ADDRESS};
+
+/// The PGN of request.
+pub const PGN_REQUEST: u32 = 0xEA00;
+/// The PGN of address claimed and cannot claim address.
+pub const PGN_ADDRESS_CLAIMED: u32 = 0xEE00;
+/// The time of contention after the address claimed in milliseconds.
+pub const ADDRESS_CLAIM_TIMEOUT: u32 = 250;
+
+/// The 64 bits NAME of controller application(J1939-81), the lower value has the higher priority in address arbitration.
+///
+/// | bits  | field                          |
+/// |-------|--------------------------------|
+/// | 0-20  | identity number                |
+/// | 21-31 | manufacturer code              |
+/// | 32-34 | ECU instance                   |
+/// | 35-39 | function instance              |
+/// | 40-47 | function                       |
+/// | 48    | reserved                       |
+/// | 49-55 | vehicle system                 |
+/// | 56-59 | vehicle system instance        |
+/// | 60-62 | industry group                 |
+/// | 63    | arbitrary address capable      |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Name(u64);
+
+impl Name {
+    #[inline(always)]
+    pub fn new(raw: u64) -> Self {
+        Self(raw)
+    }
+
+    /// Parse the NAME from the data of address claimed(little endian).
+    #[inline]
+    pub fn from_bytes(data: [u8; 8]) -> Self {
+        Self(u64::from_le_bytes(data))
+    }
+
+    #[inline(always)]
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+
+    #[inline(always)]
+    pub fn as_raw(self) -> u64 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn identity_number(self) -> u32 {
+        self.field(0, 21) as u32
+    }
+
+    #[inline(always)]
+    pub fn manufacturer_code(self) -> u16 {
+        self.field(21, 11) as u16
+    }
+
+    #[inline(always)]
+    pub fn ecu_instance(self) -> u8 {
+        self.field(32, 3) as u8
+    }
+
+    #[inline(always)]
+    pub fn function_instance(self) -> u8 {
+        self.field(35, 5) as u8
+    }
+
+    #[inline(always)]
+    pub fn function(self) -> u8 {
+        self.field(40, 8) as u8
+    }
+
+    #[inline(always)]
+    pub fn vehicle_system(self) -> u8 {
+        self.field(49, 7) as u8
+    }
+
+    #[inline(always)]
+    pub fn vehicle_system_instance(self) -> u8 {
+        self.field(56, 4) as u8
+    }
+
+    #[inline(always)]
+    pub fn industry_group(self) -> u8 {
+        self.field(60, 3) as u8
+    }
+
+    /// The controller can select another address when it lost the arbitration.
+    #[inline(always)]
+    pub fn arbitrary_address_capable(self) -> bool {
+        self.field(63, 1) != 0
+    }
+
+    #[inline(always)]
+    fn field(self, offset: u32, size: u32) -> u64 {
+        (self.0 >> offset) & ((1 << size) - 1)
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016X}", self.0)
+    }
+}
+
+/// The builder of [`Name`], the values are truncated to the size of fields.
+#[derive(Debug, Default, Clone)]
+pub struct NameBuilder {
+    identity_number: u32,
+    manufacturer_code: u16,
+    ecu_instance: u8,
+    function_instance: u8,
+    function: u8,
+    vehicle_system: u8,
+    vehicle_system_instance: u8,
+    industry_group: u8,
+    arbitrary_address_capable: bool,
+}
+
+impl NameBuilder {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_identity_number(&mut self, value: u32) -> &mut Self {
+        self.identity_number = value;
+        self
+    }
+
+    pub fn set_manufacturer_code(&mut self, value: u16) -> &mut Self {
+        self.manufacturer_code = value;
+        self
+    }
+
+    pub fn set_ecu_instance(&mut self, value: u8) -> &mut Self {
+        self.ecu_instance = value;
+        self
+    }
+
+    pub fn set_function_instance(&mut self, value: u8) -> &mut Self {
+        self.function_instance = value;
+        self
+    }
+
+    pub fn set_function(&mut self, value: u8) -> &mut Self {
+        self.function = value;
+        self
+    }
+
+    pub fn set_vehicle_system(&mut self, value: u8) -> &mut Self {
+        self.vehicle_system = value;
+        self
+    }
+
+    pub fn set_vehicle_system_instance(&mut self, value: u8) -> &mut Self {
+        self.vehicle_system_instance = value;
+        self
+    }
+
+    pub fn set_industry_group(&mut self, value: u8) -> &mut Self {
+        self.industry_group = value;
+        self
+    }
+
+    pub fn set_arbitrary_address_capable(&mut self, value: bool) -> &mut Self {
+        self.arbitrary_address_capable = value;
+        self
+    }
+
+    pub fn build(&self) -> Name {
+        let field = |value: u64, offset: u32, size: u32| (value & ((1 << size) - 1)) << offset;
+        Name(field(self.identity_number as u64, 0, 21)
+            | field(self.manufacturer_code as u64, 21, 11)
+            | field(self.ecu_instance as u64, 32, 3)
+            | field(self.function_instance as u64, 35, 5)
+            | field(self.function as u64, 40, 8)
+            | field(self.vehicle_system as u64, 49, 7)
+            | field(self.vehicle_system_instance as u64, 56, 4)
+            | field(self.industry_group as u64, 60, 3)
+            | field(self.arbitrary_address_capable as u64, 63, 1))
+    }
+}
+
+/// The state of address claiming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimState {
+    Idle,
+    /// the address is claimed and waiting for contention
+    Claiming(u8),
+    Claimed(u8),
+    /// no address is available, the cannot claim address is sent
+    CannotClaim,
+}
+
+/// The address claiming(J1939-81) of controller application, which keeps the addresses claimed by other controllers.
+#[derive(Debug, Clone)]
+pub struct AddressClaim {
+    name: Name,
+    preferred: u8,
+    range: RangeInclusive<u8>,
+    state: ClaimState,
+    deadline: Instant,
+    claims: BTreeMap<u8, Name>,
+}
+
+impl AddressClaim {
+    pub fn new(name: Name, preferred: u8) -> Self {
+        Self {
+            name,
+            preferred,
+            // the self-configurable addresses
+            range: 128..=247,
+            state: ClaimState::Idle,
+            deadline: Instant::now(),
+            claims: Default::default(),
+        }
+    }
+
+    /// Set the addresses selected when the preferred address is lost, only for the arbitrary address capable NAME.
+    pub fn set_range(&mut self, range: RangeInclusive<u8>) -> &mut Self {
+        self.range = range;
+        self
+    }
+
+    #[inline(always)]
+    pub fn name(&self) -> Name {
+        self.name
+    }
+
+    #[inline(always)]
+    pub fn state(&self) -> ClaimState {
+        self.state
+    }
+
+    /// The address which is claimed successfully.
+    #[inline]
+    pub fn address(&self) -> Option<u8> {
+        match self.state {
+            ClaimState::Claimed(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The addresses claimed by other controllers.
+    #[inline(always)]
+    pub fn claims(&self) -> &BTreeMap<u8, Name> {
+        &self.claims
+    }
+
+    /// Start claiming, return the source address of address claimed message.
+    pub(crate) fn start(&mut self, now: Instant) -> u8 {
+        match self.claims.get(&self.preferred) {
+            Some(&v) if v < self.name => self.reclaim(now),
+            _ => self.claim(self.preferred, now),
+        }
+    }
+
+    /// Update the state by time.
+    pub(crate) fn poll(&mut self, now: Instant) {
+        if let ClaimState::Claiming(address) = self.state {
+            if now >= self.deadline {
+                self.state = ClaimState::Claimed(address);
+            }
+        }
+    }
+
+    /// The deadline of contention when claiming.
+    #[inline]
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        match self.state {
+            ClaimState::Claiming(_) => Some(self.deadline),
+            _ => None,
+        }
+    }
+
+    /// Handle the network management message, return the source address of address claimed message to send.
+    pub(crate) fn handle(&mut self, message: &J1939Message, now: Instant) -> Option<u8> {
+        self.poll(now);
+        let current = match self.state {
+            ClaimState::Idle => None,
+            ClaimState::Claiming(v) | ClaimState::Claimed(v) => Some(v),
+            ClaimState::CannotClaim => Some(NULL_ADDRESS),
+        };
+        match message.pgn {
+            PGN_REQUEST => {
+                let pgn = message.data.get(..3)
+                    .map(|v| u32::from_le_bytes([v[0], v[1], v[2], 0]))?;
+                if pgn == PGN_ADDRESS_CLAIMED { current } else { None }
+            },
+            PGN_ADDRESS_CLAIMED => {
+                let name = Name::from_bytes(message.data.as_slice().try_into().ok()?);
+                let source = message.source;
+                if name == self.name || source == NULL_ADDRESS {
+                    return None;
+                }
+                // the controller may be moved to another address
+                self.claims.retain(|_, v| *v != name);
+                self.claims.insert(source, name);
+
+                match current {
+                    Some(address) if address == source => {
+                        if self.name < name {
+                            // defend the address
+                            Some(address)
+                        }
+                        else {
+                            log::warn!("RUST-CAN - J1939 address {:#04X} is lost to NAME {}", address, name);
+                            Some(self.reclaim(now))
+                        }
+                    },
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn claim(&mut self, address: u8, now: Instant) -> u8 {
+        self.state = ClaimState::Claiming(address);
+        self.deadline = now + Duration::from_millis(ADDRESS_CLAIM_TIMEOUT as u64);
+        address
+    }
+
+    /// Claim another address, or send cannot claim address.
+    fn reclaim(&mut self, now: Instant) -> u8 {
+        let free = self.range.clone()
+            .find(|v| !self.claims.contains_key(v) && *v != NULL_ADDRESS);
+        match free {
+            Some(address) if self.name.arbitrary_address_capable() => self.claim(address, now),
+            _ => {
+                self.state = ClaimState::CannotClaim;
+                NULL_ADDRESS
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::{Duration, Instant}};
+    use crate::{j1939::{J1939Error, J1939Tp, GLOBAL_ADDRESS, NULL_ADDRESS}, vcan::VirtualCan};
+    use crate::test_utils::new_device;
+    use super::{AddressClaim, ClaimState, Name, NameBuilder, PGN_ADDRESS_CLAIMED};
+
+    fn new_tp(channel: &str) -> anyhow::Result<J1939Tp<VirtualCan>> {
+        Ok(J1939Tp::new(new_device(channel)?, channel.into(), NULL_ADDRESS))
+    }
+
+    fn new_name(identity_number: u32, arbitrary_address_capable: bool) -> Name {
+        NameBuilder::new()
+            .set_identity_number(identity_number)
+            .set_manufacturer_code(0x123)
+            .set_function(0x81)
+            .set_industry_group(2)
+            .set_arbitrary_address_capable(arbitrary_address_capable)
+            .build()
+    }
+
+    /// Serve the network management in background for `duration`.
+    fn serve(mut tp: J1939Tp<VirtualCan>, duration: u64) -> thread::JoinHandle<J1939Tp<VirtualCan>> {
+        thread::spawn(move || {
+            let deadline = Instant::now() + Duration::from_millis(duration);
+            while Instant::now() < deadline {
+                let _ = tp.receive(20);
+            }
+            tp
+        })
+    }
+
+    #[test]
+    fn test_name() {
+        let name = NameBuilder::new()
+            .set_identity_number(0x1F_FFFF)
+            .set_manufacturer_code(0x7FF)
+            .set_ecu_instance(1)
+            .set_function_instance(2)
+            .set_function(0x81)
+            .set_vehicle_system(0x7F)
+            .set_vehicle_system_instance(3)
+            .set_industry_group(2)
+            .set_arbitrary_address_capable(true)
+            .build();
+        assert_eq!(name.as_raw(), 0xA3FE_8111_FFFF_FFFF);
+        let name = Name::from_bytes(name.to_bytes());
+        assert_eq!((name.identity_number(), name.manufacturer_code(), name.ecu_instance(), name.function_instance()), (0x1F_FFFF, 0x7FF, 1, 2));
+        assert_eq!((name.function(), name.vehicle_system(), name.vehicle_system_instance(), name.industry_group()), (0x81, 0x7F, 3, 2));
+        assert!(name.arbitrary_address_capable());
+    }
+
+    #[test]
+    fn test_address_claim() -> anyhow::Result<()> {
+        let channel = "j1939-address-claim";
+        let mut node1 = new_tp(channel)?;
+        assert_eq!(node1.claim_address(AddressClaim::new(new_name(2, true), 0x80))?, 0x80);
+
+        // the preferred address is lost to the higher priority NAME
+        let handle1 = serve(node1, 600);
+        let mut node2 = new_tp(channel)?;
+        assert_eq!(node2.claim_address(AddressClaim::new(new_name(1, false), 0x80))?, 0x80);
+        let node1 = handle1.join().unwrap();
+        assert_eq!(node1.address(), 0x81);
+        assert_eq!(node1.address_claim().unwrap().state(), ClaimState::Claimed(0x81));
+        assert_eq!(node2.address_claim().unwrap().claims().get(&0x81), Some(&new_name(2, true)));
+
+        // no address for the lower priority NAME which is not arbitrary address capable
+        let handle1 = serve(node1, 600);
+        let handle2 = serve(node2, 600);
+        let mut node3 = new_tp(channel)?;
+        let err = node3.claim_address(AddressClaim::new(new_name(3, false), 0x80)).unwrap_err();
+        assert!(matches!(err, J1939Error::AddressNotClaimed));
+        assert_eq!(node3.address(), NULL_ADDRESS);
+        assert!(matches!(node3.transmit(6, 0xFEF1, GLOBAL_ADDRESS, &[0x00; 8]), Err(J1939Error::AddressNotClaimed)));
+
+        // the claims are responded to request for address claimed
+        let mut node4 = new_tp(channel)?;
+        node4.request(PGN_ADDRESS_CLAIMED, GLOBAL_ADDRESS)?;
+        let mut claims = Vec::new();
+        while let Ok(message) = node4.receive(100) {
+            if message.pgn == PGN_ADDRESS_CLAIMED {
+                claims.push(message.source);
+            }
+        }
+        claims.sort();
+        assert_eq!(claims, [0x80, 0x81]);
+        handle1.join().unwrap();
+        handle2.join().unwrap();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{CanDevice, CanError};
+use super::{J1939Error, J1939Message, J1939Tp, GLOBAL_ADDRESS};
+
+/// The PGN of active diagnostic trouble codes.
+pub const PGN_DM1: u32 = 0xFECA;
+/// The PGN of previously active diagnostic trouble codes.
+pub const PGN_DM2: u32 = 0xFECB;
+/// The PGN of clearing previously active diagnostic trouble codes.
+pub const PGN_DM3: u32 = 0xFECC;
+/// The PGN of clearing active diagnostic trouble codes.
+pub const PGN_DM11: u32 = 0xFED3;
+/// The PGN of acknowledgment.
+pub const PGN_ACKNOWLEDGMENT: u32 = 0xE800;
+/// The default timeout of response to request in milliseconds.
+pub const DEFAULT_RESPONSE_TIMEOUT: u32 = 1250;
+
+/// The callback of diagnostic message.
+pub type DiagnosticCallback = Box<dyn FnMut(&DiagnosticMessage) + Send>;
+
+#[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LampStatus {
+    #[default]
+    Off = 0,
+    On = 1,
+    Reserved = 2,
+    NotAvailable = 3,
+}
+
+impl From<u8> for LampStatus {
+    /// Get the status from the lower 2 bits.
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => Self::Off,
+            1 => Self::On,
+            2 => Self::Reserved,
+            _ => Self::NotAvailable,
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FlashStatus {
+    /// 1 Hz
+    Slow = 0,
+    /// 2 Hz
+    Fast = 1,
+    ClassC = 2,
+    /// the lamp is not flashing
+    #[default]
+    NotAvailable = 3,
+}
+
+impl From<u8> for FlashStatus {
+    /// Get the status from the lower 2 bits.
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => Self::Slow,
+            1 => Self::Fast,
+            2 => Self::ClassC,
+            _ => Self::NotAvailable,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Lamp {
+    pub status: LampStatus,
+    pub flash: FlashStatus,
+}
+
+/// The lamps of diagnostic message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Lamps {
+    pub malfunction: Lamp,
+    pub red_stop: Lamp,
+    pub amber_warning: Lamp,
+    pub protect: Lamp,
+}
+
+impl Lamps {
+    fn decode(status: u8, flash: u8) -> Self {
+        let lamp = |offset: u8| Lamp {
+            status: LampStatus::from(status >> offset),
+            flash: FlashStatus::from(flash >> offset),
+        };
+        Self { malfunction: lamp(6), red_stop: lamp(4), amber_warning: lamp(2), protect: lamp(0) }
+    }
+
+    fn encode(&self) -> [u8; 2] {
+        let lamps = [(self.malfunction, 6), (self.red_stop, 4), (self.amber_warning, 2), (self.protect, 0)];
+        lamps.iter()
+            .fold([0, 0], |[status, flash], (lamp, offset)| [
+                status | (lamp.status as u8) << offset,
+                flash | (lamp.flash as u8) << offset,
+            ])
+    }
+}
+
+/// The SPN layout of DTC when the conversion method bit is set, the layout is version 4 when it is not set.
+///
+/// The 19 bits SPN is in the first 3 bytes of DTC, the FMI is the lower 5 bits of the third byte.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SpnConversion {
+    /// the SPN is in big endian: bits 18-11, bits 10-3, bits 2-0
+    Version1,
+    /// the 16 most significant bits in little endian: bits 10-3, bits 18-11, bits 2-0
+    Version2,
+    /// the same as version 4: bits 7-0, bits 15-8, bits 18-16
+    #[default]
+    Version3,
+}
+
+/// The diagnostic trouble code of J1939-73.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct J1939Dtc {
+    /// the suspect parameter number
+    pub spn: u32,
+    /// the failure mode identifier
+    pub fmi: u8,
+    /// the occurrence count, 127 is not available
+    pub occurrence_count: u8,
+    /// the conversion method bit, the SPN is version 4 when it is not set
+    pub conversion_method: bool,
+}
+
+impl J1939Dtc {
+    pub fn new(spn: u32, fmi: u8, occurrence_count: u8) -> Self {
+        Self { spn: spn & 0x7FFFF, fmi: fmi & 0x1F, occurrence_count: occurrence_count & 0x7F, conversion_method: false }
+    }
+
+    fn decode(data: &[u8], conversion: SpnConversion) -> Self {
+        let conversion_method = data[3] & 0x80 != 0;
+        let (b0, b1, b2) = (data[0] as u32, data[1] as u32, data[2] as u32);
+        let spn = match conversion {
+            SpnConversion::Version1 if conversion_method => (b0 << 11) | (b1 << 3) | (b2 >> 5),
+            SpnConversion::Version2 if conversion_method => (b1 << 11) | (b0 << 3) | (b2 >> 5),
+            _ => b0 | (b1 << 8) | ((b2 & 0xE0) << 11),
+        };
+        Self { spn, fmi: data[2] & 0x1F, occurrence_count: data[3] & 0x7F, conversion_method }
+    }
+
+    /// Encode the DTC in version 4.
+    fn encode(&self) -> [u8; 4] {
+        let spn = self.spn.to_le_bytes();
+        [spn[0], spn[1], ((spn[2] & 0x07) << 5) | (self.fmi & 0x1F), self.occurrence_count & 0x7F]
+    }
+}
+
+/// The DM1 or DM2 message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticMessage {
+    pub source: u8,
+    pub lamps: Lamps,
+    pub dtcs: Vec<J1939Dtc>,
+}
+
+impl DiagnosticMessage {
+    /// Decode the data of DM1 or DM2, the DTC of zero SPN and FMI means no DTC.
+    pub fn decode(source: u8, data: &[u8], conversion: SpnConversion) -> Result<Self, J1939Error> {
+        if data.len() < 6 {
+            return Err(J1939Error::InvalidParameter(format!("diagnostic message length {} is too short", data.len())));
+        }
+
+        let lamps = Lamps::decode(data[0], data[1]);
+        let dtcs = data[2..].chunks_exact(4)
+            .filter(|v| *v != [0xFF; 4])
+            .map(|v| J1939Dtc::decode(v, conversion))
+            .filter(|v| v.spn != 0 || v.fmi != 0)
+            .collect();
+        Ok(Self { source, lamps, dtcs })
+    }
+
+    /// Encode the data of DM1 or DM2, the DTCs are in version 4.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.lamps.encode().to_vec();
+        if self.dtcs.is_empty() {
+            data.extend([0x00; 4]);
+        }
+        self.dtcs.iter()
+            .for_each(|v| data.extend(v.encode()));
+        if data.len() < 8 {
+            data.resize(8, 0xFF);
+        }
+        data
+    }
+}
+
+/// The diagnostic services of J1939-73.
+impl<D: CanDevice> J1939Tp<D>
+where
+    D::Channel: Clone,
+{
+    /// Call the callback with the DM1 of any source address in receive loop, the invalid messages are dropped.
+    pub fn on_dm1<F>(&mut self, conversion: SpnConversion, callback: F) -> &mut Self
+    where
+        F: FnMut(&DiagnosticMessage) + Send + 'static,
+    {
+        let mut callback: DiagnosticCallback = Box::new(callback);
+        self.add_handler(PGN_DM1, move |message| {
+            match DiagnosticMessage::decode(message.source, &message.data, conversion) {
+                Ok(v) => callback(&v),
+                Err(e) => log::warn!("{} from {:#04X}", e, message.source),
+            }
+        })
+    }
+
+    /// Request the active DTCs(DM1) from `destination`.
+    #[inline]
+    pub fn read_active_dtcs(&mut self, destination: u8, conversion: SpnConversion) -> Result<DiagnosticMessage, J1939Error> {
+        self.read_dtcs(PGN_DM1, destination, conversion)
+    }
+
+    /// Request the previously active DTCs(DM2) from `destination`.
+    #[inline]
+    pub fn read_previously_active_dtcs(&mut self, destination: u8, conversion: SpnConversion) -> Result<DiagnosticMessage, J1939Error> {
+        self.read_dtcs(PGN_DM2, destination, conversion)
+    }
+
+    /// Clear the active DTCs(DM11), the acknowledgment is waited when `destination` is not global.
+    #[inline]
+    pub fn clear_active_dtcs(&mut self, destination: u8) -> Result<(), J1939Error> {
+        self.clear_dtcs(PGN_DM11, destination)
+    }
+
+    /// Clear the previously active DTCs(DM3), the acknowledgment is waited when `destination` is not global.
+    #[inline]
+    pub fn clear_previously_active_dtcs(&mut self, destination: u8) -> Result<(), J1939Error> {
+        self.clear_dtcs(PGN_DM3, destination)
+    }
+
+    fn read_dtcs(&mut self, pgn: u32, destination: u8, conversion: SpnConversion) -> Result<DiagnosticMessage, J1939Error> {
+        self.request(pgn, destination)?;
+        let message = self.wait_response(pgn, destination)?;
+        match message.pgn {
+            PGN_ACKNOWLEDGMENT => Err(not_acknowledged(pgn, &message)),
+            _ => DiagnosticMessage::decode(message.source, &message.data, conversion),
+        }
+    }
+
+    fn clear_dtcs(&mut self, pgn: u32, destination: u8) -> Result<(), J1939Error> {
+        self.request(pgn, destination)?;
+        if destination == GLOBAL_ADDRESS {
+            return Ok(());
+        }
+
+        let message = self.wait_response(pgn, destination)?;
+        match message.data.first() {
+            Some(0) if message.pgn == PGN_ACKNOWLEDGMENT => Ok(()),
+            _ => Err(not_acknowledged(pgn, &message)),
+        }
+    }
+
+    /// Wait for the message of PGN or the acknowledgment of it from `source`.
+    fn wait_response(&mut self, pgn: u32, source: u8) -> Result<J1939Message, J1939Error> {
+        self.wait_message(DEFAULT_RESPONSE_TIMEOUT, |v| {
+            v.source == source && (v.pgn == pgn || (v.pgn == PGN_ACKNOWLEDGMENT && acknowledged_pgn(v) == Some(pgn)))
+        })?
+        .ok_or(CanError::TimeoutError(format!("J1939 no response of PGN {:#07X} from {:#04X}", pgn, source)).into())
+    }
+}
+
+/// Get the PGN of acknowledgment.
+#[inline]
+fn acknowledged_pgn(message: &J1939Message) -> Option<u32> {
+    message.data.get(5..8)
+        .map(|v| u32::from_le_bytes([v[0], v[1], v[2], 0]))
+}
+
+#[inline]
+fn not_acknowledged(pgn: u32, message: &J1939Message) -> J1939Error {
+    J1939Error::NotAcknowledged { pgn, control: message.data.first().copied().unwrap_or_default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{Arc, Mutex}, thread};
+    use crate::{j1939::{J1939Error, J1939Id, J1939Tp, GLOBAL_ADDRESS, PGN_REQUEST}, vcan::VirtualCan};
+    use crate::test_utils::new_device;
+    use super::{
+        DiagnosticMessage, FlashStatus, J1939Dtc, Lamp, LampStatus, Lamps, SpnConversion, PGN_ACKNOWLEDGMENT, PGN_DM1,
+        PGN_DM11, PGN_DM2, PGN_DM3,
+    };
+
+    fn new_tp(channel: &str, address: u8) -> anyhow::Result<J1939Tp<VirtualCan>> {
+        let mut tp = J1939Tp::new(new_device(channel)?, channel.into(), address);
+        let mut config = tp.config().clone();
+        config.set_bam_interval(1);
+        tp.set_config(config);
+        Ok(tp)
+    }
+
+    fn new_message(source: u8, count: usize) -> DiagnosticMessage {
+        DiagnosticMessage {
+            source,
+            lamps: Lamps {
+                amber_warning: Lamp { status: LampStatus::On, flash: FlashStatus::Fast },
+                ..Default::default()
+            },
+            dtcs: (0..count)
+                .map(|i| J1939Dtc::new(0x7FF00 + i as u32, 3, 1))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_codec() -> anyhow::Result<()> {
+        // SPN 190 FMI 3 OC 1, no lamp is on
+        let data = [0x00, 0xFF, 0xBE, 0x00, 0x03, 0x01, 0xFF, 0xFF];
+        let message = DiagnosticMessage::decode(0x00, &data, SpnConversion::default())?;
+        assert_eq!(message.lamps.malfunction, Lamp { status: LampStatus::Off, flash: FlashStatus::NotAvailable });
+        assert_eq!(message.dtcs, [J1939Dtc::new(190, 3, 1)]);
+        let message = DiagnosticMessage::decode(0x00, &[0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF], SpnConversion::default())?;
+        assert!(message.dtcs.is_empty());
+        assert_eq!(message.encode(), [0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF]);
+
+        let message = new_message(0x00, 2);
+        let data = message.encode();
+        assert_eq!(&data[..6], [0x04, 0xF7, 0x00, 0xFF, 0xE3, 0x01]);
+        assert_eq!(DiagnosticMessage::decode(0x00, &data, SpnConversion::default())?, message);
+
+        // SPN 0x40123 FMI 5 with conversion method
+        let data = [0x00, 0xFF, 0x80, 0x24, 0x65, 0x81];
+        let dtc = DiagnosticMessage::decode(0x00, &data, SpnConversion::Version1)?.dtcs[0];
+        assert_eq!((dtc.spn, dtc.fmi, dtc.occurrence_count, dtc.conversion_method), (0x40123, 5, 1, true));
+        let data = [0x00, 0xFF, 0x24, 0x80, 0x65, 0x81];
+        assert_eq!(DiagnosticMessage::decode(0x00, &data, SpnConversion::Version2)?.dtcs[0].spn, 0x40123);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dm1() -> anyhow::Result<()> {
+        let channel = "j1939-dm1";
+        let mut tool = new_tp(channel, 0xF9)?;
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let records = Arc::clone(&messages);
+        tool.on_dm1(SpnConversion::default(), move |v| records.lock().unwrap().push(v.clone()));
+
+        let mut engine = new_tp(channel, 0x00)?;
+        let mut brake = new_tp(channel, 0x0B)?;
+        // by BAM
+        engine.transmit(6, PGN_DM1, GLOBAL_ADDRESS, &new_message(0x00, 3).encode())?;
+        brake.transmit(6, PGN_DM1, GLOBAL_ADDRESS, &new_message(0x0B, 1).encode())?;
+        while tool.receive(100).is_ok() {}
+
+        assert_eq!(*messages.lock().unwrap(), [new_message(0x00, 3), new_message(0x0B, 1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_request() -> anyhow::Result<()> {
+        let channel = "j1939-dm-request";
+        let mut tool = new_tp(channel, 0xF9)?;
+        let mut engine = new_tp(channel, 0x00)?;
+        let handle = thread::spawn(move || -> anyhow::Result<()> {
+            for i in 0..5 {
+                let request = engine.receive(1000)?;
+                assert_eq!((request.pgn, request.source), (PGN_REQUEST, 0xF9));
+                let pgn = u32::from_le_bytes([request.data[0], request.data[1], request.data[2], 0]);
+                match pgn {
+                    PGN_DM2 => engine.transmit(6, PGN_DM2, 0xF9, &new_message(0x00, 4).encode())?,
+                    // the empty response is not an acknowledgment
+                    PGN_DM11 if i == 3 => engine.send(J1939Id::new(6, PGN_DM11, 0xF9, 0x00), &[])?,
+                    PGN_DM11 | PGN_DM3 => {
+                        let control = (pgn == PGN_DM3) as u8;
+                        let mut data = vec![control, 0xFF, 0xFF, 0xFF, 0xF9];
+                        data.extend(&pgn.to_le_bytes()[..3]);
+                        engine.send(J1939Id::new(6, PGN_ACKNOWLEDGMENT, GLOBAL_ADDRESS, 0x00), &data)?;
+                    },
+                    _ => {},
+                }
+            }
+            Ok(())
+        });
+
+        // by RTS/CTS
+        assert_eq!(tool.read_previously_active_dtcs(0x00, SpnConversion::default())?, new_message(0x00, 4));
+        tool.clear_active_dtcs(0x00)?;
+        let err = tool.clear_previously_active_dtcs(0x00).unwrap_err();
+        assert!(matches!(err, J1939Error::NotAcknowledged { pgn: PGN_DM3, control: 1 }));
+        let err = tool.clear_active_dtcs(0x00).unwrap_err();
+        assert!(matches!(err, J1939Error::NotAcknowledged { pgn: PGN_DM11, control: 0 }));
+        tool.clear_active_dtcs(GLOBAL_ADDRESS)?;
+        handle.join().unwrap()?;
+
+        Ok(())
+    }
+}
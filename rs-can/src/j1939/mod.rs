@@ -0,0 +1,269 @@
+//! The SAE J1939 identifier and the transport protocol(J1939-21) over any [`CanDevice`](crate::CanDevice).
+//!
+//! The messages longer than 8 bytes are transmitted by BAM when the destination is global,
+//! otherwise by the connection mode(RTS/CTS) data transfer.
+//! The node joins the network by claiming an address(J1939-81) with its [`Name`],
+//! and the diagnostic trouble codes(J1939-73) are read by DM1/DM2 and cleared by DM11/DM3.
+//!
+//! ```no_run
+//! use rs_can::{j1939::{AddressClaim, J1939Tp, NameBuilder, SpnConversion, GLOBAL_ADDRESS, NULL_ADDRESS}, vcan::VirtualCan};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//!
+//! let name = NameBuilder::new()
+//!     .set_identity_number(0x1234)
+//!     .set_manufacturer_code(0x123)
+//!     .set_arbitrary_address_capable(true)
+//!     .build();
+//! let mut tp = J1939Tp::new(device, "vcan0".into(), NULL_ADDRESS);
+//! tp.claim_address(AddressClaim::new(name, 0x80)).unwrap();
+//! tp.on_dm1(SpnConversion::default(), |dm1| println!("{:02X}: {:?}", dm1.source, dm1.dtcs));
+//! // the component identification
+//! tp.transmit(6, 0xFEEB, GLOBAL_ADDRESS, b"VENDOR*MODEL*SERIAL*UNIT*").unwrap();
+//! let message = tp.receive(1000).unwrap();
+//! println!("PGN {:05X} from {:02X}: {:02X?}", message.pgn, message.source, message.data);
+//! ```
+mod address;
+mod dm;
+mod tp;
+
+pub use address::*;
+pub use dm::*;
+pub use tp::*;
+
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+use crate::{CanError, CanId, EFF_MASK};
+
+/// The destination address of broadcast.
+pub const GLOBAL_ADDRESS: u8 = 0xFF;
+/// The source address of the node which has not claimed an address.
+pub const NULL_ADDRESS: u8 = 0xFE;
+/// The default priority of messages.
+pub const DEFAULT_PRIORITY: u8 = 6;
+/// The max length of message transmitted by transport protocol(255 packets).
+pub const MAX_TP_LENGTH: usize = 1785;
+/// The PGN of transport protocol connection management(TP.CM).
+pub const PGN_TP_CM: u32 = 0xEC00;
+/// The PGN of transport protocol data transfer(TP.DT).
+pub const PGN_TP_DT: u32 = 0xEB00;
+
+#[derive(Debug, Clone, Error)]
+pub enum J1939Error {
+    /// The transport session is aborted by the peer or this node.
+    #[error("RUST-CAN - J1939 transport of PGN {pgn:#07X} is aborted: {reason}")]
+    Aborted { pgn: u32, reason: AbortReason },
+    /// The request is acknowledged negatively, access denied or cannot respond.
+    #[error("RUST-CAN - J1939 request of PGN {pgn:#07X} is not acknowledged, control byte: {control}")]
+    NotAcknowledged { pgn: u32, control: u8 },
+    /// The address claiming is in progress or failed.
+    #[error("RUST-CAN - J1939 address is not claimed")]
+    AddressNotClaimed,
+    /// The parameter of message is invalid.
+    #[error("RUST-CAN - J1939 invalid parameter: {0}")]
+    InvalidParameter(String),
+    /// The error of device.
+    #[error(transparent)]
+    Transport(#[from] CanError),
+}
+
+/// The reason of connection abort(TP.Conn_Abort).
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    AlreadyInSession = 1,
+    ResourcesNeeded = 2,
+    Timeout = 3,
+    CtsWhileTransferring = 4,
+    MaxRetransmit = 5,
+    UnexpectedDataTransfer = 6,
+    BadSequence = 7,
+    DuplicateSequence = 8,
+    MessageTooLarge = 9,
+    /// the reason which is not defined
+    Other = 250,
+}
+
+impl TryFrom<u8> for AbortReason {
+    type Error = J1939Error;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::AlreadyInSession),
+            2 => Ok(Self::ResourcesNeeded),
+            3 => Ok(Self::Timeout),
+            4 => Ok(Self::CtsWhileTransferring),
+            5 => Ok(Self::MaxRetransmit),
+            6 => Ok(Self::UnexpectedDataTransfer),
+            7 => Ok(Self::BadSequence),
+            8 => Ok(Self::DuplicateSequence),
+            9 => Ok(Self::MessageTooLarge),
+            250 => Ok(Self::Other),
+            _ => Err(J1939Error::InvalidParameter(format!("abort reason {} is not supported", value))),
+        }
+    }
+}
+
+impl Display for AbortReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Self::AlreadyInSession => "already in one or more connection managed sessions",
+            Self::ResourcesNeeded => "system resources were needed for another task",
+            Self::Timeout => "a timeout occurred",
+            Self::CtsWhileTransferring => "CTS messages received when data transfer is in progress",
+            Self::MaxRetransmit => "maximum retransmit request limit reached",
+            Self::UnexpectedDataTransfer => "unexpected data transfer packet",
+            Self::BadSequence => "bad sequence number",
+            Self::DuplicateSequence => "duplicate sequence number",
+            Self::MessageTooLarge => "total message size is greater than 1785 bytes",
+            Self::Other => "other reason",
+        };
+        f.pad(reason)
+    }
+}
+
+/// The 29 bits identifier of J1939.
+///
+/// | priority | EDP | DP | PF | PS(DA or group extension) | SA |
+/// |----------|-----|----|----|---------------------------|----|
+/// | 3 bits   | 1   | 1  | 8  | 8                         | 8  |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct J1939Id(u32);
+
+impl J1939Id {
+    /// Create the identifier, the `destination` is ignored when the PGN is PDU2 format.
+    pub fn new(priority: u8, pgn: u32, destination: u8, source: u8) -> Self {
+        let mut pgn = pgn & 0x3FFFF;
+        if pgn >> 8 & 0xFF < 240 {
+            pgn = (pgn & 0x3FF00) | destination as u32;
+        }
+        Self(((priority as u32 & 0x07) << 26) | (pgn << 8) | source as u32)
+    }
+
+    #[inline]
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw & EFF_MASK)
+    }
+
+    #[inline(always)]
+    pub fn as_raw(self) -> u32 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub fn priority(self) -> u8 {
+        (self.0 >> 26) as u8 & 0x07
+    }
+
+    /// The extended data page.
+    #[inline(always)]
+    pub fn edp(self) -> bool {
+        self.0 & (1 << 25) != 0
+    }
+
+    /// The data page.
+    #[inline(always)]
+    pub fn dp(self) -> bool {
+        self.0 & (1 << 24) != 0
+    }
+
+    /// The PDU format.
+    #[inline(always)]
+    pub fn pf(self) -> u8 {
+        (self.0 >> 16) as u8
+    }
+
+    /// The PDU specific, which is the destination address of PDU1 or the group extension of PDU2.
+    #[inline(always)]
+    pub fn ps(self) -> u8 {
+        (self.0 >> 8) as u8
+    }
+
+    #[inline(always)]
+    pub fn source(self) -> u8 {
+        self.0 as u8
+    }
+
+    /// The message is PDU1 format which has a destination address.
+    #[inline(always)]
+    pub fn is_pdu1(self) -> bool {
+        self.pf() < 240
+    }
+
+    /// The parameter group number, the destination address of PDU1 is cleared.
+    #[inline]
+    pub fn pgn(self) -> u32 {
+        let pgn = self.0 >> 8 & 0x3FFFF;
+        if self.is_pdu1() { pgn & 0x3FF00 } else { pgn }
+    }
+
+    /// The destination address, which is global for PDU2 format.
+    #[inline]
+    pub fn destination(self) -> u8 {
+        if self.is_pdu1() { self.ps() } else { GLOBAL_ADDRESS }
+    }
+}
+
+impl Display for J1939Id {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08X}", self.0)
+    }
+}
+
+impl From<J1939Id> for CanId {
+    #[inline]
+    fn from(id: J1939Id) -> Self {
+        CanId::Extended(id.0)
+    }
+}
+
+impl TryFrom<CanId> for J1939Id {
+    type Error = J1939Error;
+    fn try_from(id: CanId) -> Result<Self, Self::Error> {
+        match id {
+            CanId::Extended(v) => Ok(Self::from_raw(v)),
+            CanId::Standard(v) => Err(J1939Error::InvalidParameter(format!("standard identifier {:03X} is not J1939", v))),
+        }
+    }
+}
+
+/// The message received by J1939 node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct J1939Message {
+    pub priority: u8,
+    pub pgn: u32,
+    pub source: u8,
+    /// the destination address, which is global for broadcast
+    pub destination: u8,
+    pub data: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CanId;
+    use super::{AbortReason, J1939Id, GLOBAL_ADDRESS};
+
+    #[test]
+    fn test_id() -> anyhow::Result<()> {
+        // EEC1 from engine
+        let id = J1939Id::try_from(CanId::Extended(0x0CF0_0400))?;
+        assert_eq!((id.priority(), id.pgn(), id.source(), id.destination()), (3, 0xF004, 0x00, GLOBAL_ADDRESS));
+        assert!(!id.is_pdu1());
+        assert_eq!(J1939Id::new(3, 0xF004, 0x21, 0x00), id);
+
+        // request to address 0x21
+        let id = J1939Id::new(6, 0xEA00, 0x21, 0xF9);
+        assert_eq!(CanId::from(id), CanId::Extended(0x18EA_21F9));
+        assert_eq!((id.pf(), id.ps(), id.pgn(), id.destination()), (0xEA, 0x21, 0xEA00, 0x21));
+        assert!(id.is_pdu1() && !id.dp() && !id.edp());
+
+        let id = J1939Id::from_raw(0x1BFF_FF00);
+        assert!(id.dp() && id.edp());
+        assert_eq!(id.pgn(), 0x3FFFF);
+        assert!(J1939Id::try_from(CanId::Standard(0x100)).is_err());
+        assert_eq!(AbortReason::try_from(3)?, AbortReason::Timeout);
+        assert!(AbortReason::try_from(11).is_err());
+
+        Ok(())
+    }
+}
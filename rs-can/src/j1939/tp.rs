@@ -0,0 +1,777 @@
+use std::{collections::{HashMap, VecDeque}, thread, time::{Duration, Instant}};
+use derive_getters::Getters;
+use crate::{CanDevice, CanError, CanFrame, CanId};
+use super::{
+    AbortReason, AddressClaim, ClaimState, J1939Error, J1939Id, J1939Message, DEFAULT_PRIORITY, GLOBAL_ADDRESS,
+    MAX_TP_LENGTH, NULL_ADDRESS, PGN_ADDRESS_CLAIMED, PGN_REQUEST, PGN_TP_CM, PGN_TP_DT,
+};
+
+/// The default interval of BAM data transfer packets in milliseconds.
+pub const DEFAULT_BAM_INTERVAL: u32 = 50;
+/// The default timeout between data transfer packets in milliseconds.
+pub const DEFAULT_T1: u32 = 750;
+/// The default timeout of data transfer packet after CTS in milliseconds.
+pub const DEFAULT_T2: u32 = 1250;
+/// The default timeout of CTS or EndOfMsgACK after the last packet in milliseconds.
+pub const DEFAULT_T3: u32 = 1250;
+/// The default timeout of next CTS after the hold CTS in milliseconds.
+pub const DEFAULT_T4: u32 = 1050;
+/// The default timeout of transmitting frame in milliseconds.
+pub const DEFAULT_TX_TIMEOUT: u32 = 1000;
+
+/// The handler of messages, which is called by the receive loop before the message returned.
+pub type MessageHandler = Box<dyn FnMut(&J1939Message) + Send>;
+
+/// The priority of transport protocol messages.
+const TP_PRIORITY: u8 = 7;
+/// The data length of a data transfer packet.
+const PACKET_SIZE: usize = 7;
+const RESERVED: u8 = 0xFF;
+
+const RTS: u8 = 16;
+const CTS: u8 = 17;
+const END_OF_MSG_ACK: u8 = 19;
+const BAM: u8 = 32;
+const ABORT: u8 = 255;
+
+/// The connection management message(TP.CM).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Management {
+    Rts { size: usize, packets: u8, max_packets: u8, pgn: u32 },
+    Cts { packets: u8, next: u8, pgn: u32 },
+    EndOfMsgAck { size: usize, packets: u8, pgn: u32 },
+    Bam { size: usize, packets: u8, pgn: u32 },
+    Abort { reason: u8, pgn: u32 },
+}
+
+impl Management {
+    fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let size = u16::from_le_bytes([data[1], data[2]]) as usize;
+        let pgn = u32::from_le_bytes([data[5], data[6], data[7], 0]);
+        match data[0] {
+            RTS => Some(Self::Rts { size, packets: data[3], max_packets: data[4], pgn }),
+            CTS => Some(Self::Cts { packets: data[1], next: data[2], pgn }),
+            END_OF_MSG_ACK => Some(Self::EndOfMsgAck { size, packets: data[3], pgn }),
+            BAM => Some(Self::Bam { size, packets: data[3], pgn }),
+            ABORT => Some(Self::Abort { reason: data[1], pgn }),
+            _ => None,
+        }
+    }
+
+    fn encode(&self) -> [u8; 8] {
+        let (head, pgn) = match *self {
+            Self::Rts { size, packets, max_packets, pgn } => (with_size(RTS, size, packets, max_packets), pgn),
+            Self::Cts { packets, next, pgn } => ([CTS, packets, next, RESERVED, RESERVED], pgn),
+            Self::EndOfMsgAck { size, packets, pgn } => (with_size(END_OF_MSG_ACK, size, packets, RESERVED), pgn),
+            Self::Bam { size, packets, pgn } => (with_size(BAM, size, packets, RESERVED), pgn),
+            Self::Abort { reason, pgn } => ([ABORT, reason, RESERVED, RESERVED, RESERVED], pgn),
+        };
+        let pgn = pgn.to_le_bytes();
+        [head[0], head[1], head[2], head[3], head[4], pgn[0], pgn[1], pgn[2]]
+    }
+
+    #[inline]
+    fn pgn(&self) -> u32 {
+        match *self {
+            Self::Rts { pgn, .. }
+            | Self::Cts { pgn, .. }
+            | Self::EndOfMsgAck { pgn, .. }
+            | Self::Bam { pgn, .. }
+            | Self::Abort { pgn, .. } => pgn,
+        }
+    }
+}
+
+#[inline]
+fn with_size(control: u8, size: usize, packets: u8, value: u8) -> [u8; 5] {
+    let size = (size as u16).to_le_bytes();
+    [control, size[0], size[1], packets, value]
+}
+
+/// The reception of a multi-packet message.
+struct Session {
+    priority: u8,
+    pgn: u32,
+    size: usize,
+    packets: u8,
+    /// the max packets per CTS requested by the originator
+    max_packets: u8,
+    data: Vec<u8>,
+    /// the next sequence number expected
+    next: usize,
+    /// the last sequence number of packets requested by CTS
+    last: usize,
+    deadline: Instant,
+}
+
+impl Session {
+    #[inline]
+    fn is_bam(&self, destination: u8) -> bool {
+        destination == GLOBAL_ADDRESS
+    }
+}
+
+/// The configuration of transport protocol, the timeouts are in milliseconds.
+#[derive(Debug, Clone, Getters)]
+pub struct J1939TpConfig {
+    /// the interval of BAM packets, 50 to 200 milliseconds is required
+    #[getter(copy)]
+    bam_interval: u32,
+    /// the max packets per CTS, which is sent by RTS and limits the CTS sent by receiver
+    #[getter(copy)]
+    packets_per_cts: u8,
+    #[getter(copy)]
+    t1: u32,
+    #[getter(copy)]
+    t2: u32,
+    #[getter(copy)]
+    t3: u32,
+    #[getter(copy)]
+    t4: u32,
+    #[getter(copy)]
+    tx_timeout: u32,
+}
+
+impl Default for J1939TpConfig {
+    fn default() -> Self {
+        Self {
+            bam_interval: DEFAULT_BAM_INTERVAL,
+            packets_per_cts: u8::MAX,
+            t1: DEFAULT_T1,
+            t2: DEFAULT_T2,
+            t3: DEFAULT_T3,
+            t4: DEFAULT_T4,
+            tx_timeout: DEFAULT_TX_TIMEOUT,
+        }
+    }
+}
+
+impl J1939TpConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn set_bam_interval(&mut self, interval: u32) -> &mut Self {
+        self.bam_interval = interval;
+        self
+    }
+
+    pub fn set_packets_per_cts(&mut self, packets: u8) -> &mut Self {
+        self.packets_per_cts = packets.max(1);
+        self
+    }
+
+    pub fn set_t1(&mut self, timeout: u32) -> &mut Self {
+        self.t1 = timeout;
+        self
+    }
+
+    pub fn set_t2(&mut self, timeout: u32) -> &mut Self {
+        self.t2 = timeout;
+        self
+    }
+
+    pub fn set_t3(&mut self, timeout: u32) -> &mut Self {
+        self.t3 = timeout;
+        self
+    }
+
+    pub fn set_t4(&mut self, timeout: u32) -> &mut Self {
+        self.t4 = timeout;
+        self
+    }
+
+    pub fn set_tx_timeout(&mut self, timeout: u32) -> &mut Self {
+        self.tx_timeout = timeout;
+        self
+    }
+}
+
+/// The J1939 node with transport protocol on a channel of device.
+///
+/// The transmitting is blocked until the message is sent completely, the frames received meanwhile
+/// are kept for [`J1939Tp::receive`]. The receptions of several originators are reassembled concurrently.
+///
+/// When the address is claimed by [`J1939Tp::claim_address`], the address claimed messages and the requests
+/// for address claimed are handled by [`J1939Tp::receive`] before they are returned.
+pub struct J1939Tp<D: CanDevice> {
+    device: D,
+    channel: D::Channel,
+    address: u8,
+    config: J1939TpConfig,
+    pending: VecDeque<D::Frame>,
+    /// the sessions by source and destination address
+    sessions: HashMap<(u8, u8), Session>,
+    claim: Option<AddressClaim>,
+    /// the messages received while claiming address or waiting for response
+    messages: VecDeque<J1939Message>,
+    handlers: HashMap<u32, Vec<MessageHandler>>,
+}
+
+impl<D: CanDevice> J1939Tp<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(device: D, channel: D::Channel, address: u8) -> Self {
+        Self {
+            device,
+            channel,
+            address,
+            config: Default::default(),
+            pending: Default::default(),
+            sessions: Default::default(),
+            claim: None,
+            messages: Default::default(),
+            handlers: Default::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    #[inline(always)]
+    pub fn channel(&self) -> &D::Channel {
+        &self.channel
+    }
+
+    /// The source address of node.
+    #[inline(always)]
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    pub fn set_address(&mut self, address: u8) -> &mut Self {
+        self.address = address;
+        self
+    }
+
+    /// The address claiming and the addresses claimed by other controllers.
+    #[inline(always)]
+    pub fn address_claim(&self) -> Option<&AddressClaim> {
+        self.claim.as_ref()
+    }
+
+    /// Claim the address, return the address claimed when no contention in 250 milliseconds.
+    ///
+    /// The node should keep receiving after claimed, so that the address is defended and the requests are responded.
+    pub fn claim_address(&mut self, claim: AddressClaim) -> Result<u8, J1939Error> {
+        let mut claim = claim;
+        let source = claim.start(Instant::now());
+        self.claim = Some(claim);
+        self.send_address_claimed(source)?;
+
+        loop {
+            let claim = self.claim.as_mut().unwrap();
+            claim.poll(Instant::now());
+            let deadline = match claim.state() {
+                ClaimState::Claimed(address) => return Ok(address),
+                ClaimState::Claiming(_) => claim.deadline().unwrap(),
+                _ => return Err(J1939Error::AddressNotClaimed),
+            };
+            if let Some(message) = self.fetch(deadline)? {
+                self.messages.push_back(message);
+            }
+        }
+    }
+
+    /// Request the PGN from `destination`, the request is sent even if the address is not claimed.
+    pub fn request(&mut self, pgn: u32, destination: u8) -> Result<(), J1939Error> {
+        let pgn = pgn.to_le_bytes();
+        self.send(J1939Id::new(DEFAULT_PRIORITY, PGN_REQUEST, destination, self.address), &pgn[..3])
+    }
+
+    /// Add the handler of messages with the PGN, which is called when the message is received.
+    pub fn add_handler<F>(&mut self, pgn: u32, handler: F) -> &mut Self
+    where
+        F: FnMut(&J1939Message) + Send + 'static,
+    {
+        self.handlers.entry(pgn)
+            .or_default()
+            .push(Box::new(handler));
+        self
+    }
+
+    #[inline(always)]
+    pub fn config(&self) -> &J1939TpConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: J1939TpConfig) -> &mut Self {
+        self.config = config;
+        self
+    }
+
+    /// Transmit the message, the message longer than 8 bytes is transmitted by BAM
+    /// when `destination` is global, otherwise by RTS/CTS.
+    pub fn transmit(&mut self, priority: u8, pgn: u32, destination: u8, data: &[u8]) -> Result<(), J1939Error> {
+        if data.len() > MAX_TP_LENGTH {
+            return Err(J1939Error::InvalidParameter(format!("message length {} is greater than {}", data.len(), MAX_TP_LENGTH)));
+        }
+        if let Some(claim) = self.claim.as_mut() {
+            claim.poll(Instant::now());
+            if claim.address().is_none() {
+                return Err(J1939Error::AddressNotClaimed);
+            }
+        }
+        if data.len() <= 8 {
+            return self.send(J1939Id::new(priority, pgn, destination, self.address), data);
+        }
+
+        let packets = data.len().div_ceil(PACKET_SIZE) as u8;
+        if destination == GLOBAL_ADDRESS {
+            self.send_management(GLOBAL_ADDRESS, Management::Bam { size: data.len(), packets, pgn })?;
+            for (i, packet) in data.chunks(PACKET_SIZE).enumerate() {
+                thread::sleep(Duration::from_millis(self.config.bam_interval as u64));
+                self.send_packet(GLOBAL_ADDRESS, i as u8 + 1, packet)?;
+            }
+            return Ok(());
+        }
+
+        let max_packets = self.config.packets_per_cts;
+        self.send_management(destination, Management::Rts { size: data.len(), packets, max_packets, pgn })?;
+        let mut timeout = self.config.t3;
+        loop {
+            let management = match self.wait_management(destination, pgn, timeout)? {
+                Some(v) => v,
+                None => {
+                    self.abort(destination, pgn, AbortReason::Timeout)?;
+                    return Err(CanError::TimeoutError(format!("J1939 no response from {:#04X}", destination)).into());
+                },
+            };
+            match management {
+                // hold the connection
+                Management::Cts { packets: 0, .. } => timeout = self.config.t4,
+                Management::Cts { packets: count, next, .. } => {
+                    let first = next as usize;
+                    let last = first + count as usize - 1;
+                    if first == 0 || last > packets as usize {
+                        self.abort(destination, pgn, AbortReason::Other)?;
+                        return Err(J1939Error::Aborted { pgn, reason: AbortReason::Other });
+                    }
+                    for sequence in first..=last {
+                        let offset = (sequence - 1) * PACKET_SIZE;
+                        let packet = &data[offset..(offset + PACKET_SIZE).min(data.len())];
+                        self.send_packet(destination, sequence as u8, packet)?;
+                    }
+                    timeout = self.config.t3;
+                },
+                Management::EndOfMsgAck { .. } => return Ok(()),
+                Management::Abort { reason, .. } => {
+                    let reason = AbortReason::try_from(reason)
+                        .unwrap_or(AbortReason::Other);
+                    return Err(J1939Error::Aborted { pgn, reason });
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Receive a message which is broadcast or addressed to this node, waiting at most `timeout` milliseconds.
+    pub fn receive(&mut self, timeout: u32) -> Result<J1939Message, J1939Error> {
+        if let Some(message) = self.messages.pop_front() {
+            return Ok(message);
+        }
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        self.fetch(deadline)?
+            .ok_or(CanError::TimeoutError("no J1939 message received".into()).into())
+    }
+
+    /// Wait for the message matched in `timeout` milliseconds, the other messages are kept for receiving.
+    pub(crate) fn wait_message<F>(&mut self, timeout: u32, mut matched: F) -> Result<Option<J1939Message>, J1939Error>
+    where
+        F: FnMut(&J1939Message) -> bool,
+    {
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        while let Some(message) = self.fetch(deadline)? {
+            if matched(&message) {
+                return Ok(Some(message));
+            }
+            self.messages.push_back(message);
+        }
+
+        Ok(None)
+    }
+
+    /// Process the frames until a message is received or deadline.
+    fn fetch(&mut self, deadline: Instant) -> Result<Option<J1939Message>, J1939Error> {
+        loop {
+            while let Some(frame) = self.pending.pop_front() {
+                if let Some(message) = self.process(&frame)? {
+                    self.handle_claim(&message)?;
+                    if let Some(handlers) = self.handlers.get_mut(&message.pgn) {
+                        handlers.iter_mut()
+                            .for_each(|handler| handler(&message));
+                    }
+                    return Ok(Some(message));
+                }
+            }
+            self.check_sessions()?;
+
+            let now = Instant::now();
+            if let Some(claim) = self.claim.as_mut() {
+                claim.poll(now);
+            }
+            if now >= deadline {
+                return Ok(None);
+            }
+            let until = self.sessions.values()
+                .map(|v| v.deadline)
+                .min()
+                .map_or(deadline, |v| v.min(deadline));
+            let timeout = until.saturating_duration_since(now).as_millis().clamp(1, u32::MAX as u128) as u32;
+            match self.device.receive(self.channel.clone(), Some(timeout)) {
+                Ok(frames) => self.pending.extend(frames),
+                Err(CanError::TimeoutError(_)) => {},
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Transmit a frame with the J1939 identifier.
+    pub fn send(&self, id: J1939Id, data: &[u8]) -> Result<(), J1939Error> {
+        let mut frame = D::Frame::new(CanId::from(id), data)
+            .ok_or(CanError::other_error("can't create J1939 frame"))?;
+        frame.set_channel(self.channel.clone());
+        self.device.transmit(frame, Some(self.config.tx_timeout))
+            .map_err(J1939Error::from)
+    }
+
+    /// Handle the network management message when the address is claimed.
+    fn handle_claim(&mut self, message: &J1939Message) -> Result<(), J1939Error> {
+        if !matches!(message.pgn, PGN_REQUEST | PGN_ADDRESS_CLAIMED) {
+            return Ok(());
+        }
+        match self.claim.as_mut().and_then(|v| v.handle(message, Instant::now())) {
+            Some(source) => self.send_address_claimed(source),
+            None => Ok(()),
+        }
+    }
+
+    /// Send the address claimed from `source`, or the cannot claim address from null address.
+    fn send_address_claimed(&mut self, source: u8) -> Result<(), J1939Error> {
+        let name = match self.claim.as_ref() {
+            Some(v) => v.name(),
+            None => return Ok(()),
+        };
+        self.address = source;
+        if source == NULL_ADDRESS {
+            log::warn!("RUST-CAN - J1939 cannot claim address for NAME {}", name);
+        }
+        self.send(J1939Id::new(DEFAULT_PRIORITY, PGN_ADDRESS_CLAIMED, GLOBAL_ADDRESS, source), &name.to_bytes())
+    }
+
+    fn process(&mut self, frame: &D::Frame) -> Result<Option<J1939Message>, J1939Error> {
+        if frame.is_remote() || frame.is_error_frame() || !frame.is_extended() {
+            return Ok(None);
+        }
+
+        let id = J1939Id::from_raw(frame.id().into_bits());
+        let destination = id.destination();
+        if destination != self.address && destination != GLOBAL_ADDRESS {
+            return Ok(None);
+        }
+
+        let data = frame.data();
+        match id.pgn() {
+            PGN_TP_CM => self.process_management(id, data),
+            PGN_TP_DT => self.process_packet(id, data),
+            pgn => Ok(Some(J1939Message { priority: id.priority(), pgn, source: id.source(), destination, data: data.to_vec() })),
+        }
+    }
+
+    fn process_management(&mut self, id: J1939Id, data: &[u8]) -> Result<Option<J1939Message>, J1939Error> {
+        let management = match Management::decode(data) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let source = id.source();
+        let destination = id.destination();
+        let key = (source, destination);
+        match management {
+            Management::Bam { size, packets, pgn } if destination == GLOBAL_ADDRESS => {
+                if size <= 8 || size > MAX_TP_LENGTH || size.div_ceil(PACKET_SIZE) != packets as usize {
+                    log::warn!("RUST-CAN - J1939 invalid BAM of PGN {:#07X} from {:#04X}", pgn, source);
+                    self.sessions.remove(&key);
+                    return Ok(None);
+                }
+                self.sessions.insert(key, self.new_session(id.priority(), pgn, size, packets, u8::MAX, self.config.t1));
+            },
+            Management::Rts { size, packets, max_packets, pgn } if destination == self.address => {
+                if size > MAX_TP_LENGTH {
+                    self.sessions.remove(&key);
+                    return self.abort(source, pgn, AbortReason::MessageTooLarge).map(|_| None);
+                }
+                if size <= 8 || size.div_ceil(PACKET_SIZE) != packets as usize {
+                    self.sessions.remove(&key);
+                    return self.abort(source, pgn, AbortReason::Other).map(|_| None);
+                }
+                if self.sessions.remove(&key).is_some() {
+                    log::warn!("RUST-CAN - J1939 session from {:#04X} is restarted by RTS", source);
+                }
+
+                let mut session = self.new_session(id.priority(), pgn, size, packets, max_packets, self.config.t2);
+                self.send_cts(source, &mut session)?;
+                self.sessions.insert(key, session);
+            },
+            Management::Abort { reason, pgn } => {
+                if self.sessions.get(&key).is_some_and(|v| v.pgn == pgn) {
+                    self.sessions.remove(&key);
+                    log::warn!("RUST-CAN - J1939 session of PGN {:#07X} from {:#04X} is aborted by reason {}", pgn, source, reason);
+                }
+            },
+            v => log::debug!("RUST-CAN - J1939 unexpected connection management {:?} from {:#04X}", v, source),
+        }
+
+        Ok(None)
+    }
+
+    fn process_packet(&mut self, id: J1939Id, data: &[u8]) -> Result<Option<J1939Message>, J1939Error> {
+        let source = id.source();
+        let destination = id.destination();
+        let key = (source, destination);
+        let session = match self.sessions.get_mut(&key) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let bam = session.is_bam(destination);
+        let sequence = data.first().copied().unwrap_or_default() as usize;
+        if sequence != session.next || sequence > session.last {
+            let reason = if sequence != 0 && sequence < session.next { AbortReason::DuplicateSequence } else { AbortReason::BadSequence };
+            let pgn = session.pgn;
+            self.sessions.remove(&key);
+            log::warn!("RUST-CAN - J1939 session of PGN {:#07X} from {:#04X}: {}", pgn, source, reason);
+            if !bam {
+                self.abort(source, pgn, reason)?;
+            }
+            return Ok(None);
+        }
+
+        let remaining = session.size - session.data.len();
+        session.data.extend(&data[1..data.len().min(remaining + 1)]);
+        session.next += 1;
+        session.deadline = Instant::now() + Duration::from_millis(self.config.t1 as u64);
+        if session.data.len() < session.size {
+            if !bam && session.next > session.last {
+                let mut session = self.sessions.remove(&key).unwrap();
+                self.send_cts(source, &mut session)?;
+                self.sessions.insert(key, session);
+            }
+            return Ok(None);
+        }
+
+        let session = self.sessions.remove(&key).unwrap();
+        if !bam {
+            self.send_management(source, Management::EndOfMsgAck { size: session.size, packets: session.packets, pgn: session.pgn })?;
+        }
+        Ok(Some(J1939Message {
+            priority: session.priority,
+            pgn: session.pgn,
+            source,
+            destination,
+            data: session.data,
+        }))
+    }
+
+    /// Drop the sessions which are timeout, the connection mode sessions are aborted.
+    fn check_sessions(&mut self) -> Result<(), J1939Error> {
+        let now = Instant::now();
+        let expired = self.sessions.iter()
+            .filter(|(_, v)| v.deadline <= now)
+            .map(|(&k, _)| k)
+            .collect::<Vec<_>>();
+        for key @ (source, destination) in expired {
+            if let Some(session) = self.sessions.remove(&key) {
+                log::warn!("RUST-CAN - J1939 session of PGN {:#07X} from {:#04X} is timeout", session.pgn, source);
+                if !session.is_bam(destination) {
+                    self.abort(source, session.pgn, AbortReason::Timeout)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn new_session(&self, priority: u8, pgn: u32, size: usize, packets: u8, max_packets: u8, timeout: u32) -> Session {
+        Session {
+            priority,
+            pgn,
+            size,
+            packets,
+            max_packets,
+            data: Vec::with_capacity(size),
+            next: 1,
+            last: packets as usize,
+            deadline: Instant::now() + Duration::from_millis(timeout as u64),
+        }
+    }
+
+    /// Request the next packets of session.
+    fn send_cts(&self, destination: u8, session: &mut Session) -> Result<(), J1939Error> {
+        let remaining = session.packets as usize - session.next + 1;
+        let count = remaining.min(session.max_packets.max(1) as usize)
+            .min(self.config.packets_per_cts as usize);
+        session.last = session.next + count - 1;
+        session.deadline = Instant::now() + Duration::from_millis(self.config.t2 as u64);
+        self.send_management(destination, Management::Cts { packets: count as u8, next: session.next as u8, pgn: session.pgn })
+    }
+
+    /// Wait for the connection management of the PGN from `peer`, the other frames are kept for receiving.
+    fn wait_management(&mut self, peer: u8, pgn: u32, timeout: u32) -> Result<Option<Management>, J1939Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let timeout = (deadline - now).as_millis().clamp(1, u32::MAX as u128) as u32;
+            let frames = match self.device.receive(self.channel.clone(), Some(timeout)) {
+                Ok(v) => v,
+                Err(CanError::TimeoutError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let mut result = None;
+            for frame in frames {
+                if result.is_none() && frame.is_extended() && !frame.is_remote() && !frame.is_error_frame() {
+                    let id = J1939Id::from_raw(frame.id().into_bits());
+                    if id.pgn() == PGN_TP_CM && id.source() == peer && id.destination() == self.address {
+                        result = Management::decode(frame.data())
+                            .filter(|v| v.pgn() == pgn);
+                        if result.is_some() {
+                            continue;
+                        }
+                    }
+                }
+                self.pending.push_back(frame);
+            }
+            if result.is_some() {
+                return Ok(result);
+            }
+        }
+    }
+
+    #[inline]
+    fn abort(&self, destination: u8, pgn: u32, reason: AbortReason) -> Result<(), J1939Error> {
+        self.send_management(destination, Management::Abort { reason: reason as u8, pgn })
+    }
+
+    #[inline]
+    fn send_management(&self, destination: u8, management: Management) -> Result<(), J1939Error> {
+        self.send(J1939Id::new(TP_PRIORITY, PGN_TP_CM, destination, self.address), &management.encode())
+    }
+
+    #[inline]
+    fn send_packet(&self, destination: u8, sequence: u8, packet: &[u8]) -> Result<(), J1939Error> {
+        let mut data = [RESERVED; 8];
+        data[0] = sequence;
+        data[1..=packet.len()].copy_from_slice(packet);
+        self.send(J1939Id::new(TP_PRIORITY, PGN_TP_DT, destination, self.address), &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::{vcan::VirtualCan, CanDevice, CanError, CanFrame, CanId};
+    use crate::j1939::{AbortReason, J1939Error, J1939Id, GLOBAL_ADDRESS, MAX_TP_LENGTH, PGN_TP_CM};
+    use crate::test_utils::new_device;
+    use super::{J1939Tp, J1939TpConfig, Management};
+
+    fn new_tp(channel: &str, address: u8) -> anyhow::Result<J1939Tp<VirtualCan>> {
+        let mut tp = J1939Tp::new(new_device(channel)?, channel.into(), address);
+        let mut config = J1939TpConfig::new();
+        config.set_bam_interval(1)
+            .set_t3(200);
+        tp.set_config(config);
+        Ok(tp)
+    }
+
+    #[test]
+    fn test_management() {
+        let management = Management::Rts { size: 1785, packets: 255, max_packets: 16, pgn: 0xFECA };
+        assert_eq!(management.encode(), [0x10, 0xF9, 0x06, 0xFF, 0x10, 0xCA, 0xFE, 0x00]);
+        assert_eq!(Management::decode(&management.encode()), Some(management));
+        let management = Management::Abort { reason: 3, pgn: 0x1EF00 };
+        assert_eq!(management.encode(), [0xFF, 0x03, 0xFF, 0xFF, 0xFF, 0x00, 0xEF, 0x01]);
+        assert_eq!(Management::decode(&[0x11, 0x02, 0x01]), None);
+    }
+
+    #[test]
+    fn test_bam() -> anyhow::Result<()> {
+        let channel = "j1939-bam";
+        let mut receiver = new_tp(channel, 0x21)?;
+        let mut sender = new_tp(channel, 0x00)?;
+        let data = (0..40).collect::<Vec<u8>>();
+
+        sender.transmit(6, 0xFEE3, GLOBAL_ADDRESS, &data[..8])?;
+        sender.transmit(6, 0xFEE3, GLOBAL_ADDRESS, &data)?;
+        let message = receiver.receive(100)?;
+        assert_eq!((message.pgn, message.source, message.destination, message.data.as_slice()), (0xFEE3, 0x00, GLOBAL_ADDRESS, &data[..8]));
+        let message = receiver.receive(100)?;
+        assert_eq!((message.pgn, message.source, message.destination), (0xFEE3, 0x00, GLOBAL_ADDRESS));
+        assert_eq!(message.data, data);
+
+        // the session is dropped by bad sequence
+        sender.send_management(GLOBAL_ADDRESS, Management::Bam { size: 9, packets: 2, pgn: 0xFEE3 })?;
+        sender.send_packet(GLOBAL_ADDRESS, 2, &[0x00; 7])?;
+        sender.send_packet(GLOBAL_ADDRESS, 1, &[0x00; 7])?;
+        assert!(matches!(receiver.receive(50), Err(J1939Error::Transport(CanError::TimeoutError(_)))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cmdt() -> anyhow::Result<()> {
+        let channel = "j1939-cmdt";
+        let mut receiver = new_tp(channel, 0x21)?;
+        let mut config = receiver.config().clone();
+        config.set_packets_per_cts(16);
+        receiver.set_config(config);
+        let mut sender = new_tp(channel, 0xF9)?;
+        let data = (0..MAX_TP_LENGTH).map(|v| v as u8).collect::<Vec<_>>();
+
+        let expected = data.clone();
+        let handle = thread::spawn(move || -> anyhow::Result<()> {
+            let message = receiver.receive(1000)?;
+            assert_eq!((message.pgn, message.source, message.destination), (0xC600, 0xF9, 0x21));
+            assert_eq!(message.data, expected);
+            Ok(())
+        });
+        sender.transmit(6, 0xC600, 0x21, &data)?;
+        handle.join().unwrap()?;
+
+        let err = sender.transmit(6, 0xC600, 0x21, &data).unwrap_err();
+        assert!(matches!(err, J1939Error::Transport(CanError::TimeoutError(_))));
+        assert!(sender.transmit(6, 0xC600, 0x21, &[0x00; MAX_TP_LENGTH + 1]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abort() -> anyhow::Result<()> {
+        let channel = "j1939-abort";
+        let mut sender = new_tp(channel, 0xF9)?;
+        let device = new_device(channel)?;
+        let handle = thread::spawn(move || -> anyhow::Result<()> {
+            let frames = device.receive(channel.into(), Some(1000))?;
+            let rts = frames.first().unwrap();
+            assert_eq!(rts.id(), CanId::Extended(0x1CEC_21F9));
+            let abort = Management::Abort { reason: AbortReason::ResourcesNeeded as u8, pgn: 0xC600 };
+            let mut frame = crate::vcan::CanMessage::new(J1939Id::new(7, PGN_TP_CM, 0xF9, 0x21), &abort.encode()).unwrap();
+            frame.set_channel(channel.into());
+            device.transmit(frame, None)?;
+            Ok(())
+        });
+
+        let err = sender.transmit(6, 0xC600, 0x21, &[0x00; 100]).unwrap_err();
+        assert!(matches!(err, J1939Error::Aborted { pgn: 0xC600, reason: AbortReason::ResourcesNeeded }));
+        handle.join().unwrap()?;
+
+        Ok(())
+    }
+}
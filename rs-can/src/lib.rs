@@ -1,13 +1,33 @@
+#[cfg(feature = "async")]
+mod blocking;
 mod constants;
 mod device;
 mod error;
 mod frame;
+mod manager;
+#[cfg(test)]
+mod test_utils;
 pub mod can_utils;
+pub mod canopen;
+pub mod dbc;
+pub mod flash;
 pub mod interfaces;
+pub mod isotp;
+pub mod j1939;
+pub mod nmea2000;
+pub mod replay;
+pub mod trace;
+pub mod uds;
+pub mod vcan;
 
 pub(crate) use can_utils as utils;
 
 pub use crate::constants::*;
 pub use crate::device::{ChannelConfig, Device as CanDevice, DeviceBuilder, Listener as CanListener, CanResult};
+#[cfg(feature = "async")]
+pub use crate::blocking::{BlockingAdapter, BlockingStream};
+#[cfg(feature = "async")]
+pub use crate::device::AsyncDevice as AsyncCanDevice;
 pub use crate::error::{Error as CanError};
+pub use crate::manager::BusManager;
 pub use crate::frame::{Direct as CanDirect, Frame as CanFrame, Type as CanType, Id as CanId, Filter as CanFilter, IdentifierFlags};
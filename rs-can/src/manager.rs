@@ -0,0 +1,234 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard}, thread::{self, JoinHandle}};
+use crate::{CanDevice, CanError, CanFrame, CanListener};
+
+/// The timeout in milliseconds of each receiving by the workers.
+const RECEIVE_TIMEOUT: u32 = 10;
+
+type BoxListener<D> = Box<dyn CanListener<<D as CanDevice>::Channel, <D as CanDevice>::Frame>>;
+type Listeners<D> = HashMap<String, BoxListener<D>>;
+
+/// The manager which owns a device, receives frames of each opened channel in background
+/// and dispatches the frames to the registered [`CanListener`]s.
+///
+/// The callbacks are called by the worker threads while the listeners are locked,
+/// so the listeners should not be registered or unregistered in the callbacks.
+///
+/// ```no_run
+/// use rs_can::{BusManager, vcan::VirtualCan};
+///
+/// let mut device = VirtualCan::new();
+/// device.init_channel("vcan0", None).unwrap();
+///
+/// let mut manager = BusManager::new(device);
+/// // manager.register_listener("logger", Box::new(listener));
+/// manager.shutdown();
+/// ```
+pub struct BusManager<D: CanDevice> {
+    device: D,
+    listeners: Arc<Mutex<Listeners<D>>>,
+    running: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<D> BusManager<D>
+where
+    D: CanDevice + Send + Sync + 'static,
+    D::Channel: Clone + Send + 'static,
+{
+    /// Start the receive worker of each opened channel.
+    pub fn new(device: D) -> Self {
+        let listeners: Arc<Mutex<Listeners<D>>> = Default::default();
+        let running = Arc::new(AtomicBool::new(true));
+        let workers = device.opened_channels()
+            .into_iter()
+            .map(|channel| {
+                let device = device.clone();
+                let listeners = Arc::clone(&listeners);
+                let flag = Arc::clone(&running);
+                thread::spawn(move || {
+                    while flag.load(Ordering::Acquire) {
+                        match device.receive(channel.clone(), Some(RECEIVE_TIMEOUT)) {
+                            Ok(frames) => if !frames.is_empty() {
+                                match listeners.lock() {
+                                    Ok(listeners) => listeners.values()
+                                        .for_each(|l| l.on_frame_received(channel.clone(), &frames)),
+                                    Err(e) => log::warn!("RUST-CAN - {} when frames dispatching", e),
+                                }
+                            },
+                            Err(CanError::TimeoutError(_)) => {},
+                            Err(e) => {
+                                log::warn!("RUST-CAN - {} when channel: {} receiving", e, channel);
+                                thread::sleep(std::time::Duration::from_millis(RECEIVE_TIMEOUT as u64));
+                            },
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Self { device, listeners, running, workers }
+    }
+
+    #[inline(always)]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// Register the listener, the listener with same name is replaced and returned.
+    pub fn register_listener<S: Into<String>>(
+        &self,
+        name: S,
+        listener: BoxListener<D>,
+    ) -> Result<Option<BoxListener<D>>, CanError> {
+        Ok(self.lock_listeners()?.insert(name.into(), listener))
+    }
+
+    /// Unregister the listener, `None` is returned when it is not registered.
+    pub fn unregister_listener(&self, name: &str) -> Result<Option<BoxListener<D>>, CanError> {
+        Ok(self.lock_listeners()?.remove(name))
+    }
+
+    #[inline]
+    pub fn unregister_all(&self) -> Result<(), CanError> {
+        self.lock_listeners()?.clear();
+        Ok(())
+    }
+
+    #[inline]
+    pub fn listener_names(&self) -> Result<Vec<String>, CanError> {
+        Ok(self.lock_listeners()?.keys().cloned().collect())
+    }
+
+    /// Access the listener of type `T`, `None` is returned when it is not registered or the type is mismatched.
+    pub fn with_listener<T: 'static, R>(&self, name: &str, f: impl FnOnce(&T) -> R) -> Result<Option<R>, CanError> {
+        Ok(self.lock_listeners()?
+            .get(name)
+            .and_then(|l| l.as_any().downcast_ref::<T>())
+            .map(f))
+    }
+
+    /// Transmit the frame and notify the listeners before and after transmitting.
+    pub fn transmit(&self, msg: D::Frame, timeout: Option<u32>) -> Result<(), CanError> {
+        let channel = msg.channel();
+        let id = msg.id();
+        self.lock_listeners()?
+            .values()
+            .for_each(|l| l.on_frame_transmitting(channel.clone(), &msg));
+        self.device.transmit(msg, timeout)?;
+        self.lock_listeners()?
+            .values()
+            .for_each(|l| l.on_frame_transmitted(channel.clone(), id));
+
+        Ok(())
+    }
+
+    #[inline]
+    fn lock_listeners(&self) -> Result<MutexGuard<'_, Listeners<D>>, CanError> {
+        self.listeners.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))
+    }
+}
+
+impl<D: CanDevice> BusManager<D> {
+    /// Stop the workers and close the device.
+    pub fn shutdown(&mut self) {
+        self.stop();
+        self.device.shutdown();
+    }
+
+    fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        self.workers.drain(..)
+            .for_each(|h| {
+                if h.join().is_err() {
+                    log::warn!("RUST-CAN - receive worker panicked");
+                }
+            });
+    }
+}
+
+impl<D: CanDevice> Drop for BusManager<D> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{any::Any, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+    use crate::{vcan::CanMessage, CanDevice, CanFrame, CanId, CanListener};
+    use crate::test_utils::{new_device, new_message};
+    use super::BusManager;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl CanListener<String, CanMessage> for Recorder {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn on_frame_transmitting(&self, channel: String, frame: &CanMessage) {
+            self.events.lock().unwrap().push(format!("transmitting {} {:03X}", channel, frame.id().as_raw()));
+        }
+
+        fn on_frame_transmitted(&self, channel: String, id: CanId) {
+            self.events.lock().unwrap().push(format!("transmitted {} {:03X}", channel, id.as_raw()));
+        }
+
+        fn on_frame_received(&self, channel: String, frames: &[CanMessage]) {
+            frames.iter()
+                .for_each(|f| self.events.lock().unwrap().push(format!("received {} {:03X}", channel, f.id().as_raw())));
+        }
+    }
+
+    fn wait_events(events: &Arc<Mutex<Vec<String>>>, count: usize) -> Vec<String> {
+        let start = Instant::now();
+        while events.lock().unwrap().len() < count && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        events.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_bus_manager() -> anyhow::Result<()> {
+        let channel = "vbus-manager";
+        let mut manager1 = BusManager::new(new_device(channel)?);
+        let mut manager2 = BusManager::new(new_device(channel)?);
+
+        let (recorder1, recorder2) = (Recorder::default(), Recorder::default());
+        let (events1, events2) = (Arc::clone(&recorder1.events), Arc::clone(&recorder2.events));
+        assert!(manager1.register_listener("recorder", Box::new(recorder1))?.is_none());
+        manager2.register_listener("recorder", Box::new(recorder2))?;
+        assert_eq!(manager2.listener_names()?, ["recorder"]);
+        assert_eq!(manager2.with_listener("recorder", |l: &Recorder| Arc::ptr_eq(&l.events, &events2))?, Some(true));
+        assert_eq!(manager2.with_listener("recorder", |_: &String| ())?, None);
+
+        manager1.transmit(new_message(channel, 0x123, &[0x01]), None)?;
+        assert_eq!(wait_events(&events1, 2), [
+            "transmitting vbus-manager 123",
+            "transmitted vbus-manager 123",
+        ]);
+        assert_eq!(wait_events(&events2, 1), ["received vbus-manager 123"]);
+
+        assert!(manager2.unregister_listener("recorder")?.is_some());
+        assert!(manager2.unregister_listener("recorder")?.is_none());
+        manager1.transmit(new_message(channel, 0x456, &[0x02]), None)?;
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(events2.lock().unwrap().len(), 1);
+
+        manager2.shutdown();
+        assert!(!manager2.is_running());
+        assert!(manager2.device().opened_channels().is_empty());
+        manager1.shutdown();
+
+        Ok(())
+    }
+}
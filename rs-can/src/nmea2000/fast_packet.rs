@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use crate::j1939::J1939Error;
+
+/// The max length of fast-packet message(6 + 31 * 7).
+pub const MAX_FAST_PACKET_LENGTH: usize = 223;
+const PADDING: u8 = 0xFF;
+
+/// Split the message into the frames of fast-packet, `sequence` is the 3 bits sequence counter of message.
+pub fn fast_packet_frames(sequence: u8, data: &[u8]) -> Result<Vec<[u8; 8]>, J1939Error> {
+    if data.len() > MAX_FAST_PACKET_LENGTH {
+        return Err(J1939Error::InvalidParameter(format!("fast-packet length {} is greater than {}", data.len(), MAX_FAST_PACKET_LENGTH)));
+    }
+
+    let sequence = (sequence & 0x07) << 5;
+    let first = data.len().min(6);
+    let mut frame = [PADDING; 8];
+    frame[0] = sequence;
+    frame[1] = data.len() as u8;
+    frame[2..2 + first].copy_from_slice(&data[..first]);
+    let mut frames = vec![frame];
+    for (i, chunk) in data[first..].chunks(7).enumerate() {
+        let mut frame = [PADDING; 8];
+        frame[0] = sequence | (i as u8 + 1);
+        frame[1..=chunk.len()].copy_from_slice(chunk);
+        frames.push(frame);
+    }
+
+    Ok(frames)
+}
+
+struct Partial {
+    sequence: u8,
+    length: usize,
+    /// the next frame counter expected
+    next: u8,
+    data: Vec<u8>,
+}
+
+/// The reassembler of fast-packet messages from several sources.
+///
+/// The message is dropped when a frame is lost or out of order.
+#[derive(Default)]
+pub struct FastPacketAssembler {
+    partials: HashMap<(u8, u32), Partial>,
+}
+
+impl FastPacketAssembler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Push the frame data of PGN from `source`, return the message when it is complete.
+    pub fn push(&mut self, source: u8, pgn: u32, frame: &[u8]) -> Option<Vec<u8>> {
+        let (&head, payload) = frame.split_first()?;
+        let sequence = head >> 5;
+        let counter = head & 0x1F;
+        let key = (source, pgn);
+
+        if counter == 0 {
+            let (&length, payload) = payload.split_first()?;
+            let length = length as usize;
+            if length > MAX_FAST_PACKET_LENGTH {
+                log::debug!("RUST-CAN - NMEA 2000 fast-packet length {} from {:#04X} is invalid", length, source);
+                self.partials.remove(&key);
+                return None;
+            }
+            if length <= payload.len() {
+                self.partials.remove(&key);
+                return Some(payload[..length].to_vec());
+            }
+            let mut data = Vec::with_capacity(length);
+            data.extend(payload);
+            self.partials.insert(key, Partial { sequence, length, next: 1, data });
+            return None;
+        }
+
+        let partial = self.partials.get_mut(&key)?;
+        if partial.sequence != sequence || partial.next != counter {
+            log::debug!("RUST-CAN - NMEA 2000 fast-packet of PGN {} from {:#04X} is dropped by frame {}", pgn, source, counter);
+            self.partials.remove(&key);
+            return None;
+        }
+        partial.data.extend(payload);
+        partial.next += 1;
+        if partial.data.len() < partial.length {
+            return None;
+        }
+
+        self.partials.remove(&key)
+            .map(|mut v| {
+                v.data.truncate(v.length);
+                v.data
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fast_packet_frames, FastPacketAssembler, MAX_FAST_PACKET_LENGTH};
+
+    #[test]
+    fn test_fast_packet() -> anyhow::Result<()> {
+        let data = (0..MAX_FAST_PACKET_LENGTH).map(|v| v as u8).collect::<Vec<_>>();
+        let frames = fast_packet_frames(3, &data)?;
+        assert_eq!(frames.len(), 32);
+        assert_eq!(frames[0][..3], [0x60, 223, 0x00]);
+        assert_eq!(frames[31][0], 0x7F);
+        assert!(fast_packet_frames(0, &[0x00; MAX_FAST_PACKET_LENGTH + 1]).is_err());
+
+        let other = fast_packet_frames(1, &data[..20])?;
+        let mut assembler = FastPacketAssembler::new();
+        // the messages of different sources are interleaved
+        for (i, frame) in frames.iter().enumerate() {
+            assert_eq!(assembler.push(0x10, 127489, frame), None);
+            if let Some(frame) = other.get(i) {
+                let message = assembler.push(0x11, 127489, frame);
+                assert_eq!(message.is_some(), i == other.len() - 1);
+            }
+            if i == frames.len() - 2 {
+                break;
+            }
+        }
+        assert_eq!(assembler.push(0x10, 127489, &frames[31]), Some(data.clone()));
+
+        // the frame is lost
+        assembler.push(0x10, 127489, &frames[0]);
+        assert_eq!(assembler.push(0x10, 127489, &frames[2]), None);
+        assert_eq!(assembler.push(0x10, 127489, &frames[3]), None);
+        // the short message in the first frame
+        let frames = fast_packet_frames(0, &[0x01, 0x02])?;
+        assert_eq!(frames[0], [0x00, 0x02, 0x01, 0x02, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(assembler.push(0x10, 127489, &frames[0]), Some(vec![0x01, 0x02]));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! The NMEA 2000 fast-packet protocol and the decoder of PGNs over [`J1939Tp`].
+//!
+//! The messages of fast-packet PGNs(up to 223 bytes) are split into the frames with a sequence counter
+//! and a frame counter, and the others are transmitted by the J1939 transport protocol.
+//! The standard PGNs are decoded by the [`PgnRegistry`], and the others can be registered.
+//!
+//! ```no_run
+//! use rs_can::{j1939::{J1939Tp, GLOBAL_ADDRESS}, nmea2000::{Nmea2000, PgnValue, PGN_POSITION_RAPID_UPDATE}, vcan::VirtualCan};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//!
+//! let mut n2k = Nmea2000::new(J1939Tp::new(device, "vcan0".into(), 0x23));
+//! n2k.transmit(2, PGN_POSITION_RAPID_UPDATE, GLOBAL_ADDRESS, &[0x8D, 0x0F, 0x37, 0x1F, 0x7F, 0xF1, 0xEA, 0x02]).unwrap();
+//! let message = n2k.receive(1000).unwrap();
+//! if let Some(PgnValue::PositionRapidUpdate(v)) = n2k.decode(&message) {
+//!     println!("{:?}, {:?}", v.latitude, v.longitude);
+//! }
+//! ```
+mod fast_packet;
+mod pgn;
+
+pub use fast_packet::*;
+pub use pgn::*;
+
+use std::{collections::HashMap, time::{Duration, Instant}};
+use crate::{CanDevice, CanError, j1939::{J1939Error, J1939Message, J1939Tp}};
+
+/// The NMEA 2000 node which transmits and receives the messages by fast-packet.
+pub struct Nmea2000<D: CanDevice> {
+    tp: J1939Tp<D>,
+    registry: PgnRegistry,
+    assembler: FastPacketAssembler,
+    /// the sequence counter of fast-packet PGNs
+    sequences: HashMap<u32, u8>,
+}
+
+impl<D: CanDevice> Nmea2000<D>
+where
+    D::Channel: Clone,
+{
+    /// Create the node with the standard PGNs registered.
+    pub fn new(tp: J1939Tp<D>) -> Self {
+        Self {
+            tp,
+            registry: Default::default(),
+            assembler: Default::default(),
+            sequences: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn tp(&self) -> &J1939Tp<D> {
+        &self.tp
+    }
+
+    #[inline]
+    pub fn tp_mut(&mut self) -> &mut J1939Tp<D> {
+        &mut self.tp
+    }
+
+    #[inline]
+    pub fn registry(&self) -> &PgnRegistry {
+        &self.registry
+    }
+
+    #[inline]
+    pub fn registry_mut(&mut self) -> &mut PgnRegistry {
+        &mut self.registry
+    }
+
+    /// Transmit the message, which is split into the frames of fast-packet when the PGN is registered as fast-packet.
+    pub fn transmit(&mut self, priority: u8, pgn: u32, destination: u8, data: &[u8]) -> Result<(), J1939Error> {
+        if !self.registry.is_fast_packet(pgn) {
+            return self.tp.transmit(priority, pgn, destination, data);
+        }
+
+        let sequence = self.sequences.entry(pgn).or_default();
+        let frames = fast_packet_frames(*sequence, data)?;
+        *sequence = (*sequence + 1) & 0x07;
+        frames.iter()
+            .try_for_each(|frame| self.tp.transmit(priority, pgn, destination, frame))
+    }
+
+    /// Receive the message in `timeout` milliseconds, the fast-packet messages are reassembled.
+    pub fn receive(&mut self, timeout: u32) -> Result<J1939Message, J1939Error> {
+        let deadline = Instant::now() + Duration::from_millis(timeout as u64);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now()).as_millis() as u32;
+            let mut message = self.tp.receive(remaining)?;
+            if !self.registry.is_fast_packet(message.pgn) {
+                return Ok(message);
+            }
+            if let Some(data) = self.assembler.push(message.source, message.pgn, &message.data) {
+                message.data = data;
+                return Ok(message);
+            }
+            if Instant::now() >= deadline {
+                return Err(CanError::TimeoutError("no NMEA 2000 message received".into()).into());
+            }
+        }
+    }
+
+    /// Decode the message by the PGN registered.
+    #[inline]
+    pub fn decode(&self, message: &J1939Message) -> Option<PgnValue> {
+        self.registry.decode(message.pgn, &message.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{j1939::{J1939Tp, GLOBAL_ADDRESS}, vcan::VirtualCan};
+    use crate::test_utils::new_device;
+    use super::{Nmea2000, PgnValue, PGN_ENGINE_DYNAMIC, PGN_POSITION_RAPID_UPDATE};
+
+    fn new_node(channel: &str, address: u8) -> anyhow::Result<Nmea2000<VirtualCan>> {
+        Ok(Nmea2000::new(J1939Tp::new(new_device(channel)?, channel.into(), address)))
+    }
+
+    #[test]
+    fn test_nmea2000() -> anyhow::Result<()> {
+        let mut engine = new_node("n2k-bus", 0x10)?;
+        let mut display = new_node("n2k-bus", 0x20)?;
+
+        let mut data = vec![0xFF; 26];
+        data[0] = 0x00;
+        // oil pressure 350 kPa
+        data[1..3].copy_from_slice(&3500u16.to_le_bytes());
+        // coolant temperature 353.15 K
+        data[5..7].copy_from_slice(&35315u16.to_le_bytes());
+        // total hours 1000 h
+        data[11..15].copy_from_slice(&3_600_000u32.to_le_bytes());
+        data[20..24].copy_from_slice(&[0x00; 4]);
+        data[24] = 50;
+        data[25] = 0x7F;
+        // fuel rate is not available
+        data[9..11].copy_from_slice(&i16::MAX.to_le_bytes());
+        engine.transmit(2, PGN_ENGINE_DYNAMIC, GLOBAL_ADDRESS, &data)?;
+        engine.transmit(2, PGN_POSITION_RAPID_UPDATE, GLOBAL_ADDRESS, &[0x8D, 0x0F, 0x37, 0x1F, 0x7F, 0xF1, 0xEA, 0x02])?;
+
+        let message = display.receive(1000)?;
+        assert_eq!((message.pgn, message.source, message.priority), (PGN_ENGINE_DYNAMIC, 0x10, 2));
+        assert_eq!(message.data, data);
+        match display.decode(&message) {
+            Some(PgnValue::EngineDynamic(v)) => {
+                assert_eq!((v.oil_pressure, v.total_hours, v.load, v.torque), (Some(350_000.), Some(3_600_000), Some(50), None));
+                assert!((v.temperature.unwrap() - 353.15).abs() < 1e-9);
+                assert_eq!(v.fuel_rate, None);
+            },
+            v => panic!("unexpected value: {:?}", v),
+        }
+
+        let message = display.receive(1000)?;
+        assert_eq!(message.pgn, PGN_POSITION_RAPID_UPDATE);
+        assert!(matches!(display.decode(&message), Some(PgnValue::PositionRapidUpdate(_))));
+        assert!(display.receive(100).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+/// The PGN of position, rapid update.
+pub const PGN_POSITION_RAPID_UPDATE: u32 = 129025;
+/// The PGN of COG & SOG, rapid update.
+pub const PGN_COG_SOG_RAPID_UPDATE: u32 = 129026;
+/// The PGN of vessel heading.
+pub const PGN_VESSEL_HEADING: u32 = 127250;
+/// The PGN of engine parameters, rapid update.
+pub const PGN_ENGINE_RAPID_UPDATE: u32 = 127488;
+/// The PGN of engine parameters, dynamic.
+pub const PGN_ENGINE_DYNAMIC: u32 = 127489;
+
+/// The decoder of PGN, `None` is returned when the data is malformed.
+pub type PgnDecoder = Box<dyn Fn(&[u8]) -> Option<PgnValue> + Send + Sync>;
+
+/// The direction reference of heading and course.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectionReference {
+    True = 0,
+    Magnetic = 1,
+    Error = 2,
+    Null = 3,
+}
+
+impl From<u8> for DirectionReference {
+    /// Get the reference from the lower 2 bits.
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => Self::True,
+            1 => Self::Magnetic,
+            2 => Self::Error,
+            _ => Self::Null,
+        }
+    }
+}
+
+/// The PGN 129025, the latitude and longitude are in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionRapidUpdate {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// The PGN 129026, the COG is in radians and the SOG is in m/s.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CogSogRapidUpdate {
+    pub sid: u8,
+    pub reference: DirectionReference,
+    pub cog: Option<f64>,
+    pub sog: Option<f64>,
+}
+
+/// The PGN 127250, the angles are in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VesselHeading {
+    pub sid: u8,
+    pub heading: Option<f64>,
+    pub deviation: Option<f64>,
+    pub variation: Option<f64>,
+    pub reference: DirectionReference,
+}
+
+/// The PGN 127488, the speed is in rpm, the boost pressure is in Pa and the tilt/trim is in percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineRapidUpdate {
+    pub instance: u8,
+    pub speed: Option<f64>,
+    pub boost_pressure: Option<f64>,
+    pub tilt_trim: Option<i8>,
+}
+
+/// The PGN 127489, the pressures are in Pa, the temperatures are in K, the potential is in V,
+/// the fuel rate is in L/h, the total hours is in seconds and the load and torque are in percent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineDynamic {
+    pub instance: u8,
+    pub oil_pressure: Option<f64>,
+    pub oil_temperature: Option<f64>,
+    pub temperature: Option<f64>,
+    pub alternator_potential: Option<f64>,
+    pub fuel_rate: Option<f64>,
+    pub total_hours: Option<u32>,
+    pub coolant_pressure: Option<f64>,
+    pub fuel_pressure: Option<f64>,
+    pub discrete_status1: u16,
+    pub discrete_status2: u16,
+    pub load: Option<i8>,
+    pub torque: Option<i8>,
+}
+
+/// The field decoded by the decoder registered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub value: Option<f64>,
+    pub unit: String,
+}
+
+/// The decoded value of PGN.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnValue {
+    PositionRapidUpdate(PositionRapidUpdate),
+    CogSogRapidUpdate(CogSogRapidUpdate),
+    VesselHeading(VesselHeading),
+    EngineRapidUpdate(EngineRapidUpdate),
+    EngineDynamic(EngineDynamic),
+    /// the value of decoder registered
+    Fields(Vec<Field>),
+}
+
+struct Definition {
+    name: String,
+    fast_packet: bool,
+    decoder: PgnDecoder,
+}
+
+/// The registry of PGN definitions, the standard PGNs are registered by default.
+pub struct PgnRegistry {
+    definitions: HashMap<u32, Definition>,
+}
+
+impl Default for PgnRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(PGN_POSITION_RAPID_UPDATE, "Position, Rapid Update", false, |data| {
+            Some(PgnValue::PositionRapidUpdate(PositionRapidUpdate {
+                latitude: i32_value(data, 0, 1e-7),
+                longitude: i32_value(data, 4, 1e-7),
+            }))
+        })
+        .register(PGN_COG_SOG_RAPID_UPDATE, "COG & SOG, Rapid Update", false, |data| {
+            Some(PgnValue::CogSogRapidUpdate(CogSogRapidUpdate {
+                sid: *data.first()?,
+                reference: DirectionReference::from(*data.get(1)?),
+                cog: u16_value(data, 2, 1e-4),
+                sog: u16_value(data, 4, 1e-2),
+            }))
+        })
+        .register(PGN_VESSEL_HEADING, "Vessel Heading", false, |data| {
+            Some(PgnValue::VesselHeading(VesselHeading {
+                sid: *data.first()?,
+                heading: u16_value(data, 1, 1e-4),
+                deviation: i16_value(data, 3, 1e-4),
+                variation: i16_value(data, 5, 1e-4),
+                reference: DirectionReference::from(*data.get(7)?),
+            }))
+        })
+        .register(PGN_ENGINE_RAPID_UPDATE, "Engine Parameters, Rapid Update", false, |data| {
+            Some(PgnValue::EngineRapidUpdate(EngineRapidUpdate {
+                instance: *data.first()?,
+                speed: u16_value(data, 1, 0.25),
+                boost_pressure: u16_value(data, 3, 100.),
+                tilt_trim: i8_value(data, 5),
+            }))
+        })
+        .register(PGN_ENGINE_DYNAMIC, "Engine Parameters, Dynamic", true, |data| {
+            if data.len() < 26 {
+                return None;
+            }
+            Some(PgnValue::EngineDynamic(EngineDynamic {
+                instance: data[0],
+                oil_pressure: u16_value(data, 1, 100.),
+                oil_temperature: u16_value(data, 3, 0.1),
+                temperature: u16_value(data, 5, 0.01),
+                alternator_potential: i16_value(data, 7, 0.01),
+                fuel_rate: i16_value(data, 9, 0.1),
+                total_hours: raw_bytes(data, 11).map(u32::from_le_bytes).filter(|&v| v != u32::MAX),
+                coolant_pressure: u16_value(data, 15, 100.),
+                fuel_pressure: u16_value(data, 17, 1000.),
+                discrete_status1: u16::from_le_bytes([data[20], data[21]]),
+                discrete_status2: u16::from_le_bytes([data[22], data[23]]),
+                load: i8_value(data, 24),
+                torque: i8_value(data, 25),
+            }))
+        });
+        registry
+    }
+}
+
+impl PgnRegistry {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create the registry without any PGN.
+    pub fn empty() -> Self {
+        Self { definitions: Default::default() }
+    }
+
+    /// Register the PGN, the definition registered is replaced.
+    pub fn register<F>(&mut self, pgn: u32, name: &str, fast_packet: bool, decoder: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> Option<PgnValue> + Send + Sync + 'static,
+    {
+        self.definitions.insert(pgn, Definition { name: name.into(), fast_packet, decoder: Box::new(decoder) });
+        self
+    }
+
+    #[inline]
+    pub fn name(&self, pgn: u32) -> Option<&str> {
+        self.definitions.get(&pgn)
+            .map(|v| v.name.as_str())
+    }
+
+    /// The message of PGN is transmitted by fast-packet.
+    #[inline]
+    pub fn is_fast_packet(&self, pgn: u32) -> bool {
+        self.definitions.get(&pgn)
+            .is_some_and(|v| v.fast_packet)
+    }
+
+    /// Decode the data, `None` is returned when the PGN is not registered or the data is malformed.
+    #[inline]
+    pub fn decode(&self, pgn: u32, data: &[u8]) -> Option<PgnValue> {
+        self.definitions.get(&pgn)
+            .and_then(|v| (v.decoder)(data))
+    }
+}
+
+#[inline]
+fn raw_bytes<const N: usize>(data: &[u8], offset: usize) -> Option<[u8; N]> {
+    data.get(offset..offset + N)?
+        .try_into()
+        .ok()
+}
+
+/// Get the value of field, the maximum value means not available.
+#[inline]
+fn u16_value(data: &[u8], offset: usize, factor: f64) -> Option<f64> {
+    raw_bytes(data, offset)
+        .map(u16::from_le_bytes)
+        .filter(|&v| v != u16::MAX)
+        .map(|v| v as f64 * factor)
+}
+
+#[inline]
+fn i16_value(data: &[u8], offset: usize, factor: f64) -> Option<f64> {
+    raw_bytes(data, offset)
+        .map(i16::from_le_bytes)
+        .filter(|&v| v != i16::MAX)
+        .map(|v| v as f64 * factor)
+}
+
+#[inline]
+fn i32_value(data: &[u8], offset: usize, factor: f64) -> Option<f64> {
+    raw_bytes(data, offset)
+        .map(i32::from_le_bytes)
+        .filter(|&v| v != i32::MAX)
+        .map(|v| v as f64 * factor)
+}
+
+#[inline]
+fn i8_value(data: &[u8], offset: usize) -> Option<i8> {
+    data.get(offset)
+        .map(|&v| v as i8)
+        .filter(|&v| v != i8::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DirectionReference, Field, PgnRegistry, PgnValue, PGN_COG_SOG_RAPID_UPDATE, PGN_ENGINE_DYNAMIC,
+        PGN_ENGINE_RAPID_UPDATE, PGN_POSITION_RAPID_UPDATE, PGN_VESSEL_HEADING,
+    };
+
+    #[test]
+    fn test_decode() {
+        let registry = PgnRegistry::new();
+        // 52.3702157, 4.8951679
+        let data = [0x8D, 0x0F, 0x37, 0x1F, 0x7F, 0xF1, 0xEA, 0x02];
+        match registry.decode(PGN_POSITION_RAPID_UPDATE, &data) {
+            Some(PgnValue::PositionRapidUpdate(v)) => {
+                assert!((v.latitude.unwrap() - 52.3702157).abs() < 1e-9);
+                assert!((v.longitude.unwrap() - 4.8951679).abs() < 1e-9);
+            },
+            v => panic!("unexpected value: {:?}", v),
+        }
+
+        let data = [0x01, 0xFC, 0x10, 0x27, 0xE8, 0x03, 0xFF, 0xFF];
+        match registry.decode(PGN_COG_SOG_RAPID_UPDATE, &data) {
+            Some(PgnValue::CogSogRapidUpdate(v)) => {
+                assert_eq!((v.sid, v.reference), (1, DirectionReference::True));
+                assert!((v.cog.unwrap() - 1.0).abs() < 1e-9 && (v.sog.unwrap() - 10.0).abs() < 1e-9);
+            },
+            v => panic!("unexpected value: {:?}", v),
+        }
+
+        let data = [0x02, 0x10, 0x27, 0xFF, 0x7F, 0x9C, 0xFF, 0xFD];
+        match registry.decode(PGN_VESSEL_HEADING, &data) {
+            Some(PgnValue::VesselHeading(v)) => {
+                assert_eq!((v.deviation, v.reference), (None, DirectionReference::Magnetic));
+                assert!((v.variation.unwrap() + 0.01).abs() < 1e-9);
+            },
+            v => panic!("unexpected value: {:?}", v),
+        }
+
+        let data = [0x00, 0x40, 0x1F, 0xFF, 0xFF, 0x05, 0xFF, 0xFF];
+        match registry.decode(PGN_ENGINE_RAPID_UPDATE, &data) {
+            Some(PgnValue::EngineRapidUpdate(v)) => assert_eq!((v.speed, v.boost_pressure, v.tilt_trim), (Some(2000.), None, Some(5))),
+            v => panic!("unexpected value: {:?}", v),
+        }
+        assert!(registry.is_fast_packet(PGN_ENGINE_DYNAMIC));
+        assert_eq!(registry.decode(PGN_ENGINE_DYNAMIC, &[0x00; 8]), None);
+        assert_eq!(registry.name(PGN_VESSEL_HEADING), Some("Vessel Heading"));
+    }
+
+    #[test]
+    fn test_register() {
+        let mut registry = PgnRegistry::empty();
+        assert_eq!(registry.decode(PGN_POSITION_RAPID_UPDATE, &[0x00; 8]), None);
+        // water depth
+        registry.register(128267, "Water Depth", false, |data| {
+            let depth = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+            Some(PgnValue::Fields(vec![Field { name: "Depth".into(), value: Some(depth as f64 * 0.01), unit: "m".into() }]))
+        });
+        let value = registry.decode(128267, &[0x00, 0xE8, 0x03, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(value, Some(PgnValue::Fields(vec![Field { name: "Depth".into(), value: Some(10.), unit: "m".into() }])));
+        assert!(!registry.is_fast_packet(128267));
+    }
+}
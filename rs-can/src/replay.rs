@@ -0,0 +1,482 @@
+//! Replay the recorded frames onto a device.
+//!
+//! The frames are transmitted at their original relative time which is scaled by the speed factor.
+//! Every frame is scheduled against the start of replay instead of the previous frame,
+//! so the latencies of sleeping and transmitting are not accumulated.
+//!
+//! ```no_run
+//! use rs_can::{replay::Replayer, trace::asc::AscReader, vcan::VirtualCan};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//! let frames = AscReader::open("trace.asc").unwrap()
+//!     .filter_map(Result::ok);
+//!
+//! let mut replayer = Replayer::new(device);
+//! replayer.set_speed(2.)
+//!     .add_channel_map("1", "vcan0".into());
+//! let report = replayer.run(frames).unwrap();
+//! println!("{:?}", report);
+//! ```
+use std::{collections::HashMap, fmt::Display, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread, time::{Duration, Instant}};
+use crate::{CanDevice, CanDirect, CanError, CanFilter, CanFrame};
+
+/// The remaining time to deadline that the scheduler stops sleeping and starts spinning.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(1);
+/// The max duration of each sleeping, so that the replay can be stopped in time.
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// The handle to stop a running replay from other threads.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayHandle {
+    running: Arc<AtomicBool>,
+}
+
+impl ReplayHandle {
+    /// Stop the replay, the frame being transmitted is not interrupted.
+    #[inline]
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+}
+
+/// The result of replay.
+///
+/// The timing is aggregated over all frames transmitted or failed to transmit,
+/// the deviation of each frame is the duration from the intended time to the time of transmitting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// the count of frames transmitted
+    pub sent: usize,
+    /// the count of frames skipped by window and filters
+    pub skipped: usize,
+    /// the count of frames failed to transmit
+    pub failed: usize,
+    /// the count of passes completed
+    pub loops: usize,
+    /// the latest intended time of all frames since start of replay
+    pub intended: Duration,
+    /// the latest actual time of all frames since start of replay
+    pub achieved: Duration,
+    pub mean_deviation: Duration,
+    pub max_deviation: Duration,
+}
+
+/// The replay engine over any [`CanDevice`].
+pub struct Replayer<D: CanDevice> {
+    device: D,
+    speed: f64,
+    loop_count: usize,
+    loop_gap: Option<u64>,
+    start: Option<u64>,
+    stop: Option<u64>,
+    channels: HashMap<String, D::Channel>,
+    includes: Vec<CanFilter>,
+    excludes: Vec<CanFilter>,
+    skip_received: bool,
+    continue_on_error: bool,
+    timeout: Option<u32>,
+    handle: ReplayHandle,
+}
+
+impl<D> Replayer<D>
+where
+    D: CanDevice,
+    D::Channel: Clone,
+    D::Frame: Clone,
+{
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            speed: 1.,
+            loop_count: 1,
+            loop_gap: None,
+            start: None,
+            stop: None,
+            channels: Default::default(),
+            includes: Default::default(),
+            excludes: Default::default(),
+            skip_received: false,
+            continue_on_error: false,
+            timeout: None,
+            handle: Default::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// The handle to stop the replay.
+    #[inline]
+    pub fn handle(&self) -> ReplayHandle {
+        self.handle.clone()
+    }
+
+    /// Set the speed factor, `2.0` replays twice as fast as recorded, default is `1.0`.
+    pub fn set_speed(&mut self, speed: f64) -> &mut Self {
+        if speed.is_finite() && speed > 0. {
+            self.speed = speed;
+        }
+        else {
+            log::warn!("RUST-CAN - invalid replay speed: {}", speed);
+        }
+        self
+    }
+
+    /// Set the count of passes, `0` replays forever until stopped, default is `1`.
+    pub fn set_loop_count(&mut self, count: usize) -> &mut Self {
+        self.loop_count = count;
+        self
+    }
+
+    /// Set the gap in milliseconds from the last frame of a pass to the first frame of next pass,
+    /// the mean interval of frames in pass is used by default.
+    pub fn set_loop_gap(&mut self, gap: Option<u64>) -> &mut Self {
+        self.loop_gap = gap;
+        self
+    }
+
+    /// Set the window in milliseconds relative to the first frame of source.
+    ///
+    /// The frames out of window are skipped and the first frame in window is transmitted
+    /// after `frame offset - start`.
+    pub fn set_window(&mut self, start: Option<u64>, stop: Option<u64>) -> &mut Self {
+        self.start = start;
+        self.stop = stop;
+        self
+    }
+
+    /// Transmit the frames of channel `from` to the channel `to`, the frames are transmitted to
+    /// their own channels when not mapped.
+    pub fn add_channel_map<S: Display>(&mut self, from: S, to: D::Channel) -> &mut Self {
+        self.channels.insert(from.to_string(), to);
+        self
+    }
+
+    /// Only replay the frames matched any of the filters, all frames are replayed when empty.
+    ///
+    /// The filters are matched by `id & can_mask == can_id & can_mask`,
+    /// and the extended filters only match extended frames.
+    pub fn set_includes(&mut self, filters: &[CanFilter]) -> &mut Self {
+        self.includes = filters.to_vec();
+        self
+    }
+
+    /// Skip the frames matched any of the filters, it takes precedence over the include filters.
+    pub fn set_excludes(&mut self, filters: &[CanFilter]) -> &mut Self {
+        self.excludes = filters.to_vec();
+        self
+    }
+
+    /// Skip the frames which are received in source, only the transmitted frames are replayed.
+    pub fn set_skip_received(&mut self, skip: bool) -> &mut Self {
+        self.skip_received = skip;
+        self
+    }
+
+    /// Count the transmit errors and continue, the replay is aborted by the first error by default.
+    pub fn set_continue_on_error(&mut self, value: bool) -> &mut Self {
+        self.continue_on_error = value;
+        self
+    }
+
+    /// Set the timeout of transmitting.
+    pub fn set_timeout(&mut self, timeout: Option<u32>) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Replay the frames until all passes are completed or stopped by [`ReplayHandle`].
+    ///
+    /// The selected frames are buffered in the first pass when looping.
+    pub fn run<I>(&mut self, frames: I) -> Result<ReplayReport, CanError>
+    where
+        I: IntoIterator<Item = D::Frame>,
+    {
+        self.handle.running.store(true, Ordering::Release);
+        let mut schedule = Schedule::new(self.speed);
+        let cached = self.loop_count != 1;
+        let mut cache = Vec::new();
+
+        let mut first = None;
+        let mut last_offset = 0;
+        for frame in frames {
+            if !self.handle.is_running() {
+                break;
+            }
+            match self.select(&frame, &mut first) {
+                Some(offset) => {
+                    last_offset = offset;
+                    if cached {
+                        cache.push((offset, frame.clone()));
+                    }
+                    self.transmit(&mut schedule, offset, frame)?;
+                },
+                None => {
+                    if self.stop.is_some_and(|s| first.is_some_and(|f| frame.timestamp().saturating_sub(f) > s)) {
+                        break;
+                    }
+                    schedule.report.skipped += 1;
+                },
+            }
+        }
+
+        if self.handle.is_running() {
+            schedule.report.loops += 1;
+        }
+        let period = self.period(&cache, last_offset);
+        while !cache.is_empty()
+            && self.handle.is_running()
+            && (self.loop_count == 0 || schedule.report.loops < self.loop_count) {
+            schedule.next_pass(period);
+            for (offset, frame) in cache.iter() {
+                if !self.handle.is_running() {
+                    break;
+                }
+                self.transmit(&mut schedule, *offset, frame.clone())?;
+            }
+            if self.handle.is_running() {
+                schedule.report.loops += 1;
+            }
+        }
+
+        self.handle.stop();
+        Ok(schedule.finish())
+    }
+
+    /// The offset of next pass relative to the start of previous pass.
+    fn period(&self, cache: &[(u64, D::Frame)], last_offset: u64) -> u64 {
+        let first_offset = match cache.first() {
+            Some((offset, _)) => *offset,
+            None => return last_offset,
+        };
+        let gap = self.loop_gap.unwrap_or_else(|| match cache.len() {
+            1 => first_offset,
+            n => (last_offset - first_offset) / (n as u64 - 1),
+        }.max(1));
+
+        last_offset + gap - first_offset
+    }
+
+    /// Return the offset in pass when the frame is selected.
+    fn select(&self, frame: &D::Frame, first: &mut Option<u64>) -> Option<u64> {
+        let timestamp = frame.timestamp();
+        let offset = timestamp.saturating_sub(*first.get_or_insert(timestamp));
+        let start = self.start.unwrap_or_default();
+        if offset < start || self.stop.is_some_and(|s| offset > s) {
+            return None;
+        }
+        if self.skip_received && frame.direct() == CanDirect::Receive {
+            return None;
+        }
+        if self.excludes.iter().any(|f| filter_matched(f, frame)) {
+            return None;
+        }
+        if !self.includes.is_empty() && !self.includes.iter().any(|f| filter_matched(f, frame)) {
+            return None;
+        }
+
+        Some(offset - start)
+    }
+
+    fn transmit(&self, schedule: &mut Schedule, offset: u64, mut frame: D::Frame) -> Result<(), CanError> {
+        let deadline = schedule.deadline(offset);
+        if !schedule.wait(deadline, &self.handle) {
+            return Ok(());
+        }
+
+        if let Some(channel) = self.channels.get(&frame.channel().to_string()) {
+            frame.set_channel(channel.clone());
+        }
+        let now = Instant::now();
+        let result = self.device.transmit(frame, self.timeout);
+        schedule.record(deadline, now);
+        match result {
+            Ok(_) => {
+                schedule.report.sent += 1;
+                Ok(())
+            },
+            Err(e) => {
+                if self.continue_on_error {
+                    log::warn!("RUST-CAN - {} when replaying", e);
+                    schedule.report.failed += 1;
+                    Ok(())
+                }
+                else {
+                    self.handle.stop();
+                    Err(e)
+                }
+            },
+        }
+    }
+}
+
+/// The scheduler of replay, all deadlines are relative to the start of replay.
+struct Schedule {
+    speed: f64,
+    origin: Instant,
+    /// the intended start of current pass since origin
+    pass: Duration,
+    /// the count of frames recorded
+    count: u32,
+    total_deviation: Duration,
+    report: ReplayReport,
+}
+
+impl Schedule {
+    fn new(speed: f64) -> Self {
+        Self {
+            speed,
+            origin: Instant::now(),
+            pass: Duration::ZERO,
+            count: 0,
+            total_deviation: Duration::ZERO,
+            report: Default::default(),
+        }
+    }
+
+    /// The intended time of frame since origin.
+    #[inline]
+    fn deadline(&self, offset: u64) -> Duration {
+        self.pass + Duration::from_secs_f64(offset as f64 / 1000. / self.speed)
+    }
+
+    /// The next pass starts at `period` after the start of previous pass.
+    #[inline]
+    fn next_pass(&mut self, period: u64) {
+        self.pass = self.deadline(period);
+    }
+
+    /// Sleep until near the deadline and spin the rest, return `false` if stopped.
+    fn wait(&self, deadline: Duration, handle: &ReplayHandle) -> bool {
+        let deadline = self.origin + deadline;
+        loop {
+            if !handle.is_running() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            let remain = deadline - now;
+            if remain > SPIN_THRESHOLD {
+                thread::sleep((remain - SPIN_THRESHOLD).min(MAX_SLEEP));
+            }
+            else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    fn record(&mut self, deadline: Duration, now: Instant) {
+        let achieved = now.saturating_duration_since(self.origin);
+        let deviation = achieved.saturating_sub(deadline);
+        self.total_deviation += deviation;
+        self.count += 1;
+        self.report.intended = self.report.intended.max(deadline);
+        self.report.achieved = self.report.achieved.max(achieved);
+        self.report.max_deviation = self.report.max_deviation.max(deviation);
+    }
+
+    fn finish(mut self) -> ReplayReport {
+        if self.count > 0 {
+            self.report.mean_deviation = self.total_deviation / self.count;
+        }
+        self.report
+    }
+}
+
+#[inline]
+fn filter_matched<F: CanFrame>(filter: &CanFilter, frame: &F) -> bool {
+    if filter.extended && !frame.is_extended() {
+        return false;
+    }
+    frame.id().into_bits() & filter.can_mask == filter.can_id & filter.can_mask
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{CanDevice, CanDirect, CanFilter, CanFrame, CanId, vcan::CanMessage};
+    use crate::test_utils::new_device;
+    use super::Replayer;
+
+    #[test]
+    fn test_replay() -> anyhow::Result<()> {
+        let channel = "vbus-replay";
+        let device = new_device(channel)?;
+        let monitor = new_device(channel)?;
+
+        let frames = (0..20u32)
+            .map(|i| {
+                let mut frame = CanMessage::new(CanId::from_bits(0x100 + i, None), &[i as u8]).unwrap();
+                frame.set_timestamp(Some(1_700_000_000_000 + i as u64 * 10))
+                    .set_channel("1".into())
+                    .set_direct(if i % 5 == 4 { CanDirect::Receive } else { CanDirect::Transmit });
+                frame
+            })
+            .collect::<Vec<_>>();
+
+        let mut replayer = Replayer::new(device);
+        replayer.set_speed(2.)
+            .set_loop_count(2)
+            .set_window(Some(20), Some(150))
+            .add_channel_map("1", channel.into())
+            .set_excludes(&[CanFilter::from((0x105, 0x7FF))])
+            .set_skip_received(true);
+        let report = replayer.run(frames)?;
+
+        // 0x102..=0x10F in window, 0x104/0x109/0x10E are received and 0x105 is excluded
+        assert_eq!(report.sent, 20);
+        assert_eq!(report.loops, 2);
+        assert_eq!(report.skipped, 6);
+        // the second pass starts after the mean interval 130 / 9 of the first pass
+        assert_eq!(report.intended, Duration::from_millis((130 + 14 + 130) / 2));
+        assert!(report.achieved >= report.intended);
+        assert!(report.max_deviation >= report.mean_deviation);
+
+        let received = monitor.receive(channel.into(), Some(10))?;
+        assert_eq!(received.len(), 20);
+        assert_eq!(received[0].id(), CanId::Standard(0x102));
+        assert_eq!(received[2].id(), CanId::Standard(0x106));
+        assert_eq!(received[10].id(), CanId::Standard(0x102));
+        assert!(received.iter().all(|f| f.channel() == channel));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop_gap() -> anyhow::Result<()> {
+        let channel = "vbus-replay-gap";
+        let device = new_device(channel)?;
+
+        let frames = (0..2u64)
+            .map(|i| {
+                let mut frame = CanMessage::new(0x100, &[i as u8]).unwrap();
+                frame.set_timestamp(Some(1_700_000_000_000 + i * 10))
+                    .set_channel(channel.into());
+                frame
+            })
+            .collect::<Vec<_>>();
+
+        let mut replayer = Replayer::new(device);
+        replayer.set_loop_count(3)
+            .set_loop_gap(Some(20));
+        let report = replayer.run(frames)?;
+
+        assert_eq!(report.sent, 6);
+        assert_eq!(report.loops, 3);
+        // each pass takes 10ms and 20ms between passes
+        assert_eq!(report.intended, Duration::from_millis(70));
+        assert!(report.achieved >= report.intended);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,24 @@
+//! The fixtures shared by the tests.
+use crate::{isotp::{Address, IsoTp}, vcan::{CanMessage, VirtualCan}, CanFrame, CanId};
+
+/// The virtual device with the channel initialized.
+pub(crate) fn new_device(channel: &str) -> anyhow::Result<VirtualCan> {
+    let mut device = VirtualCan::new();
+    device.init_channel(channel, None)?;
+    Ok(device)
+}
+
+/// The message of channel, the identifier larger than `0x7FF` is extended.
+pub(crate) fn new_message(channel: &str, id: u32, data: &[u8]) -> CanMessage {
+    let mut msg = CanMessage::new(CanId::from_bits(id, None), data).unwrap();
+    msg.set_channel(channel.into());
+    msg
+}
+
+/// The ISO-TP over virtual device, the functional identifier is `0x7DF`.
+pub(crate) fn new_isotp(channel: &str, tx_id: u32, rx_id: u32) -> anyhow::Result<IsoTp<VirtualCan>> {
+    let device = new_device(channel)?;
+    let mut address = Address::new(CanId::from_bits(tx_id, None), CanId::from_bits(rx_id, None));
+    address.set_functional_id(CanId::Standard(0x7DF));
+    Ok(IsoTp::new(device, channel.into(), address))
+}
@@ -0,0 +1,744 @@
+//! Vector ASC(ASCII) trace file.
+//!
+//! ```text
+//! date Sat Sep 30 03:06:13.191 pm 2017
+//! base hex  timestamps absolute
+//! internal events logged
+//! // version 9.0.0
+//! Begin Triggerblock Sat Sep 30 03:06:13.191 pm 2017
+//!    0.000000 Start of measurement
+//!    0.015991 1  123x            Rx   d 8 01 02 03 04 05 06 07 08  Length = 0 BitCount = 0 ID = 291x
+//!    0.017000 CANFD   1 Rx       1A1                                   1 0 9 12 ...
+//!    2.501000 1  ErrorFrame
+//! End TriggerBlock
+//! ```
+use std::{fmt::{self, Display, Write as _}, fs::File, io::{BufRead, BufReader, BufWriter, Write}, marker::PhantomData, path::Path, str::FromStr};
+use crate::{CanDirect, CanError, CanFrame, CanId, CanType, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE, MAX_XL_FRAME_SIZE};
+use super::{bits, channel_from_number, channel_number, io_error, new_error_frame, DateTime, MONTHS, WEEKDAYS};
+
+const VERSION: &str = "9.0.0";
+const FD_EDL: u32 = 1 << 12;
+const FD_BRS: u32 = 1 << 13;
+const FD_ESI: u32 = 1 << 14;
+const FD_REMOTE: u32 = 1 << 4;
+
+/// The number base of identifiers and data bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Base {
+    #[default]
+    Hex,
+    Dec,
+}
+
+impl Base {
+    #[inline(always)]
+    fn radix(&self) -> u32 {
+        match self {
+            Self::Hex => 16,
+            Self::Dec => 10,
+        }
+    }
+}
+
+/// The mode of timestamps, `Relative` timestamps are relative to the previous event.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampMode {
+    #[default]
+    Absolute,
+    Relative,
+}
+
+/// The optional fields of a CAN-FD line.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct LineOptions {
+    pub(crate) base: Base,
+    pub(crate) bitrate: Option<u32>,
+    pub(crate) dbitrate: Option<u32>,
+    pub(crate) bit_timing_arb: u32,
+    pub(crate) bit_timing_data: u32,
+}
+
+/// Output the event of frame(without timestamp) as `asc` format.
+pub(crate) fn fmt_frame<F, C>(
+    out: &mut impl fmt::Write,
+    channel: C,
+    frame: &F,
+    options: &LineOptions,
+) -> fmt::Result
+where
+    F: CanFrame + ?Sized,
+    C: Display,
+{
+    let frame_bits = bits::frame_bits(frame);
+    let duration = frame_bits.duration(options.bitrate, options.dbitrate);
+    let id = frame.id().into_bits();
+    let id_str = match options.base {
+        Base::Hex => format!("{:X}{}", id, if frame.is_extended() { "x" } else { "" }),
+        Base::Dec => format!("{}{}", id, if frame.is_extended() { "x" } else { "" }),
+    };
+    let data = if frame.is_remote() {
+        String::new()
+    }
+    else {
+        frame.data()
+            .iter()
+            .fold(String::new(), |mut out, &b| {
+                let _ = match options.base {
+                    Base::Hex => write!(out, " {b:02X}"),
+                    Base::Dec => write!(out, " {b}"),
+                };
+                out
+            })
+    };
+
+    match frame.can_type() {
+        _ if frame.is_error_frame() => write!(out, "{}  ErrorFrame", channel),
+        CanType::Can => {
+            write!(out, "{}  {: <15} {: <4} {} {:x}{}  Length = {} BitCount = {} ID = {}{}",
+                   channel,
+                   id_str,
+                   frame.direct(),
+                   if frame.is_remote() { "r" } else { "d" },
+                   frame.length(),
+                   data,
+                   duration,
+                   frame_bits.bit_count,
+                   id,
+                   if frame.is_extended() { "x" } else { "" },
+            )
+        },
+        CanType::CanFd => {
+            let mut flags = FD_EDL;
+            if frame.is_bitrate_switch() {
+                flags |= FD_BRS;
+            }
+            if frame.is_esi() {
+                flags |= FD_ESI;
+            }
+            write!(out, "CANFD {: >3} {: <4} {: >8}  {: >32} {} {} {:x} {: >2}{} {: >8} {: >4} {: >8X} {: >8X} {: >8X} {: >8X} {: >8} {: >8}",
+                   channel,
+                   frame.direct(),
+                   id_str,
+                   "",      // symbolic name
+                   u8::from(frame.is_bitrate_switch()),
+                   u8::from(frame.is_esi()),
+                   frame.dlc().max(0),
+                   frame.length(),
+                   data,
+                   duration,
+                   frame_bits.bit_count,
+                   flags,
+                   frame_bits.crc,
+                   options.bit_timing_arb,
+                   options.bit_timing_data,
+                   0,       // bit_timing_conf_ext_arb
+                   0,       // bit_timing_conf_ext_data
+            )
+        },
+        CanType::CanXl => {
+            write!(out, "CANXL {: >3} {: <4} {: >8} {: >4}{}",
+                   channel,
+                   frame.direct(),
+                   id_str,
+                   frame.length(),
+                   data,
+            )
+        },
+    }
+}
+
+fn fmt_date(timestamp: u64) -> String {
+    let dt = DateTime::from_timestamp(timestamp);
+    let (hour, meridiem) = match dt.hour {
+        0 => (12, "am"),
+        1..=11 => (dt.hour, "am"),
+        12 => (12, "pm"),
+        _ => (dt.hour - 12, "pm"),
+    };
+    format!("{} {} {:02} {:02}:{:02}:{:02}.{:03} {} {}",
+            WEEKDAYS[dt.weekday as usize],
+            MONTHS[dt.month as usize - 1],
+            dt.day,
+            hour,
+            dt.minute,
+            dt.second,
+            dt.millisecond,
+            meridiem,
+            dt.year,
+    )
+}
+
+/// Parse date like `Sat Sep 30 03:06:13.191 pm 2017` or `Sa Sep 30 15:06:13.191 2017`.
+fn parse_date(tokens: &[&str]) -> Option<u64> {
+    let month_idx = tokens.iter()
+        .position(|t| month_of(t).is_some())?;
+    let month = month_of(tokens[month_idx])?;
+    let day = tokens.get(month_idx + 1)?.parse().ok()?;
+    let time = tokens.get(month_idx + 2)?;
+    let mut iter = time.split(':');
+    let mut hour: u32 = iter.next()?.parse().ok()?;
+    let minute = iter.next()?.parse().ok()?;
+    let (second, millisecond) = match iter.next()?.split_once('.') {
+        Some((s, ms)) => (s.parse().ok()?, format!("{:0<3}", ms)[..3].parse().ok()?),
+        None => (iter.next().unwrap_or("0").parse().ok()?, 0),
+    };
+    let mut rest = tokens[month_idx + 3..].iter();
+    let mut year = rest.next()?;
+    match year.to_ascii_lowercase().as_str() {
+        "am" => {
+            if hour == 12 { hour = 0; }
+            year = rest.next()?;
+        },
+        "pm" => {
+            if hour < 12 { hour += 12; }
+            year = rest.next()?;
+        },
+        _ => {},
+    }
+
+    Some(DateTime {
+        year: year.parse().ok()?,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        millisecond,
+        weekday: 0,
+    }.timestamp())
+}
+
+fn month_of(name: &str) -> Option<u32> {
+    let lower = name.to_lowercase();
+    let idx = match lower.as_str() {
+        "jan" => 0, "feb" => 1, "mar" | "mär" | "mrz" => 2, "apr" => 3, "may" | "mai" => 4, "jun" => 5,
+        "jul" => 6, "aug" => 7, "sep" => 8, "oct" | "okt" => 9, "nov" => 10, "dec" | "dez" => 11,
+        _ => return None,
+    };
+    Some(idx + 1)
+}
+
+/// The streaming writer of ASC file.
+///
+/// The header is written before the first frame, and the `End TriggerBlock` is written when finished or dropped.
+pub struct AscWriter<W: Write> {
+    writer: W,
+    mode: TimestampMode,
+    options: LineOptions,
+    start: Option<u64>,
+    last: u64,
+    header: bool,
+    finished: bool,
+}
+
+impl AscWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> AscWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            mode: Default::default(),
+            options: Default::default(),
+            start: None,
+            last: 0,
+            header: false,
+            finished: false,
+        }
+    }
+
+    pub fn set_base(&mut self, base: Base) -> &mut Self {
+        self.options.base = base;
+        self
+    }
+
+    pub fn set_timestamp_mode(&mut self, mode: TimestampMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the bitrate for calculating `Length` of frame, the data bitrate is used by CAN-FD frames with BRS.
+    pub fn set_bitrate(&mut self, bitrate: u32, dbitrate: Option<u32>) -> &mut Self {
+        self.options.bitrate = Some(bitrate);
+        self.options.dbitrate = dbitrate;
+        self
+    }
+
+    /// Set the bit timing configurations that are written by CAN-FD frames.
+    pub fn set_bit_timing(&mut self, arb: u32, data: u32) -> &mut Self {
+        self.options.bit_timing_arb = arb;
+        self.options.bit_timing_data = data;
+        self
+    }
+
+    /// Set the start time of measurement, the timestamp of first frame is used by default.
+    pub fn set_start_timestamp(&mut self, timestamp: u64) -> &mut Self {
+        self.start = Some(timestamp);
+        self
+    }
+
+    /// Write a frame, the channel number in file is the frame's channel number plus 1.
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let timestamp = frame.timestamp();
+        let start = self.write_header(timestamp)?;
+
+        let mut line = String::new();
+        let offset = match self.mode {
+            TimestampMode::Absolute => timestamp.saturating_sub(start),
+            TimestampMode::Relative => timestamp.saturating_sub(self.last),
+        };
+        self.last = timestamp.max(self.last);
+        let channel = channel_number(frame.channel()) + 1;
+        write!(line, "{: >11.6} ", offset as f64 / 1000.)
+            .and_then(|_| fmt_frame(&mut line, channel, frame, &self.options))
+            .map_err(|e| CanError::other_error(e.to_string()))?;
+
+        writeln!(self.writer, "{}", line)
+            .map_err(io_error)
+    }
+
+    /// Write the end of file and flush the writer.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+
+        let start = self.start.unwrap_or_else(crate::can_utils::system_timestamp);
+        self.write_header(start)?;
+        self.finished = true;
+        writeln!(self.writer, "End TriggerBlock")
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    /// Write the header if not written, return the start timestamp.
+    fn write_header(&mut self, timestamp: u64) -> Result<u64, CanError> {
+        if self.finished {
+            return Err(CanError::operation_error("ASC writer is finished"));
+        }
+
+        let start = *self.start.get_or_insert(timestamp);
+        if !self.header {
+            self.header = true;
+            self.last = start;
+            let date = fmt_date(start);
+            write!(self.writer,
+                   "date {}\nbase {}  timestamps {}\ninternal events logged\n// version {}\nBegin Triggerblock {}\n{: >11.6} Start of measurement\n",
+                   date,
+                   match self.options.base { Base::Hex => "hex", Base::Dec => "dec" },
+                   match self.mode { TimestampMode::Absolute => "absolute", TimestampMode::Relative => "relative" },
+                   VERSION,
+                   date,
+                   0.,
+            )
+                .map_err(io_error)?;
+        }
+
+        Ok(start)
+    }
+}
+
+impl<W: Write> Drop for AscWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("RUST-CAN - {} when ASC writer dropped", e);
+        }
+    }
+}
+
+/// The streaming reader of ASC file, events except frames are skipped.
+///
+/// The timestamp of frames is the start time of measurement plus the offset in file.
+pub struct AscReader<R: BufRead, F> {
+    reader: R,
+    buffer: String,
+    line_no: usize,
+    base: Base,
+    mode: TimestampMode,
+    start: Option<u64>,
+    /// offset of last event in microseconds
+    last: u64,
+    _frame: PhantomData<F>,
+}
+
+impl<F> AscReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R, F> AscReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            line_no: 0,
+            base: Default::default(),
+            mode: Default::default(),
+            start: None,
+            last: 0,
+            _frame: Default::default(),
+        }
+    }
+
+    /// The start time of measurement in header, available after the header is read.
+    #[inline(always)]
+    pub fn start_timestamp(&self) -> Option<u64> {
+        self.start
+    }
+
+    fn parse_line(&mut self) -> Result<Option<F>, CanError> {
+        let line = self.buffer.trim();
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let first = match tokens.first() {
+            Some(v) => *v,
+            None => return Ok(None),
+        };
+
+        match first {
+            "date" => {
+                if self.start.is_none() {
+                    self.start = parse_date(&tokens[1..]);
+                }
+                return Ok(None);
+            },
+            "base" => {
+                let mut iter = tokens[1..].iter();
+                while let Some(&token) = iter.next() {
+                    match token {
+                        "hex" => self.base = Base::Hex,
+                        "dec" => self.base = Base::Dec,
+                        "timestamps" => match iter.next() {
+                            Some(&"relative") => self.mode = TimestampMode::Relative,
+                            Some(&"absolute") => self.mode = TimestampMode::Absolute,
+                            _ => {},
+                        },
+                        _ => {},
+                    }
+                }
+                return Ok(None);
+            },
+            "Begin" => {
+                if let Some(start) = parse_date(&tokens[1..]) {
+                    self.start = Some(start);
+                }
+                return Ok(None);
+            },
+            _ => {},
+        }
+
+        // events
+        let offset = match first.parse::<f64>() {
+            Ok(v) if v >= 0. => (v * 1_000_000.).round() as u64,
+            _ => return Ok(None),
+        };
+        let offset = match self.mode {
+            TimestampMode::Absolute => offset,
+            TimestampMode::Relative => self.last + offset,
+        };
+        self.last = offset;
+        let timestamp = self.start.unwrap_or_default() + (offset + 500) / 1000;
+
+        let mut frame = match tokens.get(1) {
+            Some(&"CANFD") => self.parse_fd(&tokens[2..])?,
+            Some(&"CANXL") => self.parse_xl(&tokens[2..])?,
+            Some(v) if v.parse::<u32>().is_ok() => self.parse_can(&tokens[1..])?,
+            _ => None,
+        };
+        if let Some(frame) = frame.as_mut() {
+            frame.set_timestamp(Some(timestamp));
+        }
+
+        Ok(frame)
+    }
+
+    /// `<channel> <id> <dir> d|r <dlc> <data...>` or `<channel> ErrorFrame`
+    fn parse_can(&self, tokens: &[&str]) -> Result<Option<F>, CanError> {
+        let channel = self.parse_channel(tokens[0])?;
+        if tokens.get(1) == Some(&"ErrorFrame") {
+            return new_error_frame(channel).map(Some);
+        }
+
+        let (id, direct) = match (tokens.get(1).and_then(|v| self.parse_id(v)), tokens.get(2).and_then(|v| parse_direct(v))) {
+            (Some(id), Some(direct)) => (id, direct),
+            _ => return Ok(None),   // other events like `Statistic`
+        };
+        let dlc = match tokens.get(4) {
+            Some(v) => usize::from_str_radix(v, 16)
+                .map_err(|_| self.error("invalid DLC"))?,
+            None => 0,
+        };
+
+        let mut frame = match tokens.get(3) {
+            Some(&"r") => F::new_remote(id, dlc.min(MAX_FRAME_SIZE)),
+            Some(&"d") => {
+                let len = dlc.min(MAX_FRAME_SIZE);
+                let data = self.parse_data(tokens.get(5..5 + len))?;
+                F::new(id, &data)
+            },
+            _ => return Err(self.error("invalid frame type")),
+        }
+            .ok_or(self.error("invalid frame"))?;
+        frame.set_direct(direct)
+            .set_channel(channel);
+
+        Ok(Some(frame))
+    }
+
+    /// `<channel> <dir> <id> [name] <brs> <esi> <dlc> <len> <data...> <duration> <bits> <flags> ...`
+    fn parse_fd(&self, tokens: &[&str]) -> Result<Option<F>, CanError> {
+        if tokens.len() < 3 {
+            return Err(self.error("invalid CANFD event"));
+        }
+        let channel = self.parse_channel(tokens[0])?;
+        if tokens[2] == "ErrorFrame" {
+            return new_error_frame(channel).map(Some);
+        }
+
+        let direct = parse_direct(tokens[1])
+            .ok_or(self.error("invalid direct"))?;
+        let id = self.parse_id(tokens[2])
+            .ok_or(self.error("invalid identifier"))?;
+        // the symbolic name is optional
+        let mut idx = 3;
+        if !matches!(tokens.get(idx), Some(&"0") | Some(&"1")) {
+            idx += 1;
+        }
+        let brs = tokens.get(idx) == Some(&"1");
+        let esi = tokens.get(idx + 1) == Some(&"1");
+        let _dlc = tokens.get(idx + 2)
+            .and_then(|v| u8::from_str_radix(v, 16).ok())
+            .ok_or(self.error("invalid DLC"))?;
+        let len = tokens.get(idx + 3)
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|v| *v <= MAX_FD_FRAME_SIZE)
+            .ok_or(self.error("invalid data length"))?;
+        let data_idx = idx + 4;
+        let data = self.parse_data(tokens.get(data_idx..data_idx + len))?;
+        let flags = tokens.get(data_idx + len + 2)
+            .and_then(|v| u32::from_str_radix(v, 16).ok())
+            .unwrap_or(FD_EDL);
+
+        let mut frame = if flags & FD_REMOTE != 0 {
+            F::new_remote(id, len)
+        }
+        else {
+            F::new(id, &data)
+        }
+            .ok_or(self.error("invalid frame"))?;
+        if flags & FD_EDL != 0 {
+            frame.set_can_type(CanType::CanFd)
+                .set_bitrate_switch(brs)
+                .set_esi(esi);
+        }
+        frame.set_direct(direct)
+            .set_channel(channel);
+
+        Ok(Some(frame))
+    }
+
+    /// `<channel> <dir> <id> <len> <data...>`
+    fn parse_xl(&self, tokens: &[&str]) -> Result<Option<F>, CanError> {
+        if tokens.len() < 4 {
+            return Err(self.error("invalid CANXL event"));
+        }
+        let channel = self.parse_channel(tokens[0])?;
+        let direct = parse_direct(tokens[1])
+            .ok_or(self.error("invalid direct"))?;
+        let id = self.parse_id(tokens[2])
+            .ok_or(self.error("invalid identifier"))?;
+        let len = tokens[3].parse::<usize>()
+            .ok()
+            .filter(|v| *v <= MAX_XL_FRAME_SIZE)
+            .ok_or(self.error("invalid data length"))?;
+        let data = self.parse_data(tokens.get(4..4 + len))?;
+
+        let mut frame = F::new(id, &data)
+            .ok_or(self.error("invalid frame"))?;
+        frame.set_can_type(CanType::CanXl)
+            .set_direct(direct)
+            .set_channel(channel);
+
+        Ok(Some(frame))
+    }
+
+    fn parse_channel(&self, token: &str) -> Result<F::Channel, CanError> {
+        let number = token.parse::<u32>()
+            .map_err(|_| self.error("invalid channel"))?;
+        channel_from_number(number.saturating_sub(1))
+    }
+
+    fn parse_id(&self, token: &str) -> Option<CanId> {
+        let (id, extended) = match token.strip_suffix(['x', 'X']) {
+            Some(v) => (v, true),
+            None => (token, false),
+        };
+        let id = u32::from_str_radix(id, self.base.radix()).ok()?;
+        Some(CanId::from_bits(id, Some(extended)))
+    }
+
+    fn parse_data(&self, tokens: Option<&[&str]>) -> Result<Vec<u8>, CanError> {
+        tokens.ok_or(self.error("data is too short"))?
+            .iter()
+            .map(|v| u8::from_str_radix(v, self.base.radix())
+                .map_err(|_| self.error("invalid data")))
+            .collect()
+    }
+
+    #[inline]
+    fn error(&self, msg: &str) -> CanError {
+        CanError::other_error(format!("ASC line {}: {}", self.line_no, msg))
+    }
+}
+
+impl<R, F> Iterator for AscReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<F, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line_no += 1,
+                Err(e) => return Some(Err(io_error(e))),
+            }
+
+            match self.parse_line() {
+                Ok(Some(frame)) => return Some(Ok(frame)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[inline]
+fn parse_direct(token: &str) -> Option<CanDirect> {
+    match token {
+        "Rx" => Some(CanDirect::Receive),
+        "Tx" => Some(CanDirect::Transmit),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{CanDirect, CanFrame, CanId, CanType, vcan::CanMessage};
+    use super::{AscReader, AscWriter, TimestampMode};
+
+    const ASC: &str = r#"date Sam Sep 30 15:06:13.191 2017
+base hex  timestamps absolute
+internal events logged
+// version 9.0.0
+Begin Triggerblock Sam Sep 30 15:06:13.191 2017
+   0.000000 Start of measurement
+   0.015991 CAN 1 Status:chip status error active
+   1.015991 1  Statistic: D 0 R 0 XD 0 XR 0 E 0 O 0 B 0.00%
+   2.015991 2  1a2b3c4dx       Rx   d 8 01 02 03 04 05 06 07 08  Length = 235000 BitCount = 61 ID = 439041101x
+   2.501000 1  ErrorFrame
+   3.000000 1  123             Tx   r 4
+  17.876707 CANFD   3 Rx        4a                                   1 0 f 64 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10 11 12 13 14 15 16 17 18 19 1a 1b 1c 1d 1e 1f 20 21 22 23 24 25 26 27 28 29 2a 2b 2c 2d 2e 2f 30 31 32 33 34 35 36 37 38 39 3a 3b 3c 3d 3e 3f   1331984 1210 223000 0 46500250 4b280150 20011736 20002795
+  20.305233 CANFD   2 Tx        4d  Message_name  0 1 8  8 01 02 03 04 05 06 07 08    0    0 5000 0 0 0 0 0
+End TriggerBlock
+"#;
+
+    #[test]
+    fn test_reader() -> anyhow::Result<()> {
+        let reader = AscReader::<_, CanMessage>::new(Cursor::new(ASC));
+        let frames = reader.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(frames.len(), 5);
+
+        // 2017-09-30 15:06:13.191
+        let start = 1_506_783_973_191;
+        assert_eq!(frames[0].id(), CanId::Extended(0x1a2b3c4d));
+        assert_eq!(frames[0].channel(), "1");
+        assert_eq!(frames[0].data(), &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(frames[0].timestamp(), start + 2016);
+        assert_eq!(frames[1].timestamp(), start + 2501);
+        assert!(frames[1].is_error_frame());
+        assert!(frames[2].is_remote());
+        assert_eq!(frames[2].direct(), CanDirect::Transmit);
+        assert_eq!(frames[2].length(), 4);
+        assert_eq!(frames[3].can_type(), CanType::CanFd);
+        assert!(frames[3].is_bitrate_switch());
+        assert_eq!(frames[3].length(), 64);
+        assert_eq!(frames[3].channel(), "2");
+        assert_eq!(frames[4].can_type(), CanType::CanFd);
+        assert_eq!(frames[4].id(), CanId::Standard(0x4d));
+        assert!(frames[4].is_esi());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let mut frames = Vec::new();
+        let mut frame = CanMessage::new(0x123, &[0x01, 0x02, 0x03]).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_000)).set_channel("vcan0".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(CanId::Extended(0x18DA_F110), &[0x55; 20]).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_010))
+            .set_channel("1".into())
+            .set_bitrate_switch(true)
+            .set_direct(CanDirect::Receive);
+        frames.push(frame);
+        let mut frame = CanMessage::new_remote(0x7FF, 8).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_025)).set_channel("0".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(0x456, &(0..100).collect::<Vec<_>>()).unwrap();
+        frame.set_can_type(CanType::CanXl)
+            .set_timestamp(Some(1_700_000_000_030))
+            .set_channel("1".into())
+            .set_direct(CanDirect::Transmit);
+        frames.push(frame);
+
+        for mode in [TimestampMode::Absolute, TimestampMode::Relative] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = AscWriter::new(&mut buffer);
+                writer.set_timestamp_mode(mode)
+                    .set_bitrate(500_000, Some(2_000_000));
+                frames.iter().try_for_each(|f| writer.write_frame(f))?;
+                writer.finish()?;
+            }
+
+            let reader = AscReader::<_, CanMessage>::new(Cursor::new(buffer));
+            let results = reader.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(results, frames);
+            results.iter()
+                .zip(frames.iter())
+                .for_each(|(r, f)| {
+                    assert_eq!(r.timestamp(), f.timestamp());
+                    assert_eq!(r.can_type(), f.can_type());
+                    assert_eq!(r.direct(), f.direct());
+                    assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+                });
+            assert_eq!(results[0].channel(), "0");
+            assert_eq!(results[1].channel(), "1");
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+//! The bit stream of a frame on the bus, used to fill the bit count, duration and CRC fields of trace files.
+use crate::{CanFrame, CanType};
+
+const CRC15_POLY: u32 = 0x4599;
+const CRC17_POLY: u32 = 0x1_685B;
+const CRC21_POLY: u32 = 0x10_2899;
+/// CRC delimiter, ACK slot, ACK delimiter and EOF.
+const TAIL_BITS: u32 = 1 + 1 + 1 + 7;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FrameBits {
+    /// The count of bits from SOF to EOF, stuff bits included.
+    pub(crate) bit_count: u32,
+    /// The count of bits transmitted with the data bitrate.
+    pub(crate) data_bits: u32,
+    /// The CRC of frame.
+    pub(crate) crc: u32,
+}
+
+impl FrameBits {
+    /// Duration of frame in nanoseconds, `0` when the bitrate is unknown.
+    pub(crate) fn duration(&self, bitrate: Option<u32>, dbitrate: Option<u32>) -> u64 {
+        let bitrate = match bitrate {
+            Some(v) if v > 0 => v as u64,
+            _ => return 0,
+        };
+        let dbitrate = match dbitrate {
+            Some(v) if v > 0 && self.data_bits > 0 => v as u64,
+            _ => bitrate,
+        };
+
+        let arb_bits = (self.bit_count - self.data_bits) as u64;
+        arb_bits * 1_000_000_000 / bitrate + self.data_bits as u64 * 1_000_000_000 / dbitrate
+    }
+}
+
+/// Calculate the bit stream of a CAN or CAN-FD frame, error frames and CAN-XL frames are not calculated.
+pub(crate) fn frame_bits<F: CanFrame + ?Sized>(frame: &F) -> FrameBits {
+    if frame.is_error_frame() {
+        return Default::default();
+    }
+
+    match frame.can_type() {
+        CanType::Can => classic_bits(frame),
+        CanType::CanFd => fd_bits(frame),
+        CanType::CanXl => Default::default(),
+    }
+}
+
+fn classic_bits<F: CanFrame + ?Sized>(frame: &F) -> FrameBits {
+    let id = frame.id().into_bits();
+    let remote = frame.is_remote();
+    let mut bits = vec![false];     // SOF
+    if frame.is_extended() {
+        push_bits(&mut bits, id >> 18, 11);
+        bits.push(true);            // SRR
+        bits.push(true);            // IDE
+        push_bits(&mut bits, id & 0x3_FFFF, 18);
+        bits.push(remote);          // RTR
+        bits.push(false);           // r1
+    }
+    else {
+        push_bits(&mut bits, id, 11);
+        bits.push(remote);          // RTR
+        bits.push(false);           // IDE
+    }
+    bits.push(false);               // r0
+    push_bits(&mut bits, frame.length().min(0x0F) as u32, 4);
+    if !remote {
+        frame.data().iter()
+            .for_each(|&b| push_bits(&mut bits, b as u32, 8));
+    }
+    let crc = crc(&bits, CRC15_POLY, 15, 0);
+    push_bits(&mut bits, crc, 15);
+
+    let (stuffed, _) = stuff(&bits);
+    FrameBits {
+        bit_count: stuffed.len() as u32 + TAIL_BITS,
+        data_bits: 0,
+        crc,
+    }
+}
+
+fn fd_bits<F: CanFrame + ?Sized>(frame: &F) -> FrameBits {
+    let id = frame.id().into_bits();
+    let mut bits = vec![false];     // SOF
+    if frame.is_extended() {
+        push_bits(&mut bits, id >> 18, 11);
+        bits.push(true);            // SRR
+        bits.push(true);            // IDE
+        push_bits(&mut bits, id & 0x3_FFFF, 18);
+        bits.push(false);           // RRS
+    }
+    else {
+        push_bits(&mut bits, id, 11);
+        bits.push(false);           // RRS
+        bits.push(false);           // IDE
+    }
+    bits.push(true);                // FDF
+    bits.push(false);               // res
+    bits.push(frame.is_bitrate_switch());
+    let arb_len = bits.len();
+    bits.push(frame.is_esi());
+    push_bits(&mut bits, frame.dlc().max(0) as u32, 4);
+    frame.data().iter()
+        .for_each(|&b| push_bits(&mut bits, b as u32, 8));
+
+    let (mut stuffed, count) = stuff(&bits);
+    // stuff bits inserted in arbitration phase
+    let (arb_stuffed, _) = stuff(&bits[..arb_len]);
+    let data_len = stuffed.len() - arb_stuffed.len();
+
+    // stuff count: gray code of count mod 8 and even parity.
+    let gray = (count % 8) ^ ((count % 8) >> 1);
+    push_bits(&mut stuffed, gray, 3);
+    stuffed.push(gray.count_ones() % 2 == 1);
+
+    let (crc, crc_len, fixed_stuff) = if frame.length() <= 16 {
+        (crc(&stuffed, CRC17_POLY, 17, 1 << 16), 17, 6)
+    }
+    else {
+        (crc(&stuffed, CRC21_POLY, 21, 1 << 20), 21, 7)
+    };
+
+    let data_bits = (data_len + 4 + crc_len + fixed_stuff) as u32;
+    let bit_count = stuffed.len() as u32 + (crc_len + fixed_stuff) as u32 + TAIL_BITS;
+    FrameBits {
+        bit_count,
+        data_bits: if frame.is_bitrate_switch() { data_bits } else { 0 },
+        crc,
+    }
+}
+
+#[inline]
+fn push_bits(bits: &mut Vec<bool>, value: u32, len: u32) {
+    (0..len).rev()
+        .for_each(|i| bits.push((value >> i) & 1 == 1));
+}
+
+/// Insert a complement bit after each 5 consecutive equal bits, return the stuffed bits and count of stuff bits.
+fn stuff(bits: &[bool]) -> (Vec<bool>, u32) {
+    let mut result = Vec::with_capacity(bits.len() * 6 / 5 + 1);
+    let mut count = 0;
+    let mut last = None;
+    let mut run = 0;
+    for &bit in bits {
+        if last == Some(bit) {
+            run += 1;
+        }
+        else {
+            last = Some(bit);
+            run = 1;
+        }
+        result.push(bit);
+
+        if run == 5 {
+            result.push(!bit);
+            count += 1;
+            last = Some(!bit);
+            run = 1;
+        }
+    }
+
+    (result, count)
+}
+
+fn crc(bits: &[bool], poly: u32, width: u32, init: u32) -> u32 {
+    let mask = (1 << width) - 1;
+    bits.iter()
+        .fold(init, |crc, &bit| {
+            let top = (crc >> (width - 1)) & 1 == 1;
+            let crc = (crc << 1) & mask;
+            if top ^ bit { crc ^ poly } else { crc }
+        })
+}
@@ -0,0 +1,918 @@
+//! Vector BLF(Binary Logging Format) trace file.
+//!
+//! The objects are stored in zlib-compressed `LOG_CONTAINER`s, both reader and writer are streaming,
+//! at most one container is kept in memory.
+//!
+//! The supported objects are `CAN_MESSAGE`, `CAN_MESSAGE2`, `CAN_FD_MESSAGE`, `CAN_FD_MESSAGE_64`,
+//! `CAN_ERROR`, `CAN_ERROR_EXT`, `LIN_MESSAGE` and `LIN_MESSAGE2`(read only), others are skipped.
+use std::{fs::File, io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write}, marker::PhantomData, path::Path, str::FromStr};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use crate::{CanDirect, CanError, CanFrame, CanId, CanType, EFF_MASK, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE};
+use super::{bits, channel_from_number, channel_number, io_error, new_error_frame, DateTime, LinMessage, TraceObject, CAN_FD_DLC};
+
+const FILE_SIGNATURE: &[u8; 4] = b"LOGG";
+const OBJ_SIGNATURE: &[u8; 4] = b"LOBJ";
+const FILE_HEADER_SIZE: usize = 144;
+const OBJ_HEADER_BASE_SIZE: usize = 16;
+const OBJ_HEADER_V1_SIZE: usize = 16;
+const OBJ_HEADER_V2_SIZE: usize = 24;
+const LOG_CONTAINER_HEADER_SIZE: usize = 16;
+const MAX_CONTAINER_SIZE: usize = 128 * 1024;
+/// The max size of object or uncompressed container read, the larger one is treated as corrupted.
+const MAX_OBJECT_SIZE: usize = 16 * 1024 * 1024;
+
+const APPLICATION_ID: u8 = 5;
+const BIN_LOG_VERSION: [u8; 4] = [2, 6, 8, 1];
+
+const TIME_TEN_MICS: u32 = 0x01;
+const TIME_ONE_NANS: u32 = 0x02;
+const NO_COMPRESSION: u16 = 0;
+const ZLIB_DEFLATE: u16 = 2;
+
+const CAN_MESSAGE: u32 = 1;
+const CAN_ERROR: u32 = 2;
+const LOG_CONTAINER: u32 = 10;
+const LIN_MESSAGE: u32 = 11;
+const LIN_MESSAGE2: u32 = 57;
+const CAN_ERROR_EXT: u32 = 73;
+const CAN_MESSAGE2: u32 = 86;
+const CAN_FD_MESSAGE: u32 = 100;
+const CAN_FD_MESSAGE_64: u32 = 101;
+
+const CAN_MSG_EXT: u32 = 0x8000_0000;
+const CAN_MSG_DIR: u8 = 0x01;
+const CAN_MSG_REMOTE: u8 = 0x80;
+const CAN_FD_EDL: u8 = 0x01;
+const CAN_FD_BRS: u8 = 0x02;
+const CAN_FD_ESI: u8 = 0x04;
+const CAN_FD64_REMOTE: u32 = 0x0010;
+const CAN_FD64_EDL: u32 = 0x1000;
+const CAN_FD64_BRS: u32 = 0x2000;
+const CAN_FD64_ESI: u32 = 0x4000;
+
+/// The object written for classic CAN frames.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CanObjectType {
+    /// `CAN_MESSAGE`
+    #[default]
+    Message,
+    /// `CAN_MESSAGE2`, the frame length and bit count are filled.
+    Message2,
+}
+
+/// The object written for CAN-FD frames.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FdObjectType {
+    /// `CAN_FD_MESSAGE`
+    FdMessage,
+    /// `CAN_FD_MESSAGE_64`, the frame length, bit count, CRC and bit timing are filled.
+    #[default]
+    FdMessage64,
+}
+
+#[inline(always)]
+fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]])
+}
+
+#[inline(always)]
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+#[inline(always)]
+fn u64_at(buf: &[u8], pos: usize) -> u64 {
+    u64::from_le_bytes(buf[pos..pos + 8].try_into().unwrap_or_default())
+}
+
+/// Windows `SYSTEMTIME`
+fn systemtime(timestamp: u64) -> [u8; 16] {
+    let dt = DateTime::from_timestamp(timestamp);
+    let mut buf = [0; 16];
+    [
+        dt.year as u16,
+        dt.month as u16,
+        ((dt.weekday + 4) % 7) as u16,     // 0 is Sunday
+        dt.day as u16,
+        dt.hour as u16,
+        dt.minute as u16,
+        dt.second as u16,
+        dt.millisecond as u16,
+    ]
+        .iter()
+        .enumerate()
+        .for_each(|(i, v)| buf[i * 2..i * 2 + 2].copy_from_slice(&v.to_le_bytes()));
+    buf
+}
+
+fn parse_systemtime(buf: &[u8]) -> u64 {
+    let year = u16_at(buf, 0);
+    if year == 0 {
+        return 0;
+    }
+
+    DateTime {
+        year: year as i64,
+        month: u16_at(buf, 2) as u32,
+        day: u16_at(buf, 6) as u32,
+        hour: u16_at(buf, 8) as u32,
+        minute: u16_at(buf, 10) as u32,
+        second: u16_at(buf, 12) as u32,
+        millisecond: u16_at(buf, 14) as u32,
+        weekday: 0,
+    }.timestamp()
+}
+
+#[inline(always)]
+fn direct_of(dir: bool) -> CanDirect {
+    if dir { CanDirect::Transmit } else { CanDirect::Receive }
+}
+
+/// The streaming writer of BLF file.
+///
+/// The file header is updated when finished or dropped, so the writer must be seekable.
+pub struct BlfWriter<W: Write + Seek> {
+    writer: W,
+    buffer: Vec<u8>,
+    compression: u32,
+    can_object: CanObjectType,
+    fd_object: FdObjectType,
+    bitrate: Option<u32>,
+    dbitrate: Option<u32>,
+    bit_timing_arb: u32,
+    bit_timing_data: u32,
+    start: Option<u64>,
+    stop: u64,
+    count: u32,
+    uncompressed_size: u64,
+    finished: bool,
+}
+
+impl BlfWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> BlfWriter<W> {
+    pub fn new(writer: W) -> Result<Self, CanError> {
+        let mut result = Self {
+            writer,
+            buffer: Vec::with_capacity(MAX_CONTAINER_SIZE),
+            compression: Compression::default().level(),
+            can_object: Default::default(),
+            fd_object: Default::default(),
+            bitrate: None,
+            dbitrate: None,
+            bit_timing_arb: 0,
+            bit_timing_data: 0,
+            start: None,
+            stop: 0,
+            count: 0,
+            uncompressed_size: FILE_HEADER_SIZE as u64,
+            finished: false,
+        };
+        let header = result.file_header(0);
+        result.writer.write_all(&header)
+            .map_err(io_error)?;
+
+        Ok(result)
+    }
+
+    /// Set zlib compression level(0..=9) of containers, `0` means no compression.
+    pub fn set_compression_level(&mut self, level: u32) -> &mut Self {
+        self.compression = level.min(9);
+        self
+    }
+
+    pub fn set_can_object_type(&mut self, r#type: CanObjectType) -> &mut Self {
+        self.can_object = r#type;
+        self
+    }
+
+    pub fn set_fd_object_type(&mut self, r#type: FdObjectType) -> &mut Self {
+        self.fd_object = r#type;
+        self
+    }
+
+    /// Set the bitrate for calculating frame length, the data bitrate is used by CAN-FD frames with BRS.
+    pub fn set_bitrate(&mut self, bitrate: u32, dbitrate: Option<u32>) -> &mut Self {
+        self.bitrate = Some(bitrate);
+        self.dbitrate = dbitrate;
+        self
+    }
+
+    /// Set the bit timing configurations that are written by `CAN_FD_MESSAGE_64`.
+    pub fn set_bit_timing(&mut self, arb: u32, data: u32) -> &mut Self {
+        self.bit_timing_arb = arb;
+        self.bit_timing_data = data;
+        self
+    }
+
+    /// Set the start time of measurement, the timestamp of first object is used by default.
+    pub fn set_start_timestamp(&mut self, timestamp: u64) -> &mut Self {
+        self.start = Some(timestamp);
+        self
+    }
+
+    /// Write a frame, the channel number in file is the frame's channel number plus 1.
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let channel = (channel_number(frame.channel()) + 1) as u16;
+        let mut can_id = frame.id().into_bits();
+        if frame.is_extended() {
+            can_id |= CAN_MSG_EXT;
+        }
+        let transmit = frame.direct() == CanDirect::Transmit;
+        let length = frame.length();
+        let data = frame.data();
+
+        if frame.is_error_frame() {
+            let mut obj = Vec::with_capacity(32);
+            obj.extend_from_slice(&channel.to_le_bytes());
+            obj.extend_from_slice(&0u16.to_le_bytes());         // length
+            obj.extend_from_slice(&0u32.to_le_bytes());         // flags
+            obj.extend_from_slice(&[0, 0, data.len().min(MAX_FRAME_SIZE) as u8, 0]);  // ecc, position, dlc, reserved
+            obj.extend_from_slice(&0u32.to_le_bytes());         // frame length
+            obj.extend_from_slice(&can_id.to_le_bytes());
+            obj.extend_from_slice(&[0; 4]);                     // flags ext, reserved
+            obj.extend_from_slice(&fixed_data::<8>(data));
+            return self.add_object(CAN_ERROR_EXT, &obj, frame.timestamp());
+        }
+
+        let frame_bits = bits::frame_bits(frame);
+        let duration = frame_bits.duration(self.bitrate, self.dbitrate);
+        match frame.can_type() {
+            CanType::Can => {
+                let mut flags = if transmit { CAN_MSG_DIR } else { 0 };
+                if frame.is_remote() {
+                    flags |= CAN_MSG_REMOTE;
+                }
+                let mut obj = Vec::with_capacity(24);
+                obj.extend_from_slice(&channel.to_le_bytes());
+                obj.extend_from_slice(&[flags, length.min(0x0F) as u8]);
+                obj.extend_from_slice(&can_id.to_le_bytes());
+                obj.extend_from_slice(&fixed_data::<8>(if frame.is_remote() { &[] } else { data }));
+                match self.can_object {
+                    CanObjectType::Message => self.add_object(CAN_MESSAGE, &obj, frame.timestamp()),
+                    CanObjectType::Message2 => {
+                        obj.extend_from_slice(&(duration as u32).to_le_bytes());
+                        obj.extend_from_slice(&[frame_bits.bit_count.min(0xFF) as u8, 0, 0, 0]);
+                        self.add_object(CAN_MESSAGE2, &obj, frame.timestamp())
+                    },
+                }
+            },
+            CanType::CanFd => {
+                let dlc = frame.dlc().max(0) as u8;
+                match self.fd_object {
+                    FdObjectType::FdMessage => {
+                        let flags = if transmit { CAN_MSG_DIR } else { 0 };
+                        let mut fd_flags = CAN_FD_EDL;
+                        if frame.is_bitrate_switch() {
+                            fd_flags |= CAN_FD_BRS;
+                        }
+                        if frame.is_esi() {
+                            fd_flags |= CAN_FD_ESI;
+                        }
+                        let mut obj = Vec::with_capacity(84);
+                        obj.extend_from_slice(&channel.to_le_bytes());
+                        obj.extend_from_slice(&[flags, dlc]);
+                        obj.extend_from_slice(&can_id.to_le_bytes());
+                        obj.extend_from_slice(&(duration as u32).to_le_bytes());
+                        obj.extend_from_slice(&[frame_bits.bit_count.min(0xFF) as u8, fd_flags, data.len() as u8, 0, 0, 0, 0, 0]);
+                        obj.extend_from_slice(&fixed_data::<MAX_FD_FRAME_SIZE>(data));
+                        self.add_object(CAN_FD_MESSAGE, &obj, frame.timestamp())
+                    },
+                    FdObjectType::FdMessage64 => {
+                        let mut flags = CAN_FD64_EDL;
+                        if frame.is_bitrate_switch() {
+                            flags |= CAN_FD64_BRS;
+                        }
+                        if frame.is_esi() {
+                            flags |= CAN_FD64_ESI;
+                        }
+                        let mut obj = Vec::with_capacity(40 + data.len());
+                        obj.extend_from_slice(&[channel as u8, dlc, data.len() as u8, 0]);
+                        obj.extend_from_slice(&can_id.to_le_bytes());
+                        obj.extend_from_slice(&(duration as u32).to_le_bytes());
+                        obj.extend_from_slice(&flags.to_le_bytes());
+                        obj.extend_from_slice(&self.bit_timing_arb.to_le_bytes());
+                        obj.extend_from_slice(&self.bit_timing_data.to_le_bytes());
+                        obj.extend_from_slice(&[0; 8]);                 // time offset of BRS and CRC delimiter
+                        obj.extend_from_slice(&(frame_bits.bit_count as u16).to_le_bytes());
+                        obj.extend_from_slice(&[u8::from(transmit), 0]);    // direction, ext data offset
+                        obj.extend_from_slice(&frame_bits.crc.to_le_bytes());
+                        obj.extend_from_slice(data);
+                        self.add_object(CAN_FD_MESSAGE_64, &obj, frame.timestamp())
+                    },
+                }
+            },
+            CanType::CanXl => Err(CanError::NotSupportedError),
+        }
+    }
+
+    /// Write a LIN frame as `LIN_MESSAGE`.
+    pub fn write_lin(&mut self, msg: &LinMessage) -> Result<(), CanError> {
+        let mut obj = Vec::with_capacity(20);
+        obj.extend_from_slice(&((msg.channel + 1) as u16).to_le_bytes());
+        obj.extend_from_slice(&[msg.id, msg.data.len().min(8) as u8]);
+        obj.extend_from_slice(&fixed_data::<8>(&msg.data));
+        obj.extend_from_slice(&[0, 0, 0, 0]);       // fsm id, fsm state, header time, full time
+        obj.extend_from_slice(&(msg.checksum as u16).to_le_bytes());
+        obj.extend_from_slice(&[u8::from(msg.direct == CanDirect::Transmit), 0]);
+        self.add_object(LIN_MESSAGE, &obj, msg.timestamp)
+    }
+
+    /// Write all buffered objects and update the file header.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        while !self.buffer.is_empty() {
+            self.flush_container()?;
+        }
+
+        let file_size = self.writer.stream_position()
+            .map_err(io_error)?;
+        let header = self.file_header(file_size);
+        self.writer.seek(SeekFrom::Start(0))
+            .and_then(|_| self.writer.write_all(&header))
+            .and_then(|_| self.writer.seek(SeekFrom::End(0)))
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    fn add_object(&mut self, r#type: u32, data: &[u8], timestamp: u64) -> Result<(), CanError> {
+        if self.finished {
+            return Err(CanError::operation_error("BLF writer is finished"));
+        }
+
+        let start = *self.start.get_or_insert(timestamp);
+        self.stop = self.stop.max(timestamp);
+        let offset = timestamp.saturating_sub(start) * 1_000_000;
+        let header_size = OBJ_HEADER_BASE_SIZE + OBJ_HEADER_V1_SIZE;
+
+        self.buffer.extend_from_slice(OBJ_SIGNATURE);
+        self.buffer.extend_from_slice(&(header_size as u16).to_le_bytes());
+        self.buffer.extend_from_slice(&1u16.to_le_bytes());       // header version
+        self.buffer.extend_from_slice(&((header_size + data.len()) as u32).to_le_bytes());
+        self.buffer.extend_from_slice(&r#type.to_le_bytes());
+        self.buffer.extend_from_slice(&TIME_ONE_NANS.to_le_bytes());
+        self.buffer.extend_from_slice(&[0; 4]);                   // client index, object version
+        self.buffer.extend_from_slice(&offset.to_le_bytes());
+        self.buffer.extend_from_slice(data);
+        self.buffer.resize(self.buffer.len() + data.len() % 4, 0);
+        self.count += 1;
+
+        while self.buffer.len() >= MAX_CONTAINER_SIZE {
+            self.flush_container()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a container with buffered objects, objects may be split into next container.
+    fn flush_container(&mut self) -> Result<(), CanError> {
+        let size = self.buffer.len().min(MAX_CONTAINER_SIZE);
+        let uncompressed = &self.buffer[..size];
+        let (method, data) = if self.compression == 0 {
+            (NO_COMPRESSION, uncompressed.to_vec())
+        }
+        else {
+            let mut encoder = ZlibEncoder::new(Vec::with_capacity(size / 2), Compression::new(self.compression));
+            encoder.write_all(uncompressed)
+                .map_err(io_error)?;
+            (ZLIB_DEFLATE, encoder.finish().map_err(io_error)?)
+        };
+
+        let obj_size = OBJ_HEADER_BASE_SIZE + LOG_CONTAINER_HEADER_SIZE + data.len();
+        let mut header = Vec::with_capacity(OBJ_HEADER_BASE_SIZE + LOG_CONTAINER_HEADER_SIZE);
+        header.extend_from_slice(OBJ_SIGNATURE);
+        header.extend_from_slice(&(OBJ_HEADER_BASE_SIZE as u16).to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&(obj_size as u32).to_le_bytes());
+        header.extend_from_slice(&LOG_CONTAINER.to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&[0; 6]);
+        header.extend_from_slice(&(size as u32).to_le_bytes());
+        header.extend_from_slice(&[0; 4]);
+
+        self.writer.write_all(&header)
+            .and_then(|_| self.writer.write_all(&data))
+            .and_then(|_| self.writer.write_all(&vec![0; obj_size % 4]))
+            .map_err(io_error)?;
+
+        self.uncompressed_size += (OBJ_HEADER_BASE_SIZE + LOG_CONTAINER_HEADER_SIZE + size) as u64;
+        self.buffer.drain(..size);
+
+        Ok(())
+    }
+
+    fn file_header(&self, file_size: u64) -> Vec<u8> {
+        let start = self.start.unwrap_or_else(crate::can_utils::system_timestamp);
+        let mut header = Vec::with_capacity(FILE_HEADER_SIZE);
+        header.extend_from_slice(FILE_SIGNATURE);
+        header.extend_from_slice(&(FILE_HEADER_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&[APPLICATION_ID, 0, 0, 0]);
+        header.extend_from_slice(&BIN_LOG_VERSION);
+        header.extend_from_slice(&file_size.to_le_bytes());
+        header.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        header.extend_from_slice(&self.count.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&systemtime(start));
+        header.extend_from_slice(&systemtime(self.stop.max(start)));
+        header.resize(FILE_HEADER_SIZE, 0);
+        header
+    }
+}
+
+impl<W: Write + Seek> Drop for BlfWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("RUST-CAN - {} when BLF writer dropped", e);
+        }
+    }
+}
+
+#[inline]
+fn fixed_data<const N: usize>(data: &[u8]) -> [u8; N] {
+    let mut result = [0; N];
+    let len = data.len().min(N);
+    result[..len].copy_from_slice(&data[..len]);
+    result
+}
+
+/// The streaming reader of BLF file.
+///
+/// The timestamp of frames is the start time in file header plus the offset of object.
+pub struct BlfReader<R: Read, F> {
+    reader: R,
+    start: u64,
+    stop: u64,
+    count: u32,
+    /// uncompressed objects which are not parsed
+    buffer: Vec<u8>,
+    pos: usize,
+    eof: bool,
+    _frame: PhantomData<F>,
+}
+
+impl<F> BlfReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R, F> BlfReader<R, F>
+where
+    R: Read,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(mut reader: R) -> Result<Self, CanError> {
+        let mut header = vec![0; FILE_HEADER_SIZE];
+        reader.read_exact(&mut header[..8])
+            .map_err(io_error)?;
+        if &header[..4] != FILE_SIGNATURE {
+            return Err(CanError::other_error("not a BLF file"));
+        }
+        let header_size = (u32_at(&header, 4) as usize).max(40);
+        header.resize(header_size, 0);
+        reader.read_exact(&mut header[8..])
+            .map_err(io_error)?;
+
+        let (start, stop) = if header_size >= 72 {
+            (parse_systemtime(&header[40..56]), parse_systemtime(&header[56..72]))
+        }
+        else {
+            (0, 0)
+        };
+
+        Ok(Self {
+            reader,
+            start,
+            stop,
+            count: u32_at(&header, 32),
+            buffer: Vec::new(),
+            pos: 0,
+            eof: false,
+            _frame: Default::default(),
+        })
+    }
+
+    /// The start time of measurement in file header.
+    #[inline(always)]
+    pub fn start_timestamp(&self) -> u64 {
+        self.start
+    }
+
+    /// The stop time of measurement in file header.
+    #[inline(always)]
+    pub fn stop_timestamp(&self) -> u64 {
+        self.stop
+    }
+
+    /// The count of objects in file header.
+    #[inline(always)]
+    pub fn object_count(&self) -> u32 {
+        self.count
+    }
+
+    /// Only read the CAN frames.
+    pub fn frames(self) -> impl Iterator<Item = Result<F, CanError>> {
+        self.filter_map(|obj| match obj {
+            Ok(TraceObject::Frame(f)) => Some(Ok(f)),
+            Ok(TraceObject::Lin(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Read next top-level object, the data of container is appended into buffer.
+    fn fill_buffer(&mut self) -> Result<bool, CanError> {
+        let mut header = [0; OBJ_HEADER_BASE_SIZE];
+        match self.reader.read_exact(&mut header) {
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(io_error(e)),
+        }
+        if &header[..4] != OBJ_SIGNATURE {
+            return Err(CanError::other_error("invalid object signature"));
+        }
+
+        let obj_size = u32_at(&header, 8) as usize;
+        let obj_type = u32_at(&header, 12);
+        if obj_size > MAX_OBJECT_SIZE {
+            return Err(CanError::OtherError(format!("BLF object size {} is too large", obj_size)));
+        }
+        let mut data = vec![0; obj_size.saturating_sub(OBJ_HEADER_BASE_SIZE) + obj_size % 4];
+        self.reader.read_exact(&mut data)
+            .or_else(|e| match e.kind() {
+                // the padding of last object may be missing
+                std::io::ErrorKind::UnexpectedEof => Ok(()),
+                _ => Err(e),
+            })
+            .map_err(io_error)?;
+        data.truncate(obj_size.saturating_sub(OBJ_HEADER_BASE_SIZE));
+
+        // discard parsed data
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+
+        if obj_type == LOG_CONTAINER {
+            if data.len() < LOG_CONTAINER_HEADER_SIZE {
+                return Err(CanError::other_error("invalid log container"));
+            }
+            let method = u16_at(&data, 0);
+            let size = u32_at(&data, 8) as usize;
+            let container = &data[LOG_CONTAINER_HEADER_SIZE..];
+            match method {
+                NO_COMPRESSION => self.buffer.extend_from_slice(container),
+                ZLIB_DEFLATE => {
+                    if size > MAX_OBJECT_SIZE {
+                        return Err(CanError::OtherError(format!("BLF container size {} is too large", size)));
+                    }
+                    self.buffer.reserve(size);
+                    ZlibDecoder::new(container).take(size as u64)
+                        .read_to_end(&mut self.buffer)
+                        .map_err(io_error)?;
+                },
+                _ => log::warn!("RUST-CAN - unknown compression method: {} of BLF container", method),
+            }
+        }
+        else {
+            self.buffer.extend_from_slice(&header);
+            self.buffer.extend_from_slice(&data);
+        }
+
+        Ok(true)
+    }
+
+    /// Parse next object in buffer, `None` is returned when more data is needed.
+    fn parse_buffer(&mut self) -> Result<Option<TraceObject<F>>, CanError> {
+        loop {
+            let remain = &self.buffer[self.pos..];
+            if remain.len() < OBJ_HEADER_BASE_SIZE {
+                return Ok(None);
+            }
+            // objects are padded differently by writers
+            let offset = match remain.windows(4)
+                .take(8)
+                .position(|w| w == OBJ_SIGNATURE) {
+                Some(v) => v,
+                None => return Err(CanError::other_error("could not find next BLF object")),
+            };
+            let pos = self.pos + offset;
+            if self.buffer.len() - pos < OBJ_HEADER_BASE_SIZE {
+                return Ok(None);
+            }
+
+            let header_size = u16_at(&self.buffer, pos + 4) as usize;
+            let header_version = u16_at(&self.buffer, pos + 6);
+            let obj_size = u32_at(&self.buffer, pos + 8) as usize;
+            let obj_type = u32_at(&self.buffer, pos + 12);
+            if obj_size < OBJ_HEADER_BASE_SIZE {
+                return Err(CanError::other_error("invalid BLF object size"));
+            }
+            let next = pos + obj_size;
+            if next > self.buffer.len() {
+                return Ok(None);    // the object continues in the next container
+            }
+            self.pos = next;
+
+            // flags and timestamp are at the same position of V1 and V2 header
+            let min_size = match header_version {
+                1 => OBJ_HEADER_BASE_SIZE + OBJ_HEADER_V1_SIZE,
+                2 => OBJ_HEADER_BASE_SIZE + OBJ_HEADER_V2_SIZE,
+                _ => continue,
+            };
+            if obj_size < min_size {
+                continue;
+            }
+            let flags = u32_at(&self.buffer, pos + 16);
+            let timestamp = u64_at(&self.buffer, pos + 24);
+            let offset = match flags {
+                TIME_TEN_MICS => timestamp / 100,
+                _ => timestamp / 1_000_000,     // TIME_ONE_NANS
+            };
+            let timestamp = self.start + offset;
+            let content = &self.buffer[(pos + header_size).min(next)..next];
+
+            if let Some(obj) = self.parse_object(obj_type, content, timestamp)? {
+                return Ok(Some(obj));
+            }
+        }
+    }
+
+    fn parse_object(&self, r#type: u32, data: &[u8], timestamp: u64) -> Result<Option<TraceObject<F>>, CanError> {
+        let mut frame = match r#type {
+            CAN_MESSAGE | CAN_MESSAGE2 if data.len() >= 16 => {
+                let channel = u16_at(data, 0);
+                let flags = data[2];
+                let length = (data[3] as usize).min(MAX_FRAME_SIZE);
+                let can_id = u32_at(data, 4);
+                let id = CanId::from_bits(can_id & EFF_MASK, Some(can_id & CAN_MSG_EXT != 0));
+                let mut frame = if flags & CAN_MSG_REMOTE != 0 {
+                    F::new_remote(id, length)
+                }
+                else {
+                    F::new(id, &data[8..8 + length])
+                }
+                    .ok_or(CanError::other_error("invalid frame"))?;
+                frame.set_direct(direct_of(flags & CAN_MSG_DIR != 0))
+                    .set_channel(channel_from_number(channel.saturating_sub(1) as u32)?);
+                frame
+            },
+            CAN_FD_MESSAGE if data.len() >= 20 => {
+                let channel = u16_at(data, 0);
+                let flags = data[2];
+                let dlc = data[3] as usize;
+                let can_id = u32_at(data, 4);
+                let fd_flags = data[13];
+                let valid = (data[14] as usize).min(MAX_FD_FRAME_SIZE).min(data.len() - 20);
+                let id = CanId::from_bits(can_id & EFF_MASK, Some(can_id & CAN_MSG_EXT != 0));
+                let mut frame = if flags & CAN_MSG_REMOTE != 0 {
+                    F::new_remote(id, CAN_FD_DLC[dlc & 0x0F])
+                }
+                else {
+                    F::new(id, &data[20..20 + valid])
+                }
+                    .ok_or(CanError::other_error("invalid frame"))?;
+                if fd_flags & CAN_FD_EDL != 0 {
+                    frame.set_can_type(CanType::CanFd)
+                        .set_bitrate_switch(fd_flags & CAN_FD_BRS != 0)
+                        .set_esi(fd_flags & CAN_FD_ESI != 0);
+                }
+                frame.set_direct(direct_of(flags & CAN_MSG_DIR != 0))
+                    .set_channel(channel_from_number(channel.saturating_sub(1) as u32)?);
+                frame
+            },
+            CAN_FD_MESSAGE_64 if data.len() >= 40 => {
+                let channel = data[0];
+                let dlc = data[1] as usize;
+                let valid = (data[2] as usize).min(MAX_FD_FRAME_SIZE).min(data.len() - 40);
+                let can_id = u32_at(data, 4);
+                let flags = u32_at(data, 12);
+                let dir = data[34];
+                let id = CanId::from_bits(can_id & EFF_MASK, Some(can_id & CAN_MSG_EXT != 0));
+                let mut frame = if flags & CAN_FD64_REMOTE != 0 {
+                    F::new_remote(id, CAN_FD_DLC[dlc & 0x0F])
+                }
+                else {
+                    F::new(id, &data[40..40 + valid])
+                }
+                    .ok_or(CanError::other_error("invalid frame"))?;
+                if flags & CAN_FD64_EDL != 0 {
+                    frame.set_can_type(CanType::CanFd)
+                        .set_bitrate_switch(flags & CAN_FD64_BRS != 0)
+                        .set_esi(flags & CAN_FD64_ESI != 0);
+                }
+                frame.set_direct(direct_of(dir == 1))
+                    .set_channel(channel_from_number(channel.saturating_sub(1) as u32)?);
+                frame
+            },
+            CAN_ERROR | CAN_ERROR_EXT if data.len() >= 4 => {
+                let channel = u16_at(data, 0);
+                new_error_frame::<F>(channel_from_number(channel.saturating_sub(1) as u32)?)?
+            },
+            LIN_MESSAGE if data.len() >= 20 => {
+                let length = (data[3] as usize).min(8);
+                return Ok(Some(TraceObject::Lin(LinMessage {
+                    timestamp,
+                    channel: u16_at(data, 0).saturating_sub(1) as u32,
+                    id: data[2],
+                    data: data[4..4 + length].to_vec(),
+                    checksum: u16_at(data, 16) as u8,
+                    direct: direct_of(data[18] == 1),
+                })));
+            },
+            LIN_MESSAGE2 if data.len() >= 123 => {
+                let length = (data[38] as usize).min(8);
+                return Ok(Some(TraceObject::Lin(LinMessage {
+                    timestamp,
+                    channel: u16_at(data, 12).saturating_sub(1) as u32,
+                    id: data[37],
+                    data: data[112..112 + length].to_vec(),
+                    checksum: u16_at(data, 120) as u8,
+                    direct: direct_of(data[122] == 1),
+                })));
+            },
+            _ => return Ok(None),
+        };
+
+        frame.set_timestamp(Some(timestamp));
+        Ok(Some(TraceObject::Frame(frame)))
+    }
+}
+
+impl<R, F> Iterator for BlfReader<R, F>
+where
+    R: Read,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<TraceObject<F>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.parse_buffer() {
+                Ok(Some(obj)) => return Some(Ok(obj)),
+                Ok(None) => {},
+                Err(e) => {
+                    // skip the broken data
+                    self.pos = self.buffer.len();
+                    return Some(Err(e));
+                },
+            }
+
+            if self.eof {
+                return None;
+            }
+            match self.fill_buffer() {
+                Ok(true) => {},
+                Ok(false) => {
+                    self.eof = true;
+                    if self.buffer.len() > self.pos {
+                        log::warn!("RUST-CAN - {} bytes at the end of BLF file are not parsed", self.buffer.len() - self.pos);
+                    }
+                    return None;
+                },
+                Err(e) => {
+                    self.eof = true;
+                    return Some(Err(e));
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{CanDirect, CanFrame, CanId, CanType, vcan::CanMessage};
+    use crate::trace::{LinMessage, TraceObject};
+    use super::{BlfReader, BlfWriter, CanObjectType, FdObjectType};
+
+    fn frames() -> Vec<CanMessage> {
+        let mut frames = Vec::new();
+        for i in 0..10_000_u64 {
+            let mut frame = match i % 4 {
+                0 => CanMessage::new(CanId::Standard((i % 0x7FF) as u16), &[i as u8; 8]).unwrap(),
+                1 => {
+                    let mut frame = CanMessage::new(CanId::Extended(0x18DA_F100 | (i & 0xFF) as u32), &[0x55; 48]).unwrap();
+                    frame.set_bitrate_switch(true).set_direct(CanDirect::Receive);
+                    frame
+                },
+                2 => CanMessage::new_remote(0x123, 4).unwrap(),
+                _ => {
+                    let mut frame = CanMessage::new(0x456, &[0x01, 0x02]).unwrap();
+                    frame.set_can_type(CanType::CanFd).set_esi(true);
+                    frame
+                },
+            };
+            frame.set_timestamp(Some(1_700_000_000_000 + i))
+                .set_channel((i % 2).to_string());
+            frames.push(frame);
+        }
+        frames
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let frames = frames();
+        for (compression, can, fd) in [
+            (6, CanObjectType::Message, FdObjectType::FdMessage64),
+            (0, CanObjectType::Message2, FdObjectType::FdMessage),
+        ] {
+            let mut buffer = Cursor::new(Vec::new());
+            {
+                let mut writer = BlfWriter::new(&mut buffer)?;
+                writer.set_compression_level(compression)
+                    .set_can_object_type(can)
+                    .set_fd_object_type(fd)
+                    .set_bitrate(500_000, Some(2_000_000));
+                frames.iter().try_for_each(|f| writer.write_frame(f))?;
+                let mut error = CanMessage::new(0, &[]).unwrap();
+                error.set_error_frame(true).set_timestamp(Some(1_700_000_010_000)).set_channel("1".into());
+                writer.write_frame(&error)?;
+                writer.write_lin(&LinMessage {
+                    timestamp: 1_700_000_010_001,
+                    channel: 2,
+                    id: 0x3C,
+                    data: vec![0x01, 0x02, 0x03, 0x04],
+                    checksum: 0xF5,
+                    direct: CanDirect::Receive,
+                })?;
+                writer.finish()?;
+            }
+
+            buffer.set_position(0);
+            let reader = BlfReader::<_, CanMessage>::new(&mut buffer)?;
+            assert_eq!(reader.start_timestamp(), 1_700_000_000_000);
+            assert_eq!(reader.object_count(), frames.len() as u32 + 2);
+            let objects = reader.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(objects.len(), frames.len() + 2);
+            objects.iter()
+                .zip(frames.iter())
+                .for_each(|(obj, f)| match obj {
+                    TraceObject::Frame(r) => {
+                        assert_eq!(r, f);
+                        assert_eq!(r.timestamp(), f.timestamp());
+                        assert_eq!(r.channel(), f.channel());
+                        assert_eq!(r.can_type(), f.can_type());
+                        assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+                        assert_eq!(r.direct(), f.direct());
+                    },
+                    TraceObject::Lin(_) => panic!("unexpected LIN frame"),
+                });
+            match &objects[frames.len()] {
+                TraceObject::Frame(f) => {
+                    assert!(f.is_error_frame());
+                    assert_eq!(f.channel(), "1");
+                },
+                TraceObject::Lin(_) => panic!("unexpected LIN frame"),
+            }
+            match &objects[frames.len() + 1] {
+                TraceObject::Lin(lin) => {
+                    assert_eq!(lin.channel, 2);
+                    assert_eq!(lin.id, 0x3C);
+                    assert_eq!(lin.data, vec![0x01, 0x02, 0x03, 0x04]);
+                    assert_eq!(lin.checksum, 0xF5);
+                },
+                TraceObject::Frame(_) => panic!("unexpected CAN frame"),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted() -> anyhow::Result<()> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = BlfWriter::new(&mut buffer)?;
+            writer.set_compression_level(6);
+            frames().iter().take(10).try_for_each(|f| writer.write_frame(f))?;
+            writer.finish()?;
+        }
+        let data = buffer.into_inner();
+
+        // the size of first object and the uncompressed size of its container
+        for pos in [super::FILE_HEADER_SIZE + 8, super::FILE_HEADER_SIZE + super::OBJ_HEADER_BASE_SIZE + 8] {
+            let mut data = data.clone();
+            data[pos..pos + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            let mut reader = BlfReader::<_, CanMessage>::new(Cursor::new(data))?;
+            assert!(reader.next().is_some_and(|v| v.is_err()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1150 @@
+//! ASAM MDF 4.1 bus logging file.
+//!
+//! The frames of each bus are written into the channel groups `CAN_DataFrame`, `CAN_RemoteFrame`,
+//! `CAN_ErrorFrame` and `LIN_Frame`, the master channel `Timestamp` is the nanoseconds since
+//! the start time of measurement.
+//!
+//! The writer is streaming, all channel groups are written into a single unsorted data group.
+//! The reader supports sorted and unsorted files with `DT`, `DZ`, `DL` and `HL` data blocks,
+//! only the channels with fixed length are read.
+use std::{collections::HashMap, fs::File, io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write}, marker::PhantomData, path::Path, str::FromStr};
+use flate2::read::ZlibDecoder;
+use crate::{can_utils, CanDirect, CanError, CanFrame, CanId, CanType, EFF_MASK, MAX_FRAME_SIZE, SFF_MASK};
+use super::{channel_from_number, channel_number, dlc_of, io_error, new_error_frame, LinMessage, TraceObject, CAN_FD_DLC};
+
+const ID_FILE: &[u8; 8] = b"MDF     ";
+const ID_VERSION: &[u8; 8] = b"4.10    ";
+const ID_PROGRAM: &[u8; 8] = b"rs-can  ";
+const VERSION: u16 = 410;
+const HD_ADDRESS: u64 = 64;
+const BLOCK_HEADER_SIZE: usize = 24;
+/// The max length of block and the data of a data group, the larger is treated as corrupted.
+const MAX_BLOCK_SIZE: usize = 1024 * 1024 * 1024;
+
+const CN_TYPE_FIXED: u8 = 0;
+const CN_TYPE_MASTER: u8 = 2;
+const CN_TYPE_VIRTUAL_MASTER: u8 = 3;
+const SYNC_NONE: u8 = 0;
+const SYNC_TIME: u8 = 1;
+const DATA_UINT_LE: u8 = 0;
+const DATA_UINT_BE: u8 = 1;
+const DATA_FLOAT_LE: u8 = 4;
+const DATA_FLOAT_BE: u8 = 5;
+const DATA_BYTE_ARRAY: u8 = 10;
+const CN_FLAG_BUS_EVENT: u32 = 0x0400;
+const CG_FLAG_VLSD: u16 = 0x01;
+const CG_FLAG_BUS_EVENT: u16 = 0x02;
+const CG_FLAG_PLAIN_BUS_EVENT: u16 = 0x04;
+const CC_IDENTITY: u8 = 0;
+const CC_LINEAR: u8 = 1;
+const SI_TYPE_BUS: u8 = 2;
+const SI_BUS_CAN: u8 = 2;
+const SI_BUS_LIN: u8 = 3;
+const TIME_OFFSET_VALID: u8 = 0x02;
+const ZIP_DEFLATE: u8 = 0;
+const ZIP_TRANSPOSE_DEFLATE: u8 = 1;
+
+/// A member of bus event channel.
+struct Field {
+    name: &'static str,
+    byte_offset: u32,
+    bit_offset: u8,
+    bit_count: u32,
+    data_type: u8,
+}
+
+const fn uint(name: &'static str, byte_offset: u32, bit_offset: u8, bit_count: u32) -> Field {
+    Field { name, byte_offset, bit_offset, bit_count, data_type: DATA_UINT_LE }
+}
+
+const fn bytes(name: &'static str, byte_offset: u32, length: u32) -> Field {
+    Field { name, byte_offset, bit_offset: 0, bit_count: length * 8, data_type: DATA_BYTE_ARRAY }
+}
+
+// The record layouts, the master channel is at 0..8.
+const CAN_DATA_FIELDS: &[Field] = &[
+    uint("BusChannel", 8, 0, 8),
+    uint("ID", 9, 0, 29),
+    uint("IDE", 12, 7, 1),
+    uint("DLC", 13, 0, 4),
+    uint("DataLength", 14, 0, 8),
+    uint("Dir", 15, 0, 1),
+    uint("EDL", 15, 1, 1),
+    uint("BRS", 15, 2, 1),
+    uint("ESI", 15, 3, 1),
+    bytes("DataBytes", 16, 64),
+];
+const CAN_REMOTE_FIELDS: &[Field] = &[
+    uint("BusChannel", 8, 0, 8),
+    uint("ID", 9, 0, 29),
+    uint("IDE", 12, 7, 1),
+    uint("DLC", 13, 0, 4),
+    uint("DataLength", 14, 0, 8),
+    uint("Dir", 15, 0, 1),
+];
+const CAN_ERROR_FIELDS: &[Field] = &[
+    uint("BusChannel", 8, 0, 8),
+    uint("ID", 9, 0, 29),
+    uint("IDE", 12, 7, 1),
+    uint("DLC", 13, 0, 4),
+    uint("DataLength", 14, 0, 8),
+    uint("Dir", 15, 0, 1),
+    bytes("DataBytes", 16, 8),
+];
+const LIN_FIELDS: &[Field] = &[
+    uint("BusChannel", 8, 0, 8),
+    uint("ID", 9, 0, 6),
+    uint("Dir", 10, 0, 1),
+    uint("ReceivedDataByteCount", 11, 0, 4),
+    uint("DataLength", 12, 0, 4),
+    uint("Checksum", 13, 0, 8),
+    bytes("DataBytes", 14, 8),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+    CanData,
+    CanRemote,
+    CanError,
+    Lin,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::CanData => "CAN_DataFrame",
+            Self::CanRemote => "CAN_RemoteFrame",
+            Self::CanError => "CAN_ErrorFrame",
+            Self::Lin => "LIN_Frame",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "CAN_DataFrame" => Some(Self::CanData),
+            "CAN_RemoteFrame" => Some(Self::CanRemote),
+            "CAN_ErrorFrame" => Some(Self::CanError),
+            "LIN_Frame" => Some(Self::Lin),
+            _ => None,
+        }
+    }
+
+    fn fields(&self) -> &'static [Field] {
+        match self {
+            Self::CanData => CAN_DATA_FIELDS,
+            Self::CanRemote => CAN_REMOTE_FIELDS,
+            Self::CanError => CAN_ERROR_FIELDS,
+            Self::Lin => LIN_FIELDS,
+        }
+    }
+
+    /// The size of record without record ID.
+    fn record_size(&self) -> usize {
+        match self {
+            Self::CanData => 80,
+            Self::CanRemote => 16,
+            Self::CanError => 24,
+            Self::Lin => 22,
+        }
+    }
+
+    fn bus_type(&self) -> u8 {
+        match self {
+            Self::Lin => SI_BUS_LIN,
+            _ => SI_BUS_CAN,
+        }
+    }
+
+    fn bus_name(&self, bus: u8) -> String {
+        match self {
+            Self::Lin => format!("LIN{}", bus),
+            _ => format!("CAN{}", bus),
+        }
+    }
+}
+
+/// The MDF blocks which are serialized into memory, the links are absolute addresses in file.
+struct Blocks {
+    base: u64,
+    buffer: Vec<u8>,
+}
+
+impl Blocks {
+    fn new(base: u64) -> Self {
+        Self { base, buffer: Vec::new() }
+    }
+
+    /// Append a block aligned to 8 bytes, return the address of block.
+    fn add(&mut self, id: &[u8; 4], links: &[u64], data: &[u8]) -> u64 {
+        let address = self.base + self.buffer.len() as u64;
+        let length = BLOCK_HEADER_SIZE + links.len() * 8 + data.len();
+        self.buffer.extend_from_slice(id);
+        self.buffer.extend_from_slice(&[0; 4]);
+        self.buffer.extend_from_slice(&(length as u64).to_le_bytes());
+        self.buffer.extend_from_slice(&(links.len() as u64).to_le_bytes());
+        links.iter()
+            .for_each(|l| self.buffer.extend_from_slice(&l.to_le_bytes()));
+        self.buffer.extend_from_slice(data);
+        self.buffer.resize(self.buffer.len().div_ceil(8) * 8, 0);
+        address
+    }
+
+    /// Append a `TX` or `MD` block.
+    fn text(&mut self, id: &[u8; 4], text: &str) -> u64 {
+        let mut data = text.as_bytes().to_vec();
+        data.resize((data.len() + 1).div_ceil(8) * 8, 0);
+        self.add(id, &[], &data)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn channel(
+        &mut self,
+        links: [u64; 8],
+        cn_type: u8,
+        sync_type: u8,
+        data_type: u8,
+        bit_offset: u8,
+        byte_offset: u32,
+        bit_count: u32,
+        flags: u32,
+    ) -> u64 {
+        let mut data = Vec::with_capacity(72);
+        data.extend_from_slice(&[cn_type, sync_type, data_type, bit_offset]);
+        data.extend_from_slice(&byte_offset.to_le_bytes());
+        data.extend_from_slice(&bit_count.to_le_bytes());
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());     // invalidation bit position
+        data.extend_from_slice(&[0, 0, 0, 0]);           // precision, reserved, attachment count
+        data.resize(72, 0);                             // value range and limits
+        self.add(b"##CN", &links, &data)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    kind: Kind,
+    bus: u8,
+    record_id: u8,
+    count: u64,
+}
+
+/// The streaming writer of MDF file.
+///
+/// The metadata blocks are written when finished or dropped, so the writer must be seekable.
+pub struct MdfWriter<W: Write + Seek> {
+    writer: W,
+    start: Option<u64>,
+    fh_address: u64,
+    dt_address: u64,
+    dt_size: u64,
+    groups: Vec<Group>,
+    group_index: HashMap<(Kind, u8), usize>,
+    finished: bool,
+}
+
+impl MdfWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> MdfWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, CanError> {
+        let mut id = Vec::with_capacity(HD_ADDRESS as usize);
+        id.extend_from_slice(ID_FILE);
+        id.extend_from_slice(ID_VERSION);
+        id.extend_from_slice(ID_PROGRAM);
+        id.extend_from_slice(&[0; 4]);
+        id.extend_from_slice(&VERSION.to_le_bytes());
+        id.resize(HD_ADDRESS as usize, 0);
+
+        let mut blocks = Blocks::new(HD_ADDRESS);
+        Self::header(&mut blocks, 0, 0, 0);
+        let comment = format!(
+            "<FHcomment><TX>created</TX><tool_id>rs-can</tool_id><tool_vendor>rs-can</tool_vendor><tool_version>{}</tool_version></FHcomment>",
+            env!("CARGO_PKG_VERSION")
+        );
+        let comment = blocks.text(b"##MD", &comment);
+        let mut fh = Vec::with_capacity(16);
+        fh.extend_from_slice(&(can_utils::system_timestamp() * 1_000_000).to_le_bytes());
+        fh.extend_from_slice(&[0, 0, 0, 0, TIME_OFFSET_VALID, 0, 0, 0]);
+        let fh_address = blocks.add(b"##FH", &[0, comment], &fh);
+
+        // the length of data block is updated when finished
+        let dt_address = blocks.add(b"##DT", &[], &[]);
+        blocks.buffer.truncate((dt_address - HD_ADDRESS) as usize + BLOCK_HEADER_SIZE);
+
+        writer.write_all(&id)
+            .and_then(|_| writer.write_all(&blocks.buffer))
+            .map_err(io_error)?;
+
+        Ok(Self {
+            writer,
+            start: None,
+            fh_address,
+            dt_address,
+            dt_size: 0,
+            groups: Vec::new(),
+            group_index: Default::default(),
+            finished: false,
+        })
+    }
+
+    /// Set the start time of measurement, the timestamp of first frame is used by default.
+    pub fn set_start_timestamp(&mut self, timestamp: u64) -> &mut Self {
+        if self.dt_size > 0 {
+            log::warn!("RUST-CAN - the start time of MDF can't be changed after written");
+        }
+        else {
+            self.start = Some(timestamp);
+        }
+        self
+    }
+
+    /// Write a frame into the channel group of bus, the bus number is the frame's channel number plus 1.
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let bus = (channel_number(frame.channel()) + 1) as u8;
+        let data = frame.data();
+        let mut can_id = frame.id().into_bits() & EFF_MASK;
+        if frame.is_extended() {
+            can_id |= 0x8000_0000;
+        }
+        let dir = u8::from(frame.direct() == CanDirect::Transmit);
+
+        let (kind, length) = if frame.is_error_frame() {
+            (Kind::CanError, data.len().min(MAX_FRAME_SIZE))
+        }
+        else if frame.is_remote() {
+            (Kind::CanRemote, frame.length().min(MAX_FRAME_SIZE))
+        }
+        else {
+            match frame.can_type() {
+                CanType::Can | CanType::CanFd => (Kind::CanData, data.len()),
+                CanType::CanXl => return Err(CanError::NotSupportedError),
+            }
+        };
+
+        let dlc = dlc_of(length);
+        self.write_record(kind, bus, frame.timestamp(), |record| {
+            record[9..13].copy_from_slice(&can_id.to_le_bytes());
+            record[13] = dlc;
+            record[14] = length as u8;
+            record[15] = dir;
+            match kind {
+                Kind::CanData => {
+                    if frame.can_type() == CanType::CanFd {
+                        record[15] |= 0x02;
+                        if frame.is_bitrate_switch() {
+                            record[15] |= 0x04;
+                        }
+                        if frame.is_esi() {
+                            record[15] |= 0x08;
+                        }
+                    }
+                    record[16..16 + length].copy_from_slice(data);
+                },
+                Kind::CanError => record[16..16 + length].copy_from_slice(&data[..length]),
+                _ => {},
+            }
+        })
+    }
+
+    /// Write a LIN frame into the channel group of bus, the bus number is the frame's channel plus 1.
+    pub fn write_lin(&mut self, msg: &LinMessage) -> Result<(), CanError> {
+        let length = msg.data.len().min(8);
+        self.write_record(Kind::Lin, (msg.channel + 1) as u8, msg.timestamp, |record| {
+            record[9] = msg.id & 0x3F;
+            record[10] = u8::from(msg.direct == CanDirect::Transmit);
+            record[11] = length as u8;
+            record[12] = length as u8;
+            record[13] = msg.checksum;
+            record[14..14 + length].copy_from_slice(&msg.data[..length]);
+        })
+    }
+
+    /// Write the metadata blocks and update the file header.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let end = self.writer.stream_position()
+            .map_err(io_error)?;
+        let base = end.div_ceil(8) * 8;
+        let mut blocks = Blocks::new(base);
+        let mut cg_first = 0;
+        for group in self.groups.iter().rev() {
+            cg_first = Self::group(&mut blocks, group, cg_first);
+        }
+        let dg = blocks.add(b"##DG", &[0, cg_first, self.dt_address, 0], &[1, 0, 0, 0, 0, 0, 0, 0]);
+        let mut header = Blocks::new(HD_ADDRESS);
+        let start = self.start.unwrap_or_else(can_utils::system_timestamp);
+        Self::header(&mut header, dg, self.fh_address, start * 1_000_000);
+
+        let dt_length = (BLOCK_HEADER_SIZE as u64 + self.dt_size).to_le_bytes();
+        self.writer.write_all(&vec![0; (base - end) as usize])
+            .and_then(|_| self.writer.write_all(&blocks.buffer))
+            .and_then(|_| self.writer.seek(SeekFrom::Start(self.dt_address + 8)))
+            .and_then(|_| self.writer.write_all(&dt_length))
+            .and_then(|_| self.writer.seek(SeekFrom::Start(HD_ADDRESS)))
+            .and_then(|_| self.writer.write_all(&header.buffer))
+            .and_then(|_| self.writer.seek(SeekFrom::End(0)))
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    fn write_record(&mut self, kind: Kind, bus: u8, timestamp: u64, fill: impl FnOnce(&mut [u8])) -> Result<(), CanError> {
+        if self.finished {
+            return Err(CanError::operation_error("MDF writer is finished"));
+        }
+
+        let index = match self.group_index.get(&(kind, bus)) {
+            Some(&v) => v,
+            None => {
+                if self.groups.len() >= u8::MAX as usize {
+                    return Err(CanError::operation_error("too many channel groups of MDF"));
+                }
+                let index = self.groups.len();
+                self.groups.push(Group { kind, bus, record_id: index as u8 + 1, count: 0 });
+                self.group_index.insert((kind, bus), index);
+                index
+            },
+        };
+        let group = &mut self.groups[index];
+
+        let start = *self.start.get_or_insert(timestamp);
+        let offset = timestamp.saturating_sub(start) * 1_000_000;
+        let mut record = vec![0; 1 + kind.record_size()];
+        if BLOCK_HEADER_SIZE as u64 + self.dt_size + record.len() as u64 > MAX_BLOCK_SIZE as u64 {
+            return Err(CanError::operation_error("MDF data block is full"));
+        }
+        record[0] = group.record_id;
+        record[1..9].copy_from_slice(&offset.to_le_bytes());
+        record[9] = bus;
+        fill(&mut record[1..]);
+
+        self.writer.write_all(&record)
+            .map_err(io_error)?;
+        group.count += 1;
+        self.dt_size += record.len() as u64;
+
+        Ok(())
+    }
+
+    fn header(blocks: &mut Blocks, dg: u64, fh: u64, start: u64) -> u64 {
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&start.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, TIME_OFFSET_VALID, 0, 0, 0]);
+        data.resize(32, 0);     // start angle and distance
+        blocks.add(b"##HD", &[dg, fh, 0, 0, 0, 0], &data)
+    }
+
+    /// Append the blocks of a channel group, return the address of `CG` block.
+    fn group(blocks: &mut Blocks, group: &Group, next: u64) -> u64 {
+        let kind = group.kind;
+        let bus_name = blocks.text(b"##TX", &kind.bus_name(group.bus));
+        let mut si = vec![SI_TYPE_BUS, kind.bus_type()];
+        si.resize(8, 0);
+        let source = blocks.add(b"##SI", &[bus_name, bus_name, 0], &si);
+
+        let mut component = 0;
+        for field in kind.fields().iter().rev() {
+            let name = blocks.text(b"##TX", &format!("{}.{}", kind.name(), field.name));
+            component = blocks.channel(
+                [component, 0, name, source, 0, 0, 0, 0],
+                CN_TYPE_FIXED, SYNC_NONE, field.data_type, field.bit_offset, field.byte_offset, field.bit_count, 0,
+            );
+        }
+        let name = blocks.text(b"##TX", kind.name());
+        let frame = blocks.channel(
+            [0, component, name, source, 0, 0, 0, 0],
+            CN_TYPE_FIXED, SYNC_NONE, DATA_BYTE_ARRAY, 0, 8, (kind.record_size() as u32 - 8) * 8, CN_FLAG_BUS_EVENT,
+        );
+
+        let mut cc = vec![CC_LINEAR, 0, 0, 0, 0, 0, 2, 0];
+        cc.extend_from_slice(&[0; 16]);     // physical range
+        cc.extend_from_slice(&0f64.to_le_bytes());
+        cc.extend_from_slice(&1e-9f64.to_le_bytes());
+        let conversion = blocks.add(b"##CC", &[0, 0, 0, 0], &cc);
+        let unit = blocks.text(b"##TX", "s");
+        let name = blocks.text(b"##TX", "Timestamp");
+        let master = blocks.channel(
+            [frame, 0, name, source, conversion, 0, unit, 0],
+            CN_TYPE_MASTER, SYNC_TIME, DATA_UINT_LE, 0, 0, 64, 0,
+        );
+
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&(group.record_id as u64).to_le_bytes());
+        data.extend_from_slice(&group.count.to_le_bytes());
+        data.extend_from_slice(&(CG_FLAG_BUS_EVENT | CG_FLAG_PLAIN_BUS_EVENT).to_le_bytes());
+        data.extend_from_slice(&(b'.' as u16).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(kind.record_size() as u32).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        blocks.add(b"##CG", &[next, master, bus_name, source, 0, 0], &data)
+    }
+}
+
+impl<W: Write + Seek> Drop for MdfWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("RUST-CAN - {} when MDF writer dropped", e);
+        }
+    }
+}
+
+struct Block {
+    id: [u8; 4],
+    links: Vec<u64>,
+    data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Signal {
+    byte_offset: usize,
+    bit_offset: u8,
+    bit_count: u32,
+    data_type: u8,
+}
+
+impl Signal {
+    fn uint(&self, record: &[u8]) -> Option<u64> {
+        if self.bit_count == 0 || self.bit_count > 64 {
+            return None;
+        }
+        let size = (self.bit_offset as usize + self.bit_count as usize).div_ceil(8);
+        let bytes = record.get(self.byte_offset..self.byte_offset + size)?;
+        let value = match self.data_type {
+            DATA_UINT_BE => bytes.iter().fold(0u128, |v, &b| (v << 8) | b as u128),
+            _ => bytes.iter().rev().fold(0u128, |v, &b| (v << 8) | b as u128),
+        };
+        let value = (value >> self.bit_offset) as u64;
+        Some(if self.bit_count == 64 { value } else { value & ((1 << self.bit_count) - 1) })
+    }
+
+    fn float(&self, record: &[u8]) -> Option<f64> {
+        let bytes = record.get(self.byte_offset..self.byte_offset + self.bit_count as usize / 8)?;
+        match (self.data_type, self.bit_count) {
+            (DATA_FLOAT_LE, 64) => Some(f64::from_le_bytes(bytes.try_into().ok()?)),
+            (DATA_FLOAT_BE, 64) => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+            (DATA_FLOAT_LE, 32) => Some(f32::from_le_bytes(bytes.try_into().ok()?) as f64),
+            (DATA_FLOAT_BE, 32) => Some(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+            _ => None,
+        }
+    }
+
+    fn bytes<'a>(&self, record: &'a [u8]) -> Option<&'a [u8]> {
+        record.get(self.byte_offset..self.byte_offset + self.bit_count as usize / 8)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Master {
+    signal: Signal,
+    /// physical = offset + factor * raw
+    offset: f64,
+    factor: f64,
+}
+
+impl Master {
+    /// The seconds since start time.
+    fn seconds(&self, record: &[u8]) -> f64 {
+        let raw = match self.signal.data_type {
+            DATA_FLOAT_LE | DATA_FLOAT_BE => self.signal.float(record),
+            _ => self.signal.uint(record).map(|v| v as f64),
+        };
+        self.offset + self.factor * raw.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChannelGroup {
+    record_id: u64,
+    /// the size of record without record ID
+    size: usize,
+    vlsd: bool,
+    kind: Option<Kind>,
+    master: Option<Master>,
+    signals: HashMap<String, Signal>,
+}
+
+impl ChannelGroup {
+    #[inline]
+    fn uint(&self, name: &str, record: &[u8]) -> Option<u64> {
+        self.signals.get(name)
+            .and_then(|s| s.uint(record))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DataGroup {
+    record_id_size: usize,
+    data: u64,
+    groups: Vec<ChannelGroup>,
+}
+
+/// The reader of MDF 4 bus logging file.
+///
+/// The records are read in the order of data groups,
+/// which is the chronological order for the files written by [`MdfWriter`].
+pub struct MdfReader<R: Read + Seek, F> {
+    reader: R,
+    /// the length of file
+    size: u64,
+    /// nanoseconds since UNIX epoch
+    start: u64,
+    data_groups: Vec<DataGroup>,
+    /// the index of next data group
+    next_group: usize,
+    buffer: Vec<u8>,
+    pos: usize,
+    _frame: PhantomData<F>,
+}
+
+impl<F> MdfReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R, F> MdfReader<R, F>
+where
+    R: Read + Seek,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(reader: R) -> Result<Self, CanError> {
+        let mut result = Self {
+            reader,
+            size: 0,
+            start: 0,
+            data_groups: Vec::new(),
+            next_group: 0,
+            buffer: Vec::new(),
+            pos: 0,
+            _frame: Default::default(),
+        };
+
+        let mut id = [0; HD_ADDRESS as usize];
+        result.size = result.reader.seek(SeekFrom::End(0))
+            .map_err(io_error)?;
+        result.reader.seek(SeekFrom::Start(0))
+            .and_then(|_| result.reader.read_exact(&mut id))
+            .map_err(io_error)?;
+        if &id[..8] != ID_FILE {
+            return Err(CanError::other_error("not a MDF file"));
+        }
+        let version = u16::from_le_bytes([id[28], id[29]]);
+        if version < 400 {
+            return Err(CanError::OtherError(format!("MDF version: {} is not supported", version)));
+        }
+
+        let header = result.read_block(HD_ADDRESS)?;
+        if &header.id != b"##HD" || header.links.is_empty() || header.data.len() < 8 {
+            return Err(CanError::other_error("invalid MDF header block"));
+        }
+        result.start = u64::from_le_bytes(header.data[..8].try_into().unwrap_or_default());
+
+        let mut address = header.links[0];
+        while address != 0 {
+            let block = result.read_block(address)?;
+            if &block.id != b"##DG" || block.links.len() < 3 || block.data.is_empty() {
+                return Err(CanError::other_error("invalid MDF data group block"));
+            }
+            let mut groups = Vec::new();
+            let mut cg = block.links[1];
+            while cg != 0 {
+                let (group, next) = result.read_channel_group(cg)?;
+                groups.push(group);
+                cg = next;
+            }
+            result.data_groups.push(DataGroup {
+                record_id_size: block.data[0] as usize,
+                data: block.links[2],
+                groups,
+            });
+            address = block.links[0];
+        }
+
+        Ok(result)
+    }
+
+    /// The start time of measurement in file header.
+    #[inline(always)]
+    pub fn start_timestamp(&self) -> u64 {
+        self.start / 1_000_000
+    }
+
+    /// Only read the CAN frames.
+    pub fn frames(self) -> impl Iterator<Item = Result<F, CanError>> {
+        self.filter_map(|obj| match obj {
+            Ok(TraceObject::Frame(f)) => Some(Ok(f)),
+            Ok(TraceObject::Lin(_)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    fn read_block(&mut self, address: u64) -> Result<Block, CanError> {
+        let mut header = [0; BLOCK_HEADER_SIZE];
+        self.reader.seek(SeekFrom::Start(address))
+            .and_then(|_| self.reader.read_exact(&mut header))
+            .map_err(io_error)?;
+        let length = u64::from_le_bytes(header[8..16].try_into().unwrap_or_default());
+        let link_count = u64::from_le_bytes(header[16..24].try_into().unwrap_or_default());
+        // the block should contain its links and be in the file
+        let links_size = link_count.checked_mul(8)
+            .filter(|v| v.checked_add(BLOCK_HEADER_SIZE as u64).is_some_and(|v| v <= length));
+        let end = address.checked_add(length);
+        let (links_size, length) = match (links_size, end) {
+            (Some(v), Some(end)) if &header[..2] == b"##"
+                && length <= MAX_BLOCK_SIZE as u64
+                && end <= self.size => (v as usize, length as usize),
+            _ => return Err(CanError::OtherError(format!("invalid MDF block at: {:#x}", address))),
+        };
+
+        let mut body = vec![0; length - BLOCK_HEADER_SIZE];
+        self.reader.read_exact(&mut body)
+            .map_err(io_error)?;
+        let links = body[..links_size].chunks(8)
+            .map(|v| u64::from_le_bytes(v.try_into().unwrap_or_default()))
+            .collect();
+        let data = body.split_off(links_size);
+
+        Ok(Block { id: [header[0], header[1], header[2], header[3]], links, data })
+    }
+
+    fn read_text(&mut self, address: u64) -> Result<String, CanError> {
+        if address == 0 {
+            return Ok(String::new());
+        }
+        let block = self.read_block(address)?;
+        Ok(String::from_utf8_lossy(&block.data)
+            .trim_end_matches('\0')
+            .to_owned())
+    }
+
+    fn read_channel_group(&mut self, address: u64) -> Result<(ChannelGroup, u64), CanError> {
+        let block = self.read_block(address)?;
+        if &block.id != b"##CG" || block.links.len() < 2 || block.data.len() < 32 {
+            return Err(CanError::other_error("invalid MDF channel group block"));
+        }
+        let data = &block.data;
+        let flags = u16::from_le_bytes([data[16], data[17]]);
+        let mut group = ChannelGroup {
+            record_id: u64::from_le_bytes(data[..8].try_into().unwrap_or_default()),
+            size: u32::from_le_bytes(data[24..28].try_into().unwrap_or_default()) as usize
+                + u32::from_le_bytes(data[28..32].try_into().unwrap_or_default()) as usize,
+            vlsd: flags & CG_FLAG_VLSD != 0,
+            kind: None,
+            master: None,
+            signals: Default::default(),
+        };
+
+        let mut address = block.links[1];
+        while address != 0 {
+            let cn = self.read_block(address)?;
+            if &cn.id != b"##CN" || cn.links.len() < 5 || cn.data.len() < 16 {
+                return Err(CanError::other_error("invalid MDF channel block"));
+            }
+            let name = self.read_text(cn.links[2])?;
+            let signal = Self::signal(&cn)?;
+            match cn.data[0] {
+                CN_TYPE_MASTER | CN_TYPE_VIRTUAL_MASTER if cn.data[1] == SYNC_TIME => {
+                    let (offset, factor) = self.conversion(cn.links[4])?;
+                    group.master = Some(Master { signal, offset, factor });
+                },
+                CN_TYPE_FIXED => {
+                    let kind = Kind::from_name(&name);
+                    if kind.is_some() {
+                        group.kind = kind;
+                    }
+                    // the members of bus event channel
+                    let mut member = if kind.is_some() { cn.links[1] } else { 0 };
+                    while member != 0 {
+                        let cn = self.read_block(member)?;
+                        if &cn.id != b"##CN" || cn.links.len() < 3 || cn.data.len() < 16 {
+                            break;
+                        }
+                        if cn.data[0] == CN_TYPE_FIXED {
+                            let name = self.read_text(cn.links[2])?;
+                            let name = name.rsplit('.').next().unwrap_or_default().to_owned();
+                            group.signals.insert(name, Self::signal(&cn)?);
+                        }
+                        member = cn.links[0];
+                    }
+                },
+                _ => {},
+            }
+            address = cn.links[0];
+        }
+
+        Ok((group, block.links[0]))
+    }
+
+    fn signal(cn: &Block) -> Result<Signal, CanError> {
+        let data = &cn.data;
+        // the bit offset is 0..=7 by the specification
+        if data[3] > 7 {
+            return Err(CanError::OtherError(format!("invalid MDF channel bit offset: {}", data[3])));
+        }
+        Ok(Signal {
+            byte_offset: u32::from_le_bytes(data[4..8].try_into().unwrap_or_default()) as usize,
+            bit_offset: data[3],
+            bit_count: u32::from_le_bytes(data[8..12].try_into().unwrap_or_default()),
+            data_type: data[2],
+        })
+    }
+
+    /// Read the linear conversion, return `(offset, factor)`.
+    fn conversion(&mut self, address: u64) -> Result<(f64, f64), CanError> {
+        if address == 0 {
+            return Ok((0., 1.));
+        }
+        let block = self.read_block(address)?;
+        match block.data.first() {
+            Some(&CC_IDENTITY) => Ok((0., 1.)),
+            Some(&CC_LINEAR) if block.data.len() >= 40 => Ok((
+                f64::from_le_bytes(block.data[24..32].try_into().unwrap_or_default()),
+                f64::from_le_bytes(block.data[32..40].try_into().unwrap_or_default()),
+            )),
+            _ => {
+                log::warn!("RUST-CAN - the conversion of MDF master channel is not supported");
+                Ok((0., 1.))
+            },
+        }
+    }
+
+    /// Read the data of data block or list.
+    fn read_data(&mut self, address: u64, data: &mut Vec<u8>) -> Result<(), CanError> {
+        let mut address = address;
+        while address != 0 {
+            let block = self.read_block(address)?;
+            // the rest length of data group
+            let limit = MAX_BLOCK_SIZE - data.len().min(MAX_BLOCK_SIZE);
+            match &block.id {
+                b"##DT" => {
+                    if block.data.len() > limit {
+                        return Err(CanError::other_error("MDF data group is too large"));
+                    }
+                    data.extend_from_slice(&block.data);
+                    return Ok(());
+                },
+                b"##DZ" => {
+                    if block.data.len() < 24 {
+                        return Err(CanError::other_error("invalid MDF zipped data block"));
+                    }
+                    let zip_type = block.data[2];
+                    let columns = u32::from_le_bytes(block.data[4..8].try_into().unwrap_or_default()) as usize;
+                    let length = u64::from_le_bytes(block.data[8..16].try_into().unwrap_or_default());
+                    if length > limit as u64 {
+                        return Err(CanError::other_error("MDF data group is too large"));
+                    }
+                    // the buffer grows with the data inflated instead of the length in file
+                    let mut inflated = Vec::new();
+                    ZlibDecoder::new(&block.data[24..]).take(length).read_to_end(&mut inflated)
+                        .map_err(io_error)?;
+                    match zip_type {
+                        ZIP_DEFLATE => data.extend_from_slice(&inflated),
+                        ZIP_TRANSPOSE_DEFLATE if columns > 0 => {
+                            let rows = inflated.len() / columns;
+                            let size = rows * columns;
+                            let start = data.len();
+                            data.resize(start + size, 0);
+                            inflated[..size].iter()
+                                .enumerate()
+                                .for_each(|(i, &v)| data[start + (i % rows) * columns + i / rows] = v);
+                            data.extend_from_slice(&inflated[size..]);
+                        },
+                        _ => return Err(CanError::OtherError(format!("MDF zip type: {} is not supported", zip_type))),
+                    }
+                    return Ok(());
+                },
+                b"##DL" => {
+                    for &link in block.links.iter().skip(1) {
+                        self.read_data(link, data)?;
+                    }
+                    address = block.links.first().copied().unwrap_or_default();
+                },
+                b"##HL" => address = block.links.first().copied().unwrap_or_default(),
+                _ => return Err(CanError::OtherError(format!("MDF data block: {} is not supported", String::from_utf8_lossy(&block.id)))),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse next record in buffer, `None` is returned when the buffer is empty.
+    fn parse_record(&mut self) -> Result<Option<Option<TraceObject<F>>>, CanError> {
+        let dg = &self.data_groups[self.next_group - 1];
+        let remain = &self.buffer[self.pos..];
+        if remain.is_empty() {
+            return Ok(None);
+        }
+
+        let id_size = dg.record_id_size;
+        let group = if id_size == 0 {
+            dg.groups.first()
+        }
+        else {
+            let bytes = remain.get(..id_size)
+                .ok_or(CanError::other_error("truncated MDF record"))?;
+            let id = bytes.iter().rev().fold(0u64, |v, &b| (v << 8) | b as u64);
+            dg.groups.iter().find(|g| g.record_id == id)
+        }
+            .ok_or(CanError::other_error("unknown MDF record ID"))?;
+
+        if group.vlsd {
+            let bytes = remain.get(id_size..id_size + 4)
+                .ok_or(CanError::other_error("truncated MDF record"))?;
+            let length = u32::from_le_bytes(bytes.try_into().unwrap_or_default()) as usize;
+            let size = (id_size + 4).checked_add(length)
+                .filter(|&v| v <= remain.len())
+                .ok_or(CanError::other_error("truncated MDF record"))?;
+            self.pos += size;
+            return Ok(Some(None));
+        }
+
+        let record = remain.get(id_size..id_size + group.size)
+            .ok_or(CanError::other_error("truncated MDF record"))?;
+        self.pos += id_size + group.size;
+
+        let kind = match group.kind {
+            Some(v) => v,
+            None => return Ok(Some(None)),
+        };
+        let seconds = group.master
+            .map(|m| m.seconds(record))
+            .unwrap_or_default();
+        let timestamp = ((self.start as f64 + seconds * 1e9) / 1e6).round() as u64;
+        let bus = group.uint("BusChannel", record).unwrap_or(1) as u32;
+        let channel = bus.saturating_sub(1);
+        let direct = match group.uint("Dir", record) {
+            Some(1) => CanDirect::Transmit,
+            _ => CanDirect::Receive,
+        };
+        let data_bytes = group.signals.get("DataBytes")
+            .and_then(|s| s.bytes(record))
+            .unwrap_or_default();
+        let dlc = group.uint("DLC", record).unwrap_or_default() as usize;
+
+        if kind == Kind::Lin {
+            let length = group.uint("DataLength", record)
+                .or_else(|| group.uint("ReceivedDataByteCount", record))
+                .unwrap_or(dlc as u64) as usize;
+            return Ok(Some(Some(TraceObject::Lin(LinMessage {
+                timestamp,
+                channel,
+                id: group.uint("ID", record).unwrap_or_default() as u8 & 0x3F,
+                data: data_bytes[..length.min(data_bytes.len())].to_vec(),
+                checksum: group.uint("Checksum", record).unwrap_or_default() as u8,
+                direct,
+            }))));
+        }
+
+        let channel = channel_from_number(channel)?;
+        let id = group.uint("ID", record).unwrap_or_default() as u32;
+        let extended = group.uint("IDE", record)
+            .map(|v| v != 0)
+            .unwrap_or(id > SFF_MASK);
+        let id = CanId::from_bits(id, Some(extended));
+        let edl = group.uint("EDL", record).unwrap_or_default() != 0;
+        let length = group.uint("DataLength", record)
+            .map(|v| v as usize)
+            .unwrap_or(if edl { CAN_FD_DLC[dlc & 0x0F] } else { dlc.min(MAX_FRAME_SIZE) });
+
+        let mut frame = match kind {
+            Kind::CanData => {
+                let mut frame = F::new(id, &data_bytes[..length.min(data_bytes.len())])
+                    .ok_or(CanError::other_error("invalid frame"))?;
+                if edl || length > MAX_FRAME_SIZE {
+                    frame.set_can_type(CanType::CanFd)
+                        .set_bitrate_switch(group.uint("BRS", record).unwrap_or_default() != 0)
+                        .set_esi(group.uint("ESI", record).unwrap_or_default() != 0);
+                }
+                frame.set_channel(channel);
+                frame
+            },
+            Kind::CanRemote => {
+                let mut frame = F::new_remote(id, length.min(MAX_FRAME_SIZE))
+                    .ok_or(CanError::other_error("invalid frame"))?;
+                frame.set_channel(channel);
+                frame
+            },
+            _ => new_error_frame::<F>(channel)?,
+        };
+        frame.set_timestamp(Some(timestamp))
+            .set_direct(direct);
+
+        Ok(Some(Some(TraceObject::Frame(frame))))
+    }
+}
+
+impl<R, F> Iterator for MdfReader<R, F>
+where
+    R: Read + Seek,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<TraceObject<F>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.next_group > 0 {
+                match self.parse_record() {
+                    Ok(Some(Some(obj))) => return Some(Ok(obj)),
+                    Ok(Some(None)) => continue,
+                    Ok(None) => {},
+                    Err(e) => {
+                        // skip the rest of data group
+                        self.pos = self.buffer.len();
+                        return Some(Err(e));
+                    },
+                }
+            }
+
+            let address = self.data_groups.get(self.next_group)?.data;
+            self.next_group += 1;
+            self.buffer.clear();
+            self.pos = 0;
+            let mut buffer = std::mem::take(&mut self.buffer);
+            let result = self.read_data(address, &mut buffer);
+            self.buffer = buffer;
+            if let Err(e) = result {
+                self.buffer.clear();
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{CanDirect, CanFrame, CanId, CanType, vcan::CanMessage};
+    use crate::trace::{dlc_of, LinMessage, TraceObject};
+    use super::{MdfReader, MdfWriter, BLOCK_HEADER_SIZE, HD_ADDRESS};
+
+    #[test]
+    fn test_dlc() {
+        // the DLC field of CAN_DataFrame is the code, not the data length of CAN-FD frames
+        assert_eq!([dlc_of(0), dlc_of(8), dlc_of(12), dlc_of(20), dlc_of(64)], [0, 8, 9, 11, 15]);
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let mut frames = Vec::new();
+        let mut frame = CanMessage::new(0x123, &[0x01, 0x02, 0x03, 0x04]).unwrap();
+        frame.set_channel("0".into()).set_direct(CanDirect::Transmit);
+        frames.push(frame);
+        let mut frame = CanMessage::new(CanId::Extended(0x18FE_F100), &[0x55; 20]).unwrap();
+        frame.set_bitrate_switch(true).set_channel("1".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new_remote(0x7FF, 3).unwrap();
+        frame.set_channel("0".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(0, &[]).unwrap();
+        frame.set_error_frame(true).set_channel("1".into());
+        frames.push(frame);
+        frames.iter_mut()
+            .enumerate()
+            .for_each(|(i, f)| { f.set_timestamp(Some(1_700_000_000_000 + i as u64 * 10)); });
+        let lin = LinMessage {
+            timestamp: 1_700_000_000_100,
+            channel: 0,
+            id: 0x21,
+            data: vec![0x11, 0x22],
+            checksum: 0x8C,
+            direct: CanDirect::Receive,
+        };
+
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = MdfWriter::new(&mut buffer)?;
+            frames.iter().try_for_each(|f| writer.write_frame(f))?;
+            writer.write_lin(&lin)?;
+        }
+
+        buffer.set_position(0);
+        let reader = MdfReader::<_, CanMessage>::new(&mut buffer)?;
+        assert_eq!(reader.start_timestamp(), 1_700_000_000_000);
+        let objects = reader.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(objects.len(), frames.len() + 1);
+        objects.iter()
+            .zip(frames.iter())
+            .for_each(|(obj, f)| match obj {
+                TraceObject::Frame(r) => {
+                    assert_eq!(r, f);
+                    assert_eq!(r.timestamp(), f.timestamp());
+                    assert_eq!(r.channel(), f.channel());
+                    assert_eq!(r.direct(), f.direct());
+                    assert_eq!(r.is_remote(), f.is_remote());
+                    assert_eq!(r.is_error_frame(), f.is_error_frame());
+                },
+                TraceObject::Lin(_) => panic!("unexpected LIN frame"),
+            });
+        match &objects[1] {
+            TraceObject::Frame(f) => {
+                assert_eq!(f.can_type(), CanType::CanFd);
+                assert!(f.is_bitrate_switch());
+            },
+            TraceObject::Lin(_) => panic!("unexpected LIN frame"),
+        }
+        match &objects[frames.len()] {
+            TraceObject::Lin(v) => assert_eq!(v, &lin),
+            TraceObject::Frame(_) => panic!("unexpected CAN frame"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted() -> anyhow::Result<()> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = MdfWriter::new(&mut buffer)?;
+            for i in 0..10 {
+                let mut frame = CanMessage::new(0x123, &[i; 8]).unwrap();
+                frame.set_timestamp(Some(1_700_000_000_000 + i as u64))
+                    .set_channel("0".into());
+                writer.write_frame(&frame)?;
+            }
+        }
+        let buffer = buffer.into_inner();
+        let position = |id: &[u8]| buffer.windows(4).position(|v| v == id).unwrap();
+        let read = |data: Vec<u8>| MdfReader::<_, CanMessage>::new(Cursor::new(data))
+            .and_then(|r| r.collect::<Result<Vec<_>, _>>());
+        assert_eq!(read(buffer.clone())?.len(), 10);
+
+        // the metadata blocks are at the end of file
+        assert!(read(buffer[..buffer.len() - 8].to_vec()).is_err());
+        // the length of header block, data block and the link count of header block
+        let dt = position(b"##DT");
+        for (offset, value) in [
+            (HD_ADDRESS as usize + 8, u64::MAX),
+            (dt + 8, u64::MAX),
+            (dt + 8, 1 << 40),
+            (HD_ADDRESS as usize + 16, u64::MAX / 8 + 1),
+        ] {
+            let mut data = buffer.clone();
+            data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+            assert!(read(data).is_err());
+        }
+        // the bit offset of channel
+        let mut data = buffer.clone();
+        let cn = position(b"##CN");
+        let links = u64::from_le_bytes(data[cn + 16..cn + 24].try_into()?) as usize;
+        data[cn + BLOCK_HEADER_SIZE + links * 8 + 3] = 0xFF;
+        assert!(read(data).is_err());
+
+        Ok(())
+    }
+}
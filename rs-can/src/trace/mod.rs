@@ -0,0 +1,147 @@
+//! Reading and writing of trace(log) files.
+//!
+//! The timestamp of frames is the milliseconds since UNIX epoch,
+//! the channel of frames is converted from/to the channel number used by trace files.
+pub mod asc;
+pub mod blf;
+pub mod mdf;
+pub mod pcap;
+pub mod trc;
+pub(crate) mod bits;
+
+use std::str::FromStr;
+use crate::{CanDirect, CanError, CanFrame, CanId};
+
+pub(crate) const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+pub(crate) const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+/// The data length of CAN-FD frames indexed by DLC.
+pub(crate) const CAN_FD_DLC: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Get the channel number(starts from 0) of a frame's channel.
+///
+/// The number is the channel itself when it's a number, or the trailing digits of channel(`can1` => 1),
+/// `0` is returned when no digit is found.
+pub fn channel_number<C: std::fmt::Display>(channel: C) -> u32 {
+    let channel = channel.to_string();
+    let digits = channel.chars()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+
+    channel[channel.len() - digits..].parse()
+        .unwrap_or_default()
+}
+
+/// Convert a channel number(starts from 0) to a frame's channel.
+pub fn channel_from_number<C: FromStr>(number: u32) -> Result<C, CanError> {
+    number.to_string()
+        .parse()
+        .map_err(|_| CanError::other_error(format!("channel {} is not supported by frame", number)))
+}
+
+/// A LIN frame in trace files.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LinMessage {
+    /// milliseconds since UNIX epoch
+    pub timestamp: u64,
+    /// the channel number, starts from 0
+    pub channel: u32,
+    /// the frame identifier(0..=0x3F)
+    pub id: u8,
+    pub data: Vec<u8>,
+    pub checksum: u8,
+    pub direct: CanDirect,
+}
+
+/// A frame read from trace files which contain both CAN and LIN frames.
+#[derive(Debug, Clone)]
+pub enum TraceObject<F> {
+    Frame(F),
+    Lin(LinMessage),
+}
+
+/// Get the DLC(0..=15) of data length, the length greater than 64 is treated as 64.
+#[inline]
+pub(crate) fn dlc_of(length: usize) -> u8 {
+    CAN_FD_DLC.iter()
+        .position(|&v| v >= length)
+        .unwrap_or(CAN_FD_DLC.len() - 1) as u8
+}
+
+#[inline(always)]
+pub(crate) fn io_error(e: std::io::Error) -> CanError {
+    CanError::OperationError(e.to_string())
+}
+
+/// Create an error frame received by `channel`.
+#[inline]
+pub(crate) fn new_error_frame<F: CanFrame>(channel: F::Channel) -> Result<F, CanError> {
+    let mut frame = F::new(CanId::Standard(0), &[])
+        .ok_or(CanError::other_error("invalid frame"))?;
+    frame.set_error_frame(true)
+        .set_direct(CanDirect::Receive)
+        .set_channel(channel);
+    Ok(frame)
+}
+
+/// A UTC date time which is converted from/to milliseconds since UNIX epoch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct DateTime {
+    pub(crate) year: i64,
+    /// 1..=12
+    pub(crate) month: u32,
+    /// 1..=31
+    pub(crate) day: u32,
+    pub(crate) hour: u32,
+    pub(crate) minute: u32,
+    pub(crate) second: u32,
+    pub(crate) millisecond: u32,
+    /// 0 is Thursday(1970-01-01)
+    pub(crate) weekday: u32,
+}
+
+impl DateTime {
+    pub(crate) fn from_timestamp(timestamp: u64) -> Self {
+        let days = (timestamp / 86_400_000) as i64;
+        let millis = timestamp % 86_400_000;
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (millis / 3_600_000) as u32,
+            minute: (millis / 60_000 % 60) as u32,
+            second: (millis / 1000 % 60) as u32,
+            millisecond: (millis % 1000) as u32,
+            weekday: (days.rem_euclid(7)) as u32,
+        }
+    }
+
+    pub(crate) fn timestamp(&self) -> u64 {
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = if self.month <= 2 { self.year - 1 } else { self.year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = self.month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+
+        let millis = days * 86_400_000
+            + self.hour as i64 * 3_600_000
+            + self.minute as i64 * 60_000
+            + self.second as i64 * 1000
+            + self.millisecond as i64;
+        millis.max(0) as u64
+    }
+}
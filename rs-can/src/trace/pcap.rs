@@ -0,0 +1,742 @@
+//! PCAP and PCAPNG capture file with `LINKTYPE_CAN_SOCKETCAN`(227), which can be opened by Wireshark.
+//!
+//! The classic and CAN-FD frames are written as `can_frame`/`canfd_frame` of SocketCAN,
+//! the CAN-XL frames are written with the CAN-XL header.
+//!
+//! Each channel has its own interface description block in PCAPNG file,
+//! the channels can't be distinguished in PCAP file, all frames are read as channel `0`.
+use std::{collections::HashMap, fs::File, io::{BufReader, BufWriter, Read, Write}, marker::PhantomData, path::Path, str::FromStr};
+use crate::{CanDirect, CanError, CanFrame, CanId, CanType, IdentifierFlags, EFF_MASK, ERR_MASK, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE, MAX_XL_FRAME_SIZE};
+use super::{channel_from_number, channel_number, io_error};
+
+/// `LINKTYPE_CAN_SOCKETCAN`
+pub const LINKTYPE_CAN_SOCKETCAN: u32 = 227;
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAP_HEADER_SIZE: usize = 24;
+const PCAP_RECORD_HEADER_SIZE: usize = 16;
+
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_EPB: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_END: u16 = 0;
+const OPT_SHB_USERAPPL: u16 = 4;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+const EPB_INBOUND: u32 = 0x01;
+const EPB_OUTBOUND: u32 = 0x02;
+
+const CAN_MTU: usize = 16;
+const CANFD_MTU: usize = 72;
+const CANXL_HEADER_SIZE: usize = 12;
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+const CANXL_XLF: u8 = 0x80;
+const CANXL_PRIO_MASK: u32 = 0x7FF;
+
+const SNAPLEN: u32 = 0xFFFF;
+/// The maximum length of record or block read, the larger is treated as corrupted.
+const MAX_BLOCK_SIZE: usize = 256 * 1024;
+
+/// The format of capture file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PcapFormat {
+    Pcap,
+    #[default]
+    PcapNg,
+}
+
+/// The resolution of timestamp written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Microsecond,
+    #[default]
+    Nanosecond,
+}
+
+impl Resolution {
+    #[inline]
+    fn units_per_millis(&self) -> u64 {
+        match self {
+            Self::Microsecond => 1_000,
+            Self::Nanosecond => 1_000_000,
+        }
+    }
+}
+
+/// Encode a frame into the packet of `LINKTYPE_CAN_SOCKETCAN`.
+pub fn encode_packet<F: CanFrame + ?Sized>(frame: &F) -> Result<Vec<u8>, CanError> {
+    let data = frame.data();
+    let mut can_id = frame.id().into_bits();
+    if frame.is_extended() {
+        can_id |= IdentifierFlags::EXTENDED.bits();
+    }
+    if frame.is_error_frame() {
+        can_id = (can_id & ERR_MASK) | IdentifierFlags::ERROR.bits();
+    }
+
+    match frame.can_type() {
+        CanType::Can => {
+            if data.len() > MAX_FRAME_SIZE {
+                return Err(CanError::OtherError(format!("invalid data length: {} of CAN frame", data.len())));
+            }
+            let length = if frame.is_remote() && !frame.is_error_frame() {
+                can_id |= IdentifierFlags::REMOTE.bits();
+                frame.length().min(MAX_FRAME_SIZE)
+            }
+            else {
+                data.len()
+            };
+            let mut packet = vec![0; CAN_MTU];
+            packet[..4].copy_from_slice(&can_id.to_be_bytes());
+            packet[4] = length as u8;
+            if !frame.is_remote() {
+                packet[8..8 + data.len()].copy_from_slice(data);
+            }
+            Ok(packet)
+        },
+        CanType::CanFd => {
+            if data.len() > MAX_FD_FRAME_SIZE {
+                return Err(CanError::OtherError(format!("invalid data length: {} of CAN-FD frame", data.len())));
+            }
+            let mut flags = CANFD_FDF;
+            if frame.is_bitrate_switch() {
+                flags |= CANFD_BRS;
+            }
+            if frame.is_esi() {
+                flags |= CANFD_ESI;
+            }
+            let mut packet = vec![0; CANFD_MTU];
+            packet[..4].copy_from_slice(&can_id.to_be_bytes());
+            packet[4] = data.len() as u8;
+            packet[5] = flags;
+            packet[8..8 + data.len()].copy_from_slice(data);
+            Ok(packet)
+        },
+        CanType::CanXl => {
+            if data.is_empty() || data.len() > MAX_XL_FRAME_SIZE {
+                return Err(CanError::OtherError(format!("invalid data length: {} of CAN-XL frame", data.len())));
+            }
+            // the fields of CAN-XL header are little-endian
+            let mut packet = Vec::with_capacity(CANXL_HEADER_SIZE + data.len());
+            packet.extend_from_slice(&(frame.id().into_bits() & CANXL_PRIO_MASK).to_le_bytes());
+            packet.extend_from_slice(&[CANXL_XLF, 0]);          // flags, SDU type
+            packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            packet.extend_from_slice(&0u32.to_le_bytes());      // acceptance field
+            packet.extend_from_slice(data);
+            Ok(packet)
+        },
+    }
+}
+
+/// Decode a frame from the packet of `LINKTYPE_CAN_SOCKETCAN`, the channel and timestamp are not set.
+pub fn decode_packet<F: CanFrame>(packet: &[u8]) -> Result<F, CanError> {
+    if packet.len() < 8 {
+        return Err(CanError::other_error("invalid SocketCAN packet"));
+    }
+
+    if packet[4] & CANXL_XLF != 0 {
+        if packet.len() < CANXL_HEADER_SIZE {
+            return Err(CanError::other_error("invalid CAN-XL packet"));
+        }
+        let prio = u32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]) & CANXL_PRIO_MASK;
+        let length = (u16::from_le_bytes([packet[6], packet[7]]) as usize)
+            .min(packet.len() - CANXL_HEADER_SIZE);
+        let mut frame = F::new(CanId::Standard(prio as u16), &packet[CANXL_HEADER_SIZE..CANXL_HEADER_SIZE + length])
+            .ok_or(CanError::other_error("invalid frame"))?;
+        frame.set_can_type(CanType::CanXl);
+        return Ok(frame);
+    }
+
+    let can_id = u32::from_be_bytes([packet[0], packet[1], packet[2], packet[3]]);
+    let flags = packet[5];
+    let fd = flags & CANFD_FDF != 0 || packet.len() == CANFD_MTU;
+    let length = (packet[4] as usize)
+        .min(if fd { MAX_FD_FRAME_SIZE } else { MAX_FRAME_SIZE });
+    let error = can_id & IdentifierFlags::ERROR.bits() != 0;
+    let id = if error {
+        CanId::from_bits(can_id & ERR_MASK, None)
+    }
+    else {
+        CanId::from_bits(can_id & EFF_MASK, Some(can_id & IdentifierFlags::EXTENDED.bits() != 0))
+    };
+
+    let mut frame = if !fd && !error && can_id & IdentifierFlags::REMOTE.bits() != 0 {
+        F::new_remote(id, length)
+    }
+    else {
+        let data = packet.get(8..8 + length)
+            .ok_or(CanError::other_error("truncated SocketCAN packet"))?;
+        F::new(id, data)
+    }
+        .ok_or(CanError::other_error("invalid frame"))?;
+    if fd {
+        frame.set_can_type(CanType::CanFd)
+            .set_bitrate_switch(flags & CANFD_BRS != 0)
+            .set_esi(flags & CANFD_ESI != 0);
+    }
+    if error {
+        frame.set_error_frame(true);
+    }
+
+    Ok(frame)
+}
+
+/// Append an option of PCAPNG block, the value is padded to 32 bits.
+fn push_option(buf: &mut Vec<u8>, code: u16, value: &[u8]) {
+    buf.extend_from_slice(&code.to_le_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + (4 - value.len() % 4) % 4, 0);
+}
+
+fn block(r#type: u32, body: &[u8]) -> Vec<u8> {
+    let length = (12 + body.len()) as u32;
+    let mut result = Vec::with_capacity(length as usize);
+    result.extend_from_slice(&r#type.to_le_bytes());
+    result.extend_from_slice(&length.to_le_bytes());
+    result.extend_from_slice(body);
+    result.extend_from_slice(&length.to_le_bytes());
+    result
+}
+
+/// The writer of PCAP/PCAPNG file.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    format: PcapFormat,
+    resolution: Resolution,
+    header_written: bool,
+    /// the interface ID of channels
+    interfaces: HashMap<String, u32>,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P, format: PcapFormat) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(writer: W, format: PcapFormat) -> Self {
+        Self {
+            writer,
+            format,
+            resolution: Default::default(),
+            header_written: false,
+            interfaces: Default::default(),
+        }
+    }
+
+    /// Set the resolution of timestamp, must be set before any frame is written.
+    pub fn set_resolution(&mut self, resolution: Resolution) -> &mut Self {
+        if self.header_written {
+            log::warn!("RUST-CAN - the resolution of capture file can't be changed after written");
+        }
+        else {
+            self.resolution = resolution;
+        }
+        self
+    }
+
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let packet = encode_packet(frame)?;
+        self.write_header()?;
+
+        let timestamp = frame.timestamp() * self.resolution.units_per_millis();
+        match self.format {
+            PcapFormat::Pcap => {
+                let units = self.resolution.units_per_millis() * 1000;
+                let mut record = Vec::with_capacity(PCAP_RECORD_HEADER_SIZE + packet.len());
+                record.extend_from_slice(&((timestamp / units) as u32).to_le_bytes());
+                record.extend_from_slice(&((timestamp % units) as u32).to_le_bytes());
+                record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+                record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+                record.extend_from_slice(&packet);
+                self.writer.write_all(&record)
+                    .map_err(io_error)
+            },
+            PcapFormat::PcapNg => {
+                let interface = self.interface_id(frame.channel().to_string())?;
+                let mut body = Vec::with_capacity(20 + packet.len() + 16);
+                body.extend_from_slice(&interface.to_le_bytes());
+                body.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+                body.extend_from_slice(&(timestamp as u32).to_le_bytes());
+                body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+                body.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+                body.extend_from_slice(&packet);
+                body.resize(body.len() + (4 - packet.len() % 4) % 4, 0);
+                let flags = match frame.direct() {
+                    CanDirect::Transmit => EPB_OUTBOUND,
+                    CanDirect::Receive => EPB_INBOUND,
+                };
+                push_option(&mut body, OPT_EPB_FLAGS, &flags.to_le_bytes());
+                push_option(&mut body, OPT_END, &[]);
+                self.writer.write_all(&block(BLOCK_EPB, &body))
+                    .map_err(io_error)
+            },
+        }
+    }
+
+    /// Write the file header if no frame is written, and flush the writer.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        self.write_header()?;
+        self.writer.flush()
+            .map_err(io_error)
+    }
+
+    fn write_header(&mut self) -> Result<(), CanError> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+
+        let header = match self.format {
+            PcapFormat::Pcap => {
+                let magic = match self.resolution {
+                    Resolution::Microsecond => PCAP_MAGIC_MICROS,
+                    Resolution::Nanosecond => PCAP_MAGIC_NANOS,
+                };
+                let mut header = Vec::with_capacity(PCAP_HEADER_SIZE);
+                header.extend_from_slice(&magic.to_le_bytes());
+                header.extend_from_slice(&2u16.to_le_bytes());
+                header.extend_from_slice(&4u16.to_le_bytes());
+                header.extend_from_slice(&[0; 8]);      // thiszone, sigfigs
+                header.extend_from_slice(&SNAPLEN.to_le_bytes());
+                header.extend_from_slice(&LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+                header
+            },
+            PcapFormat::PcapNg => {
+                let mut body = Vec::new();
+                body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+                body.extend_from_slice(&1u16.to_le_bytes());
+                body.extend_from_slice(&0u16.to_le_bytes());
+                body.extend_from_slice(&(-1i64).to_le_bytes());     // section length is unknown
+                push_option(&mut body, OPT_SHB_USERAPPL, concat!("rs-can ", env!("CARGO_PKG_VERSION")).as_bytes());
+                push_option(&mut body, OPT_END, &[]);
+                block(BLOCK_SHB, &body)
+            },
+        };
+
+        self.writer.write_all(&header)
+            .map_err(io_error)
+    }
+
+    /// Get the interface ID of channel, the interface description block is written when the channel is new.
+    fn interface_id(&mut self, channel: String) -> Result<u32, CanError> {
+        if let Some(&id) = self.interfaces.get(&channel) {
+            return Ok(id);
+        }
+
+        let id = self.interfaces.len() as u32;
+        let name = if channel.is_empty() || channel.chars().all(|c| c.is_ascii_digit()) {
+            format!("can{}", channel_number(&channel))
+        }
+        else {
+            channel.clone()
+        };
+        let resolution = match self.resolution {
+            Resolution::Microsecond => 6u8,
+            Resolution::Nanosecond => 9,
+        };
+        let mut body = Vec::new();
+        body.extend_from_slice(&(LINKTYPE_CAN_SOCKETCAN as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes());
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        push_option(&mut body, OPT_IF_NAME, name.as_bytes());
+        push_option(&mut body, OPT_IF_TSRESOL, &[resolution]);
+        push_option(&mut body, OPT_END, &[]);
+        self.writer.write_all(&block(BLOCK_IDB, &body))
+            .map_err(io_error)?;
+
+        self.interfaces.insert(channel, id);
+        Ok(id)
+    }
+}
+
+impl<W: Write> Drop for PcapWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("RUST-CAN - {} when capture writer dropped", e);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Interface {
+    link_type: u16,
+    name: Option<String>,
+    /// timestamp units per second
+    units: u64,
+}
+
+/// The reader of PCAP/PCAPNG file, the format is detected automatically.
+///
+/// The packets of other link types are skipped.
+pub struct PcapReader<R: Read, F> {
+    reader: R,
+    format: PcapFormat,
+    big_endian: bool,
+    /// the interfaces of current section, PCAP file has only one interface
+    interfaces: Vec<Interface>,
+    _frame: PhantomData<F>,
+}
+
+impl<F> PcapReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R, F> PcapReader<R, F>
+where
+    R: Read,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(mut reader: R) -> Result<Self, CanError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)
+            .map_err(io_error)?;
+
+        let mut result = Self {
+            reader,
+            format: PcapFormat::Pcap,
+            big_endian: false,
+            interfaces: Vec::new(),
+            _frame: Default::default(),
+        };
+
+        if u32::from_le_bytes(magic) == BLOCK_SHB {
+            result.format = PcapFormat::PcapNg;
+            result.read_section_header()?;
+            return Ok(result);
+        }
+
+        let (big_endian, units) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, 1_000_000),
+            (PCAP_MAGIC_NANOS, _) => (false, 1_000_000_000),
+            (_, PCAP_MAGIC_MICROS) => (true, 1_000_000),
+            (_, PCAP_MAGIC_NANOS) => (true, 1_000_000_000),
+            _ => return Err(CanError::other_error("not a PCAP or PCAPNG file")),
+        };
+        result.big_endian = big_endian;
+        let mut header = [0; PCAP_HEADER_SIZE - 4];
+        result.reader.read_exact(&mut header)
+            .map_err(io_error)?;
+        let link_type = result.u32_at(&header, 16) & 0xFFFF;
+        if link_type != LINKTYPE_CAN_SOCKETCAN {
+            return Err(CanError::OtherError(format!("link type: {} is not supported", link_type)));
+        }
+        result.interfaces.push(Interface { link_type: link_type as u16, name: None, units });
+
+        Ok(result)
+    }
+
+    /// The format of file.
+    #[inline(always)]
+    pub fn format(&self) -> PcapFormat {
+        self.format
+    }
+
+    #[inline]
+    fn u16_at(&self, buf: &[u8], pos: usize) -> u16 {
+        let bytes = [buf[pos], buf[pos + 1]];
+        if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    }
+
+    #[inline]
+    fn u32_at(&self, buf: &[u8], pos: usize) -> u32 {
+        let bytes = [buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+
+    /// Read the section header block after block type.
+    fn read_section_header(&mut self) -> Result<(), CanError> {
+        let mut buf = [0; 8];
+        self.reader.read_exact(&mut buf)
+            .map_err(io_error)?;
+        self.big_endian = match (u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]), u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]])) {
+            (BYTE_ORDER_MAGIC, _) => false,
+            (_, BYTE_ORDER_MAGIC) => true,
+            _ => return Err(CanError::other_error("invalid byte-order magic of PCAPNG")),
+        };
+        let length = self.u32_at(&buf, 0) as usize;
+        if length > MAX_BLOCK_SIZE {
+            return Err(CanError::OtherError(format!("invalid section header length: {}", length)));
+        }
+        let mut rest = vec![0; length.saturating_sub(12)];
+        self.reader.read_exact(&mut rest)
+            .map_err(io_error)?;
+        self.interfaces.clear();
+
+        Ok(())
+    }
+
+    /// Read a block, return `None` at the end of file.
+    fn read_block(&mut self) -> Result<Option<(u32, Vec<u8>)>, CanError> {
+        let mut buf = [0; 8];
+        match self.reader.read_exact(&mut buf[..4]) {
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(io_error(e)),
+        }
+        if u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) == BLOCK_SHB {
+            self.read_section_header()?;
+            return Ok(Some((BLOCK_SHB, Vec::new())));
+        }
+
+        self.reader.read_exact(&mut buf[4..])
+            .map_err(io_error)?;
+        let r#type = self.u32_at(&buf, 0);
+        let length = self.u32_at(&buf, 4) as usize;
+        if !(12..=MAX_BLOCK_SIZE).contains(&length) || length & 0x03 != 0 {
+            return Err(CanError::OtherError(format!("invalid block length: {}", length)));
+        }
+        let mut body = vec![0; length - 8];
+        self.reader.read_exact(&mut body)
+            .map_err(io_error)?;
+        body.truncate(length - 12);
+
+        Ok(Some((r#type, body)))
+    }
+
+    /// Parse the options of block, return `(code, value)`.
+    fn options<'a>(&self, mut buf: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut result = Vec::new();
+        while buf.len() >= 4 {
+            let code = self.u16_at(buf, 0);
+            let length = self.u16_at(buf, 2) as usize;
+            if code == OPT_END || buf.len() < 4 + length {
+                break;
+            }
+            result.push((code, &buf[4..4 + length]));
+            buf = &buf[(4 + length).div_ceil(4) * 4..];
+        }
+        result
+    }
+
+    fn parse_interface(&mut self, body: &[u8]) -> Result<(), CanError> {
+        if body.len() < 8 {
+            return Err(CanError::other_error("invalid interface description block"));
+        }
+        let mut interface = Interface {
+            link_type: self.u16_at(body, 0),
+            name: None,
+            units: 1_000_000,
+        };
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                OPT_IF_NAME => interface.name = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_owned()),
+                OPT_IF_TSRESOL if !value.is_empty() => {
+                    interface.units = if value[0] & 0x80 == 0 {
+                        10u64.checked_pow(value[0] as u32)
+                    }
+                    else {
+                        2u64.checked_pow((value[0] & 0x7F) as u32)
+                    }
+                        .ok_or(CanError::other_error("invalid timestamp resolution"))?;
+                },
+                _ => {},
+            }
+        }
+        self.interfaces.push(interface);
+
+        Ok(())
+    }
+
+    fn new_frame(&self, interface: usize, timestamp: u64, packet: &[u8]) -> Result<F, CanError> {
+        let iface = &self.interfaces[interface];
+        let mut frame = decode_packet::<F>(packet)?;
+        let channel = match &iface.name {
+            Some(name) => name.parse()
+                .or_else(|_| channel_from_number(channel_number(name)))?,
+            None => channel_from_number(interface as u32)?,
+        };
+        let timestamp = (timestamp as u128 * 1000 / iface.units as u128) as u64;
+        frame.set_timestamp(Some(timestamp))
+            .set_channel(channel);
+        Ok(frame)
+    }
+
+    fn next_pcap(&mut self) -> Result<Option<F>, CanError> {
+        let mut header = [0; PCAP_RECORD_HEADER_SIZE];
+        match self.reader.read_exact(&mut header) {
+            Ok(_) => {},
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(io_error(e)),
+        }
+        let seconds = self.u32_at(&header, 0) as u64;
+        let fraction = self.u32_at(&header, 4) as u64;
+        let length = self.u32_at(&header, 8) as usize;
+        if length > MAX_BLOCK_SIZE {
+            return Err(CanError::OtherError(format!("invalid record length: {}", length)));
+        }
+        let mut packet = vec![0; length];
+        self.reader.read_exact(&mut packet)
+            .map_err(io_error)?;
+
+        let timestamp = seconds * self.interfaces[0].units + fraction;
+        let mut frame = self.new_frame(0, timestamp, &packet)?;
+        frame.set_direct(CanDirect::Receive);
+        Ok(Some(frame))
+    }
+
+    fn next_pcapng(&mut self) -> Result<Option<F>, CanError> {
+        loop {
+            let (r#type, body) = match self.read_block()? {
+                Some(v) => v,
+                None => return Ok(None),
+            };
+
+            match r#type {
+                BLOCK_IDB => self.parse_interface(&body)?,
+                BLOCK_EPB if body.len() >= 20 => {
+                    let interface = self.u32_at(&body, 0) as usize;
+                    let timestamp = ((self.u32_at(&body, 4) as u64) << 32) | self.u32_at(&body, 8) as u64;
+                    let length = self.u32_at(&body, 12) as usize;
+                    match self.interfaces.get(interface) {
+                        Some(v) if v.link_type as u32 == LINKTYPE_CAN_SOCKETCAN => {},
+                        Some(_) => continue,
+                        None => return Err(CanError::OtherError(format!("interface: {} is not described", interface))),
+                    }
+                    let packet = body.get(20..20 + length)
+                        .ok_or(CanError::other_error("truncated enhanced packet block"))?;
+                    let mut frame = self.new_frame(interface, timestamp, packet)?;
+                    let direct = self.options(&body[(20 + length).div_ceil(4) * 4..])
+                        .into_iter()
+                        .find(|(code, v)| *code == OPT_EPB_FLAGS && v.len() == 4)
+                        .map(|(_, v)| self.u32_at(v, 0) & 0x03);
+                    frame.set_direct(match direct {
+                        Some(EPB_OUTBOUND) => CanDirect::Transmit,
+                        _ => CanDirect::Receive,
+                    });
+                    return Ok(Some(frame));
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+impl<R, F> Iterator for PcapReader<R, F>
+where
+    R: Read,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<F, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            PcapFormat::Pcap => self.next_pcap(),
+            PcapFormat::PcapNg => self.next_pcapng(),
+        }
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{CanDirect, CanFrame, CanId, CanType, vcan::CanMessage};
+    use super::{PcapFormat, PcapReader, PcapWriter, Resolution, PCAP_HEADER_SIZE};
+
+    fn frames() -> Vec<CanMessage> {
+        let mut frames = Vec::new();
+        let mut frame = CanMessage::new(0x123, &[0x01, 0x02, 0x03]).unwrap();
+        frame.set_channel("vcan0".into()).set_direct(CanDirect::Transmit);
+        frames.push(frame);
+        let mut frame = CanMessage::new(CanId::Extended(0x18DA_F1F2), &[0x55; 32]).unwrap();
+        frame.set_bitrate_switch(true).set_esi(true).set_channel("vcan1".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new_remote(0x7FF, 2).unwrap();
+        frame.set_channel("vcan0".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(0x0F0, &[0xAA; 300]).unwrap();
+        frame.set_channel("vcan1".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(0x04, &[0; 8]).unwrap();
+        frame.set_error_frame(true).set_channel("vcan0".into());
+        frames.push(frame);
+        frames.iter_mut()
+            .enumerate()
+            .for_each(|(i, f)| { f.set_timestamp(Some(1_700_000_000_000 + i as u64 * 1001)); });
+        frames
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let frames = frames();
+        for (format, resolution) in [
+            (PcapFormat::PcapNg, Resolution::Nanosecond),
+            (PcapFormat::PcapNg, Resolution::Microsecond),
+            (PcapFormat::Pcap, Resolution::Nanosecond),
+            (PcapFormat::Pcap, Resolution::Microsecond),
+        ] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = PcapWriter::new(&mut buffer, format);
+                writer.set_resolution(resolution);
+                frames.iter().try_for_each(|f| writer.write_frame(f))?;
+            }
+
+            let reader = PcapReader::<_, CanMessage>::new(Cursor::new(buffer))?;
+            assert_eq!(reader.format(), format);
+            let result = reader.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(result, frames);
+            result.iter()
+                .zip(frames.iter())
+                .for_each(|(r, f)| {
+                    assert_eq!(r.timestamp(), f.timestamp());
+                    assert_eq!(r.can_type(), f.can_type());
+                    assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+                    match format {
+                        PcapFormat::Pcap => assert_eq!(r.channel(), "0"),
+                        PcapFormat::PcapNg => {
+                            assert_eq!(r.channel(), f.channel());
+                            assert_eq!(r.direct(), f.direct());
+                        },
+                    }
+                });
+            assert_eq!(result[3].can_type(), CanType::CanXl);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted() -> anyhow::Result<()> {
+        let frames = frames();
+        for (format, offset) in [
+            // the length of first record
+            (PcapFormat::Pcap, PCAP_HEADER_SIZE + 8),
+            // the length of section header block
+            (PcapFormat::PcapNg, 4),
+        ] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = PcapWriter::new(&mut buffer, format);
+                frames.iter().try_for_each(|f| writer.write_frame(f))?;
+            }
+            buffer[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+            let result = PcapReader::<_, CanMessage>::new(Cursor::new(buffer))
+                .and_then(|r| r.collect::<Result<Vec<_>, _>>());
+            assert!(result.is_err());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,690 @@
+//! PEAK-System TRC trace file(PCAN-View, PCAN-Explorer), the versions 1.1 to 2.1 are supported.
+//!
+//! ```text
+//! ;$FILEVERSION=2.1
+//! ;$STARTTIME=45230.5000000000
+//! ;$COLUMNS=N,O,T,B,I,d,R,L,D
+//! ;
+//! ;   Start time: 01.11.2023 12:00:00.000.0
+//! ;-------------------------------------------------------------------------------
+//!       1         0.000 DT 1      0123 Rx -  4    01 02 03 04
+//!       2        10.250 FB 2  18DAF110 Tx -  9    55 55 55 55 55 55 55 55 55 55 55 55
+//!       3        25.000 RR 1      07FF Rx -  8
+//!       4        30.000 ER 1         - Rx -  0
+//! ```
+//!
+//! The versions 1.x have fixed columns and don't support CAN-FD frames,
+//! the bus column is available since version 1.2.
+use std::{fmt::Write as _, fs::File, io::{BufRead, BufReader, BufWriter, Write}, marker::PhantomData, path::Path, str::FromStr};
+use crate::{CanDirect, CanError, CanFrame, CanId, CanType, MAX_FRAME_SIZE};
+use super::{channel_from_number, channel_number, dlc_of, io_error, new_error_frame, DateTime, CAN_FD_DLC};
+
+/// The days from 1899-12-30(the epoch of `$STARTTIME`) to 1970-01-01.
+const START_TIME_EPOCH: f64 = 25569.;
+const MILLIS_PER_DAY: f64 = 86_400_000.;
+
+/// The version of TRC file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TrcVersion {
+    V1_1,
+    V1_2,
+    V1_3,
+    V2_0,
+    #[default]
+    V2_1,
+}
+
+impl TrcVersion {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::V1_1 => "1.1",
+            Self::V1_2 => "1.2",
+            Self::V1_3 => "1.3",
+            Self::V2_0 => "2.0",
+            Self::V2_1 => "2.1",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "1.1" => Some(Self::V1_1),
+            "1.2" => Some(Self::V1_2),
+            "1.3" => Some(Self::V1_3),
+            "2.0" => Some(Self::V2_0),
+            "2.1" => Some(Self::V2_1),
+            _ => None,
+        }
+    }
+
+    /// The columns written by version 2.x.
+    fn columns(&self) -> &'static str {
+        match self {
+            Self::V2_0 => "N,O,T,B,I,d,l,D",
+            _ => "N,O,T,B,I,d,R,L,D",
+        }
+    }
+
+    #[inline(always)]
+    fn is_v1(&self) -> bool {
+        *self < Self::V2_0
+    }
+}
+
+/// Format the start time as `$STARTTIME`, the days since 1899-12-30.
+#[inline]
+fn fmt_start_time(timestamp: u64) -> String {
+    format!("{:.10}", timestamp as f64 / MILLIS_PER_DAY + START_TIME_EPOCH)
+}
+
+#[inline]
+fn parse_start_time(value: &str) -> Option<u64> {
+    let days = value.trim().parse::<f64>().ok()?;
+    Some(((days - START_TIME_EPOCH) * MILLIS_PER_DAY).round().max(0.) as u64)
+}
+
+/// Parse the date of `Start time: dd.mm.yyyy hh:mm:ss.mmm.u` in comment.
+fn parse_date(value: &str) -> Option<u64> {
+    let (date, time) = value.trim().split_once(' ')?;
+    let mut date = date.split('.');
+    let day = date.next()?.parse().ok()?;
+    let month = date.next()?.parse().ok()?;
+    let year = date.next()?.parse().ok()?;
+    let mut time = time.trim().split([':', '.']);
+    let hour = time.next()?.parse().ok()?;
+    let minute = time.next()?.parse().ok()?;
+    let second = time.next()?.parse().ok()?;
+    let millisecond = time.next().and_then(|v| v.parse().ok()).unwrap_or_default();
+
+    Some(DateTime { year, month, day, hour, minute, second, millisecond, weekday: 0 }.timestamp())
+}
+
+/// The streaming writer of TRC file.
+///
+/// The header is written before the first frame, the message number starts from 1.
+pub struct TrcWriter<W: Write> {
+    writer: W,
+    version: TrcVersion,
+    start: Option<u64>,
+    number: u64,
+    header: bool,
+    finished: bool,
+}
+
+impl TrcWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> TrcWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            version: Default::default(),
+            start: None,
+            number: 0,
+            header: false,
+            finished: false,
+        }
+    }
+
+    /// Set the version of file, it can't be changed after the header is written.
+    pub fn set_version(&mut self, version: TrcVersion) -> &mut Self {
+        if self.header {
+            log::warn!("RUST-CAN - the version of TRC can't be changed after written");
+        }
+        else {
+            self.version = version;
+        }
+        self
+    }
+
+    /// Set the start time of measurement, the timestamp of first frame is used by default.
+    pub fn set_start_timestamp(&mut self, timestamp: u64) -> &mut Self {
+        self.start = Some(timestamp);
+        self
+    }
+
+    /// Write a frame, the bus number in file is the frame's channel number plus 1.
+    ///
+    /// The CAN-FD frames are not supported by versions 1.x, and CAN-XL frames are not supported.
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let can_type = frame.can_type();
+        if can_type == CanType::CanXl || (self.version.is_v1() && can_type == CanType::CanFd) {
+            return Err(CanError::NotSupportedError);
+        }
+
+        let timestamp = frame.timestamp();
+        let start = self.write_header(timestamp)?;
+        self.number += 1;
+        let offset = timestamp.saturating_sub(start) as f64;
+        let bus = channel_number(frame.channel()) + 1;
+        let id = if frame.is_extended() {
+            format!("{:08X}", frame.id().into_bits())
+        }
+        else {
+            format!("{:04X}", frame.id().into_bits())
+        };
+        let data = frame.data();
+        let length = if frame.is_remote() { frame.length() } else { data.len() };
+        let data = if frame.is_remote() {
+            String::new()
+        }
+        else {
+            data.iter()
+                .fold(String::new(), |mut out, b| {
+                    let _ = write!(out, "{:02X} ", b);
+                    out
+                })
+        };
+
+        let mut line = String::new();
+        if self.version.is_v1() {
+            let type_ = if frame.is_error_frame() { "Error".into() } else { frame.direct().to_string() };
+            let length = length.min(MAX_FRAME_SIZE);
+            let data = if frame.is_remote() { "RTR" } else { data.trim_end() };
+            let _ = match self.version {
+                TrcVersion::V1_1 => write!(line, "{:>6}) {:>11.1}  {:<4} {:>8}  {}  {}", self.number, offset, type_, id, length, data),
+                TrcVersion::V1_2 => write!(line, "{:>7}) {:>13.3} {:<2} {:<4} {:>8}  {}  {}", self.number, offset, bus, type_, id, length, data),
+                _ => write!(line, "{:>7}) {:>13.3} {:<2} {:<4} {:>8} - {}  {}", self.number, offset, bus, type_, id, length, data),
+            };
+        }
+        else {
+            let type_ = match can_type {
+                _ if frame.is_error_frame() => "ER",
+                _ if frame.is_remote() => "RR",
+                CanType::CanFd => match (frame.is_bitrate_switch(), frame.is_esi()) {
+                    (false, false) => "FD",
+                    (true, false) => "FB",
+                    (false, true) => "FE",
+                    (true, true) => "BI",
+                },
+                _ => "DT",
+            };
+            let id = if frame.is_error_frame() { "-".into() } else { id };
+            for column in self.version.columns().split(',') {
+                let _ = match column {
+                    "N" => write!(line, "{:>7}", self.number),
+                    "O" => write!(line, " {:>13.3}", offset),
+                    "T" => write!(line, " {}", type_),
+                    "B" => write!(line, " {:<2}", bus),
+                    "I" => write!(line, " {:>8}", id),
+                    "d" => write!(line, " {}", frame.direct()),
+                    "R" => write!(line, " -"),
+                    "L" => write!(line, " {:<4}", dlc_of(length)),
+                    "l" => write!(line, " {:<4}", length),
+                    _ => write!(line, " {}", data.trim_end()),
+                };
+            }
+        }
+
+        writeln!(self.writer, "{}", line.trim_end())
+            .map_err(io_error)
+    }
+
+    /// Write the header if no frame is written and flush the writer.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+
+        let start = self.start.unwrap_or_else(crate::can_utils::system_timestamp);
+        self.write_header(start)?;
+        self.finished = true;
+        self.writer.flush()
+            .map_err(io_error)
+    }
+
+    /// Write the header if not written, return the start timestamp.
+    fn write_header(&mut self, timestamp: u64) -> Result<u64, CanError> {
+        if self.finished {
+            return Err(CanError::operation_error("TRC writer is finished"));
+        }
+
+        let start = *self.start.get_or_insert(timestamp);
+        if !self.header {
+            self.header = true;
+            let date = DateTime::from_timestamp(start);
+            let mut header = format!(";$FILEVERSION={}\n;$STARTTIME={}\n", self.version.as_str(), fmt_start_time(start));
+            if !self.version.is_v1() {
+                let _ = writeln!(header, ";$COLUMNS={}", self.version.columns());
+            }
+            let _ = write!(header,
+                           ";\n;   Start time: {:02}.{:02}.{} {:02}:{:02}:{:02}.{:03}.0\n;   Generated by rs-can {}\n;{}\n",
+                           date.day, date.month, date.year, date.hour, date.minute, date.second, date.millisecond,
+                           env!("CARGO_PKG_VERSION"),
+                           "-".repeat(79),
+            );
+            self.writer.write_all(header.as_bytes())
+                .map_err(io_error)?;
+        }
+
+        Ok(start)
+    }
+}
+
+impl<W: Write> Drop for TrcWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("RUST-CAN - {} when TRC writer dropped", e);
+        }
+    }
+}
+
+/// The streaming reader of TRC file, events except frames are skipped.
+///
+/// The timestamp of frames is the start time of measurement plus the offset in file,
+/// the channel is converted from the bus number minus 1, and it's `0` when the file has no bus column.
+pub struct TrcReader<R: BufRead, F> {
+    reader: R,
+    buffer: String,
+    line_no: usize,
+    version: TrcVersion,
+    columns: Vec<String>,
+    start: Option<u64>,
+    _frame: PhantomData<F>,
+}
+
+impl<F> TrcReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R, F> TrcReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: String::new(),
+            line_no: 0,
+            version: TrcVersion::V1_1,
+            columns: Vec::new(),
+            start: None,
+            _frame: Default::default(),
+        }
+    }
+
+    /// The version of file, available after the header is read.
+    #[inline(always)]
+    pub fn version(&self) -> TrcVersion {
+        self.version
+    }
+
+    /// The start time of measurement in header, available after the header is read.
+    #[inline(always)]
+    pub fn start_timestamp(&self) -> Option<u64> {
+        self.start
+    }
+
+    fn parse_line(&mut self) -> Result<Option<F>, CanError> {
+        let line = self.buffer.trim();
+        if let Some(comment) = line.strip_prefix(';') {
+            let comment = comment.to_owned();
+            self.parse_comment(&comment)?;
+            return Ok(None);
+        }
+
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        if self.version.is_v1() {
+            self.parse_v1(&tokens)
+        }
+        else {
+            self.parse_v2(&tokens)
+        }
+    }
+
+    fn parse_comment(&mut self, comment: &str) -> Result<(), CanError> {
+        let comment = comment.trim();
+        if let Some(value) = comment.strip_prefix("$FILEVERSION=") {
+            self.version = TrcVersion::parse(value)
+                .ok_or(self.error("file version is not supported"))?;
+            if !self.version.is_v1() && self.columns.is_empty() {
+                self.columns = self.version.columns().split(',').map(String::from).collect();
+            }
+        }
+        else if let Some(value) = comment.strip_prefix("$STARTTIME=") {
+            self.start = Some(parse_start_time(value).ok_or(self.error("invalid start time"))?);
+        }
+        else if let Some(value) = comment.strip_prefix("$COLUMNS=") {
+            self.columns = value.split(',').map(|v| v.trim().to_owned()).collect();
+            if self.columns.last().map(|v| v.as_str()) != Some("D") {
+                return Err(self.error("the data column must be the last one"));
+            }
+        }
+        else if let Some(value) = comment.strip_prefix("Start time:") {
+            if self.start.is_none() {
+                self.start = parse_date(value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// `N) O [B] T I [-] L D...`, the type is `Rx`, `Tx`, `Error` or `Warng`.
+    fn parse_v1(&self, tokens: &[&str]) -> Result<Option<F>, CanError> {
+        if !tokens[0].ends_with(')') {
+            return Err(self.error("invalid message number"));
+        }
+        let mut idx = 1;
+        let timestamp = self.parse_offset(tokens.get(idx))?;
+        idx += 1;
+        let channel = if self.version >= TrcVersion::V1_2 {
+            idx += 1;
+            self.parse_channel(tokens.get(idx - 1))?
+        }
+        else {
+            channel_from_number(0)?
+        };
+        let direct = match tokens.get(idx) {
+            Some(&"Rx") => CanDirect::Receive,
+            Some(&"Tx") => CanDirect::Transmit,
+            Some(&"Error") => {
+                let mut frame = new_error_frame::<F>(channel)?;
+                frame.set_timestamp(Some(timestamp));
+                return Ok(Some(frame));
+            },
+            Some(&"Warng") => return Ok(None),
+            _ => return Err(self.error("invalid message type")),
+        };
+        let id = self.parse_id(tokens.get(idx + 1))?;
+        idx += 2;
+        if tokens.get(idx) == Some(&"-") {
+            idx += 1;
+        }
+        let length = tokens.get(idx)
+            .and_then(|v| v.parse::<usize>().ok())
+            .ok_or(self.error("invalid data length"))?
+            .min(MAX_FRAME_SIZE);
+
+        let mut frame = if tokens.get(idx + 1) == Some(&"RTR") {
+            F::new_remote(id, length)
+        }
+        else {
+            let data = self.parse_data(&tokens[idx + 1..], length)?;
+            F::new(id, &data)
+        }
+            .ok_or(self.error("invalid frame"))?;
+        frame.set_timestamp(Some(timestamp))
+            .set_direct(direct)
+            .set_channel(channel);
+
+        Ok(Some(frame))
+    }
+
+    /// The columns are defined by `$COLUMNS`, the type is `DT`, `FD`, `FB`, `FE`, `BI`, `RR` or `ER`.
+    fn parse_v2(&self, tokens: &[&str]) -> Result<Option<F>, CanError> {
+        let column = |name: &str| self.columns.iter()
+            .position(|c| c == name)
+            .and_then(|i| tokens.get(i).copied());
+
+        let type_ = column("T").unwrap_or("DT");
+        let (can_type, brs, esi) = match type_ {
+            "DT" | "RR" | "ER" => (CanType::Can, false, false),
+            "FD" => (CanType::CanFd, false, false),
+            "FB" => (CanType::CanFd, true, false),
+            "FE" => (CanType::CanFd, false, true),
+            "BI" => (CanType::CanFd, true, true),
+            // status, error counter and event
+            "ST" | "EC" | "EV" => return Ok(None),
+            _ => return Err(self.error("invalid message type")),
+        };
+        let timestamp = self.parse_offset(column("O").as_ref())?;
+        let channel = match column("B") {
+            Some(v) => self.parse_channel(Some(&v))?,
+            None => channel_from_number(0)?,
+        };
+        let direct = match column("d") {
+            Some("Tx") => CanDirect::Transmit,
+            _ => CanDirect::Receive,
+        };
+
+        if type_ == "ER" {
+            let mut frame = new_error_frame::<F>(channel)?;
+            frame.set_timestamp(Some(timestamp))
+                .set_direct(direct);
+            return Ok(Some(frame));
+        }
+
+        let id = self.parse_id(column("I").as_ref())?;
+        let length = match (column("l"), column("L")) {
+            (Some(v), _) => v.parse::<usize>().ok(),
+            (None, Some(v)) => v.parse::<usize>().ok()
+                .filter(|&v| v < CAN_FD_DLC.len())
+                .map(|v| if can_type == CanType::CanFd { CAN_FD_DLC[v] } else { v.min(MAX_FRAME_SIZE) }),
+            (None, None) => None,
+        }
+            .ok_or(self.error("invalid data length"))?;
+
+        let mut frame = if type_ == "RR" {
+            F::new_remote(id, length.min(MAX_FRAME_SIZE))
+                .ok_or(self.error("invalid frame"))?
+        }
+        else {
+            let data = self.parse_data(tokens.get(self.columns.len() - 1..).unwrap_or_default(), length)?;
+            let mut frame = F::new(id, &data)
+                .ok_or(self.error("invalid frame"))?;
+            if can_type == CanType::CanFd {
+                frame.set_can_type(can_type)
+                    .set_bitrate_switch(brs)
+                    .set_esi(esi);
+            }
+            frame
+        };
+        frame.set_timestamp(Some(timestamp))
+            .set_direct(direct)
+            .set_channel(channel);
+
+        Ok(Some(frame))
+    }
+
+    /// Parse the time offset in milliseconds, return the timestamp.
+    fn parse_offset(&self, token: Option<&&str>) -> Result<u64, CanError> {
+        let offset = token.and_then(|v| v.parse::<f64>().ok())
+            .ok_or(self.error("invalid time offset"))?;
+        Ok(self.start.unwrap_or_default() + offset.round().max(0.) as u64)
+    }
+
+    fn parse_channel(&self, token: Option<&&str>) -> Result<F::Channel, CanError> {
+        let number = token.and_then(|v| v.parse::<u32>().ok())
+            .ok_or(self.error("invalid bus"))?;
+        channel_from_number(number.saturating_sub(1))
+    }
+
+    /// The identifier of extended frame has 8 digits.
+    fn parse_id(&self, token: Option<&&str>) -> Result<CanId, CanError> {
+        let token = token.ok_or(self.error("invalid identifier"))?;
+        let id = u32::from_str_radix(token, 16)
+            .map_err(|_| self.error("invalid identifier"))?;
+        Ok(CanId::from_bits(id, Some(token.len() > 4)))
+    }
+
+    fn parse_data(&self, tokens: &[&str], length: usize) -> Result<Vec<u8>, CanError> {
+        if tokens.len() < length {
+            return Err(self.error("data is too short"));
+        }
+        tokens[..length].iter()
+            .map(|v| u8::from_str_radix(v, 16)
+                .map_err(|_| self.error("invalid data")))
+            .collect()
+    }
+
+    #[inline]
+    fn error(&self, msg: &str) -> CanError {
+        CanError::other_error(format!("TRC line {}: {}", self.line_no, msg))
+    }
+}
+
+impl<R, F> Iterator for TrcReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<F, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line_no += 1,
+                Err(e) => return Some(Err(io_error(e))),
+            }
+
+            match self.parse_line() {
+                Ok(Some(frame)) => return Some(Ok(frame)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::{CanDirect, CanFrame, CanId, CanType, vcan::CanMessage};
+    use super::{TrcReader, TrcVersion, TrcWriter};
+
+    const TRC_V11: &str = r#";$FILEVERSION=1.1
+;$STARTTIME=43563.4473669907
+;
+;   Start time: 08.04.2019 10:44:12.508.0
+;---+--   ----+----  --+--  ----+---  +  -+ -- -- -- -- -- -- --
+     1)      1841.0  Rx         0001  8  00 00 00 00 00 00 00 00
+     2)      1842.3  Tx     1A2B3C4D  2  11 22
+     3)      1843.0  Rx         0123  4  RTR
+     4)      1844.0  Warng  FFFFFFFF  4  00 00 00 08  BUSHEAVY
+     5)      1845.0  Error  00000000  0
+"#;
+
+    const TRC_V21: &str = r#";$FILEVERSION=2.1
+;$STARTTIME=43563.4473669907
+;$COLUMNS=N,O,T,B,I,d,R,L,D
+;
+      1      1059.900 DT 1      0300 Rx -  7    00 00 00 00 04 00 00
+      2      1283.231 FB 2  18DAF110 Tx -  9    01 02 03 04 05 06 07 08 09 0A 0B 0C
+      3      1300.000 RR 1      07FF Rx -  8
+      4      1400.000 ST 1         - Rx -  4    00 00 00 04
+      5      1500.000 ER 2         - Rx -  5    04 00 08 00 00
+"#;
+
+    #[test]
+    fn test_reader() -> anyhow::Result<()> {
+        // 2019-04-08 10:44:12.508
+        let start = 1_554_720_252_508;
+        let mut reader = TrcReader::<_, CanMessage>::new(Cursor::new(TRC_V11));
+        let frames = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reader.version(), TrcVersion::V1_1);
+        assert_eq!(reader.start_timestamp(), Some(start));
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].timestamp(), start + 1841);
+        assert_eq!(frames[0].id(), CanId::Standard(0x0001));
+        assert_eq!(frames[0].channel(), "0");
+        assert_eq!(frames[1].id(), CanId::Extended(0x1A2B_3C4D));
+        assert_eq!(frames[1].direct(), CanDirect::Transmit);
+        assert_eq!(frames[1].data(), &[0x11, 0x22]);
+        assert!(frames[2].is_remote());
+        assert_eq!(frames[2].length(), 4);
+        assert!(frames[3].is_error_frame());
+
+        let mut reader = TrcReader::<_, CanMessage>::new(Cursor::new(TRC_V21));
+        let frames = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reader.version(), TrcVersion::V2_1);
+        assert_eq!(frames.len(), 4);
+        assert_eq!(frames[0].timestamp(), start + 1060);
+        assert_eq!(frames[0].data().len(), 7);
+        assert_eq!(frames[1].can_type(), CanType::CanFd);
+        assert!(frames[1].is_bitrate_switch());
+        assert!(!frames[1].is_esi());
+        assert_eq!(frames[1].length(), 12);
+        assert_eq!(frames[1].channel(), "1");
+        assert!(frames[2].is_remote());
+        assert_eq!(frames[2].length(), 8);
+        assert!(frames[3].is_error_frame());
+        assert_eq!(frames[3].channel(), "1");
+
+        let reader = TrcReader::<_, CanMessage>::new(Cursor::new(";$FILEVERSION=2.1\n      1      1.000 DT 1 0300 Rx -  7    00 00\n"));
+        assert!(reader.collect::<Result<Vec<_>, _>>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let mut frames = Vec::new();
+        let mut frame = CanMessage::new(0x123, &[0x01, 0x02, 0x03]).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_000)).set_channel("0".into());
+        frames.push(frame);
+        let mut frame = CanMessage::new(CanId::Extended(0x18DA_F110), &[0x55; 8]).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_010))
+            .set_channel("1".into())
+            .set_direct(CanDirect::Transmit);
+        frames.push(frame);
+        let mut frame = CanMessage::new_remote(0x7FF, 2).unwrap();
+        frame.set_timestamp(Some(1_700_000_000_025)).set_channel("0".into());
+        frames.push(frame);
+        let mut fd_frame = CanMessage::new(0x456, &[0xAA; 20]).unwrap();
+        fd_frame.set_bitrate_switch(true)
+            .set_esi(true)
+            .set_timestamp(Some(1_700_000_000_030))
+            .set_channel("1".into());
+
+        for version in [TrcVersion::V1_1, TrcVersion::V1_2, TrcVersion::V1_3, TrcVersion::V2_0, TrcVersion::V2_1] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = TrcWriter::new(&mut buffer);
+                writer.set_version(version);
+                frames.iter().try_for_each(|f| writer.write_frame(f))?;
+                if version >= TrcVersion::V2_0 {
+                    writer.write_frame(&fd_frame)?;
+                }
+                else {
+                    assert!(writer.write_frame(&fd_frame).is_err());
+                }
+                writer.finish()?;
+            }
+
+            let mut reader = TrcReader::<_, CanMessage>::new(Cursor::new(buffer));
+            let results = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(reader.version(), version);
+            assert_eq!(reader.start_timestamp(), Some(1_700_000_000_000));
+            assert_eq!(&results[..frames.len()], &frames);
+            results.iter()
+                .zip(frames.iter().chain([&fd_frame]))
+                .for_each(|(r, f)| {
+                    assert_eq!(r.timestamp(), f.timestamp());
+                    assert_eq!(r.direct(), f.direct());
+                    assert_eq!(r.length(), f.length());
+                    assert_eq!(r.can_type(), f.can_type());
+                    assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+                    assert_eq!(r.is_esi(), f.is_esi());
+                    if version != TrcVersion::V1_1 {
+                        assert_eq!(r.channel(), f.channel());
+                    }
+                });
+            if version >= TrcVersion::V2_0 {
+                assert_eq!(results[frames.len()], fd_frame);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+use std::{sync::mpsc::{self, RecvTimeoutError, Sender}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+use crate::{isotp::{IsoTp, TargetType}, CanDevice, CanError};
+use super::{
+    CommunicationControlType, Dtc, DtcCount, DtcList, DtcReportType, DtcSettingType, ResetType, ResponseCode,
+    RoutineControlType, Service, SessionTiming, SessionType, UdsError, NEGATIVE_RESPONSE, POSITIVE_OFFSET,
+    SUPPRESS_POSITIVE,
+};
+
+/// The default timeout of response in milliseconds(P2 client).
+pub const DEFAULT_P2: u32 = 150;
+/// The default timeout of response after `ResponsePending` in milliseconds(P2* client).
+pub const DEFAULT_P2_STAR: u32 = 5100;
+/// The margin which is added to the timing reported by server for network delays.
+pub const P2_MARGIN: u32 = 100;
+
+/// The algorithm computing key from security level and seed.
+pub type SecurityAlgorithm = Box<dyn Fn(u8, &[u8]) -> Result<Vec<u8>, UdsError> + Send>;
+
+/// The background sender of `TesterPresent`, stopped when dropped.
+struct KeepAlive {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for KeepAlive {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - UDS tester present thread panicked");
+            }
+        }
+    }
+}
+
+/// The UDS client over ISO-TP.
+pub struct UdsClient<D: CanDevice> {
+    isotp: IsoTp<D>,
+    p2: u32,
+    p2_star: u32,
+    algorithm: Option<SecurityAlgorithm>,
+    keep_alive: Option<KeepAlive>,
+}
+
+impl<D: CanDevice> UdsClient<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(isotp: IsoTp<D>) -> Self {
+        Self {
+            isotp,
+            p2: DEFAULT_P2,
+            p2_star: DEFAULT_P2_STAR,
+            algorithm: None,
+            keep_alive: None,
+        }
+    }
+
+    #[inline(always)]
+    pub fn isotp(&self) -> &IsoTp<D> {
+        &self.isotp
+    }
+
+    #[inline(always)]
+    pub fn isotp_mut(&mut self) -> &mut IsoTp<D> {
+        &mut self.isotp
+    }
+
+    /// Get the P2 and P2* timeouts of client.
+    #[inline]
+    pub fn timing(&self) -> SessionTiming {
+        SessionTiming { p2: self.p2, p2_star: self.p2_star }
+    }
+
+    /// Set the P2 and P2* timeouts of client, they are updated by `DiagnosticSessionControl` too.
+    pub fn set_timing(&mut self, timing: SessionTiming) -> &mut Self {
+        self.p2 = timing.p2;
+        self.p2_star = timing.p2_star;
+        self
+    }
+
+    pub fn set_security_algorithm<F>(&mut self, algorithm: F) -> &mut Self
+    where
+        F: Fn(u8, &[u8]) -> Result<Vec<u8>, UdsError> + Send + 'static,
+    {
+        self.algorithm = Some(Box::new(algorithm));
+        self
+    }
+
+    /// Send the request and wait for the positive response.
+    ///
+    /// The negative response `ResponsePending` extends the timeout to P2*.
+    /// `None` is returned when `suppress` is set and no response received in P2.
+    pub fn request(&mut self, data: &[u8], suppress: bool) -> Result<Option<Vec<u8>>, UdsError> {
+        self.isotp.transmit(data)?;
+        let service = data[0];
+
+        let mut pending = false;
+        let mut deadline = Instant::now() + Duration::from_millis(self.p2 as u64);
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now()).as_millis() as u32;
+            let response = match self.isotp.receive_with_target(timeout) {
+                // the functional requests of other testers, the deadline is kept
+                Ok((TargetType::Functional, _)) => continue,
+                Ok((_, v)) => v,
+                Err(CanError::TimeoutError(_)) if suppress && !pending => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            match response.as_slice() {
+                [NEGATIVE_RESPONSE, sid, code, ..] if *sid == service => {
+                    if *code == ResponseCode::RequestCorrectlyReceivedResponsePending as u8 {
+                        pending = true;
+                        deadline = Instant::now() + Duration::from_millis(self.p2_star as u64);
+                        continue;
+                    }
+                    return Err(UdsError::NegativeResponse { service, code: *code });
+                },
+                [sid, ..] if *sid == service.wrapping_add(POSITIVE_OFFSET) => return Ok(Some(response)),
+                _ => return Err(UdsError::InvalidResponse(response)),
+            }
+        }
+    }
+
+    pub fn session_control(&mut self, session: SessionType, suppress: bool) -> Result<Option<SessionTiming>, UdsError> {
+        let response = match self.request_sub(Service::DiagnosticSessionControl, session as u8, &[], suppress)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        match response.as_slice() {
+            [_, _, p2_hi, p2_lo, p2_star_hi, p2_star_lo, ..] => {
+                let timing = SessionTiming {
+                    p2: u16::from_be_bytes([*p2_hi, *p2_lo]) as u32,
+                    p2_star: u16::from_be_bytes([*p2_star_hi, *p2_star_lo]) as u32 * 10,
+                };
+                self.p2 = timing.p2 + P2_MARGIN;
+                self.p2_star = timing.p2_star + P2_MARGIN;
+                Ok(Some(timing))
+            },
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    #[inline]
+    pub fn ecu_reset(&mut self, reset: ResetType, suppress: bool) -> Result<(), UdsError> {
+        self.request_sub(Service::EcuReset, reset as u8, &[], suppress)
+            .map(|_| ())
+    }
+
+    /// Unlock the security level by requesting seed and sending key computed by the security algorithm.
+    ///
+    /// The level is the odd sub-function of `requestSeed`.
+    pub fn security_access(&mut self, level: u8) -> Result<(), UdsError> {
+        if level & 0x01 == 0 || level >= 0x7F {
+            return Err(UdsError::InvalidParameter(format!("security level {:#04X} is not odd", level)));
+        }
+
+        let response = self.request_positive(Service::SecurityAccess, &[level])?;
+        let seed = match response.get(2..) {
+            Some(v) if response[1] == level => v,
+            _ => return Err(UdsError::InvalidResponse(response)),
+        };
+        // the level has been unlocked
+        if seed.iter().all(|&v| v == 0) {
+            return Ok(());
+        }
+
+        let algorithm = self.algorithm.as_ref()
+            .ok_or(UdsError::InvalidParameter("security algorithm is not set".into()))?;
+        let key = algorithm(level, seed)?;
+        let mut data = vec![level + 1];
+        data.extend(key);
+        self.request_positive(Service::SecurityAccess, &data)
+            .map(|_| ())
+    }
+
+    #[inline]
+    pub fn tester_present(&mut self, suppress: bool) -> Result<(), UdsError> {
+        self.request_sub(Service::TesterPresent, 0x00, &[], suppress)
+            .map(|_| ())
+    }
+
+    /// Send `TesterPresent` with suppressing positive response every `interval` milliseconds
+    /// in background, the functional identifier is used when it is set.
+    pub fn start_tester_present(&mut self, interval: u32) -> Result<(), UdsError>
+    where
+        D: Send + 'static,
+        D::Frame: Clone,
+    {
+        self.stop_tester_present();
+
+        let address = self.isotp.address();
+        let id = address.functional_id().unwrap_or(address.tx_id());
+        let frame = self.isotp.single_frame(id, &[Service::TesterPresent as u8, SUPPRESS_POSITIVE])?;
+        let device = self.isotp.device().clone();
+        let timeout = Some(self.isotp.config().n_as());
+        let interval = Duration::from_millis(interval as u64);
+        let (stop, receiver) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = receiver.recv_timeout(interval) {
+                if let Err(e) = device.transmit(frame.clone(), timeout) {
+                    log::warn!("RUST-CAN - {} when tester present transmitting", e);
+                }
+            }
+        });
+        self.keep_alive = Some(KeepAlive { stop: Some(stop), handle: Some(handle) });
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn stop_tester_present(&mut self) {
+        self.keep_alive.take();
+    }
+
+    pub fn read_data_by_identifier(&mut self, did: u16) -> Result<Vec<u8>, UdsError> {
+        let did = did.to_be_bytes();
+        let response = self.request_positive(Service::ReadDataByIdentifier, &did)?;
+        match response.get(1..3) {
+            Some(v) if v == did => Ok(response[3..].to_vec()),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    pub fn write_data_by_identifier(&mut self, did: u16, data: &[u8]) -> Result<(), UdsError> {
+        let did = did.to_be_bytes();
+        let mut request = did.to_vec();
+        request.extend(data);
+        let response = self.request_positive(Service::WriteDataByIdentifier, &request)?;
+        match response.get(1..3) {
+            Some(v) if v == did => Ok(()),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    /// Control the routine, return the routine status record.
+    pub fn routine_control(&mut self, control: RoutineControlType, id: u16, option: &[u8]) -> Result<Vec<u8>, UdsError> {
+        let mut request = vec![control as u8];
+        request.extend(id.to_be_bytes());
+        request.extend(option);
+        let response = self.request_positive(Service::RoutineControl, &request)?;
+        match response.get(1..4) {
+            Some(v) if v == &request[..3] => Ok(response[4..].to_vec()),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    /// Request `ReadDTCInformation`, return the response data after the sub-function.
+    pub fn read_dtc_information(&mut self, report: DtcReportType, data: &[u8]) -> Result<Vec<u8>, UdsError> {
+        let mut request = vec![report as u8];
+        request.extend(data);
+        let response = self.request_positive(Service::ReadDtcInformation, &request)?;
+        match response.get(1) {
+            Some(&v) if v == report as u8 => Ok(response[2..].to_vec()),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    pub fn read_dtc_count(&mut self, status_mask: u8) -> Result<DtcCount, UdsError> {
+        let data = self.read_dtc_information(DtcReportType::NumberOfDtcByStatusMask, &[status_mask])?;
+        match data.as_slice() {
+            [availability_mask, format, count_hi, count_lo] => Ok(DtcCount {
+                availability_mask: *availability_mask,
+                format: *format,
+                count: u16::from_be_bytes([*count_hi, *count_lo]),
+            }),
+            _ => Err(UdsError::InvalidResponse(data)),
+        }
+    }
+
+    #[inline]
+    pub fn read_dtcs(&mut self, status_mask: u8) -> Result<DtcList, UdsError> {
+        let data = self.read_dtc_information(DtcReportType::DtcByStatusMask, &[status_mask])?;
+        parse_dtc_list(data)
+    }
+
+    #[inline]
+    pub fn read_supported_dtcs(&mut self) -> Result<DtcList, UdsError> {
+        let data = self.read_dtc_information(DtcReportType::SupportedDtc, &[])?;
+        parse_dtc_list(data)
+    }
+
+    /// Read the snapshot record of DTC, return the response data after the sub-function.
+    #[inline]
+    pub fn read_dtc_snapshot(&mut self, dtc: u32, record: u8) -> Result<Vec<u8>, UdsError> {
+        let mut request = dtc_bytes(dtc).to_vec();
+        request.push(record);
+        self.read_dtc_information(DtcReportType::DtcSnapshotRecordByDtcNumber, &request)
+    }
+
+    /// Read the extended data record of DTC, return the response data after the sub-function.
+    #[inline]
+    pub fn read_dtc_extended_data(&mut self, dtc: u32, record: u8) -> Result<Vec<u8>, UdsError> {
+        let mut request = dtc_bytes(dtc).to_vec();
+        request.push(record);
+        self.read_dtc_information(DtcReportType::DtcExtDataRecordByDtcNumber, &request)
+    }
+
+    /// Clear the DTCs of group, `0xFFFFFF` is all groups.
+    #[inline]
+    pub fn clear_diagnostic_information(&mut self, group: u32) -> Result<(), UdsError> {
+        self.request_positive(Service::ClearDiagnosticInformation, &dtc_bytes(group))
+            .map(|_| ())
+    }
+
+    #[inline]
+    pub fn communication_control(
+        &mut self,
+        control: CommunicationControlType,
+        communication_type: u8,
+        suppress: bool,
+    ) -> Result<(), UdsError> {
+        self.request_sub(Service::CommunicationControl, control as u8, &[communication_type], suppress)
+            .map(|_| ())
+    }
+
+    #[inline]
+    pub fn control_dtc_setting(&mut self, setting: DtcSettingType, suppress: bool) -> Result<(), UdsError> {
+        self.request_sub(Service::ControlDtcSetting, setting as u8, &[], suppress)
+            .map(|_| ())
+    }
+
+    /// Request to download, return the max length of `TransferData` request(maxNumberOfBlockLength).
+    ///
+    /// The high nibble of `address_format` is the length of memory size, the low nibble is the length of address.
+    pub fn request_download(&mut self, data_format: u8, address_format: u8, address: u32, size: u32) -> Result<usize, UdsError> {
+        let mut request = vec![data_format, address_format];
+        request.extend(memory_bytes(address_format, address, size)?);
+        let response = self.request_positive(Service::RequestDownload, &request)?;
+        let length = response.get(1)
+            .map(|v| (v >> 4) as usize)
+            .unwrap_or_default();
+        match response.get(2..) {
+            Some(v) if (1..=8).contains(&length) && v.len() == length => Ok(v.iter()
+                .fold(0u64, |max, v| (max << 8) | *v as u64)
+                .min(usize::MAX as u64) as usize),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    /// Transfer the block of data, return the transfer response parameters.
+    pub fn transfer_data(&mut self, counter: u8, data: &[u8]) -> Result<Vec<u8>, UdsError> {
+        let mut request = vec![counter];
+        request.extend(data);
+        let response = self.request_positive(Service::TransferData, &request)?;
+        match response.get(1) {
+            Some(&v) if v == counter => Ok(response[2..].to_vec()),
+            _ => Err(UdsError::InvalidResponse(response)),
+        }
+    }
+
+    /// Exit the transfer, return the transfer response parameters.
+    #[inline]
+    pub fn request_transfer_exit(&mut self, data: &[u8]) -> Result<Vec<u8>, UdsError> {
+        self.request_positive(Service::RequestTransferExit, data)
+            .map(|v| v[1..].to_vec())
+    }
+
+    /// Request the service with sub-function, the positive response is suppressed when `suppress` is set.
+    fn request_sub(&mut self, service: Service, sub_function: u8, data: &[u8], suppress: bool) -> Result<Option<Vec<u8>>, UdsError> {
+        let sub_function = if suppress { sub_function | SUPPRESS_POSITIVE } else { sub_function };
+        let mut request = vec![service as u8, sub_function];
+        request.extend(data);
+        let response = self.request(&request, suppress)?;
+        match response {
+            Some(v) if v.get(1).is_none_or(|&v| v != sub_function & !SUPPRESS_POSITIVE) => Err(UdsError::InvalidResponse(v)),
+            v => Ok(v),
+        }
+    }
+
+    fn request_positive(&mut self, service: Service, data: &[u8]) -> Result<Vec<u8>, UdsError> {
+        let mut request = vec![service as u8];
+        request.extend(data);
+        self.request(&request, false)?
+            .ok_or(UdsError::InvalidResponse(vec![]))
+    }
+}
+
+/// Encode the address and size by `addressAndLengthFormatIdentifier`.
+pub(crate) fn memory_bytes(address_format: u8, address: u32, size: u32) -> Result<Vec<u8>, UdsError> {
+    let address_len = (address_format & 0x0F) as usize;
+    let size_len = (address_format >> 4) as usize;
+    let fits = |value: u32, len: usize| (1..=4).contains(&len) && (len == 4 || value >> (len * 8) == 0);
+    if !fits(address, address_len) || !fits(size, size_len) {
+        return Err(UdsError::InvalidParameter(
+            format!("address {:#X} or size {:#X} is out of format {:#04X}", address, size, address_format)
+        ));
+    }
+
+    let mut result = address.to_be_bytes()[4 - address_len..].to_vec();
+    result.extend(&size.to_be_bytes()[4 - size_len..]);
+    Ok(result)
+}
+
+#[inline]
+fn dtc_bytes(dtc: u32) -> [u8; 3] {
+    let bytes = dtc.to_be_bytes();
+    [bytes[1], bytes[2], bytes[3]]
+}
+
+fn parse_dtc_list(data: Vec<u8>) -> Result<DtcList, UdsError> {
+    match data.split_first() {
+        Some((&availability_mask, records)) if records.len() & 0x03 == 0 => Ok(DtcList {
+            availability_mask,
+            dtcs: records.chunks(4)
+                .map(|v| Dtc { code: u32::from_be_bytes([0, v[0], v[1], v[2]]), status: v[3] })
+                .collect(),
+        }),
+        _ => Err(UdsError::InvalidResponse(data)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use crate::{isotp::IsoTp, vcan::VirtualCan, CanDevice, CanError, CanFrame, CanId};
+    use crate::test_utils::{new_device, new_isotp};
+    use crate::uds::{Dtc, DtcSettingType, ResetType, ResponseCode, RoutineControlType, SessionTiming, SessionType, UdsError};
+    use super::UdsClient;
+
+    /// Reply the requests by script, the response `None` means no response.
+    fn serve(mut server: IsoTp<VirtualCan>, script: Vec<(Vec<u8>, Vec<Vec<u8>>)>) -> thread::JoinHandle<Result<(), CanError>> {
+        thread::spawn(move || {
+            for (request, responses) in script {
+                assert_eq!(server.receive(1000)?, request);
+                for response in responses {
+                    server.transmit(&response)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_services() -> anyhow::Result<()> {
+        let channel = "uds-client-services";
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        client.set_security_algorithm(|level, seed| Ok(seed.iter().map(|v| v ^ level).collect()));
+        let server = serve(new_isotp(channel, 0x7E8, 0x7E0)?, vec![
+            (vec![0x10, 0x03], vec![vec![0x50, 0x03, 0x00, 0x32, 0x01, 0xF4]]),
+            (vec![0x27, 0x01], vec![vec![0x67, 0x01, 0x12, 0x34]]),
+            (vec![0x27, 0x02, 0x13, 0x35], vec![vec![0x7F, 0x27, 0x78], vec![0x67, 0x02]]),
+            (vec![0x22, 0xF1, 0x90], vec![[vec![0x62, 0xF1, 0x90], b"WDD2030461A123456".to_vec()].concat()]),
+            (vec![0x2E, 0xF1, 0x90, 0x01], vec![vec![0x7F, 0x2E, 0x31]]),
+            (vec![0x31, 0x01, 0xFF, 0x00, 0x01], vec![vec![0x71, 0x01, 0xFF, 0x00, 0x00]]),
+            (vec![0x19, 0x01, 0x09], vec![vec![0x59, 0x01, 0xFF, 0x01, 0x00, 0x02]]),
+            (vec![0x19, 0x02, 0x09], vec![vec![0x59, 0x02, 0xFF, 0x12, 0x34, 0x56, 0x09, 0xC0, 0x01, 0x00, 0x08]]),
+            (vec![0x14, 0xFF, 0xFF, 0xFF], vec![vec![0x54]]),
+            (vec![0x3E, 0x80], vec![]),
+            (vec![0x85, 0x82], vec![vec![0x7F, 0x85, 0x22]]),
+            (vec![0x11, 0x01], vec![vec![0x51, 0x02]]),
+        ]);
+
+        let timing = client.session_control(SessionType::Extended, false)?;
+        assert_eq!(timing, Some(SessionTiming { p2: 50, p2_star: 5000 }));
+        assert_eq!(client.timing(), SessionTiming { p2: 150, p2_star: 5100 });
+        client.security_access(0x01)?;
+        assert_eq!(client.read_data_by_identifier(0xF190)?, b"WDD2030461A123456");
+        let err = client.write_data_by_identifier(0xF190, &[0x01]).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::RequestOutOfRange));
+        assert_eq!(client.routine_control(RoutineControlType::Start, 0xFF00, &[0x01])?, [0x00]);
+        assert_eq!(client.read_dtc_count(0x09)?.count, 2);
+        let dtcs = client.read_dtcs(0x09)?;
+        assert_eq!(dtcs.availability_mask, 0xFF);
+        assert_eq!(dtcs.dtcs, [Dtc { code: 0x123456, status: 0x09 }, Dtc { code: 0xC00100, status: 0x08 }]);
+        client.clear_diagnostic_information(0xFFFFFF)?;
+        // no response is expected
+        client.tester_present(true)?;
+        // the negative response is reported even if positive response is suppressed
+        let err = client.control_dtc_setting(DtcSettingType::Off, true).unwrap_err();
+        assert!(matches!(err, UdsError::NegativeResponse { service: 0x85, code: 0x22 }));
+        assert!(matches!(client.ecu_reset(ResetType::Hard, false), Err(UdsError::InvalidResponse(_))));
+
+        server.join().unwrap()?;
+        assert!(client.security_access(0x02).is_err());
+        assert!(matches!(client.read_data_by_identifier(0xF190), Err(UdsError::Transport(CanError::TimeoutError(_)))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_functional_frames() -> anyhow::Result<()> {
+        let channel = "uds-client-functional";
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        let mut tester = new_isotp(channel, 0x7E1, 0x7E9)?;
+        let sender = thread::spawn(move || {
+            for _ in 0..100 {
+                tester.transmit_functional(&[0x3E, 0x80])?;
+                thread::sleep(std::time::Duration::from_millis(10));
+            }
+            Ok::<_, CanError>(())
+        });
+
+        // the functional requests of other testers don't extend P2
+        let start = std::time::Instant::now();
+        let result = client.request(&[0x22, 0xF1, 0x90], false);
+        assert!(matches!(result, Err(UdsError::Transport(CanError::TimeoutError(_)))));
+        assert!(start.elapsed() < std::time::Duration::from_millis(500));
+        sender.join().unwrap()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tester_present() -> anyhow::Result<()> {
+        let channel = "uds-client-tester-present";
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        let monitor = new_device(channel)?;
+
+        client.start_tester_present(20)?;
+        thread::sleep(std::time::Duration::from_millis(110));
+        client.stop_tester_present();
+        let frames = monitor.receive(channel.into(), Some(10))?;
+        // the count depends on the scheduling of threads, only the periodic transmitting is checked
+        assert!(frames.len() >= 2);
+        assert!(frames.iter().all(|f| f.id() == CanId::Standard(0x7DF)
+            && f.data() == [0x02, 0x3E, 0x80, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA]));
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(monitor.receive(channel.into(), None)?.is_empty());
+
+        Ok(())
+    }
+}
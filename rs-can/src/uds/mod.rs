@@ -0,0 +1,343 @@
+//! The UDS(ISO 14229) diagnostic services over ISO-TP.
+//!
+//! The [`UdsClient`] is the tester, and the [`UdsServer`] simulates an [`Ecu`] for testing the diagnostic tools.
+//!
+//! ```no_run
+//! use rs_can::{isotp::{Address, IsoTp}, uds::{SessionType, UdsClient}, vcan::VirtualCan, CanId};
+//!
+//! let mut device = VirtualCan::new();
+//! device.init_channel("vcan0", None).unwrap();
+//! let address = Address::new(CanId::Standard(0x7E0), CanId::Standard(0x7E8));
+//!
+//! let mut client = UdsClient::new(IsoTp::new(device, "vcan0".into(), address));
+//! client.set_security_algorithm(|_level, seed| Ok(seed.iter().map(|v| !v).collect()));
+//! client.session_control(SessionType::Extended, false).unwrap();
+//! client.security_access(0x01).unwrap();
+//! let vin = client.read_data_by_identifier(0xF190).unwrap();
+//! ```
+mod client;
+mod server;
+
+pub use client::*;
+pub use server::*;
+
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+use crate::CanError;
+
+/// The service identifier of negative response.
+pub const NEGATIVE_RESPONSE: u8 = 0x7F;
+/// The offset of positive response service identifier.
+pub const POSITIVE_OFFSET: u8 = 0x40;
+/// The bit of sub-function that suppresses positive response.
+pub const SUPPRESS_POSITIVE: u8 = 0x80;
+
+#[derive(Debug, Clone, Error)]
+pub enum UdsError {
+    /// The negative response of server.
+    #[error("RUST-CAN - UDS negative response of service {service:#04X}: {}", display_code(*.code))]
+    NegativeResponse { service: u8, code: u8 },
+    /// The response is not matched with request.
+    #[error("RUST-CAN - UDS invalid response: {0:02X?}")]
+    InvalidResponse(Vec<u8>),
+    /// The parameter of request is invalid.
+    #[error("RUST-CAN - UDS invalid parameter: {0}")]
+    InvalidParameter(String),
+    /// The error of transport.
+    #[error(transparent)]
+    Transport(#[from] CanError),
+}
+
+impl UdsError {
+    /// Get the response code of negative response.
+    #[inline]
+    pub fn response_code(&self) -> Option<ResponseCode> {
+        match self {
+            Self::NegativeResponse { code, .. } => ResponseCode::try_from(*code).ok(),
+            _ => None,
+        }
+    }
+}
+
+#[inline]
+fn display_code(code: u8) -> String {
+    match ResponseCode::try_from(code) {
+        Ok(v) => format!("{} ({:#04X})", v, code),
+        Err(_) => format!("{:#04X}", code),
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    DiagnosticSessionControl = 0x10,
+    EcuReset = 0x11,
+    ClearDiagnosticInformation = 0x14,
+    ReadDtcInformation = 0x19,
+    ReadDataByIdentifier = 0x22,
+    ReadMemoryByAddress = 0x23,
+    SecurityAccess = 0x27,
+    CommunicationControl = 0x28,
+    WriteDataByIdentifier = 0x2E,
+    RoutineControl = 0x31,
+    RequestDownload = 0x34,
+    RequestUpload = 0x35,
+    TransferData = 0x36,
+    RequestTransferExit = 0x37,
+    WriteMemoryByAddress = 0x3D,
+    TesterPresent = 0x3E,
+    ControlDtcSetting = 0x85,
+}
+
+impl TryFrom<u8> for Service {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x10 => Ok(Self::DiagnosticSessionControl),
+            0x11 => Ok(Self::EcuReset),
+            0x14 => Ok(Self::ClearDiagnosticInformation),
+            0x19 => Ok(Self::ReadDtcInformation),
+            0x22 => Ok(Self::ReadDataByIdentifier),
+            0x23 => Ok(Self::ReadMemoryByAddress),
+            0x27 => Ok(Self::SecurityAccess),
+            0x28 => Ok(Self::CommunicationControl),
+            0x2E => Ok(Self::WriteDataByIdentifier),
+            0x31 => Ok(Self::RoutineControl),
+            0x34 => Ok(Self::RequestDownload),
+            0x35 => Ok(Self::RequestUpload),
+            0x36 => Ok(Self::TransferData),
+            0x37 => Ok(Self::RequestTransferExit),
+            0x3D => Ok(Self::WriteMemoryByAddress),
+            0x3E => Ok(Self::TesterPresent),
+            0x85 => Ok(Self::ControlDtcSetting),
+            _ => Err(UdsError::InvalidParameter(format!("service {:#04X} is not supported", value))),
+        }
+    }
+}
+
+/// The negative response code.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseCode {
+    GeneralReject = 0x10,
+    ServiceNotSupported = 0x11,
+    SubFunctionNotSupported = 0x12,
+    IncorrectMessageLengthOrInvalidFormat = 0x13,
+    ResponseTooLong = 0x14,
+    BusyRepeatRequest = 0x21,
+    ConditionsNotCorrect = 0x22,
+    RequestSequenceError = 0x24,
+    NoResponseFromSubnetComponent = 0x25,
+    FailurePreventsExecutionOfRequestedAction = 0x26,
+    RequestOutOfRange = 0x31,
+    SecurityAccessDenied = 0x33,
+    InvalidKey = 0x35,
+    ExceededNumberOfAttempts = 0x36,
+    RequiredTimeDelayNotExpired = 0x37,
+    UploadDownloadNotAccepted = 0x70,
+    TransferDataSuspended = 0x71,
+    GeneralProgrammingFailure = 0x72,
+    WrongBlockSequenceCounter = 0x73,
+    RequestCorrectlyReceivedResponsePending = 0x78,
+    SubFunctionNotSupportedInActiveSession = 0x7E,
+    ServiceNotSupportedInActiveSession = 0x7F,
+}
+
+impl TryFrom<u8> for ResponseCode {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x10 => Ok(Self::GeneralReject),
+            0x11 => Ok(Self::ServiceNotSupported),
+            0x12 => Ok(Self::SubFunctionNotSupported),
+            0x13 => Ok(Self::IncorrectMessageLengthOrInvalidFormat),
+            0x14 => Ok(Self::ResponseTooLong),
+            0x21 => Ok(Self::BusyRepeatRequest),
+            0x22 => Ok(Self::ConditionsNotCorrect),
+            0x24 => Ok(Self::RequestSequenceError),
+            0x25 => Ok(Self::NoResponseFromSubnetComponent),
+            0x26 => Ok(Self::FailurePreventsExecutionOfRequestedAction),
+            0x31 => Ok(Self::RequestOutOfRange),
+            0x33 => Ok(Self::SecurityAccessDenied),
+            0x35 => Ok(Self::InvalidKey),
+            0x36 => Ok(Self::ExceededNumberOfAttempts),
+            0x37 => Ok(Self::RequiredTimeDelayNotExpired),
+            0x70 => Ok(Self::UploadDownloadNotAccepted),
+            0x71 => Ok(Self::TransferDataSuspended),
+            0x72 => Ok(Self::GeneralProgrammingFailure),
+            0x73 => Ok(Self::WrongBlockSequenceCounter),
+            0x78 => Ok(Self::RequestCorrectlyReceivedResponsePending),
+            0x7E => Ok(Self::SubFunctionNotSupportedInActiveSession),
+            0x7F => Ok(Self::ServiceNotSupportedInActiveSession),
+            _ => Err(UdsError::InvalidParameter(format!("response code {:#04X} is not supported", value))),
+        }
+    }
+}
+
+impl Display for ResponseCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionType {
+    Default = 0x01,
+    Programming = 0x02,
+    Extended = 0x03,
+    SafetySystem = 0x04,
+}
+
+impl TryFrom<u8> for SessionType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Default),
+            0x02 => Ok(Self::Programming),
+            0x03 => Ok(Self::Extended),
+            0x04 => Ok(Self::SafetySystem),
+            _ => Err(UdsError::InvalidParameter(format!("session type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetType {
+    Hard = 0x01,
+    KeyOffOn = 0x02,
+    Soft = 0x03,
+    EnableRapidPowerShutDown = 0x04,
+    DisableRapidPowerShutDown = 0x05,
+}
+
+impl TryFrom<u8> for ResetType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Hard),
+            0x02 => Ok(Self::KeyOffOn),
+            0x03 => Ok(Self::Soft),
+            0x04 => Ok(Self::EnableRapidPowerShutDown),
+            0x05 => Ok(Self::DisableRapidPowerShutDown),
+            _ => Err(UdsError::InvalidParameter(format!("reset type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutineControlType {
+    Start = 0x01,
+    Stop = 0x02,
+    RequestResults = 0x03,
+}
+
+impl TryFrom<u8> for RoutineControlType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Start),
+            0x02 => Ok(Self::Stop),
+            0x03 => Ok(Self::RequestResults),
+            _ => Err(UdsError::InvalidParameter(format!("routine control type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommunicationControlType {
+    EnableRxAndTx = 0x00,
+    EnableRxAndDisableTx = 0x01,
+    DisableRxAndEnableTx = 0x02,
+    DisableRxAndTx = 0x03,
+}
+
+impl TryFrom<u8> for CommunicationControlType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::EnableRxAndTx),
+            0x01 => Ok(Self::EnableRxAndDisableTx),
+            0x02 => Ok(Self::DisableRxAndEnableTx),
+            0x03 => Ok(Self::DisableRxAndTx),
+            _ => Err(UdsError::InvalidParameter(format!("communication control type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcSettingType {
+    On = 0x01,
+    Off = 0x02,
+}
+
+impl TryFrom<u8> for DtcSettingType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::On),
+            0x02 => Ok(Self::Off),
+            _ => Err(UdsError::InvalidParameter(format!("DTC setting type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+/// The sub-functions of `ReadDTCInformation`.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DtcReportType {
+    NumberOfDtcByStatusMask = 0x01,
+    DtcByStatusMask = 0x02,
+    DtcSnapshotIdentification = 0x03,
+    DtcSnapshotRecordByDtcNumber = 0x04,
+    DtcExtDataRecordByDtcNumber = 0x06,
+    SupportedDtc = 0x0A,
+}
+
+impl TryFrom<u8> for DtcReportType {
+    type Error = UdsError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::NumberOfDtcByStatusMask),
+            0x02 => Ok(Self::DtcByStatusMask),
+            0x03 => Ok(Self::DtcSnapshotIdentification),
+            0x04 => Ok(Self::DtcSnapshotRecordByDtcNumber),
+            0x06 => Ok(Self::DtcExtDataRecordByDtcNumber),
+            0x0A => Ok(Self::SupportedDtc),
+            _ => Err(UdsError::InvalidParameter(format!("DTC report type {:#04X} is not supported", value))),
+        }
+    }
+}
+
+/// The timing parameters of session in milliseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SessionTiming {
+    pub p2: u32,
+    pub p2_star: u32,
+}
+
+/// The diagnostic trouble code with 3 bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Dtc {
+    pub code: u32,
+    pub status: u8,
+}
+
+/// The response of `reportNumberOfDTCByStatusMask`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DtcCount {
+    pub availability_mask: u8,
+    pub format: u8,
+    pub count: u16,
+}
+
+/// The response of `reportDTCByStatusMask` and `reportSupportedDTC`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DtcList {
+    pub availability_mask: u8,
+    pub dtcs: Vec<Dtc>,
+}
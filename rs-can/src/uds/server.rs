@@ -0,0 +1,978 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, thread::{self, JoinHandle}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use crate::{isotp::{IsoTp, TargetType}, CanDevice, CanError};
+use super::{
+    CommunicationControlType, Dtc, DtcReportType, DtcSettingType, ResetType, ResponseCode, RoutineControlType, Service,
+    SessionType, NEGATIVE_RESPONSE, POSITIVE_OFFSET, SUPPRESS_POSITIVE,
+};
+
+/// The default P2 server in milliseconds.
+pub const DEFAULT_P2_SERVER: u16 = 50;
+/// The default P2* server in milliseconds.
+pub const DEFAULT_P2_STAR_SERVER: u32 = 5000;
+/// The default timeout of non-default session in milliseconds(S3 server).
+pub const DEFAULT_S3: u32 = 5000;
+/// The default max count of invalid keys before the security access is denied.
+pub const DEFAULT_MAX_ATTEMPTS: u8 = 3;
+/// The length of seed generated.
+pub const SEED_SIZE: usize = 4;
+/// The default max length of `TransferData` request(maxNumberOfBlockLength).
+pub const DEFAULT_MAX_BLOCK_LENGTH: u16 = 0x0402;
+/// The default max size of each download(memorySize).
+pub const DEFAULT_MAX_MEMORY_SIZE: usize = 16 * 1024 * 1024;
+
+/// The algorithm computing key from seed of a security level.
+pub type KeyAlgorithm = Box<dyn Fn(&[u8]) -> Vec<u8> + Send>;
+/// The routine handler which returns the routine status record.
+pub type RoutineHandler = Box<dyn FnMut(RoutineControlType, &[u8]) -> Result<Vec<u8>, ResponseCode> + Send>;
+/// The handler of custom service which receives the whole request and returns the whole positive response.
+pub type ServiceHandler = Box<dyn FnMut(&EcuStatus, &[u8]) -> Result<Vec<u8>, ResponseCode> + Send>;
+
+/// The status of session and security.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcuStatus {
+    pub session: SessionType,
+    /// the unlocked level of security access(the `requestSeed` sub-function)
+    pub security_level: Option<u8>,
+    pub communication: CommunicationControlType,
+    pub dtc_setting: DtcSettingType,
+}
+
+impl Default for EcuStatus {
+    fn default() -> Self {
+        Self {
+            session: SessionType::Default,
+            security_level: None,
+            communication: CommunicationControlType::EnableRxAndTx,
+            dtc_setting: DtcSettingType::On,
+        }
+    }
+}
+
+struct DataIdentifier {
+    data: Vec<u8>,
+    writable: bool,
+    security_level: Option<u8>,
+}
+
+#[derive(Default)]
+struct DtcRecord {
+    status: u8,
+    snapshots: BTreeMap<u8, Vec<u8>>,
+    extended_data: BTreeMap<u8, Vec<u8>>,
+}
+
+struct Routine {
+    security_level: Option<u8>,
+    handler: RoutineHandler,
+}
+
+/// The download requested by `RequestDownload`.
+struct Download {
+    address: u32,
+    size: usize,
+    /// the block sequence counter of last accepted `TransferData`
+    counter: u8,
+    data: Vec<u8>,
+}
+
+/// The simulated ECU which processes UDS requests.
+pub struct Ecu {
+    status: EcuStatus,
+    p2: u16,
+    p2_star: u32,
+    s3: u32,
+    last_request: Instant,
+    keys: HashMap<u8, KeyAlgorithm>,
+    seed: Option<(u8, Vec<u8>)>,
+    attempts: u8,
+    max_attempts: u8,
+    random: u64,
+    dids: HashMap<u16, DataIdentifier>,
+    availability_mask: u8,
+    dtcs: BTreeMap<u32, DtcRecord>,
+    routines: HashMap<u16, Routine>,
+    services: HashMap<u8, ServiceHandler>,
+    /// the response code and remaining count of injections by service
+    injections: HashMap<u8, (u8, usize)>,
+    delays: HashMap<u8, u32>,
+    max_block_length: u16,
+    max_memory_size: usize,
+    download: Option<Download>,
+    memory: BTreeMap<u32, Vec<u8>>,
+}
+
+impl Default for Ecu {
+    fn default() -> Self {
+        let random = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_nanos() as u64)
+            .unwrap_or_default() | 0x01;
+        Self {
+            status: Default::default(),
+            p2: DEFAULT_P2_SERVER,
+            p2_star: DEFAULT_P2_STAR_SERVER,
+            s3: DEFAULT_S3,
+            last_request: Instant::now(),
+            keys: Default::default(),
+            seed: None,
+            attempts: 0,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            random,
+            dids: Default::default(),
+            availability_mask: 0xFF,
+            dtcs: Default::default(),
+            routines: Default::default(),
+            services: Default::default(),
+            injections: Default::default(),
+            delays: Default::default(),
+            max_block_length: DEFAULT_MAX_BLOCK_LENGTH,
+            max_memory_size: DEFAULT_MAX_MEMORY_SIZE,
+            download: None,
+            memory: Default::default(),
+        }
+    }
+}
+
+impl Ecu {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    #[inline(always)]
+    pub fn status(&self) -> &EcuStatus {
+        &self.status
+    }
+
+    /// Set the P2 and P2* server in milliseconds, which are reported by `DiagnosticSessionControl`.
+    pub fn set_timing(&mut self, p2: u16, p2_star: u32) -> &mut Self {
+        self.p2 = p2;
+        self.p2_star = p2_star;
+        self
+    }
+
+    /// Set the timeout of non-default session in milliseconds.
+    pub fn set_s3(&mut self, s3: u32) -> &mut Self {
+        self.s3 = s3;
+        self
+    }
+
+    /// Set the max count of invalid keys, the security access is denied until ECU reset when it is exceeded.
+    pub fn set_max_attempts(&mut self, max_attempts: u8) -> &mut Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Add the security level by the odd sub-function of `requestSeed`.
+    pub fn add_security_level<F>(&mut self, level: u8, algorithm: F) -> &mut Self
+    where
+        F: Fn(&[u8]) -> Vec<u8> + Send + 'static,
+    {
+        self.keys.insert(level, Box::new(algorithm));
+        self
+    }
+
+    /// Add the read-only data identifier.
+    pub fn add_did(&mut self, did: u16, data: &[u8]) -> &mut Self {
+        self.dids.insert(did, DataIdentifier { data: data.to_vec(), writable: false, security_level: None });
+        self
+    }
+
+    /// Add the data identifier which is writable in non-default session when the security level is unlocked.
+    pub fn add_writable_did(&mut self, did: u16, data: &[u8], security_level: Option<u8>) -> &mut Self {
+        self.dids.insert(did, DataIdentifier { data: data.to_vec(), writable: true, security_level });
+        self
+    }
+
+    #[inline]
+    pub fn did(&self, did: u16) -> Option<&[u8]> {
+        self.dids.get(&did)
+            .map(|v| v.data.as_slice())
+    }
+
+    pub fn set_availability_mask(&mut self, mask: u8) -> &mut Self {
+        self.availability_mask = mask;
+        self
+    }
+
+    /// Add the DTC or update its status, the status is not changed when DTC setting is off.
+    pub fn set_dtc(&mut self, code: u32, status: u8) -> &mut Self {
+        let record = self.dtcs.entry(code & 0x00FF_FFFF).or_default();
+        if self.status.dtc_setting == DtcSettingType::On {
+            record.status = status;
+        }
+        self
+    }
+
+    pub fn add_dtc_snapshot(&mut self, code: u32, record: u8, data: &[u8]) -> &mut Self {
+        self.dtcs.entry(code & 0x00FF_FFFF)
+            .or_default()
+            .snapshots
+            .insert(record, data.to_vec());
+        self
+    }
+
+    pub fn add_dtc_extended_data(&mut self, code: u32, record: u8, data: &[u8]) -> &mut Self {
+        self.dtcs.entry(code & 0x00FF_FFFF)
+            .or_default()
+            .extended_data
+            .insert(record, data.to_vec());
+        self
+    }
+
+    /// Get the DTCs and their status.
+    pub fn dtcs(&self) -> Vec<Dtc> {
+        self.dtcs.iter()
+            .map(|(&code, r)| Dtc { code, status: r.status })
+            .collect()
+    }
+
+    pub fn add_routine<F>(&mut self, id: u16, security_level: Option<u8>, handler: F) -> &mut Self
+    where
+        F: FnMut(RoutineControlType, &[u8]) -> Result<Vec<u8>, ResponseCode> + Send + 'static,
+    {
+        self.routines.insert(id, Routine { security_level, handler: Box::new(handler) });
+        self
+    }
+
+    /// Add the handler of service, the builtin service is overridden.
+    pub fn add_service<F>(&mut self, service: u8, handler: F) -> &mut Self
+    where
+        F: FnMut(&EcuStatus, &[u8]) -> Result<Vec<u8>, ResponseCode> + Send + 'static,
+    {
+        self.services.insert(service, Box::new(handler));
+        self
+    }
+
+    /// Respond the next `count`(at least one) requests of service with the negative response code.
+    pub fn inject_nrc(&mut self, service: u8, code: u8, count: usize) -> &mut Self {
+        self.injections.insert(service, (code, count));
+        self
+    }
+
+    /// Delay the response of service in milliseconds,
+    /// `ResponsePending` is sent when the delay is not less than P2.
+    pub fn set_response_delay(&mut self, service: u8, delay: u32) -> &mut Self {
+        self.delays.insert(service, delay);
+        self
+    }
+
+    #[inline]
+    pub fn response_delay(&self, service: u8) -> u32 {
+        self.delays.get(&service)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the max length of `TransferData` request including the service and the block sequence counter.
+    pub fn set_max_block_length(&mut self, length: u16) -> &mut Self {
+        self.max_block_length = length.max(3);
+        self
+    }
+
+    /// Set the max size of each download, the larger is rejected by `RequestOutOfRange`.
+    pub fn set_max_memory_size(&mut self, size: usize) -> &mut Self {
+        self.max_memory_size = size;
+        self
+    }
+
+    /// Get the memory downloaded by address, the memory is replaced when it is downloaded again.
+    #[inline(always)]
+    pub fn memory(&self) -> &BTreeMap<u32, Vec<u8>> {
+        &self.memory
+    }
+
+    /// Return to default session when S3 is elapsed.
+    pub fn check_s3(&mut self) {
+        if self.status.session != SessionType::Default
+            && self.last_request.elapsed() >= Duration::from_millis(self.s3 as u64) {
+            log::info!("RUST-CAN - UDS session timeout, return to default session");
+            self.change_session(SessionType::Default);
+        }
+    }
+
+    /// Process the request, `None` is returned when the response is suppressed.
+    pub fn process(&mut self, request: &[u8], target: TargetType) -> Option<Vec<u8>> {
+        let service = *request.first()?;
+        self.check_s3();
+        self.last_request = Instant::now();
+
+        let result = match self.take_injection(service) {
+            Some(code) => Err(code),
+            None => self.dispatch(request)
+                .map_err(|code| code as u8),
+        };
+        match result {
+            Ok(response) => response,
+            // the negative responses are suppressed for functional request
+            Err(code) if target == TargetType::Functional && matches!(code, 0x11 | 0x12 | 0x31 | 0x7E | 0x7F) => None,
+            Err(code) => Some(vec![NEGATIVE_RESPONSE, service, code]),
+        }
+    }
+
+    fn take_injection(&mut self, service: u8) -> Option<u8> {
+        let (code, count) = self.injections.get_mut(&service)?;
+        let code = *code;
+        if *count > 1 {
+            *count -= 1;
+        }
+        else {
+            self.injections.remove(&service);
+        }
+        Some(code)
+    }
+
+    fn dispatch(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let service = request[0];
+        if let Some(handler) = self.services.get_mut(&service) {
+            return handler(&self.status, request).map(Some);
+        }
+
+        let service = Service::try_from(service)
+            .map_err(|_| ResponseCode::ServiceNotSupported)?;
+        match service {
+            Service::DiagnosticSessionControl => self.session_control(request),
+            Service::EcuReset => self.ecu_reset(request),
+            Service::ClearDiagnosticInformation => self.clear_diagnostic_information(request).map(Some),
+            Service::ReadDtcInformation => self.read_dtc_information(request).map(Some),
+            Service::ReadDataByIdentifier => self.read_data_by_identifier(request).map(Some),
+            Service::SecurityAccess => self.security_access(request),
+            Service::CommunicationControl => self.communication_control(request),
+            Service::WriteDataByIdentifier => self.write_data_by_identifier(request).map(Some),
+            Service::RoutineControl => self.routine_control(request),
+            Service::RequestDownload => self.request_download(request),
+            Service::TransferData => self.transfer_data(request),
+            Service::RequestTransferExit => self.request_transfer_exit(request),
+            Service::TesterPresent => {
+                let (sub_function, suppress) = sub_function(request, 2)?;
+                if sub_function != 0x00 {
+                    return Err(ResponseCode::SubFunctionNotSupported);
+                }
+                Ok(positive(request, suppress, &[]))
+            },
+            Service::ControlDtcSetting => {
+                let (sub_function, suppress) = sub_function(request, 2)?;
+                self.check_non_default()?;
+                self.status.dtc_setting = DtcSettingType::try_from(sub_function)
+                    .map_err(|_| ResponseCode::SubFunctionNotSupported)?;
+                Ok(positive(request, suppress, &[]))
+            },
+            _ => Err(ResponseCode::ServiceNotSupported),
+        }
+    }
+
+    fn session_control(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let (sub_function, suppress) = sub_function(request, 2)?;
+        let session = SessionType::try_from(sub_function)
+            .map_err(|_| ResponseCode::SubFunctionNotSupported)?;
+        self.change_session(session);
+
+        let mut data = self.p2.to_be_bytes().to_vec();
+        data.extend(((self.p2_star / 10) as u16).to_be_bytes());
+        Ok(positive(request, suppress, &data))
+    }
+
+    fn ecu_reset(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let (sub_function, suppress) = sub_function(request, 2)?;
+        let reset = ResetType::try_from(sub_function)
+            .map_err(|_| ResponseCode::SubFunctionNotSupported)?;
+        self.change_session(SessionType::Default);
+        self.attempts = 0;
+
+        let data = match reset {
+            ResetType::EnableRapidPowerShutDown => vec![0xFF],
+            _ => vec![],
+        };
+        Ok(positive(request, suppress, &data))
+    }
+
+    fn clear_diagnostic_information(&mut self, request: &[u8]) -> Result<Vec<u8>, ResponseCode> {
+        if request.len() != 4 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let group = u32::from_be_bytes([0, request[1], request[2], request[3]]);
+        let records = self.dtcs.iter_mut()
+            .filter(|(&code, _)| group == 0x00FF_FFFF || code == group)
+            .map(|(_, r)| r)
+            .collect::<Vec<_>>();
+        if records.is_empty() && group != 0x00FF_FFFF {
+            return Err(ResponseCode::RequestOutOfRange);
+        }
+        records.into_iter()
+            .for_each(|r| *r = Default::default());
+
+        Ok(vec![Service::ClearDiagnosticInformation as u8 + POSITIVE_OFFSET])
+    }
+
+    fn read_dtc_information(&mut self, request: &[u8]) -> Result<Vec<u8>, ResponseCode> {
+        let report = request.get(1)
+            .ok_or(ResponseCode::IncorrectMessageLengthOrInvalidFormat)
+            .and_then(|&v| DtcReportType::try_from(v).map_err(|_| ResponseCode::SubFunctionNotSupported))?;
+        let mut response = vec![Service::ReadDtcInformation as u8 + POSITIVE_OFFSET, report as u8];
+        let expected = match report {
+            DtcReportType::NumberOfDtcByStatusMask | DtcReportType::DtcByStatusMask => 3,
+            DtcReportType::SupportedDtc | DtcReportType::DtcSnapshotIdentification => 2,
+            DtcReportType::DtcSnapshotRecordByDtcNumber | DtcReportType::DtcExtDataRecordByDtcNumber => 6,
+        };
+        if request.len() != expected {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        match report {
+            DtcReportType::NumberOfDtcByStatusMask => {
+                let mask = request[2];
+                let count = self.dtcs.values()
+                    .filter(|r| r.status & mask & self.availability_mask != 0)
+                    .count() as u16;
+                // ISO 14229-1 DTC format
+                response.extend([self.availability_mask, 0x01]);
+                response.extend(count.to_be_bytes());
+            },
+            DtcReportType::DtcByStatusMask | DtcReportType::SupportedDtc => {
+                let mask = if report == DtcReportType::SupportedDtc { None } else { Some(request[2]) };
+                response.push(self.availability_mask);
+                self.dtcs.iter()
+                    .filter(|(_, r)| mask.is_none_or(|m| r.status & m & self.availability_mask != 0))
+                    .for_each(|(&code, r)| {
+                        response.extend(&code.to_be_bytes()[1..]);
+                        response.push(r.status & self.availability_mask);
+                    });
+            },
+            DtcReportType::DtcSnapshotIdentification => {
+                self.dtcs.iter()
+                    .for_each(|(&code, r)| r.snapshots.keys().for_each(|&record| {
+                        response.extend(&code.to_be_bytes()[1..]);
+                        response.push(record);
+                    }));
+            },
+            DtcReportType::DtcSnapshotRecordByDtcNumber | DtcReportType::DtcExtDataRecordByDtcNumber => {
+                let code = u32::from_be_bytes([0, request[2], request[3], request[4]]);
+                let number = request[5];
+                let record = self.dtcs.get(&code)
+                    .ok_or(ResponseCode::RequestOutOfRange)?;
+                let records = if report == DtcReportType::DtcSnapshotRecordByDtcNumber { &record.snapshots } else { &record.extended_data };
+                response.extend(&request[2..5]);
+                response.push(record.status & self.availability_mask);
+                let mut found = false;
+                records.iter()
+                    .filter(|(&n, _)| number == 0xFF || n == number)
+                    .for_each(|(&n, data)| {
+                        found = true;
+                        response.push(n);
+                        response.extend(data);
+                    });
+                if !found && number != 0xFF {
+                    return Err(ResponseCode::RequestOutOfRange);
+                }
+            },
+        }
+
+        Ok(response)
+    }
+
+    fn read_data_by_identifier(&mut self, request: &[u8]) -> Result<Vec<u8>, ResponseCode> {
+        let dids = &request[1..];
+        if dids.is_empty() || dids.len() & 0x01 != 0 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        let mut response = vec![Service::ReadDataByIdentifier as u8 + POSITIVE_OFFSET];
+        for did in dids.chunks(2) {
+            let entry = self.dids.get(&u16::from_be_bytes([did[0], did[1]]))
+                .ok_or(ResponseCode::RequestOutOfRange)?;
+            response.extend(did);
+            response.extend(&entry.data);
+        }
+
+        Ok(response)
+    }
+
+    fn security_access(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let (sub_function, suppress) = sub_function(request, 0)?;
+        self.check_non_default()?;
+        // the sub-function 0x00 is reserved by ISO 14229
+        if sub_function == 0 {
+            return Err(ResponseCode::SubFunctionNotSupported);
+        }
+
+        if sub_function & 0x01 == 1 {
+            if request.len() != 2 {
+                return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+            }
+            if !self.keys.contains_key(&sub_function) {
+                return Err(ResponseCode::SubFunctionNotSupported);
+            }
+            if self.attempts >= self.max_attempts {
+                return Err(ResponseCode::RequiredTimeDelayNotExpired);
+            }
+
+            let seed = if self.status.security_level == Some(sub_function) {
+                vec![0x00; SEED_SIZE]
+            }
+            else {
+                let seed = self.next_seed();
+                self.seed = Some((sub_function, seed.clone()));
+                seed
+            };
+            return Ok(positive(request, suppress, &seed));
+        }
+
+        let level = sub_function - 1;
+        let seed = match self.seed.take() {
+            Some((v, seed)) if v == level => seed,
+            _ => return Err(ResponseCode::RequestSequenceError),
+        };
+        let key = self.keys.get(&level)
+            .ok_or(ResponseCode::SubFunctionNotSupported)?;
+        if key(&seed) != request[2..] {
+            self.attempts += 1;
+            return Err(if self.attempts >= self.max_attempts { ResponseCode::ExceededNumberOfAttempts } else { ResponseCode::InvalidKey });
+        }
+
+        self.attempts = 0;
+        self.status.security_level = Some(level);
+        Ok(positive(request, suppress, &[]))
+    }
+
+    fn communication_control(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let (sub_function, suppress) = sub_function(request, 3)?;
+        self.check_non_default()?;
+        self.status.communication = CommunicationControlType::try_from(sub_function)
+            .map_err(|_| ResponseCode::SubFunctionNotSupported)?;
+        Ok(positive(request, suppress, &[]))
+    }
+
+    fn write_data_by_identifier(&mut self, request: &[u8]) -> Result<Vec<u8>, ResponseCode> {
+        if request.len() < 4 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let did = u16::from_be_bytes([request[1], request[2]]);
+        let status = self.status;
+        let entry = self.dids.get_mut(&did)
+            .filter(|v| v.writable)
+            .ok_or(ResponseCode::RequestOutOfRange)?;
+        if status.session == SessionType::Default {
+            return Err(ResponseCode::ServiceNotSupportedInActiveSession);
+        }
+        if entry.security_level.is_some_and(|v| status.security_level != Some(v)) {
+            return Err(ResponseCode::SecurityAccessDenied);
+        }
+        if entry.data.len() != request.len() - 3 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        entry.data = request[3..].to_vec();
+        let mut response = request[..3].to_vec();
+        response[0] += POSITIVE_OFFSET;
+        Ok(response)
+    }
+
+    fn routine_control(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let (sub_function, suppress) = sub_function(request, 0)?;
+        if request.len() < 4 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let control = RoutineControlType::try_from(sub_function)
+            .map_err(|_| ResponseCode::SubFunctionNotSupported)?;
+        let id = u16::from_be_bytes([request[2], request[3]]);
+        let level = self.status.security_level;
+        let routine = self.routines.get_mut(&id)
+            .ok_or(ResponseCode::RequestOutOfRange)?;
+        if routine.security_level.is_some_and(|v| level != Some(v)) {
+            return Err(ResponseCode::SecurityAccessDenied);
+        }
+
+        let status = (routine.handler)(control, &request[4..])?;
+        let mut data = request[2..4].to_vec();
+        data.extend(status);
+        Ok(positive(request, suppress, &data))
+    }
+
+    fn request_download(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        if request.len() < 3 {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+        if self.status.session != SessionType::Programming {
+            return Err(ResponseCode::ServiceNotSupportedInActiveSession);
+        }
+        if !self.keys.is_empty() && self.status.security_level.is_none() {
+            return Err(ResponseCode::SecurityAccessDenied);
+        }
+        let address_len = (request[2] & 0x0F) as usize;
+        let size_len = (request[2] >> 4) as usize;
+        if !(1..=4).contains(&address_len) || !(1..=4).contains(&size_len) {
+            return Err(ResponseCode::RequestOutOfRange);
+        }
+        if request.len() != 3 + address_len + size_len {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+        let value = |bytes: &[u8]| bytes.iter().fold(0u32, |value, v| (value << 8) | *v as u32);
+        let address = value(&request[3..3 + address_len]);
+        let size = value(&request[3 + address_len..]) as usize;
+        // the memory is out of the simulated memory or 32-bit address space
+        if size == 0 || size > self.max_memory_size || address as u64 + size as u64 > 1 << 32 {
+            return Err(ResponseCode::RequestOutOfRange);
+        }
+
+        // the unfinished download is aborted
+        self.download = Some(Download { address, size, counter: 0, data: Vec::with_capacity(size) });
+        let mut response = vec![Service::RequestDownload as u8 + POSITIVE_OFFSET, 0x20];
+        response.extend(self.max_block_length.to_be_bytes());
+        Ok(Some(response))
+    }
+
+    fn transfer_data(&mut self, request: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        let download = self.download.as_mut()
+            .ok_or(ResponseCode::RequestSequenceError)?;
+        if request.len() < 2 || request.len() > self.max_block_length as usize {
+            return Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat);
+        }
+
+        let counter = request[1];
+        if counter == download.counter.wrapping_add(1) {
+            if download.data.len() + request.len() - 2 > download.size {
+                return Err(ResponseCode::TransferDataSuspended);
+            }
+            download.data.extend(&request[2..]);
+            download.counter = counter;
+        }
+        // the repeated request is accepted without writing again
+        else if counter != download.counter || download.data.is_empty() {
+            return Err(ResponseCode::WrongBlockSequenceCounter);
+        }
+
+        Ok(Some(vec![Service::TransferData as u8 + POSITIVE_OFFSET, counter]))
+    }
+
+    fn request_transfer_exit(&mut self, _: &[u8]) -> Result<Option<Vec<u8>>, ResponseCode> {
+        match self.download.take() {
+            Some(v) if v.data.len() == v.size => {
+                self.memory.insert(v.address, v.data);
+                Ok(Some(vec![Service::RequestTransferExit as u8 + POSITIVE_OFFSET]))
+            },
+            Some(_) => Err(ResponseCode::GeneralProgrammingFailure),
+            None => Err(ResponseCode::RequestSequenceError),
+        }
+    }
+
+    fn change_session(&mut self, session: SessionType) {
+        if session != self.status.session {
+            self.status.security_level = None;
+            self.seed = None;
+            self.download = None;
+        }
+        self.status.session = session;
+        if session == SessionType::Default {
+            self.status.communication = CommunicationControlType::EnableRxAndTx;
+            self.status.dtc_setting = DtcSettingType::On;
+        }
+    }
+
+    #[inline]
+    fn check_non_default(&self) -> Result<(), ResponseCode> {
+        match self.status.session {
+            SessionType::Default => Err(ResponseCode::ServiceNotSupportedInActiveSession),
+            _ => Ok(()),
+        }
+    }
+
+    /// The xorshift generator of seed.
+    fn next_seed(&mut self) -> Vec<u8> {
+        let mut seed = Vec::with_capacity(SEED_SIZE);
+        while seed.len() < SEED_SIZE {
+            self.random ^= self.random << 13;
+            self.random ^= self.random >> 7;
+            self.random ^= self.random << 17;
+            seed.extend(self.random.to_be_bytes().iter().filter(|&&v| v != 0).take(SEED_SIZE - seed.len()));
+        }
+        seed
+    }
+}
+
+/// Get the sub-function and the suppress bit of request, `length` is the exact length of request when it is not zero.
+#[inline]
+fn sub_function(request: &[u8], length: usize) -> Result<(u8, bool), ResponseCode> {
+    match request.get(1) {
+        Some(&v) if length == 0 || request.len() == length => Ok((v & !SUPPRESS_POSITIVE, v & SUPPRESS_POSITIVE != 0)),
+        _ => Err(ResponseCode::IncorrectMessageLengthOrInvalidFormat),
+    }
+}
+
+/// Build the positive response of service with sub-function.
+#[inline]
+fn positive(request: &[u8], suppress: bool, data: &[u8]) -> Option<Vec<u8>> {
+    if suppress {
+        return None;
+    }
+    let mut response = vec![request[0] + POSITIVE_OFFSET, request[1] & !SUPPRESS_POSITIVE];
+    response.extend(data);
+    Some(response)
+}
+
+/// The UDS server which serves an [`Ecu`] over ISO-TP.
+///
+/// Several ECUs are simulated on one bus by the servers with different addresses.
+pub struct UdsServer<D: CanDevice> {
+    isotp: IsoTp<D>,
+    ecu: Arc<Mutex<Ecu>>,
+}
+
+impl<D: CanDevice> UdsServer<D>
+where
+    D::Channel: Clone,
+{
+    pub fn new(isotp: IsoTp<D>, ecu: Ecu) -> Self {
+        Self { isotp, ecu: Arc::new(Mutex::new(ecu)) }
+    }
+
+    /// The ECU which is shared with the running server.
+    #[inline(always)]
+    pub fn ecu(&self) -> Arc<Mutex<Ecu>> {
+        Arc::clone(&self.ecu)
+    }
+
+    /// Serve a request, return `false` when no request received in `timeout` milliseconds.
+    pub fn serve(&mut self, timeout: u32) -> Result<bool, CanError> {
+        let (target, request) = match self.isotp.receive_with_target(timeout) {
+            Ok(v) => v,
+            Err(CanError::TimeoutError(_)) => {
+                self.lock_ecu()?.check_s3();
+                return Ok(false);
+            },
+            Err(e) => return Err(e),
+        };
+
+        let service = request[0];
+        let (response, delay, p2, p2_star) = {
+            let mut ecu = self.lock_ecu()?;
+            (ecu.process(&request, target), ecu.response_delay(service), ecu.p2 as u32, ecu.p2_star)
+        };
+        let response = match response {
+            Some(v) => v,
+            None => return Ok(true),
+        };
+
+        if delay > 0 {
+            let deadline = Instant::now() + Duration::from_millis(delay as u64);
+            if delay >= p2 {
+                // ResponsePending is repeated before P2* is elapsed
+                let interval = Duration::from_millis((p2_star / 2).max(1) as u64);
+                let pending = [NEGATIVE_RESPONSE, service, ResponseCode::RequestCorrectlyReceivedResponsePending as u8];
+                loop {
+                    self.isotp.transmit(&pending)?;
+                    let now = Instant::now();
+                    if now + interval >= deadline {
+                        break;
+                    }
+                    thread::sleep(interval);
+                }
+            }
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        }
+
+        self.isotp.transmit(&response)?;
+        Ok(true)
+    }
+
+    /// Serve the requests in background until the handle is stopped or dropped.
+    pub fn spawn(mut self) -> UdsServerHandle
+    where
+        D: Send + 'static,
+        D::Channel: Send,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let ecu = self.ecu();
+        let flag = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            while flag.load(Ordering::Acquire) {
+                if let Err(e) = self.serve(10) {
+                    log::warn!("RUST-CAN - {} when UDS serving", e);
+                }
+            }
+        });
+
+        UdsServerHandle { running, ecu, handle: Some(handle) }
+    }
+
+    #[inline]
+    fn lock_ecu(&self) -> Result<std::sync::MutexGuard<'_, Ecu>, CanError> {
+        self.ecu.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))
+    }
+}
+
+/// The handle of the server running in background.
+pub struct UdsServerHandle {
+    running: Arc<AtomicBool>,
+    ecu: Arc<Mutex<Ecu>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl UdsServerHandle {
+    #[inline(always)]
+    pub fn ecu(&self) -> Arc<Mutex<Ecu>> {
+        Arc::clone(&self.ecu)
+    }
+
+    /// Stop the server and wait for the request being served.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - UDS server thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for UdsServerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::test_utils::new_isotp;
+    use crate::uds::{
+        CommunicationControlType, Dtc, DtcSettingType, ResetType, ResponseCode, RoutineControlType, SessionTiming,
+        SessionType, UdsClient, UdsError,
+    };
+    use super::{Ecu, UdsServer};
+
+    fn new_ecu(vin: &[u8]) -> Ecu {
+        let mut ecu = Ecu::new();
+        ecu.add_security_level(0x01, |seed| seed.iter().map(|v| !v).collect())
+            .add_did(0xF190, vin)
+            .add_writable_did(0xF198, &[0x00; 4], Some(0x01))
+            .set_dtc(0x123456, 0x09)
+            .set_dtc(0xC00100, 0x08)
+            .add_dtc_snapshot(0x123456, 0x01, &[0x02, 0xAA])
+            .add_routine(0xFF00, Some(0x01), |control, option| match control {
+                RoutineControlType::Start => Ok(option.to_vec()),
+                _ => Err(ResponseCode::RequestSequenceError),
+            });
+        ecu
+    }
+
+    #[test]
+    fn test_services() -> anyhow::Result<()> {
+        let channel = "uds-server-services";
+        let server = UdsServer::new(new_isotp(channel, 0x7E8, 0x7E0)?, new_ecu(b"WDD2030461A123456"));
+        let handle = server.spawn();
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        client.set_security_algorithm(|_, seed| Ok(seed.iter().map(|v| !v).collect()));
+
+        assert_eq!(client.read_data_by_identifier(0xF190)?, b"WDD2030461A123456");
+        let err = client.security_access(0x01).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::ServiceNotSupportedInActiveSession));
+        assert_eq!(client.session_control(SessionType::Extended, false)?, Some(SessionTiming { p2: 50, p2_star: 5000 }));
+        let err = client.write_data_by_identifier(0xF198, &[0x01; 4]).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::SecurityAccessDenied));
+        for request in [[0x27, 0x00], [0x27, 0x80]] {
+            let err = client.request(&request, false).unwrap_err();
+            assert_eq!(err.response_code(), Some(ResponseCode::SubFunctionNotSupported));
+        }
+        client.security_access(0x01)?;
+        // the seed of unlocked level is zero
+        client.security_access(0x01)?;
+        client.write_data_by_identifier(0xF198, &[0x01; 4])?;
+        assert_eq!(client.read_data_by_identifier(0xF198)?, [0x01; 4]);
+        assert_eq!(client.routine_control(RoutineControlType::Start, 0xFF00, &[0x05])?, [0x05]);
+        let err = client.routine_control(RoutineControlType::Stop, 0xFF00, &[]).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::RequestSequenceError));
+
+        assert_eq!(client.read_dtc_count(0x01)?.count, 1);
+        assert_eq!(client.read_supported_dtcs()?.dtcs.len(), 2);
+        assert_eq!(client.read_dtc_snapshot(0x123456, 0xFF)?, [0x12, 0x34, 0x56, 0x09, 0x01, 0x02, 0xAA]);
+        client.control_dtc_setting(DtcSettingType::Off, true)?;
+        client.communication_control(CommunicationControlType::DisableRxAndTx, 0x01, false)?;
+        {
+            let ecu = handle.ecu();
+            let mut ecu = ecu.lock().unwrap();
+            assert_eq!(ecu.status().dtc_setting, DtcSettingType::Off);
+            assert_eq!(ecu.status().communication, CommunicationControlType::DisableRxAndTx);
+            // the status is frozen when DTC setting is off
+            ecu.set_dtc(0x123456, 0x2F);
+        }
+        client.clear_diagnostic_information(0xFFFFFF)?;
+        assert_eq!(client.read_dtcs(0xFF)?.dtcs, []);
+        client.tester_present(true)?;
+
+        client.session_control(SessionType::Programming, false)?;
+        client.security_access(0x01)?;
+        // the memory size is larger than the simulated memory
+        let err = client.request(&[0x34, 0x00, 0x44, 0x08, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF], false).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::RequestOutOfRange));
+        client.ecu_reset(ResetType::Hard, false)?;
+        let status = *handle.ecu().lock().unwrap().status();
+        assert_eq!((status.session, status.security_level), (SessionType::Default, None));
+        let err = client.request(&[0x23, 0x00], false).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::ServiceNotSupported));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_injection_and_delay() -> anyhow::Result<()> {
+        let channel = "uds-server-injection";
+        let mut ecu = new_ecu(b"VIN");
+        ecu.inject_nrc(0x22, 0x21, 2)
+            .set_response_delay(0x3E, 120)
+            .set_max_attempts(2)
+            .set_s3(300);
+        let handle = UdsServer::new(new_isotp(channel, 0x7E8, 0x7E0)?, ecu).spawn();
+        let mut client = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        client.set_security_algorithm(|_, seed| Ok(seed.to_vec()));
+
+        for _ in 0..2 {
+            let err = client.read_data_by_identifier(0xF190).unwrap_err();
+            assert_eq!(err.response_code(), Some(ResponseCode::BusyRepeatRequest));
+        }
+        assert_eq!(client.read_data_by_identifier(0xF190)?, b"VIN");
+
+        // the response is pending after P2
+        let start = Instant::now();
+        client.tester_present(false)?;
+        assert!(start.elapsed() >= Duration::from_millis(120));
+
+        client.session_control(SessionType::Extended, true)?;
+        let err = client.security_access(0x01).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::InvalidKey));
+        let err = client.security_access(0x01).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::ExceededNumberOfAttempts));
+        let err = client.security_access(0x01).unwrap_err();
+        assert_eq!(err.response_code(), Some(ResponseCode::RequiredTimeDelayNotExpired));
+
+        // return to default session by S3 timeout
+        std::thread::sleep(Duration::from_millis(350));
+        assert_eq!(handle.ecu().lock().unwrap().status().session, SessionType::Default);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_ecus() -> anyhow::Result<()> {
+        let channel = "uds-server-multiple";
+        let engine = UdsServer::new(new_isotp(channel, 0x7E8, 0x7E0)?, new_ecu(b"ENGINE")).spawn();
+        let gearbox = UdsServer::new(new_isotp(channel, 0x7E9, 0x7E1)?, new_ecu(b"GEARBOX")).spawn();
+        let mut client1 = UdsClient::new(new_isotp(channel, 0x7E0, 0x7E8)?);
+        let mut client2 = UdsClient::new(new_isotp(channel, 0x7E1, 0x7E9)?);
+
+        assert_eq!(client1.read_data_by_identifier(0xF190)?, b"ENGINE");
+        assert_eq!(client2.read_data_by_identifier(0xF190)?, b"GEARBOX");
+
+        // the functional request is served by all ECUs
+        client1.isotp_mut().transmit_functional(&[0x10, 0x83])?;
+        // the negative response is suppressed for functional request
+        client1.isotp_mut().transmit_functional(&[0x22, 0x12, 0x34])?;
+        assert!(matches!(client1.isotp_mut().receive(100), Err(crate::CanError::TimeoutError(_))));
+        for handle in [&engine, &gearbox] {
+            assert_eq!(handle.ecu().lock().unwrap().status().session, SessionType::Extended);
+        }
+        assert_eq!(engine.ecu().lock().unwrap().dtcs()[0], Dtc { code: 0x123456, status: 0x09 });
+        assert!(matches!(client2.read_data_by_identifier(0x1234), Err(UdsError::NegativeResponse { code: 0x31, .. })));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Condvar, Mutex, OnceLock, Weak}, time::{Duration, Instant}};
+use crate::{can_utils, CanDirect, CanError, CanFilter, CanFrame, IdentifierFlags};
+use super::CanMessage;
+
+/// All living buses, a bus is dropped and removed when the last endpoint detached.
+fn buses() -> &'static Mutex<HashMap<String, Weak<Bus>>> {
+    static BUSES: OnceLock<Mutex<HashMap<String, Weak<Bus>>>> = OnceLock::new();
+    BUSES.get_or_init(Default::default)
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Bus {
+    endpoints: Mutex<Vec<Weak<Endpoint>>>,
+}
+
+impl Bus {
+    /// Get the bus named `name` or create it.
+    pub(crate) fn attach(name: &str) -> Result<Arc<Self>, CanError> {
+        let mut buses = buses().lock()
+            .map_err(|e| CanError::InitializeError(e.to_string()))?;
+        buses.retain(|_, v| v.strong_count() > 0);
+        if let Some(bus) = buses.get(name).and_then(Weak::upgrade) {
+            return Ok(bus);
+        }
+
+        let bus = Arc::new(Self::default());
+        buses.insert(name.to_owned(), Arc::downgrade(&bus));
+        Ok(bus)
+    }
+
+    /// The bus named `name` is living.
+    #[cfg(test)]
+    pub(crate) fn exists(name: &str) -> bool {
+        buses().lock()
+            .map(|v| v.contains_key(name))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn register(&self, endpoint: &Arc<Endpoint>) -> Result<(), CanError> {
+        let mut endpoints = self.endpoints.lock()
+            .map_err(|e| CanError::InitializeError(e.to_string()))?;
+        endpoints.retain(|e| e.strong_count() > 0);
+        endpoints.push(Arc::downgrade(endpoint));
+        Ok(())
+    }
+
+    pub(crate) fn unregister(&self, endpoint: &Arc<Endpoint>) -> Result<(), CanError> {
+        let mut endpoints = self.endpoints.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        endpoints.retain(|e| e.strong_count() > 0 && !std::ptr::eq(e.as_ptr(), Arc::as_ptr(endpoint)));
+        Ok(())
+    }
+
+    /// Deliver the frame to every endpoint attached, the sender's options decide who will receive it.
+    pub(crate) fn transmit(&self, sender: &Arc<Endpoint>, mut msg: CanMessage) -> Result<(), CanError> {
+        let (loopback, recv_own_msgs) = sender.options()?;
+        if !loopback {
+            return Ok(());
+        }
+
+        // the endpoints are locked until the frame is delivered to all of them,
+        // so a frame replied by the receiver never overtakes it on other endpoints.
+        let endpoints = self.endpoints.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        msg.set_timestamp(Some(can_utils::system_timestamp()));
+        endpoints.iter()
+            .filter_map(Weak::upgrade)
+            .try_for_each(|endpoint| {
+                let own = Arc::ptr_eq(&endpoint, sender);
+                if own && !recv_own_msgs {
+                    return Ok(());
+                }
+
+                let mut msg = msg.clone();
+                msg.set_direct(if own { CanDirect::Transmit } else { CanDirect::Receive });
+                endpoint.deliver(msg)
+            })
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        match buses().lock() {
+            Ok(mut buses) => buses.retain(|_, v| v.strong_count() > 0),
+            Err(e) => log::warn!("RUST-CAN - {} when virtual bus releasing", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EndpointInner {
+    queue: VecDeque<CanMessage>,
+    filters: Option<Vec<CanFilter>>,
+    loopback: bool,
+    recv_own_msgs: bool,
+}
+
+/// A channel of a device which is attached to a bus.
+#[derive(Debug)]
+pub(crate) struct Endpoint {
+    name: String,
+    bus: Arc<Bus>,
+    capacity: usize,
+    inner: Mutex<EndpointInner>,
+    cond: Condvar,
+}
+
+impl Endpoint {
+    pub(crate) fn new(name: &str, bus: Arc<Bus>, capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_owned(),
+            bus,
+            capacity,
+            inner: Mutex::new(EndpointInner {
+                queue: Default::default(),
+                filters: None,
+                loopback: true,
+                recv_own_msgs: false,
+            }),
+            cond: Default::default(),
+        })
+    }
+
+    #[inline(always)]
+    pub(crate) fn bus(&self) -> &Arc<Bus> {
+        &self.bus
+    }
+
+    pub(crate) fn set_filters(&self, filters: Option<Vec<CanFilter>>) -> Result<(), CanError> {
+        self.inner_handler(|inner| inner.filters = filters)
+    }
+
+    pub(crate) fn set_loopback(&self, enabled: bool) -> Result<(), CanError> {
+        self.inner_handler(|inner| inner.loopback = enabled)
+    }
+
+    pub(crate) fn set_recv_own_msgs(&self, enabled: bool) -> Result<(), CanError> {
+        self.inner_handler(|inner| inner.recv_own_msgs = enabled)
+    }
+
+    /// Return the `loopback` and `recv-own-msg` options.
+    pub(crate) fn options(&self) -> Result<(bool, bool), CanError> {
+        self.inner_handler(|inner| (inner.loopback, inner.recv_own_msgs))
+    }
+
+    /// Receive all buffered frames, waiting at most `timeout` when no frame is buffered.
+    pub(crate) fn receive(&self, timeout: Duration) -> Result<Vec<CanMessage>, CanError> {
+        let deadline = Instant::now() + timeout;
+        let mut inner = self.inner.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        while inner.queue.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(vec![]);
+            }
+
+            inner = self.cond.wait_timeout(inner, deadline - now)
+                .map_err(|e| CanError::OperationError(e.to_string()))?
+                .0;
+        }
+
+        Ok(inner.queue.drain(..).collect())
+    }
+
+    fn deliver(&self, mut msg: CanMessage) -> Result<(), CanError> {
+        let mut inner = self.inner.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        if let Some(filters) = &inner.filters {
+            if !filters.iter().any(|f| filter_matched(f, &msg)) {
+                return Ok(());
+            }
+        }
+
+        if inner.queue.len() >= self.capacity {
+            log::warn!("RUST-CAN - channel: {} buffer is full, the oldest frame is dropped", self.name);
+            inner.queue.pop_front();
+        }
+        msg.set_channel(self.name.clone());
+        inner.queue.push_back(msg);
+        self.cond.notify_all();
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn inner_handler<R>(&self, callback: impl FnOnce(&mut EndpointInner) -> R) -> Result<R, CanError> {
+        let mut inner = self.inner.lock()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        Ok(callback(&mut inner))
+    }
+}
+
+/// Filter matched like SocketCAN: `<received_can_id> & mask == can_id & mask`.
+#[inline]
+fn filter_matched(filter: &CanFilter, msg: &CanMessage) -> bool {
+    let mut can_id = msg.arbitration_id;
+    if msg.is_extended_id {
+        can_id |= IdentifierFlags::EXTENDED.bits();
+    }
+    if msg.is_remote_frame {
+        can_id |= IdentifierFlags::REMOTE.bits();
+    }
+    let mut filter_id = filter.can_id;
+    if filter.extended {
+        filter_id |= IdentifierFlags::EXTENDED.bits();
+    }
+
+    can_id & filter.can_mask == filter_id & filter.can_mask
+}
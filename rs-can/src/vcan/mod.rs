@@ -0,0 +1,289 @@
+//! An in-process virtual CAN bus.
+//!
+//! Every channel of a [`VirtualCan`] device is attached to the bus with the same name.
+//! All devices attached to the same bus see each other's frames, just like sockets
+//! bound to a `vcan` interface do.
+mod bus;
+mod frame;
+
+pub use frame::*;
+
+use std::{collections::HashMap, sync::{Arc, RwLock}, time::Duration};
+use crate::{interfaces, CanDevice, CanError, CanFilter, CanFrame, CanResult, CanType, DeviceBuilder, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE, MAX_XL_FRAME_SIZE};
+use self::bus::{Bus, Endpoint};
+
+/// `Vec<CanFilter>`, the receive filters of channel.
+pub const FILTERS: &str = "filters";
+/// `bool`, deliver transmitted frames to other devices on the bus, default is `true`.
+pub const LOOPBACK: &str = "loopback";
+/// `bool`, deliver transmitted frames back to sender, default is `false`.
+pub const RECV_OWN_MSG: &str = "recv-own-msg";
+/// `usize`, the max count of frames buffered by channel, default is [`DEFAULT_QUEUE_SIZE`].
+pub const QUEUE_SIZE: &str = "queue-size";
+/// The default max count of frames buffered by channel.
+pub const DEFAULT_QUEUE_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Default)]
+pub struct VirtualCan {
+    endpoints: Arc<RwLock<HashMap<String, Arc<Endpoint>>>>,
+}
+
+impl VirtualCan {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Attach the device to bus named `channel`, the bus is created when it is not existed.
+    pub fn init_channel(&mut self, channel: &str, queue_size: Option<usize>) -> Result<(), CanError> {
+        let mut endpoints = self.endpoints.write()
+            .map_err(|e| CanError::InitializeError(e.to_string()))?;
+        if endpoints.contains_key(channel) {
+            return Err(CanError::InitializeError(format!("channel: {} is already opened", channel)));
+        }
+
+        let bus = Bus::attach(channel)?;
+        let endpoint = Endpoint::new(channel, bus, queue_size.unwrap_or(DEFAULT_QUEUE_SIZE));
+        endpoint.bus().register(&endpoint)?;
+        endpoints.insert(channel.to_owned(), endpoint);
+
+        Ok(())
+    }
+
+    /// Detach the device from bus named `channel`.
+    pub fn close_channel(&mut self, channel: &str) -> Result<(), CanError> {
+        let endpoint = self.endpoints.write()
+            .map_err(|e| CanError::OperationError(e.to_string()))?
+            .remove(channel)
+            .ok_or(CanError::channel_not_opened(channel))?;
+        endpoint.bus().unregister(&endpoint)
+    }
+
+    /// Sets CAN ID filters on the channel.
+    ///
+    /// A frame is received when it matches any of the filters.
+    /// An empty filter list drops all frames.
+    pub fn set_filters(&self, channel: &str, filters: &[CanFilter]) -> Result<(), CanError> {
+        self.endpoint_handler(channel, |e| e.set_filters(Some(filters.to_vec())))
+    }
+
+    /// Accept all frames, disabling any kind of filtering.
+    #[inline(always)]
+    pub fn set_filter_accept_all(&self, channel: &str) -> Result<(), CanError> {
+        self.endpoint_handler(channel, |e| e.set_filters(None))
+    }
+
+    /// Disable reception of CAN frames.
+    #[inline(always)]
+    pub fn set_filter_drop_all(&self, channel: &str) -> Result<(), CanError> {
+        self.set_filters(channel, &[])
+    }
+
+    /// Enable or disable loopback.
+    ///
+    /// By default, loopback is enabled, causing other devices that attached
+    /// the same bus to see frames emitted by this device.
+    pub fn set_loopback(&self, channel: &str, enabled: bool) -> Result<(), CanError> {
+        self.endpoint_handler(channel, |e| e.set_loopback(enabled))
+    }
+
+    /// Enable or disable receiving of own frames.
+    ///
+    /// When loopback is enabled, this settings controls if CAN frames sent
+    /// are received back immediately by sender. Default is off.
+    pub fn set_recv_own_msgs(&self, channel: &str, enabled: bool) -> Result<(), CanError> {
+        self.endpoint_handler(channel, |e| e.set_recv_own_msgs(enabled))
+    }
+
+    fn endpoint_handler<R>(
+        &self,
+        channel: &str,
+        callback: impl FnOnce(&Arc<Endpoint>) -> Result<R, CanError>
+    ) -> Result<R, CanError> {
+        let endpoints = self.endpoints.read()
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        match endpoints.get(channel) {
+            Some(e) => callback(e),
+            None => Err(CanError::channel_not_opened(channel)),
+        }
+    }
+}
+
+impl TryFrom<DeviceBuilder> for VirtualCan {
+    type Error = CanError;
+
+    fn try_from(builder: DeviceBuilder) -> Result<Self, Self::Error> {
+        if builder.interface() != interfaces::VIRTUAL {
+            return Err(CanError::interface_not_matched(builder.interface()));
+        }
+
+        let mut device = VirtualCan::new();
+        builder.channel_configs()
+            .iter()
+            .try_for_each(|(chl, cfg)| {
+                let queue_size = match cfg.get_other::<usize>(QUEUE_SIZE)? {
+                    Some(v) => Some(v),
+                    None => builder.get_other::<usize>(QUEUE_SIZE)?,
+                };
+                device.init_channel(chl, queue_size)?;
+
+                let filters = match cfg.get_other::<Vec<CanFilter>>(FILTERS)? {
+                    Some(v) => Some(v),
+                    None => builder.get_other::<Vec<CanFilter>>(FILTERS)?,
+                };
+                if let Some(filters) = filters {
+                    device.set_filters(chl, &filters)?;
+                }
+
+                let loopback = match cfg.get_other::<bool>(LOOPBACK)? {
+                    Some(v) => Some(v),
+                    None => builder.get_other::<bool>(LOOPBACK)?,
+                };
+                if let Some(loopback) = loopback {
+                    device.set_loopback(chl, loopback)?;
+                }
+
+                let recv_own_msg = match cfg.get_other::<bool>(RECV_OWN_MSG)? {
+                    Some(v) => Some(v),
+                    None => builder.get_other::<bool>(RECV_OWN_MSG)?,
+                };
+                if let Some(recv_own_msg) = recv_own_msg {
+                    device.set_recv_own_msgs(chl, recv_own_msg)?;
+                }
+
+                Ok(())
+            })?;
+
+        Ok(device)
+    }
+}
+
+impl CanDevice for VirtualCan {
+    type Channel = String;
+    type Frame = CanMessage;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::Channel> {
+        match self.endpoints.read() {
+            Ok(v) => v.keys()
+                .cloned()
+                .collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    fn transmit(&self, msg: Self::Frame, _: Option<u32>) -> CanResult<(), CanError> {
+        let length = msg.data.len();
+        let valid = match msg.can_type {
+            CanType::Can => length <= MAX_FRAME_SIZE,
+            CanType::CanFd => length <= MAX_FD_FRAME_SIZE,
+            CanType::CanXl => length <= MAX_XL_FRAME_SIZE,
+        };
+        if !valid {
+            return Err(CanError::OperationError(format!("invalid data length: {} of {:?} frame", length, msg.can_type)));
+        }
+
+        let channel = msg.channel();
+        self.endpoint_handler(&channel, |e| e.bus().transmit(e, msg))
+    }
+
+    fn receive(&self, channel: Self::Channel, timeout: Option<u32>) -> CanResult<Vec<Self::Frame>, CanError> {
+        let timeout = Duration::from_millis(timeout.unwrap_or(0) as u64);
+        self.endpoint_handler(&channel, |e| e.receive(timeout))
+    }
+
+    fn shutdown(&mut self) {
+        let endpoints = match self.endpoints.write() {
+            Ok(mut v) => v.drain()
+                .collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        endpoints.into_iter()
+            .for_each(|(channel, endpoint)| {
+                if let Err(e) = endpoint.bus().unregister(&endpoint) {
+                    log::warn!("RUST-CAN - {} when channel: {} closing", e, channel);
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interfaces, CanDevice, CanDirect, CanFilter, CanFrame, CanId, CanType, ChannelConfig, DeviceBuilder};
+    use crate::test_utils::{new_device, new_message};
+    use super::{bus::Bus, VirtualCan, RECV_OWN_MSG};
+
+    #[test]
+    fn test_broadcast() -> anyhow::Result<()> {
+        let channel = "vbus-broadcast";
+        let dev1 = new_device(channel)?;
+        let dev2 = new_device(channel)?;
+        let dev3 = new_device(channel)?;
+
+        dev1.transmit(new_message(channel, 0x123, &[0x01, 0x02]), None)?;
+        let mut fd = new_message(channel, 0x1234_5678, &[0x55; 24]);
+        fd.set_bitrate_switch(true);
+        dev1.transmit(fd, None)?;
+        dev1.transmit(new_message(channel, 0x7FF, &[0xAA; 512]), None)?;
+
+        for dev in [&dev2, &dev3] {
+            let frames = dev.receive(channel.into(), Some(10))?;
+            assert_eq!(frames.len(), 3);
+            assert_eq!(frames[0].id(), CanId::Standard(0x123));
+            assert_eq!(frames[0].direct(), CanDirect::Receive);
+            assert_eq!(frames[1].can_type(), CanType::CanFd);
+            assert!(frames[1].is_extended());
+            assert!(frames[1].is_bitrate_switch());
+            assert_eq!(frames[2].can_type(), CanType::CanXl);
+            assert_eq!(frames[2].length(), 512);
+        }
+        // recv-own-msg is disabled by default
+        assert!(dev1.receive(channel.into(), None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_loopback_and_filter() -> anyhow::Result<()> {
+        let channel = "vbus-options";
+        let mut cfg = ChannelConfig::new(500_000);
+        cfg.add_other(RECV_OWN_MSG, Box::new(true));
+        let mut builder = DeviceBuilder::new(interfaces::VIRTUAL);
+        builder.add_config(channel, cfg);
+        let dev1 = builder.build::<VirtualCan>()?;
+        let dev2 = new_device(channel)?;
+        dev2.set_filters(channel, &[CanFilter::from((0x100, 0x700))])?;
+
+        dev1.transmit(new_message(channel, 0x123, &[0x01]), None)?;
+        dev1.transmit(new_message(channel, 0x223, &[0x02]), None)?;
+
+        let frames = dev1.receive(channel.into(), Some(10))?;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].direct(), CanDirect::Transmit);
+
+        let frames = dev2.receive(channel.into(), Some(10))?;
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].id(), CanId::Standard(0x123));
+
+        dev1.set_loopback(channel, false)?;
+        dev1.transmit(new_message(channel, 0x124, &[0x03]), None)?;
+        assert!(dev1.receive(channel.into(), None)?.is_empty());
+        assert!(dev2.receive(channel.into(), None)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bus_released() -> anyhow::Result<()> {
+        let channel = "vbus-released";
+        let mut dev1 = new_device(channel)?;
+        let dev2 = new_device(channel)?;
+        assert!(Bus::exists(channel));
+
+        dev1.shutdown();
+        assert!(Bus::exists(channel));
+        drop(dev2);
+        assert!(!Bus::exists(channel));
+
+        Ok(())
+    }
+}
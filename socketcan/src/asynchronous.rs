@@ -0,0 +1,172 @@
+use std::{collections::HashMap, future::Future, os::fd::{AsRawFd, RawFd}, pin::Pin, sync::Arc, task::{Context, Poll}, time::Duration};
+use futures_core::Stream;
+use rs_can::{AsyncCanDevice, CanDevice, CanError, CanFrame, DeviceBuilder};
+use tokio::io::unix::AsyncFd;
+use crate::{raw_read_message, raw_write_message, CanAnyFrame, CanMessage, SocketCan};
+
+/// The SocketCAN device whose sockets are registered with the tokio reactor.
+///
+/// The sockets are switched to non-blocking mode, so the blocking read and write of
+/// the wrapped [`SocketCan`] should not be used any more.
+///
+/// ```no_run
+/// use rs_can::AsyncCanDevice;
+/// use socketcan_rs::{AsyncSocketCan, SocketCan};
+///
+/// # async fn example() -> Result<(), rs_can::CanError> {
+/// let mut device = SocketCan::new();
+/// device.init_channel("can0", false)?;
+///
+/// let device = AsyncSocketCan::new(device)?;
+/// let frames = device.receive("can0".into(), Some(100)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct AsyncSocketCan {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    // deregister the sockets before they are closed by device
+    fds: HashMap<String, AsyncFd<RawFd>>,
+    device: SocketCan,
+}
+
+impl AsyncSocketCan {
+    /// Register all opened channels of device, it must be called within the tokio runtime.
+    pub fn new(device: SocketCan) -> Result<Self, CanError> {
+        let mut fds = HashMap::new();
+        for (channel, socket) in device.sockets.iter() {
+            device.set_nonblocking(channel, true)?;
+            let fd = AsyncFd::new(socket.as_raw_fd())
+                .map_err(|e| CanError::InitializeError(e.to_string()))?;
+            fds.insert(channel.clone(), fd);
+        }
+
+        Ok(Self { inner: Arc::new(Inner { fds, device }) })
+    }
+
+    /// Get the device for setting filters or other socket options.
+    #[inline]
+    pub fn device(&self) -> &SocketCan {
+        &self.inner.device
+    }
+
+    /// Read a single frame, wait until the socket is readable.
+    pub async fn read(&self, channel: &str) -> Result<CanMessage, CanError> {
+        let fd = self.fd(channel)?;
+        loop {
+            let mut guard = fd.readable().await
+                .map_err(|e| CanError::OperationError(e.to_string()))?;
+            if let Ok(result) = guard.try_io(|fd| raw_read_message(fd.as_raw_fd())) {
+                return result.map_err(|e| CanError::OperationError(e.to_string()));
+            }
+        }
+    }
+
+    /// Write a single frame, wait until the socket is writable.
+    pub async fn write(&self, msg: CanMessage) -> Result<(), CanError> {
+        let channel = msg.channel();
+        let fd = self.fd(&channel)?;
+        let frame: CanAnyFrame = msg.into();
+        loop {
+            let mut guard = fd.writable().await
+                .map_err(|e| CanError::OperationError(e.to_string()))?;
+            if let Ok(result) = guard.try_io(|fd| raw_write_message(fd.as_raw_fd(), &frame)) {
+                return result.map_err(|e| CanError::OperationError(e.to_string()));
+            }
+        }
+    }
+
+    #[inline]
+    fn fd(&self, channel: &str) -> Result<&AsyncFd<RawFd>, CanError> {
+        self.inner.fds.get(channel)
+            .ok_or(CanError::channel_not_opened(channel))
+    }
+}
+
+impl TryFrom<DeviceBuilder> for AsyncSocketCan {
+    type Error = CanError;
+
+    fn try_from(builder: DeviceBuilder) -> Result<Self, Self::Error> {
+        SocketCan::try_from(builder)
+            .and_then(Self::new)
+    }
+}
+
+async fn with_timeout<R>(
+    channel: &str,
+    timeout: Option<u32>,
+    fut: impl Future<Output = Result<R, CanError>>,
+) -> Result<R, CanError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(Duration::from_millis(timeout as u64), fut).await
+            .map_err(|_| CanError::channel_timeout(channel))?,
+        None => fut.await,
+    }
+}
+
+impl AsyncCanDevice for AsyncSocketCan {
+    type Channel = String;
+    type Frame = CanMessage;
+    type Stream = SocketCanStream;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::Channel> {
+        self.inner.fds.keys()
+            .cloned()
+            .collect()
+    }
+
+    async fn transmit(&self, msg: Self::Frame, timeout: Option<u32>) -> Result<(), CanError> {
+        let channel = msg.channel();
+        with_timeout(&channel, timeout, self.write(msg)).await
+    }
+
+    async fn receive(&self, channel: Self::Channel, timeout: Option<u32>) -> Result<Vec<Self::Frame>, CanError> {
+        let msg = with_timeout(&channel, timeout, self.read(&channel)).await?;
+        Ok(vec![msg, ])
+    }
+
+    fn stream(&self, channel: Self::Channel) -> Result<Self::Stream, CanError> {
+        self.fd(&channel)?;
+        Ok(SocketCanStream { inner: self.inner.clone(), channel })
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.fds.clear();
+            inner.device.shutdown();
+        }
+    }
+}
+
+/// The stream of frames received from a channel of [`AsyncSocketCan`].
+#[derive(Debug)]
+pub struct SocketCanStream {
+    inner: Arc<Inner>,
+    channel: String,
+}
+
+impl Stream for SocketCanStream {
+    type Item = Result<CanMessage, CanError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let fd = match self.inner.fds.get(&self.channel) {
+            Some(v) => v,
+            None => return Poll::Ready(None),
+        };
+        loop {
+            let mut guard = match fd.poll_read_ready(cx) {
+                Poll::Ready(Ok(v)) => v,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(CanError::OperationError(e.to_string())))),
+                Poll::Pending => return Poll::Pending,
+            };
+            if let Ok(result) = guard.try_io(|fd| raw_read_message(fd.as_raw_fd())) {
+                return Poll::Ready(Some(result.map_err(|e| CanError::OperationError(e.to_string()))));
+            }
+        }
+    }
+}
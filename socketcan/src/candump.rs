@@ -0,0 +1,496 @@
+//! The log file and compact screen formats of can-utils `candump`.
+//!
+//! ```text
+//! (1690000000.123456) vcan0 123#DEADBEEF          # candump -l
+//! (1690000000.123456) vcan0 12345678##5DEADBEEF   # CAN-FD with flags
+//! (1690000000.123456) vcan0 123#R4                # remote frame
+//!   vcan0  123   [4]  DE AD BE EF                 # candump
+//!   vcan0  TX B -  123  [04]  DE AD BE EF         # candump -x
+//! ```
+//!
+//! The frames are converted from/to any [`CanFrame`], the interface in file is the frame's channel,
+//! the channel that is only a number(e.g. `0`) is written as `can0`.
+use std::{fmt::Write as _, fs::File, io::{BufRead, BufReader, BufWriter, Write}, marker::PhantomData, path::Path, str::FromStr};
+use rs_can::{can_utils, trace::{channel_from_number, channel_number}, CanDirect, CanError, CanFrame, CanId, CanType, IdentifierFlags, ERR_MASK, MAX_FD_FRAME_SIZE, MAX_FRAME_SIZE};
+use crate::CanMessage;
+
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+
+/// The line format of candump.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `candump -l`, which can be replayed by `canplayer`.
+    #[default]
+    Log,
+    /// The default screen output of `candump`.
+    Compact,
+}
+
+/// Parse a frame in `cansend` syntax, e.g. `123#DEADBEEF`, `123##1DEADBEEF` or `123#R`.
+pub fn parse_frame<F: CanFrame>(s: &str) -> Result<F, CanError> {
+    _parse_frame(s)
+        .map_err(CanError::OtherError)
+}
+
+/// Format a frame in `cansend` syntax, the CAN-XL frame is not supported.
+pub fn format_frame<F: CanFrame + ?Sized>(frame: &F) -> Result<String, CanError> {
+    let mut result = fmt_id(frame);
+    let data = frame.data();
+    match frame.can_type() {
+        CanType::Can => {
+            if data.len() > MAX_FRAME_SIZE {
+                return Err(CanError::OtherError(format!("invalid data length: {} of CAN frame", data.len())));
+            }
+            result.push('#');
+            if frame.is_remote() && !frame.is_error_frame() {
+                result.push('R');
+                if frame.length() > 0 {
+                    let _ = write!(result, "{}", frame.length());
+                }
+            }
+            else {
+                data.iter()
+                    .for_each(|b| { let _ = write!(result, "{:02X}", b); });
+            }
+        },
+        CanType::CanFd => {
+            if data.len() > MAX_FD_FRAME_SIZE {
+                return Err(CanError::OtherError(format!("invalid data length: {} of CAN-FD frame", data.len())));
+            }
+            let mut flags = CANFD_FDF;
+            if frame.is_bitrate_switch() {
+                flags |= CANFD_BRS;
+            }
+            if frame.is_esi() {
+                flags |= CANFD_ESI;
+            }
+            let _ = write!(result, "##{:X}", flags);
+            data.iter()
+                .for_each(|b| { let _ = write!(result, "{:02X}", b); });
+        },
+        CanType::CanXl => return Err(CanError::NotSupportedError),
+    }
+
+    Ok(result)
+}
+
+/// Parse a line of log or compact format, the format is detected automatically.
+///
+/// The timestamp is `0` when the line has no timestamp.
+pub fn parse_line<F>(line: &str) -> Result<F, CanError>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    _parse_line(line)
+        .map_err(CanError::OtherError)
+}
+
+/// Format a frame as a line(without line ending) of candump.
+pub fn format_line<F>(frame: &F, format: Format, timestamp: bool, extra: bool) -> Result<String, CanError>
+where
+    F: CanFrame + ?Sized,
+{
+    let iface = iface_name(frame.channel());
+    let mut result = String::new();
+    let ts = frame.timestamp();
+    match format {
+        Format::Log => {
+            let _ = write!(result, "({}.{:06}) {} {}", ts / 1000, ts % 1000 * 1000, iface, format_frame(frame)?);
+            if extra {
+                result.push_str(if frame.direct() == CanDirect::Transmit { " T" } else { " R" });
+            }
+        },
+        Format::Compact => {
+            if timestamp {
+                let _ = write!(result, "({}.{:06}) ", ts / 1000, ts % 1000 * 1000);
+            }
+            let _ = write!(result, "  {}  ", iface);
+            if extra {
+                let _ = write!(result, "{} {} {}  ",
+                               if frame.direct() == CanDirect::Transmit { "TX" } else { "RX" },
+                               if frame.is_bitrate_switch() { "B" } else { "-" },
+                               if frame.is_esi() { "E" } else { "-" });
+            }
+            result.push_str(&fmt_id(frame));
+            let data = frame.data();
+            match frame.can_type() {
+                CanType::Can => {
+                    if data.len() > MAX_FRAME_SIZE {
+                        return Err(CanError::OtherError(format!("invalid data length: {} of CAN frame", data.len())));
+                    }
+                    let _ = write!(result, "   [{}]", frame.length());
+                },
+                CanType::CanFd => {
+                    if data.len() > MAX_FD_FRAME_SIZE {
+                        return Err(CanError::OtherError(format!("invalid data length: {} of CAN-FD frame", data.len())));
+                    }
+                    let _ = write!(result, "  [{:02}]", data.len());
+                },
+                CanType::CanXl => return Err(CanError::NotSupportedError),
+            }
+            if frame.is_remote() && !frame.is_error_frame() {
+                result.push_str("  remote request");
+            }
+            else {
+                result.push(' ');
+                data.iter()
+                    .for_each(|b| { let _ = write!(result, " {:02X}", b); });
+                if frame.is_error_frame() {
+                    result.push_str("   ERRORFRAME");
+                }
+            }
+        },
+    }
+
+    Ok(result)
+}
+
+/// The writer of candump file.
+pub struct CandumpWriter<W: Write> {
+    writer: W,
+    format: Format,
+    timestamp: bool,
+    extra: bool,
+}
+
+impl CandumpWriter<BufWriter<File>> {
+    /// Create the file and the writer of it.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path)
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> CandumpWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: Default::default(),
+            timestamp: true,
+            extra: false,
+        }
+    }
+
+    pub fn set_format(&mut self, format: Format) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Write the timestamp in compact format(`candump -ta`), the log format always has timestamp.
+    pub fn set_timestamp(&mut self, enabled: bool) -> &mut Self {
+        self.timestamp = enabled;
+        self
+    }
+
+    /// Write the direction and flags like `candump -x`.
+    pub fn set_extra_info(&mut self, enabled: bool) -> &mut Self {
+        self.extra = enabled;
+        self
+    }
+
+    pub fn write_frame<F: CanFrame + ?Sized>(&mut self, frame: &F) -> Result<(), CanError> {
+        let line = format_line(frame, self.format, self.timestamp, self.extra)?;
+        writeln!(self.writer, "{}", line)
+            .map_err(|e| CanError::OperationError(e.to_string()))
+    }
+
+    pub fn flush(&mut self) -> Result<(), CanError> {
+        self.writer.flush()
+            .map_err(|e| CanError::OperationError(e.to_string()))
+    }
+}
+
+/// The reader of candump file, the lines of log and compact format can be mixed.
+pub struct CandumpReader<R: BufRead, F = CanMessage> {
+    reader: R,
+    line_no: usize,
+    _frame: PhantomData<F>,
+}
+
+impl<F> CandumpReader<BufReader<File>, F>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    /// Open the file and create the reader of it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path)
+            .map_err(|e| CanError::OperationError(e.to_string()))?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R, F> CandumpReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    pub fn new(reader: R) -> Self {
+        Self { reader, line_no: 0, _frame: Default::default() }
+    }
+}
+
+impl<R, F> Iterator for CandumpReader<R, F>
+where
+    R: BufRead,
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    type Item = Result<F, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => self.line_no += 1,
+                Err(e) => return Some(Err(CanError::OperationError(e.to_string()))),
+            }
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            return Some(_parse_line(&line)
+                .map_err(|e| CanError::OtherError(format!("candump line {}: {}", self.line_no, e))));
+        }
+    }
+}
+
+impl FromStr for CanMessage {
+    type Err = CanError;
+
+    /// Parse the message from a line of candump.
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_line(s)
+    }
+}
+
+/// The interface name of channel.
+fn iface_name(channel: impl ToString) -> String {
+    let channel = channel.to_string();
+    if channel.is_empty() || channel.chars().all(|c| c.is_ascii_digit()) {
+        format!("can{}", channel_number(channel))
+    }
+    else {
+        channel
+    }
+}
+
+fn parse_channel<C: FromStr>(iface: &str) -> Result<C, String> {
+    iface.parse()
+        .or_else(|_| channel_from_number(channel_number(iface)))
+        .map_err(|_| format!("invalid interface: {}", iface))
+}
+
+#[inline]
+fn fmt_id<F: CanFrame + ?Sized>(frame: &F) -> String {
+    let id = frame.id().into_bits();
+    if frame.is_error_frame() {
+        format!("{:08X}", (id & ERR_MASK) | IdentifierFlags::ERROR.bits())
+    }
+    else if frame.is_extended() {
+        format!("{:08X}", id)
+    }
+    else {
+        format!("{:03X}", id)
+    }
+}
+
+/// Parse identifier, the 3 digits is standard and the 8 digits is extended, return `(id, is_error)`
+fn parse_id(s: &str) -> Result<(CanId, bool), String> {
+    let id = u32::from_str_radix(s, 16)
+        .map_err(|_| format!("invalid identifier: {}", s))?;
+    match s.len() {
+        3 if id <= 0x7FF => Ok((CanId::Standard(id as u16), false)),
+        8 if id & IdentifierFlags::ERROR.bits() != 0 =>
+            Ok((CanId::from_bits(id & ERR_MASK, None), true)),
+        8 if id <= ERR_MASK => Ok((CanId::Extended(id), false)),
+        _ => Err(format!("invalid identifier: {}", s)),
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, String> {
+    // `.` is allowed as separator by `cansend`
+    let s = s.replace('.', "");
+    s.as_bytes()
+        .chunks(2)
+        .map(|v| std::str::from_utf8(v).ok()
+            .filter(|v| v.len() == 2)
+            .and_then(|v| u8::from_str_radix(v, 16).ok())
+            .ok_or(format!("invalid data: {}", s)))
+        .collect()
+}
+
+fn new_frame<F: CanFrame>(id: CanId, data: &[u8]) -> Result<F, String> {
+    F::new(id, data)
+        .ok_or(format!("invalid frame: {:?}", data))
+}
+
+fn _parse_frame<F: CanFrame>(s: &str) -> Result<F, String> {
+    let (id, rest) = s.split_once('#')
+        .ok_or(format!("invalid frame: {}", s))?;
+    let (id, error) = parse_id(id)?;
+
+    if let Some(rest) = rest.strip_prefix('#') {
+        let flags = rest.get(..1)
+            .and_then(|v| u8::from_str_radix(v, 16).ok())
+            .ok_or(format!("invalid CAN-FD flags: {}", rest))?;
+        let data = parse_hex(&rest[1..])?;
+        if data.len() > MAX_FD_FRAME_SIZE || can_utils::can_dlc(data.len(), CanType::CanFd) as usize != data.len() {
+            return Err(format!("invalid data length: {} of CAN-FD frame", data.len()));
+        }
+        let mut frame = new_frame::<F>(id, &data)?;
+        frame.set_can_type(CanType::CanFd)
+            .set_bitrate_switch(flags & CANFD_BRS != 0)
+            .set_esi(flags & CANFD_ESI != 0);
+        return Ok(frame);
+    }
+
+    if let Some(rest) = rest.strip_prefix(['R', 'r']) {
+        // the raw DLC after `_` is ignored
+        let len = rest.split('_').next().unwrap_or_default();
+        let len = match len {
+            "" => 0,
+            v => v.parse::<usize>()
+                .ok()
+                .filter(|v| *v <= MAX_FRAME_SIZE)
+                .ok_or(format!("invalid remote length: {}", v))?,
+        };
+        return F::new_remote(id, len)
+            .ok_or(format!("invalid remote length: {}", len));
+    }
+
+    let data = parse_hex(rest.split('_').next().unwrap_or_default())?;
+    if data.len() > MAX_FRAME_SIZE {
+        return Err(format!("invalid data length: {} of CAN frame", data.len()));
+    }
+    let mut frame = new_frame::<F>(id, &data)?;
+    if error {
+        frame.set_error_frame(true);
+    }
+    Ok(frame)
+}
+
+/// Parse the timestamp `(1690000000.123456)` to milliseconds.
+fn parse_timestamp(s: &str) -> Result<u64, String> {
+    let (sec, frac) = s.split_once('.')
+        .unwrap_or((s, ""));
+    let sec = sec.parse::<u64>()
+        .map_err(|_| format!("invalid timestamp: {}", s))?;
+    let mut frac = frac.chars()
+        .take(3)
+        .collect::<String>();
+    while frac.len() < 3 {
+        frac.push('0');
+    }
+    let millis = frac.parse::<u64>()
+        .map_err(|_| format!("invalid timestamp: {}", s))?;
+
+    Ok(sec * 1000 + millis)
+}
+
+fn _parse_line<F>(line: &str) -> Result<F, String>
+where
+    F: CanFrame,
+    F::Channel: FromStr,
+{
+    let mut line = line.trim();
+    let mut timestamp = 0;
+    if let Some(rest) = line.strip_prefix('(') {
+        let (ts, rest) = rest.split_once(')')
+            .ok_or(format!("invalid timestamp: {}", line))?;
+        timestamp = parse_timestamp(ts.trim())?;
+        line = rest;
+    }
+
+    let mut tokens = line.split_whitespace();
+    let iface = tokens.next()
+        .ok_or("interface is missing".to_string())?;
+    let token = tokens.next()
+        .ok_or("frame is missing".to_string())?;
+
+    let mut direct = CanDirect::Receive;
+    let mut frame = if token.contains('#') {
+        if let Some(v) = tokens.next() {
+            match v {
+                "T" => direct = CanDirect::Transmit,
+                "R" => {},
+                _ => return Err(format!("unexpected token: {}", v)),
+            }
+        }
+        _parse_frame::<F>(token)?
+    }
+    else {
+        parse_compact(token, &mut tokens, &mut direct)?
+    };
+
+    frame.set_timestamp(Some(timestamp))
+        .set_direct(direct)
+        .set_channel(parse_channel(iface)?);
+
+    Ok(frame)
+}
+
+fn parse_compact<'a, F: CanFrame>(
+    token: &'a str,
+    tokens: &mut impl Iterator<Item = &'a str>,
+    direct: &mut CanDirect,
+) -> Result<F, String> {
+    let mut token = token;
+    let (mut brs, mut esi) = (false, false);
+    if matches!(token, "RX" | "TX") {
+        if token == "TX" {
+            *direct = CanDirect::Transmit;
+        }
+        brs = tokens.next() == Some("B");
+        esi = tokens.next() == Some("E");
+        token = tokens.next()
+            .ok_or("identifier is missing".to_string())?;
+    }
+
+    let (id, error) = parse_id(token)?;
+    let length = tokens.next()
+        .and_then(|v| v.strip_prefix('['))
+        .and_then(|v| v.strip_suffix(']'))
+        .ok_or("length is missing".to_string())?;
+    // the length of CAN-FD frame has 2 digits
+    let fd = length.len() == 2;
+    let length = length.parse::<usize>()
+        .map_err(|_| format!("invalid length: {}", length))?;
+
+    let mut tokens = tokens.peekable();
+    if tokens.peek() == Some(&"remote") {
+        return F::new_remote(id, length)
+            .ok_or(format!("invalid remote length: {}", length));
+    }
+
+    let data = tokens.by_ref()
+        .take(length)
+        .map(|v| u8::from_str_radix(v, 16)
+            .map_err(|_| format!("invalid data: {}", v)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if data.len() != length {
+        return Err(format!("expected {} bytes data, but got {}", length, data.len()));
+    }
+    let error = error || tokens.next() == Some("ERRORFRAME");
+
+    let mut frame = new_frame::<F>(id, &data)?;
+    if fd {
+        frame.set_can_type(CanType::CanFd)
+            .set_bitrate_switch(brs)
+            .set_esi(esi);
+    }
+    if error {
+        frame.set_error_frame(true);
+    }
+
+    Ok(frame)
+}
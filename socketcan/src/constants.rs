@@ -2,3 +2,12 @@ pub const CANFD: &'static str = "canfd";
 pub const FILTERS: &'static str = "filters";
 pub const LOOPBACK: &'static str = "loopback";
 pub const RECV_OWN_MSG: &'static str = "recv-own-msg";
+/// The sample point(`u32`) in one-tenth of a percent of [`ChannelConfig`](rs_can::ChannelConfig).
+pub const SAMPLE_POINT: &str = "sample-point";
+pub const DATA_SAMPLE_POINT: &str = "data-sample-point";
+/// The explicit [`CanBitTiming`](crate::netlink::CanBitTiming) instead of bitrate.
+pub const BIT_TIMING: &str = "bit-timing";
+pub const DATA_BIT_TIMING: &str = "data-bit-timing";
+/// The [`CanCtrlMode`](crate::netlink::CanCtrlMode) to be enabled.
+pub const CTRL_MODE: &str = "ctrl-mode";
+pub const RESTART_MS: &str = "restart-ms";
@@ -1,7 +1,13 @@
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+pub use asynchronous::*;
+pub mod candump;
 mod constants;
 pub use constants::*;
 mod frame;
 pub use frame::*;
+pub mod netlink;
 mod socket;
 pub use socket::*;
 
@@ -47,36 +53,8 @@ impl SocketCan {
 
     pub fn read(&self, channel: &str) -> Result<CanMessage, CanError> {
         match self.sockets.get(channel) {
-            Some(s) => {
-                let mut buffer = [0; XL_FRAME_SIZE];
-
-                let rd = unsafe { read(
-                    s.as_raw_fd(),
-                    &mut buffer as *mut _ as *mut c_void,
-                    XL_FRAME_SIZE
-                ) };
-                match rd {
-                    FRAME_SIZE => {
-                        let frame = unsafe { *(&buffer as *const _ as *const can_frame) };
-                        let mut frame = CanMessage::from(CanAnyFrame::from(frame));
-                        frame.set_direct(CanDirect::Receive);
-                        Ok(frame)
-                    },
-                    FD_FRAME_SIZE => {
-                        let frame = unsafe { *(&buffer as *const _ as *const canfd_frame) };
-                        let mut frame = CanMessage::from(CanAnyFrame::from(frame));
-                        frame.set_direct(CanDirect::Receive);
-                        Ok(frame)
-                    },
-                    XL_FRAME_SIZE => {
-                        let frame = unsafe { *(&buffer as *const _ as *const canxl_frame) };
-                        let mut frame = CanMessage::from(CanAnyFrame::from(frame));
-                        frame.set_direct(CanDirect::Receive);
-                        Ok(frame)
-                    },
-                    _ => Err(CanError::OperationError(io::Error::last_os_error().to_string()))
-                }
-            },
+            Some(s) => raw_read_message(s.as_raw_fd())
+                .map_err(|e| CanError::OperationError(e.to_string())),
             None => Err(CanError::channel_not_opened(channel))
         }
     }
@@ -103,25 +81,8 @@ impl SocketCan {
     pub fn write(&self, msg: CanMessage) -> Result<(), CanError> {
         let channel = msg.channel();
         match self.sockets.get(&channel) {
-            Some(s) => {
-                let frame: CanAnyFrame = msg.into();
-                match frame {
-                    CanAnyFrame::Normal(f) |
-                    CanAnyFrame::Remote(f) |
-                    CanAnyFrame::Error(f) => {
-                        raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                            .map_err(|e| CanError::OtherError(e.to_string()))
-                    }
-                    CanAnyFrame::Fd(f) => {
-                        raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                            .map_err(|e| CanError::OtherError(e.to_string()))
-                    },
-                    CanAnyFrame::Xl(f) => {
-                        raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                            .map_err(|e| CanError::OtherError(e.to_string()))
-                    },
-                }
-            },
+            Some(s) => raw_write_message(s.as_raw_fd(), &msg.into())
+                .map_err(|e| CanError::OtherError(e.to_string())),
             None => Err(CanError::channel_not_opened(channel))
         }
     }
@@ -134,19 +95,7 @@ impl SocketCan {
         while start.elapsed() < timeout {
             match self.sockets.get(&channel) {
                 Some(s) => {
-                    if let Err(e) = match frame {
-                        CanAnyFrame::Normal(f) |
-                        CanAnyFrame::Remote(f) |
-                        CanAnyFrame::Error(f) => {
-                            raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                        }
-                        CanAnyFrame::Fd(f) => {
-                            raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                        },
-                        CanAnyFrame::Xl(f) => {
-                            raw_write_frame(s.as_raw_fd(), &f, frame.size())
-                        }
-                    } {
+                    if let Err(e) = raw_write_message(s.as_raw_fd(), &frame) {
                         match e.kind() {
                             io::ErrorKind::WouldBlock => {},
                             io::ErrorKind::Other =>
@@ -369,6 +318,40 @@ impl SocketCan {
     }
 }
 
+/// Read a single frame of any type from the socket, fd.
+pub(crate) fn raw_read_message(fd: c_int) -> io::Result<CanMessage> {
+    let mut buffer = [0; XL_FRAME_SIZE];
+
+    let rd = unsafe { read(
+        fd,
+        &mut buffer as *mut _ as *mut c_void,
+        XL_FRAME_SIZE
+    ) };
+    let frame = match rd {
+        ..0 => return Err(io::Error::last_os_error()),
+        rd => match rd as usize {
+            FRAME_SIZE => CanAnyFrame::from(unsafe { *(&buffer as *const _ as *const can_frame) }),
+            FD_FRAME_SIZE => CanAnyFrame::from(unsafe { *(&buffer as *const _ as *const canfd_frame) }),
+            XL_FRAME_SIZE => CanAnyFrame::from(unsafe { *(&buffer as *const _ as *const canxl_frame) }),
+            size => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid frame size: {}", size))),
+        },
+    };
+    let mut frame = CanMessage::from(frame);
+    frame.set_direct(CanDirect::Receive);
+    Ok(frame)
+}
+
+/// Write a single frame of any type to the socket, fd.
+pub(crate) fn raw_write_message(fd: c_int, frame: &CanAnyFrame) -> io::Result<()> {
+    match frame {
+        CanAnyFrame::Normal(f) |
+        CanAnyFrame::Remote(f) |
+        CanAnyFrame::Error(f) => raw_write_frame(fd, f, frame.size()),
+        CanAnyFrame::Fd(f) => raw_write_frame(fd, f, frame.size()),
+        CanAnyFrame::Xl(f) => raw_write_frame(fd, f, frame.size()),
+    }
+}
+
 impl TryFrom<DeviceBuilder> for SocketCan {
     type Error = CanError;
 
@@ -381,6 +364,8 @@ impl TryFrom<DeviceBuilder> for SocketCan {
         builder.channel_configs()
             .iter()
             .try_for_each(|(clh, cfg)| {
+                netlink::CanInterface::configure(clh, &cfg.try_into()?)?;
+
                 let canfd = builder.get_other::<bool>(CANFD)?
                     .unwrap_or_default();
                 device.init_channel(clh, canfd)?;
@@ -393,7 +378,7 @@ impl TryFrom<DeviceBuilder> for SocketCan {
                     device.set_loopback(clh, loopback)?;
                 }
 
-                if let Some(recv_own_msg) = builder.get_other::<bool>(RECV_OWN_MSG) {
+                if let Some(recv_own_msg) = builder.get_other::<bool>(RECV_OWN_MSG)? {
                     device.set_recv_own_msgs(clh, recv_own_msg)?;
                 }
 
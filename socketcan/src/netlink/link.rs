@@ -0,0 +1,423 @@
+use std::io;
+use bitflags::bitflags;
+use derive_getters::Getters;
+use libc::IFF_UP;
+use rs_can::{CanError, ChannelConfig};
+use crate::{BIT_TIMING, CTRL_MODE, DATA_BIT_TIMING, DATA_SAMPLE_POINT, RESTART_MS, SAMPLE_POINT};
+use super::{if_index, netlink_error, CanBerrCounter, CanDeviceStats, CanLinkStatus, CanState, LinkMessage, LinkStats, NetlinkSocket, IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINKINFO, NLM_F_ACK, RTM_GETLINK, RTM_NEWLINK};
+
+// the attributes of CAN link(linux/can/netlink.h)
+pub(crate) const IFLA_CAN_BITTIMING: u16 = 1;
+pub(crate) const IFLA_CAN_CTRLMODE: u16 = 5;
+pub(crate) const IFLA_CAN_RESTART_MS: u16 = 6;
+pub(crate) const IFLA_CAN_RESTART: u16 = 7;
+pub(crate) const IFLA_CAN_DATA_BITTIMING: u16 = 9;
+pub(crate) const IFLA_CAN_TERMINATION: u16 = 11;
+
+/// The kind of CAN link.
+pub const CAN_LINK_KIND: &str = "can";
+/// The resistance of termination in Ohm when it is enabled.
+pub const TERMINATION_ENABLED: u16 = 120;
+/// The length of `struct can_bittiming`.
+const BIT_TIMING_LEN: usize = 32;
+
+/// The bit-timing of CAN controller(`struct can_bittiming`).
+///
+/// The kernel calculates the other parameters when only `bitrate` and `sample_point` are set.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanBitTiming {
+    /// the bitrate in bits/second
+    pub bitrate: u32,
+    /// the sample point in one-tenth of a percent, 0 means the default of kernel
+    pub sample_point: u32,
+    /// the time quanta in nanoseconds
+    pub tq: u32,
+    pub prop_seg: u32,
+    pub phase_seg1: u32,
+    pub phase_seg2: u32,
+    /// the synchronisation jump width in time quanta
+    pub sjw: u32,
+    /// the bitrate prescaler
+    pub brp: u32,
+}
+
+impl CanBitTiming {
+    pub fn new(bitrate: u32, sample_point: Option<u32>) -> Self {
+        Self { bitrate, sample_point: sample_point.unwrap_or_default(), ..Default::default() }
+    }
+
+    pub fn encode(&self) -> [u8; BIT_TIMING_LEN] {
+        let mut data = [0x00; BIT_TIMING_LEN];
+        [self.bitrate, self.sample_point, self.tq, self.prop_seg, self.phase_seg1, self.phase_seg2, self.sjw, self.brp]
+            .iter()
+            .enumerate()
+            .for_each(|(i, v)| data[i * 4..(i + 1) * 4].copy_from_slice(&v.to_ne_bytes()));
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < BIT_TIMING_LEN {
+            return None;
+        }
+        let value = |i: usize| u32::from_ne_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
+        Some(Self {
+            bitrate: value(0),
+            sample_point: value(1),
+            tq: value(2),
+            prop_seg: value(3),
+            phase_seg1: value(4),
+            phase_seg2: value(5),
+            sjw: value(6),
+            brp: value(7),
+        })
+    }
+}
+
+bitflags! {
+    /// The control mode of CAN controller(`CAN_CTRLMODE_*`).
+    #[repr(transparent)]
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct CanCtrlMode: u32 {
+        const LOOPBACK = 0x01;
+        const LISTEN_ONLY = 0x02;
+        const TRIPLE_SAMPLING = 0x04;
+        const ONE_SHOT = 0x08;
+        const BERR_REPORTING = 0x10;
+        const FD = 0x20;
+        const PRESUME_ACK = 0x40;
+        const FD_NON_ISO = 0x80;
+        const CC_LEN8_DLC = 0x100;
+    }
+}
+
+/// The configuration of CAN link, the parameters which are not set are kept unchanged.
+#[derive(Debug, Default, Clone, Getters)]
+pub struct CanLinkConfig {
+    #[getter(copy)]
+    bit_timing: Option<CanBitTiming>,
+    #[getter(copy)]
+    data_bit_timing: Option<CanBitTiming>,
+    /// the control modes to be changed
+    #[getter(copy)]
+    ctrl_mask: CanCtrlMode,
+    /// the control modes to be enabled, which must be in `ctrl_mask`
+    #[getter(copy)]
+    ctrl_flags: CanCtrlMode,
+    #[getter(copy)]
+    restart_ms: Option<u32>,
+    #[getter(copy)]
+    termination: Option<u16>,
+}
+
+impl CanLinkConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the bitrate, the `sample_point` is in one-tenth of a percent.
+    #[inline]
+    pub fn set_bitrate(&mut self, bitrate: u32, sample_point: Option<u32>) -> &mut Self {
+        self.set_bit_timing(CanBitTiming::new(bitrate, sample_point))
+    }
+
+    pub fn set_bit_timing(&mut self, timing: CanBitTiming) -> &mut Self {
+        self.bit_timing = Some(timing);
+        self
+    }
+
+    /// Set the bitrate of data phase, the CAN-FD mode is enabled.
+    #[inline]
+    pub fn set_data_bitrate(&mut self, bitrate: u32, sample_point: Option<u32>) -> &mut Self {
+        self.set_data_bit_timing(CanBitTiming::new(bitrate, sample_point))
+    }
+
+    /// Set the bit-timing of data phase, the CAN-FD mode is enabled.
+    pub fn set_data_bit_timing(&mut self, timing: CanBitTiming) -> &mut Self {
+        self.data_bit_timing = Some(timing);
+        self.set_ctrl_mode(CanCtrlMode::FD, true)
+    }
+
+    pub fn set_ctrl_mode(&mut self, mode: CanCtrlMode, enabled: bool) -> &mut Self {
+        self.ctrl_mask |= mode;
+        self.ctrl_flags.set(mode, enabled);
+        self
+    }
+
+    /// Set the delay of automatic restart after bus-off, 0 means disabled.
+    pub fn set_restart_ms(&mut self, restart_ms: u32) -> &mut Self {
+        self.restart_ms = Some(restart_ms);
+        self
+    }
+
+    /// Set the resistance of termination in Ohm, 0 means disabled.
+    pub fn set_termination(&mut self, termination: u16) -> &mut Self {
+        self.termination = Some(termination);
+        self
+    }
+
+    /// Whether nothing is set.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bit_timing.is_none()
+            && self.data_bit_timing.is_none()
+            && self.ctrl_mask.is_empty()
+            && self.restart_ms.is_none()
+            && self.termination.is_none()
+    }
+
+    fn add_attrs(&self, msg: &mut LinkMessage) {
+        if !self.ctrl_mask.is_empty() {
+            let mut data = [0x00; 8];
+            data[..4].copy_from_slice(&self.ctrl_mask.bits().to_ne_bytes());
+            data[4..].copy_from_slice(&(self.ctrl_flags & self.ctrl_mask).bits().to_ne_bytes());
+            msg.add_attr(IFLA_CAN_CTRLMODE, &data);
+        }
+        if let Some(timing) = self.bit_timing {
+            msg.add_attr(IFLA_CAN_BITTIMING, &timing.encode());
+        }
+        if let Some(timing) = self.data_bit_timing {
+            msg.add_attr(IFLA_CAN_DATA_BITTIMING, &timing.encode());
+        }
+        if let Some(restart_ms) = self.restart_ms {
+            msg.add_u32(IFLA_CAN_RESTART_MS, restart_ms);
+        }
+        if let Some(termination) = self.termination {
+            msg.add_attr(IFLA_CAN_TERMINATION, &termination.to_ne_bytes());
+        }
+    }
+}
+
+impl TryFrom<&ChannelConfig> for CanLinkConfig {
+    type Error = CanError;
+
+    /// The bitrate of 0 is not set, and the termination is set by the resistance.
+    fn try_from(cfg: &ChannelConfig) -> Result<Self, Self::Error> {
+        let mut config = Self::new();
+        if let Some(timing) = cfg.get_other::<CanBitTiming>(BIT_TIMING)? {
+            config.set_bit_timing(timing);
+        }
+        else if cfg.bitrate() > 0 {
+            config.set_bitrate(cfg.bitrate(), cfg.get_other::<u32>(SAMPLE_POINT)?);
+        }
+        if let Some(timing) = cfg.get_other::<CanBitTiming>(DATA_BIT_TIMING)? {
+            config.set_data_bit_timing(timing);
+        }
+        else if let Some(bitrate) = cfg.dbitrate() {
+            config.set_data_bitrate(bitrate, cfg.get_other::<u32>(DATA_SAMPLE_POINT)?);
+        }
+        if let Some(mode) = cfg.get_other::<CanCtrlMode>(CTRL_MODE)? {
+            config.set_ctrl_mode(mode, true);
+        }
+        if let Some(restart_ms) = cfg.get_other::<u32>(RESTART_MS)? {
+            config.set_restart_ms(restart_ms);
+        }
+        if let Some(resistance) = cfg.resistance() {
+            config.set_termination(if resistance { TERMINATION_ENABLED } else { 0 });
+        }
+
+        Ok(config)
+    }
+}
+
+/// The network interface of SocketCAN, which is configured over rtnetlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanInterface {
+    name: String,
+    index: u32,
+}
+
+impl CanInterface {
+    pub fn open(name: &str) -> Result<Self, CanError> {
+        Ok(Self { name: name.into(), index: if_index(name)? })
+    }
+
+    #[inline(always)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline(always)]
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Get the status of link, including the state, error counters and statistics of CAN controller.
+    pub fn status(&self) -> Result<CanLinkStatus, CanError> {
+        let mut msg = LinkMessage::new(RTM_GETLINK, NLM_F_ACK, self.index);
+        self.request(&mut msg)?
+            .first()
+            .map(CanLinkStatus::parse)
+            .ok_or_else(|| CanError::OperationError(format!("no link status of interface: {}", self.name)))
+    }
+
+    /// Get the kind of link, such as `can`, `vcan` and `vxcan`.
+    #[inline]
+    pub fn kind(&self) -> Result<Option<String>, CanError> {
+        Ok(self.status()?.kind)
+    }
+
+    /// The interface is up.
+    #[inline]
+    pub fn is_up(&self) -> Result<bool, CanError> {
+        Ok(self.status()?.up)
+    }
+
+    /// Get the state of CAN controller, `None` is returned when the link is not a CAN link.
+    #[inline]
+    pub fn state(&self) -> Result<Option<CanState>, CanError> {
+        Ok(self.status()?.state)
+    }
+
+    /// Get the error counters, `None` is returned when the driver doesn't report them.
+    #[inline]
+    pub fn berr_counter(&self) -> Result<Option<CanBerrCounter>, CanError> {
+        Ok(self.status()?.berr_counter)
+    }
+
+    #[inline]
+    pub fn device_stats(&self) -> Result<Option<CanDeviceStats>, CanError> {
+        Ok(self.status()?.device_stats)
+    }
+
+    #[inline]
+    pub fn link_stats(&self) -> Result<Option<LinkStats>, CanError> {
+        Ok(self.status()?.link_stats)
+    }
+
+    #[inline]
+    pub fn up(&self) -> Result<(), CanError> {
+        self.set_up(true)
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    #[inline]
+    pub fn down(&self) -> Result<(), CanError> {
+        self.set_up(false)
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    /// Set the bitrate, the `sample_point` is in one-tenth of a percent.
+    #[inline]
+    pub fn set_bitrate(&self, bitrate: u32, sample_point: Option<u32>) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_bitrate(bitrate, sample_point))
+    }
+
+    #[inline]
+    pub fn set_bit_timing(&self, timing: CanBitTiming) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_bit_timing(timing))
+    }
+
+    /// Set the bitrate of data phase, the CAN-FD mode is enabled.
+    #[inline]
+    pub fn set_data_bitrate(&self, bitrate: u32, sample_point: Option<u32>) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_data_bitrate(bitrate, sample_point))
+    }
+
+    /// Set the bit-timing of data phase, the CAN-FD mode is enabled.
+    #[inline]
+    pub fn set_data_bit_timing(&self, timing: CanBitTiming) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_data_bit_timing(timing))
+    }
+
+    #[inline]
+    pub fn set_ctrl_mode(&self, mode: CanCtrlMode, enabled: bool) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_ctrl_mode(mode, enabled))
+    }
+
+    /// Set the delay of automatic restart after bus-off, 0 means disabled.
+    #[inline]
+    pub fn set_restart_ms(&self, restart_ms: u32) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_restart_ms(restart_ms))
+    }
+
+    /// Set the resistance of termination in Ohm, 0 means disabled.
+    #[inline]
+    pub fn set_termination(&self, termination: u16) -> Result<(), CanError> {
+        self.apply(CanLinkConfig::new().set_termination(termination))
+    }
+
+    /// Restart the controller manually, which is only allowed in bus-off state.
+    pub fn restart(&self) -> Result<(), CanError> {
+        let mut msg = self.can_message();
+        msg.add_u32(IFLA_CAN_RESTART, 1);
+        self.request(msg.end_nested().end_nested())
+            .map(|_| ())
+    }
+
+    /// Apply the configuration, the bit-timing can only be changed when the interface is down.
+    pub fn apply(&self, config: &CanLinkConfig) -> Result<(), CanError> {
+        self.apply_config(config)
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    /// Bring the interface down, apply the configuration and then bring it up.
+    pub fn reconfigure(&self, config: &CanLinkConfig) -> Result<(), CanError> {
+        self.reconfigure_link(config)
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    /// Reconfigure the CAN link by the configuration of [`DeviceBuilder`](rs_can::DeviceBuilder),
+    /// the links of other kinds(`vcan` and so on) are skipped, and so as the lack of `CAP_NET_ADMIN`.
+    pub(crate) fn configure(channel: &str, config: &CanLinkConfig) -> Result<(), CanError> {
+        if config.is_empty() {
+            return Ok(());
+        }
+        let iface = Self::open(channel)?;
+        match iface.kind()? {
+            Some(kind) if kind == CAN_LINK_KIND => {},
+            kind => {
+                log::debug!("RUST-CAN - interface: {} of kind: {:?} is not configured", channel, kind);
+                return Ok(());
+            },
+        }
+
+        match iface.reconfigure_link(config) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                log::warn!("RUST-CAN - {} when interface: {} configuring, the configuration is skipped", e, channel);
+                Ok(())
+            },
+            result => result.map_err(|e| netlink_error(e, channel)),
+        }
+    }
+
+    fn reconfigure_link(&self, config: &CanLinkConfig) -> io::Result<()> {
+        self.set_up(false)?;
+        let result = self.apply_config(config);
+        self.set_up(true)?;
+        result
+    }
+
+    fn set_up(&self, up: bool) -> io::Result<()> {
+        let mut msg = LinkMessage::new(RTM_NEWLINK, NLM_F_ACK, self.index);
+        msg.set_flags(if up { IFF_UP as u32 } else { 0 }, IFF_UP as u32);
+        NetlinkSocket::open(0)?
+            .request(&mut msg)
+            .map(|_| ())
+    }
+
+    fn apply_config(&self, config: &CanLinkConfig) -> io::Result<()> {
+        if config.is_empty() {
+            return Ok(());
+        }
+        let mut msg = self.can_message();
+        config.add_attrs(&mut msg);
+        NetlinkSocket::open(0)?
+            .request(msg.end_nested().end_nested())
+            .map(|_| ())
+    }
+
+    /// The message whose nested `IFLA_LINKINFO` and `IFLA_INFO_DATA` should be ended by caller.
+    fn can_message(&self) -> LinkMessage {
+        let mut msg = LinkMessage::new(RTM_NEWLINK, NLM_F_ACK, self.index);
+        msg.begin_nested(IFLA_LINKINFO)
+            .add_attr(IFLA_INFO_KIND, CAN_LINK_KIND.as_bytes())
+            .begin_nested(IFLA_INFO_DATA);
+        msg
+    }
+
+    fn request(&self, msg: &mut LinkMessage) -> Result<Vec<super::NetlinkMessage>, CanError> {
+        NetlinkSocket::open(0)
+            .and_then(|mut s| s.request(msg))
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+}
@@ -0,0 +1,317 @@
+//! The configuration and status of SocketCAN interfaces over rtnetlink, the same as `ip link set`
+//! and `ip -details -statistics link show`.
+//!
+//! The configuration needs the `CAP_NET_ADMIN` capability, and the bit-timing of
+//! a CAN interface can only be changed when the interface is down.
+//!
+//! ```no_run
+//! use socketcan_rs::netlink::{CanCtrlMode, CanInterface, CanLinkConfig};
+//!
+//! let iface = CanInterface::open("can0").unwrap();
+//! let mut config = CanLinkConfig::new();
+//! config.set_bitrate(500_000, Some(875))
+//!     .set_data_bitrate(2_000_000, None)
+//!     .set_ctrl_mode(CanCtrlMode::BERR_REPORTING, true)
+//!     .set_restart_ms(100);
+//! iface.reconfigure(&config).unwrap();
+//!
+//! let status = iface.status().unwrap();
+//! println!("{:?} {:?} {:?}", status.state, status.berr_counter, status.device_stats);
+//! ```
+mod link;
+pub use link::*;
+mod monitor;
+pub use monitor::*;
+mod status;
+pub use status::*;
+#[cfg(feature = "vcan")]
+mod vcan;
+#[cfg(feature = "vcan")]
+pub use vcan::*;
+
+use std::{ffi::CString, io, mem, os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd}};
+use libc::{bind, c_void, recv, send, sockaddr, sockaddr_nl, socket, AF_NETLINK, NETLINK_ROUTE, SOCK_CLOEXEC, SOCK_RAW};
+use rs_can::CanError;
+
+pub(crate) const RTM_NEWLINK: u16 = 16;
+pub(crate) const RTM_DELLINK: u16 = 17;
+pub(crate) const RTM_GETLINK: u16 = 18;
+
+pub(crate) const NLM_F_REQUEST: u16 = 0x01;
+pub(crate) const NLM_F_MULTI: u16 = 0x02;
+pub(crate) const NLM_F_ACK: u16 = 0x04;
+
+const NLMSG_ERROR: u16 = 0x02;
+const NLMSG_DONE: u16 = 0x03;
+/// The length of `struct nlmsghdr`.
+const NLMSG_HDRLEN: usize = 16;
+/// The length of `struct ifinfomsg`.
+pub(crate) const IFINFOMSG_LEN: usize = 16;
+/// The length of `struct rtattr`.
+const RTA_HDRLEN: usize = 4;
+/// The flags of attribute type, which are ignored when parsing.
+const NLA_TYPE_MASK: u16 = !(0x8000 | 0x4000);
+const RECV_BUFFER_SIZE: usize = 32 * 1024;
+
+// the attributes of link(linux/if_link.h)
+pub(crate) const IFLA_LINKINFO: u16 = 18;
+pub(crate) const IFLA_INFO_KIND: u16 = 1;
+pub(crate) const IFLA_INFO_DATA: u16 = 2;
+
+#[inline(always)]
+const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// The rtnetlink message of link, which starts with `struct ifinfomsg`.
+#[derive(Debug, Clone)]
+pub(crate) struct LinkMessage {
+    buffer: Vec<u8>,
+    nests: Vec<usize>,
+}
+
+impl LinkMessage {
+    pub(crate) fn new(r#type: u16, flags: u16, index: u32) -> Self {
+        let mut buffer = vec![0x00; NLMSG_HDRLEN + IFINFOMSG_LEN];
+        buffer[4..6].copy_from_slice(&r#type.to_ne_bytes());
+        buffer[6..8].copy_from_slice(&(flags | NLM_F_REQUEST).to_ne_bytes());
+        // the family is AF_UNSPEC
+        buffer[NLMSG_HDRLEN + 4..NLMSG_HDRLEN + 8].copy_from_slice(&index.to_ne_bytes());
+        Self { buffer, nests: Default::default() }
+    }
+
+    /// Set the `ifi_flags` and `ifi_change` of link.
+    pub(crate) fn set_flags(&mut self, flags: u32, change: u32) -> &mut Self {
+        self.buffer[NLMSG_HDRLEN + 8..NLMSG_HDRLEN + 12].copy_from_slice(&flags.to_ne_bytes());
+        self.buffer[NLMSG_HDRLEN + 12..NLMSG_HDRLEN + 16].copy_from_slice(&change.to_ne_bytes());
+        self
+    }
+
+    pub(crate) fn add_attr(&mut self, r#type: u16, data: &[u8]) -> &mut Self {
+        let len = RTA_HDRLEN + data.len();
+        self.buffer.extend((len as u16).to_ne_bytes());
+        self.buffer.extend(r#type.to_ne_bytes());
+        self.buffer.extend(data);
+        self.buffer.resize(align(self.buffer.len()), 0x00);
+        self
+    }
+
+    /// Append the data without attribute header, such as `struct ifinfomsg` of nested link.
+    #[cfg(feature = "vcan")]
+    pub(crate) fn add_raw(&mut self, data: &[u8]) -> &mut Self {
+        self.buffer.extend(data);
+        self.buffer.resize(align(self.buffer.len()), 0x00);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn add_u32(&mut self, r#type: u16, value: u32) -> &mut Self {
+        self.add_attr(r#type, &value.to_ne_bytes())
+    }
+
+    /// Begin the nested attribute, which must be ended by [`Self::end_nested`].
+    pub(crate) fn begin_nested(&mut self, r#type: u16) -> &mut Self {
+        self.nests.push(self.buffer.len());
+        self.add_attr(r#type, &[])
+    }
+
+    pub(crate) fn end_nested(&mut self) -> &mut Self {
+        if let Some(start) = self.nests.pop() {
+            let len = (self.buffer.len() - start) as u16;
+            self.buffer[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+        }
+        self
+    }
+
+    #[inline]
+    fn flags(&self) -> u16 {
+        u16::from_ne_bytes([self.buffer[6], self.buffer[7]])
+    }
+
+    pub(crate) fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buffer.len() as u32;
+        self.buffer[0..4].copy_from_slice(&len.to_ne_bytes());
+        self.buffer[8..12].copy_from_slice(&seq.to_ne_bytes());
+        &self.buffer
+    }
+}
+
+/// The message received from rtnetlink.
+#[derive(Debug, Clone)]
+pub(crate) struct NetlinkMessage {
+    pub(crate) r#type: u16,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl NetlinkMessage {
+    /// The `ifi_index` of link, the `payload` starts with `struct ifinfomsg`.
+    #[inline]
+    pub(crate) fn link_index(&self) -> Option<u32> {
+        self.payload.get(4..8)
+            .map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    /// The `ifi_flags` of link, the `payload` starts with `struct ifinfomsg`.
+    #[inline]
+    pub(crate) fn link_flags(&self) -> Option<u32> {
+        self.payload.get(8..12)
+            .map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+    }
+
+    /// The attributes of link.
+    #[inline]
+    pub(crate) fn link_attrs(&self) -> Vec<(u16, &[u8])> {
+        self.payload.get(IFINFOMSG_LEN..)
+            .map(parse_attrs)
+            .unwrap_or_default()
+    }
+}
+
+/// Parse the attributes, the flags of type are masked.
+pub(crate) fn parse_attrs(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attrs = Vec::new();
+    while data.len() >= RTA_HDRLEN {
+        let len = u16::from_ne_bytes([data[0], data[1]]) as usize;
+        let r#type = u16::from_ne_bytes([data[2], data[3]]) & NLA_TYPE_MASK;
+        if len < RTA_HDRLEN || len > data.len() {
+            break;
+        }
+        attrs.push((r#type, &data[RTA_HDRLEN..len]));
+        data = &data[align(len).min(data.len())..];
+    }
+    attrs
+}
+
+/// Find the attribute by type.
+#[inline]
+pub(crate) fn find_attr<'a>(attrs: &[(u16, &'a [u8])], r#type: u16) -> Option<&'a [u8]> {
+    attrs.iter()
+        .find(|(t, _)| *t == r#type)
+        .map(|(_, v)| *v)
+}
+
+/// The string attribute which may be ended with NUL.
+#[inline]
+pub(crate) fn attr_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&c| c == 0x00).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// The rtnetlink socket.
+#[derive(Debug)]
+pub(crate) struct NetlinkSocket {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl NetlinkSocket {
+    /// Open the socket which joins the multicast `groups`.
+    pub(crate) fn open(groups: u32) -> io::Result<Self> {
+        let fd = unsafe { socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_ROUTE) };
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = AF_NETLINK as u16;
+        addr.nl_groups = groups;
+        let ret = unsafe {
+            bind(fd.as_raw_fd(), &addr as *const _ as *const sockaddr, mem::size_of::<sockaddr_nl>() as u32)
+        };
+        if ret == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self { fd, seq: 0 })
+    }
+
+    /// Send the request and wait for the acknowledgement, the messages replied are returned.
+    pub(crate) fn request(&mut self, msg: &mut LinkMessage) -> io::Result<Vec<NetlinkMessage>> {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let data = msg.finish(seq);
+        let ret = unsafe { send(self.fd.as_raw_fd(), data.as_ptr() as *const c_void, data.len(), 0) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut results = Vec::new();
+        loop {
+            for (message, msg_seq, flags) in self.recv_messages()? {
+                if msg_seq != seq {
+                    continue;
+                }
+                match message.r#type {
+                    NLMSG_ERROR => {
+                        let code = message.payload.get(0..4)
+                            .map(|v| i32::from_ne_bytes([v[0], v[1], v[2], v[3]]))
+                            .unwrap_or_default();
+                        return match code {
+                            0 => Ok(results),
+                            code => Err(io::Error::from_raw_os_error(-code)),
+                        };
+                    },
+                    NLMSG_DONE => return Ok(results),
+                    _ => {
+                        let multi = flags & NLM_F_MULTI != 0;
+                        results.push(message);
+                        if !multi && msg.flags() & NLM_F_ACK == 0 {
+                            return Ok(results);
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Receive the messages, each with the sequence number and flags.
+    pub(crate) fn recv_messages(&self) -> io::Result<Vec<(NetlinkMessage, u32, u16)>> {
+        let mut buffer = vec![0x00; RECV_BUFFER_SIZE];
+        let size = unsafe { recv(self.fd.as_raw_fd(), buffer.as_mut_ptr() as *mut c_void, buffer.len(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut data = &buffer[..size as usize];
+        let mut results = Vec::new();
+        while data.len() >= NLMSG_HDRLEN {
+            let len = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]) as usize;
+            if len < NLMSG_HDRLEN || len > data.len() {
+                break;
+            }
+            let r#type = u16::from_ne_bytes([data[4], data[5]]);
+            let flags = u16::from_ne_bytes([data[6], data[7]]);
+            let seq = u32::from_ne_bytes([data[8], data[9], data[10], data[11]]);
+            results.push((NetlinkMessage { r#type, payload: data[NLMSG_HDRLEN..len].to_vec() }, seq, flags));
+            data = &data[align(len).min(data.len())..];
+        }
+
+        Ok(results)
+    }
+}
+
+impl AsFd for NetlinkSocket {
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+/// Get the index of interface by name.
+pub(crate) fn if_index(name: &str) -> Result<u32, CanError> {
+    let c_name = CString::new(name)
+        .map_err(|e| CanError::InitializeError(e.to_string()))?;
+    match unsafe { libc::if_nametoindex(c_name.as_ptr()) } {
+        0 => Err(CanError::InitializeError(format!("{} of interface: {}", io::Error::last_os_error(), name))),
+        index => Ok(index),
+    }
+}
+
+#[inline]
+pub(crate) fn netlink_error(e: io::Error, iface: &str) -> CanError {
+    match e.kind() {
+        io::ErrorKind::PermissionDenied => CanError::OperationError(format!("{} when interface: {} configuring, CAP_NET_ADMIN is required", e, iface)),
+        _ => CanError::OperationError(format!("{} when interface: {} configuring", e, iface)),
+    }
+}
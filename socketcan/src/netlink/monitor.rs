@@ -0,0 +1,146 @@
+use std::{io, os::fd::AsFd, sync::{atomic::{AtomicBool, Ordering}, Arc}, thread::{self, JoinHandle}};
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use rs_can::CanError;
+use super::{CanLinkStatus, NetlinkSocket, RTM_DELLINK, RTM_NEWLINK};
+
+/// The multicast group of link(`RTMGRP_LINK`).
+const RTMGRP_LINK: u32 = 0x01;
+/// The timeout in milliseconds of each receiving by the monitor thread.
+const RECEIVE_TIMEOUT: u32 = 10;
+
+/// The event of link reported by rtnetlink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkEvent {
+    /// The link is added or changed, such as up, down, bus-off and restarted.
+    Changed(Box<CanLinkStatus>),
+    /// The link is removed.
+    Removed { index: u32, name: String },
+}
+
+impl LinkEvent {
+    #[inline]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Changed(status) => &status.name,
+            Self::Removed { name, .. } => name,
+        }
+    }
+}
+
+/// The monitor of link events, which subscribes the link group of rtnetlink.
+///
+/// The kernel notifies when the carrier of CAN link is changed, so the controller going
+/// bus-off or being restarted is reported, but the changes of error-warning or error-passive
+/// are not notified and should be polled by [`CanInterface::state`](super::CanInterface::state).
+///
+/// ```no_run
+/// use socketcan_rs::netlink::{LinkEvent, LinkMonitor};
+///
+/// let handle = LinkMonitor::open().unwrap()
+///     .spawn(|event| if let LinkEvent::Changed(status) = event {
+///         if status.name == "can0" && (status.is_bus_off() || !status.running) {
+///             eprintln!("can0 is bus-off");
+///         }
+///     });
+/// // ...
+/// handle.stop();
+/// ```
+#[derive(Debug)]
+pub struct LinkMonitor {
+    socket: NetlinkSocket,
+}
+
+impl LinkMonitor {
+    pub fn open() -> Result<Self, CanError> {
+        let socket = NetlinkSocket::open(RTMGRP_LINK)
+            .map_err(|e| CanError::InitializeError(format!("{} when link monitor opening", e)))?;
+        Ok(Self { socket })
+    }
+
+    /// Receive the events, the result is empty when timeout.
+    pub fn recv(&self, timeout: Option<u32>) -> Result<Vec<LinkEvent>, CanError> {
+        self.recv_events(timeout)
+            .map_err(|e| CanError::OperationError(format!("{} when link events receiving", e)))
+    }
+
+    /// Receive the events in background and call the `callback` with each event.
+    pub fn spawn<F>(self, mut callback: F) -> LinkMonitorHandle
+    where
+        F: FnMut(LinkEvent) + Send + 'static,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let flag = Arc::clone(&running);
+        let handle = thread::spawn(move || {
+            while flag.load(Ordering::Acquire) {
+                match self.recv(Some(RECEIVE_TIMEOUT)) {
+                    Ok(events) => events.into_iter().for_each(&mut callback),
+                    Err(e) => {
+                        log::warn!("RUST-CAN - {}", e);
+                        thread::sleep(std::time::Duration::from_millis(RECEIVE_TIMEOUT as u64));
+                    },
+                }
+            }
+        });
+
+        LinkMonitorHandle { running, handle: Some(handle) }
+    }
+
+    fn recv_events(&self, timeout: Option<u32>) -> io::Result<Vec<LinkEvent>> {
+        let timeout = match timeout {
+            Some(v) => PollTimeout::try_from(v).unwrap_or(PollTimeout::MAX),
+            None => PollTimeout::NONE,
+        };
+        let pollfd = PollFd::new(self.socket.as_fd(), PollFlags::POLLIN);
+        if poll(&mut [pollfd], timeout)? == 0 {
+            return Ok(Vec::new());
+        }
+
+        let events = self.socket.recv_messages()?
+            .into_iter()
+            .filter_map(|(msg, _, _)| match msg.r#type {
+                RTM_NEWLINK => Some(LinkEvent::Changed(Box::new(CanLinkStatus::parse(&msg)))),
+                RTM_DELLINK => {
+                    let status = CanLinkStatus::parse(&msg);
+                    Some(LinkEvent::Removed { index: status.index, name: status.name })
+                },
+                _ => None,
+            })
+            .collect();
+
+        Ok(events)
+    }
+}
+
+/// The handle of [`LinkMonitor`] running in background, the monitor is stopped when dropped.
+#[derive(Debug)]
+pub struct LinkMonitorHandle {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LinkMonitorHandle {
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn stop(mut self) {
+        self.stop_monitor();
+    }
+
+    fn stop_monitor(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - link monitor panicked");
+            }
+        }
+    }
+}
+
+impl Drop for LinkMonitorHandle {
+    fn drop(&mut self) {
+        self.stop_monitor();
+    }
+}
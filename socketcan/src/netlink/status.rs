@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use libc::{IFF_RUNNING, IFF_UP};
+use rs_can::CanError;
+use super::{attr_string, find_attr, parse_attrs, CanBitTiming, CanCtrlMode, NetlinkMessage, IFLA_CAN_BITTIMING, IFLA_CAN_CTRLMODE, IFLA_CAN_DATA_BITTIMING, IFLA_CAN_RESTART_MS, IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINKINFO};
+
+// the attributes of link(linux/if_link.h)
+pub(crate) const IFLA_IFNAME: u16 = 3;
+pub(crate) const IFLA_STATS64: u16 = 23;
+pub(crate) const IFLA_INFO_XSTATS: u16 = 3;
+// the attributes of CAN link(linux/can/netlink.h)
+pub(crate) const IFLA_CAN_STATE: u16 = 4;
+pub(crate) const IFLA_CAN_BERR_COUNTER: u16 = 8;
+
+#[inline(always)]
+fn read_u32(data: &[u8], i: usize) -> u32 {
+    u32::from_ne_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]])
+}
+
+#[inline(always)]
+fn read_u64(data: &[u8], i: usize) -> u64 {
+    let mut value = [0x00; 8];
+    value.copy_from_slice(&data[i * 8..(i + 1) * 8]);
+    u64::from_ne_bytes(value)
+}
+
+/// The state of CAN controller(`enum can_state`).
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanState {
+    /// RX/TX error count < 96
+    ErrorActive = 0,
+    /// RX/TX error count < 128
+    ErrorWarning = 1,
+    /// RX/TX error count < 256
+    ErrorPassive = 2,
+    /// RX/TX error count >= 256
+    BusOff = 3,
+    /// the device is stopped
+    Stopped = 4,
+    /// the device is sleeping
+    Sleeping = 5,
+}
+
+impl TryFrom<u32> for CanState {
+    type Error = CanError;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::ErrorActive),
+            1 => Ok(Self::ErrorWarning),
+            2 => Ok(Self::ErrorPassive),
+            3 => Ok(Self::BusOff),
+            4 => Ok(Self::Stopped),
+            5 => Ok(Self::Sleeping),
+            _ => Err(CanError::OtherError(format!("CAN state: {} is not supported", value))),
+        }
+    }
+}
+
+impl Display for CanState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            Self::ErrorActive => "ERROR-ACTIVE",
+            Self::ErrorWarning => "ERROR-WARNING",
+            Self::ErrorPassive => "ERROR-PASSIVE",
+            Self::BusOff => "BUS-OFF",
+            Self::Stopped => "STOPPED",
+            Self::Sleeping => "SLEEPING",
+        };
+        f.pad(state)
+    }
+}
+
+/// The error counters of CAN controller(`struct can_berr_counter`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanBerrCounter {
+    pub tx_errors: u16,
+    pub rx_errors: u16,
+}
+
+impl CanBerrCounter {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 4 {
+            return None;
+        }
+        Some(Self {
+            tx_errors: u16::from_ne_bytes([data[0], data[1]]),
+            rx_errors: u16::from_ne_bytes([data[2], data[3]]),
+        })
+    }
+}
+
+/// The statistics of CAN device(`struct can_device_stats`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanDeviceStats {
+    /// the count of bus errors
+    pub bus_error: u32,
+    /// the count of changes to error warning state
+    pub error_warning: u32,
+    /// the count of changes to error passive state
+    pub error_passive: u32,
+    /// the count of changes to bus off state
+    pub bus_off: u32,
+    /// the count of arbitration lost errors
+    pub arbitration_lost: u32,
+    /// the count of restarts of controller
+    pub restarts: u32,
+}
+
+impl CanDeviceStats {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 24 {
+            return None;
+        }
+        Some(Self {
+            bus_error: read_u32(data, 0),
+            error_warning: read_u32(data, 1),
+            error_passive: read_u32(data, 2),
+            bus_off: read_u32(data, 3),
+            arbitration_lost: read_u32(data, 4),
+            restarts: read_u32(data, 5),
+        })
+    }
+}
+
+/// The generic statistics of link(`struct rtnl_link_stats64`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+    /// the count of receiver overruns
+    pub rx_over_errors: u64,
+    pub rx_crc_errors: u64,
+    pub rx_frame_errors: u64,
+    /// the count of FIFO overruns of receiver
+    pub rx_fifo_errors: u64,
+    pub rx_missed_errors: u64,
+    pub tx_aborted_errors: u64,
+    pub tx_fifo_errors: u64,
+}
+
+impl LinkStats {
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        // the fields until `tx_fifo_errors`
+        if data.len() < 19 * 8 {
+            return None;
+        }
+        Some(Self {
+            rx_packets: read_u64(data, 0),
+            tx_packets: read_u64(data, 1),
+            rx_bytes: read_u64(data, 2),
+            tx_bytes: read_u64(data, 3),
+            rx_errors: read_u64(data, 4),
+            tx_errors: read_u64(data, 5),
+            rx_dropped: read_u64(data, 6),
+            tx_dropped: read_u64(data, 7),
+            rx_over_errors: read_u64(data, 11),
+            rx_crc_errors: read_u64(data, 12),
+            rx_frame_errors: read_u64(data, 13),
+            rx_fifo_errors: read_u64(data, 14),
+            rx_missed_errors: read_u64(data, 15),
+            tx_aborted_errors: read_u64(data, 16),
+            tx_fifo_errors: read_u64(data, 18),
+        })
+    }
+}
+
+/// The status of link reported by rtnetlink, the CAN specific fields are `None` for other links.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CanLinkStatus {
+    pub index: u32,
+    pub name: String,
+    /// the kind of link, such as `can`, `vcan` and `vxcan`
+    pub kind: Option<String>,
+    /// the interface is up
+    pub up: bool,
+    /// the carrier is on, which is off when the controller is bus-off or stopped
+    pub running: bool,
+    pub state: Option<CanState>,
+    pub berr_counter: Option<CanBerrCounter>,
+    pub bit_timing: Option<CanBitTiming>,
+    pub data_bit_timing: Option<CanBitTiming>,
+    pub ctrl_mode: Option<CanCtrlMode>,
+    pub restart_ms: Option<u32>,
+    pub device_stats: Option<CanDeviceStats>,
+    pub link_stats: Option<LinkStats>,
+}
+
+impl CanLinkStatus {
+    pub(crate) fn parse(msg: &NetlinkMessage) -> Self {
+        let flags = msg.link_flags().unwrap_or_default();
+        let attrs = msg.link_attrs();
+        let info = find_attr(&attrs, IFLA_LINKINFO)
+            .map(parse_attrs)
+            .unwrap_or_default();
+        let data = find_attr(&info, IFLA_INFO_DATA)
+            .map(parse_attrs)
+            .unwrap_or_default();
+        let data_u32 = |r#type| find_attr(&data, r#type)
+            .filter(|v| v.len() >= 4)
+            .map(|v| read_u32(v, 0));
+
+        Self {
+            index: msg.link_index().unwrap_or_default(),
+            name: find_attr(&attrs, IFLA_IFNAME).map(attr_string).unwrap_or_default(),
+            kind: find_attr(&info, IFLA_INFO_KIND).map(attr_string),
+            up: flags & IFF_UP as u32 != 0,
+            running: flags & IFF_RUNNING as u32 != 0,
+            state: data_u32(IFLA_CAN_STATE).and_then(|v| CanState::try_from(v).ok()),
+            berr_counter: find_attr(&data, IFLA_CAN_BERR_COUNTER).and_then(CanBerrCounter::decode),
+            bit_timing: find_attr(&data, IFLA_CAN_BITTIMING).and_then(CanBitTiming::decode),
+            data_bit_timing: find_attr(&data, IFLA_CAN_DATA_BITTIMING).and_then(CanBitTiming::decode),
+            // the flags of `struct can_ctrlmode`
+            ctrl_mode: find_attr(&data, IFLA_CAN_CTRLMODE)
+                .filter(|v| v.len() >= 8)
+                .map(|v| CanCtrlMode::from_bits_retain(read_u32(v, 1))),
+            restart_ms: data_u32(IFLA_CAN_RESTART_MS),
+            device_stats: find_attr(&info, IFLA_INFO_XSTATS).and_then(CanDeviceStats::decode),
+            link_stats: find_attr(&attrs, IFLA_STATS64).and_then(LinkStats::decode),
+        }
+    }
+
+    /// The controller is bus-off.
+    #[inline]
+    pub fn is_bus_off(&self) -> bool {
+        matches!(self.state, Some(CanState::BusOff))
+    }
+}
@@ -0,0 +1,299 @@
+use std::{fs::File, io, os::fd::AsRawFd, path::{Path, PathBuf}, thread};
+use derive_getters::Getters;
+use libc::{CLONE_NEWNET, IFF_UP};
+use rs_can::CanError;
+use super::{netlink_error, LinkMessage, NetlinkSocket, IFLA_IFNAME, IFLA_INFO_DATA, IFLA_INFO_KIND, IFLA_LINKINFO, IFINFOMSG_LEN, NLM_F_ACK, RTM_DELLINK, RTM_NEWLINK};
+
+pub(crate) const NLM_F_EXCL: u16 = 0x200;
+pub(crate) const NLM_F_CREATE: u16 = 0x400;
+// the attributes of link(linux/if_link.h)
+pub(crate) const IFLA_MTU: u16 = 4;
+pub(crate) const IFLA_NET_NS_FD: u16 = 28;
+pub(crate) const VXCAN_INFO_PEER: u16 = 1;
+
+/// The MTU of classical CAN(`sizeof(struct can_frame)`).
+pub const CAN_MTU: u32 = 16;
+/// The MTU of CAN FD(`sizeof(struct canfd_frame)`).
+pub const CANFD_MTU: u32 = 72;
+/// The maximum MTU of CAN XL(`CANXL_MAX_MTU`).
+pub const CANXL_MTU: u32 = 2060;
+
+/// The kind of virtual CAN link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VirtualKind {
+    /// The virtual CAN interface, the frames sent are received by all sockets of the interface.
+    Vcan,
+    /// The virtual CAN tunnel, a pair of interfaces which frames sent by one end are received by the peer.
+    Vxcan,
+}
+
+impl VirtualKind {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vcan => "vcan",
+            Self::Vxcan => "vxcan",
+        }
+    }
+}
+
+/// The configuration of virtual CAN link.
+#[derive(Debug, Clone, Getters)]
+pub struct VirtualLinkConfig {
+    /// the MTU, such as [`CANFD_MTU`], the default of kernel is used when `None`
+    #[getter(copy)]
+    mtu: Option<u32>,
+    /// the network namespace file where the link is created, such as `/var/run/netns/test`
+    netns: Option<PathBuf>,
+    /// the network namespace file where the peer of vxcan is moved to
+    peer_netns: Option<PathBuf>,
+    /// bring the link up after created
+    #[getter(copy)]
+    up: bool,
+}
+
+impl Default for VirtualLinkConfig {
+    fn default() -> Self {
+        Self { mtu: None, netns: None, peer_netns: None, up: true }
+    }
+}
+
+impl VirtualLinkConfig {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn set_mtu(&mut self, mtu: u32) -> &mut Self {
+        self.mtu = Some(mtu);
+        self
+    }
+
+    #[inline]
+    pub fn set_netns<P: Into<PathBuf>>(&mut self, netns: P) -> &mut Self {
+        self.netns = Some(netns.into());
+        self
+    }
+
+    #[inline]
+    pub fn set_peer_netns<P: Into<PathBuf>>(&mut self, netns: P) -> &mut Self {
+        self.peer_netns = Some(netns.into());
+        self
+    }
+
+    #[inline]
+    pub fn set_up(&mut self, up: bool) -> &mut Self {
+        self.up = up;
+        self
+    }
+}
+
+/// The virtual CAN link created over rtnetlink, which is deleted when dropped.
+///
+/// The creation needs the `CAP_NET_ADMIN` capability and the `vcan` or `vxcan` kernel module.
+///
+/// ```no_run
+/// use socketcan_rs::netlink::{VirtualLink, VirtualLinkConfig, CANFD_MTU};
+///
+/// let mut config = VirtualLinkConfig::new();
+/// config.set_mtu(CANFD_MTU);
+/// // the same as `ip link add dev vcan-test type vcan && ip link set vcan-test mtu 72 up`
+/// let link = VirtualLink::vcan("vcan-test", &config).unwrap();
+/// // ...
+/// drop(link);
+/// ```
+#[derive(Debug)]
+pub struct VirtualLink {
+    kind: VirtualKind,
+    name: String,
+    peer: Option<String>,
+    netns: Option<PathBuf>,
+    persistent: bool,
+}
+
+impl VirtualLink {
+    /// Create the vcan interface.
+    pub fn vcan(name: &str, config: &VirtualLinkConfig) -> Result<Self, CanError> {
+        let netns = config.netns.as_deref();
+        let mut msg = create_message(name, config);
+        msg.begin_nested(IFLA_LINKINFO)
+            .add_attr(IFLA_INFO_KIND, VirtualKind::Vcan.as_str().as_bytes())
+            .end_nested();
+        in_netns(netns, move || NetlinkSocket::open(0)?.request(&mut msg))
+            .map_err(|e| netlink_error(e, name))?;
+
+        Ok(Self {
+            kind: VirtualKind::Vcan,
+            name: name.into(),
+            peer: None,
+            netns: config.netns.clone(),
+            persistent: false,
+        })
+    }
+
+    /// Create the vxcan pair, the peer is moved to `peer_netns` when it is set.
+    pub fn vxcan(name: &str, peer: &str, config: &VirtualLinkConfig) -> Result<Self, CanError> {
+        let netns = config.netns.as_deref();
+        let peer_ns = config.peer_netns.as_deref()
+            .map(File::open)
+            .transpose()
+            .map_err(|e| netlink_error(e, peer))?;
+
+        let mut msg = create_message(name, config);
+        msg.begin_nested(IFLA_LINKINFO)
+            .add_attr(IFLA_INFO_KIND, VirtualKind::Vxcan.as_str().as_bytes())
+            .begin_nested(IFLA_INFO_DATA)
+            .begin_nested(VXCAN_INFO_PEER);
+        // the peer starts with `struct ifinfomsg`
+        msg.add_raw(&peer_ifinfomsg(config.up));
+        msg.add_attr(IFLA_IFNAME, &c_string(peer));
+        if let Some(mtu) = config.mtu {
+            msg.add_u32(IFLA_MTU, mtu);
+        }
+        if let Some(file) = &peer_ns {
+            msg.add_u32(IFLA_NET_NS_FD, file.as_raw_fd() as u32);
+        }
+        msg.end_nested()
+            .end_nested()
+            .end_nested();
+        in_netns(netns, move || NetlinkSocket::open(0)?.request(&mut msg))
+            .map_err(|e| netlink_error(e, name))?;
+
+        Ok(Self {
+            kind: VirtualKind::Vxcan,
+            name: name.into(),
+            peer: Some(peer.into()),
+            netns: config.netns.clone(),
+            persistent: false,
+        })
+    }
+
+    #[inline(always)]
+    pub fn kind(&self) -> VirtualKind {
+        self.kind
+    }
+
+    #[inline(always)]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name of peer when the link is a vxcan pair.
+    #[inline(always)]
+    pub fn peer(&self) -> Option<&str> {
+        self.peer.as_deref()
+    }
+
+    #[inline(always)]
+    pub fn netns(&self) -> Option<&Path> {
+        self.netns.as_deref()
+    }
+
+    /// Set the MTU, the link is brought down while setting and then up.
+    pub fn set_mtu(&self, mtu: u32) -> Result<(), CanError> {
+        let name = self.name.clone();
+        in_netns(self.netns.as_deref(), move || {
+            let mut socket = NetlinkSocket::open(0)?;
+            socket.request(&mut link_message(&name, Some(false)))?;
+            socket.request(link_message(&name, None).add_u32(IFLA_MTU, mtu))?;
+            socket.request(&mut link_message(&name, Some(true)))
+        })
+        .map(|_| ())
+        .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    /// Delete the link, the peer of vxcan is deleted together.
+    #[inline]
+    pub fn delete(mut self) -> Result<(), CanError> {
+        self.persistent = true;
+        self.delete_link()
+            .map_err(|e| netlink_error(e, &self.name))
+    }
+
+    /// Keep the link after dropped, the name is returned.
+    #[inline]
+    pub fn persist(mut self) -> String {
+        self.persistent = true;
+        std::mem::take(&mut self.name)
+    }
+
+    fn delete_link(&self) -> io::Result<()> {
+        let mut msg = LinkMessage::new(RTM_DELLINK, NLM_F_ACK, 0);
+        msg.add_attr(IFLA_IFNAME, &c_string(&self.name));
+        in_netns(self.netns.as_deref(), move || NetlinkSocket::open(0)?.request(&mut msg))
+            .map(|_| ())
+    }
+}
+
+impl Drop for VirtualLink {
+    fn drop(&mut self) {
+        if !self.persistent {
+            if let Err(e) = self.delete_link() {
+                log::warn!("RUST-CAN - {} when interface: {} deleting", e, self.name);
+            }
+        }
+    }
+}
+
+#[inline]
+fn c_string(name: &str) -> Vec<u8> {
+    let mut data = name.as_bytes().to_vec();
+    data.push(0x00);
+    data
+}
+
+/// The message of link by name, the link is brought up or down when `up` is set.
+fn link_message(name: &str, up: Option<bool>) -> LinkMessage {
+    let mut msg = LinkMessage::new(RTM_NEWLINK, NLM_F_ACK, 0);
+    if let Some(up) = up {
+        msg.set_flags(if up { IFF_UP as u32 } else { 0 }, IFF_UP as u32);
+    }
+    msg.add_attr(IFLA_IFNAME, &c_string(name));
+    msg
+}
+
+fn create_message(name: &str, config: &VirtualLinkConfig) -> LinkMessage {
+    let mut msg = LinkMessage::new(RTM_NEWLINK, NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL, 0);
+    if config.up {
+        msg.set_flags(IFF_UP as u32, IFF_UP as u32);
+    }
+    msg.add_attr(IFLA_IFNAME, &c_string(name));
+    if let Some(mtu) = config.mtu {
+        msg.add_u32(IFLA_MTU, mtu);
+    }
+    msg
+}
+
+/// The `struct ifinfomsg` of vxcan peer.
+fn peer_ifinfomsg(up: bool) -> [u8; IFINFOMSG_LEN] {
+    let mut data = [0x00; IFINFOMSG_LEN];
+    if up {
+        data[8..12].copy_from_slice(&(IFF_UP as u32).to_ne_bytes());
+        data[12..16].copy_from_slice(&(IFF_UP as u32).to_ne_bytes());
+    }
+    data
+}
+
+/// Run `f` in the network namespace, a thread is spawned to enter the namespace
+/// so that the namespace of caller is not changed.
+fn in_netns<T, F>(netns: Option<&Path>, f: F) -> io::Result<T>
+where
+    T: Send,
+    F: FnOnce() -> io::Result<T> + Send,
+{
+    match netns {
+        None => f(),
+        Some(path) => thread::scope(|s| {
+            s.spawn(|| {
+                let file = File::open(path)?;
+                if unsafe { libc::setns(file.as_raw_fd(), CLONE_NEWNET) } == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                f()
+            })
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("network namespace thread panicked")))
+        }),
+    }
+}
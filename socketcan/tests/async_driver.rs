@@ -0,0 +1,29 @@
+#![cfg(feature = "async")]
+
+use std::{future::poll_fn, pin::Pin};
+use futures_core::Stream;
+use rs_can::{AsyncCanDevice, CanError, CanFrame};
+use socketcan_rs::{AsyncSocketCan, CanMessage, SocketCan};
+
+#[tokio::test]
+async fn test_async_driver() -> anyhow::Result<(), CanError> {
+    let iface = "vcan0";
+    let mut sock = SocketCan::new();
+    sock.init_channel(iface, true)?;
+    sock.set_loopback(iface, true)?;
+    sock.set_recv_own_msgs(iface, true)?;
+    let sock = AsyncSocketCan::new(sock)?;
+    let mut stream = sock.stream(iface.to_string())?;
+
+    let data = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+    let mut message = CanMessage::new(0x1234, &data).unwrap();
+    message.set_channel(iface.to_string());
+    sock.transmit(message, Some(100)).await?;
+
+    let frame = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        .unwrap()?;
+    println!("{}", frame);
+    assert_eq!(frame.data(), data);
+
+    Ok(())
+}
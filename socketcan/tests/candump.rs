@@ -0,0 +1,109 @@
+use std::io::Cursor;
+use rs_can::{CanDirect, CanFrame, CanId, CanType};
+use socketcan_rs::{CanMessage, candump::{self, CandumpReader, CandumpWriter, Format}};
+
+const LOG: &str = r#"(1690000000.123456) vcan0 123#DEADBEEF
+(1690000000.124000) vcan0 12345678#
+(1690000000.125000) vcan1 7FF#R4
+(1690000000.126000) vcan0 456##511223344556677889900AABB T
+(1690000000.127000) vcan0 20000080#0000000000000000
+
+  vcan0  123   [4]  DE AD BE EF
+(1690000000.128000)   can1  TX B E  1ABCDEF0  [12]  01 02 03 04 05 06 07 08 09 0A 0B 0C
+  vcan0  321   [2]  remote request
+"#;
+
+#[test]
+fn test_reader() -> anyhow::Result<()> {
+    let frames = CandumpReader::<_, CanMessage>::new(Cursor::new(LOG))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(frames.len(), 8);
+
+    assert_eq!(frames[0].timestamp(), 1_690_000_000_123);
+    assert_eq!(frames[0].channel(), "vcan0");
+    assert_eq!(frames[0].id(), CanId::Standard(0x123));
+    assert_eq!(frames[0].data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+    assert_eq!(frames[0].direct(), CanDirect::Receive);
+
+    assert!(frames[1].is_extended());
+    assert!(frames[1].data().is_empty());
+
+    assert!(frames[2].is_remote());
+    assert_eq!(frames[2].length(), 4);
+    assert_eq!(frames[2].channel(), "vcan1");
+
+    assert_eq!(frames[3].can_type(), CanType::CanFd);
+    assert!(frames[3].is_bitrate_switch());
+    assert!(!frames[3].is_esi());
+    assert_eq!(frames[3].length(), 12);
+    assert_eq!(frames[3].direct(), CanDirect::Transmit);
+
+    assert!(frames[4].is_error_frame());
+    assert_eq!(frames[4].id().into_bits(), 0x80);
+
+    assert_eq!(frames[5].timestamp(), 0);
+    assert_eq!(frames[5].data(), &[0xDE, 0xAD, 0xBE, 0xEF]);
+
+    assert_eq!(frames[6].can_type(), CanType::CanFd);
+    assert!(frames[6].is_bitrate_switch());
+    assert!(frames[6].is_esi());
+    assert_eq!(frames[6].id(), CanId::Extended(0x1ABC_DEF0));
+    assert_eq!(frames[6].direct(), CanDirect::Transmit);
+
+    assert!(frames[7].is_remote());
+    assert_eq!(frames[7].length(), 2);
+
+    assert!(candump::parse_line::<CanMessage>("(1690000000.1) vcan0 1234#00").is_err());
+    assert!(candump::parse_line::<CanMessage>("vcan0 123##1112233445566778899").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_round_trip() -> anyhow::Result<()> {
+    let mut frames = Vec::new();
+    let mut frame = CanMessage::new(0x123, &[0x01, 0x02, 0x03]).unwrap();
+    frame.set_timestamp(Some(1_690_000_000_001)).set_channel("vcan0".into());
+    frames.push(frame);
+    let mut frame = CanMessage::new(CanId::Extended(0x1FFF_FFFF), &[0x55; 64]).unwrap();
+    frame.set_bitrate_switch(true)
+        .set_timestamp(Some(1_690_000_000_002))
+        .set_channel("vcan1".into());
+    frames.push(frame);
+    let mut frame = CanMessage::new_remote(0x7FF, 8).unwrap();
+    frame.set_timestamp(Some(1_690_000_000_003)).set_channel("vcan0".into());
+    frames.push(frame);
+
+    for format in [Format::Log, Format::Compact] {
+        let mut buffer = Vec::new();
+        let mut writer = CandumpWriter::new(&mut buffer);
+        writer.set_format(format)
+            .set_extra_info(true);     // the flags of CAN-FD frames are only written by extra info in compact format
+        frames.iter().try_for_each(|f| writer.write_frame(f))?;
+        writer.flush()?;
+
+        let result = CandumpReader::<_, CanMessage>::new(Cursor::new(buffer))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(result, frames);
+        result.iter()
+            .zip(frames.iter())
+            .for_each(|(r, f)| {
+                assert_eq!(r.timestamp(), f.timestamp());
+                assert_eq!(r.channel(), f.channel());
+                assert_eq!(r.can_type(), f.can_type());
+                assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+            });
+    }
+
+    assert_eq!(
+        candump::format_line(&frames[0], Format::Log, true, false)?,
+        "(1690000000.001000) vcan0 123#010203"
+    );
+    assert_eq!(candump::format_frame(&frames[1])?, format!("1FFFFFFF##5{}", "55".repeat(64)));
+    assert_eq!(candump::format_frame(&frames[2])?, "7FF#R8");
+
+    let msg: CanMessage = "(1690000000.001000) vcan0 123#010203".parse()?;
+    assert_eq!(msg, frames[0]);
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use rs_can::ChannelConfig;
+use socketcan_rs::{netlink::{CanBerrCounter, CanBitTiming, CanCtrlMode, CanDeviceStats, CanInterface, CanLinkConfig, CanState, LinkMonitor, LinkStats, TERMINATION_ENABLED}, CTRL_MODE, SAMPLE_POINT};
+
+#[test]
+fn test_bit_timing() {
+    let timing = CanBitTiming { bitrate: 500_000, sample_point: 875, tq: 125, prop_seg: 6, phase_seg1: 7, phase_seg2: 2, sjw: 1, brp: 10 };
+    let data = timing.encode();
+    assert_eq!(data[..4], 500_000u32.to_ne_bytes());
+    assert_eq!(CanBitTiming::decode(&data), Some(timing));
+    assert_eq!(CanBitTiming::decode(&data[..16]), None);
+}
+
+#[test]
+fn test_link_config() -> anyhow::Result<()> {
+    let mut cfg = ChannelConfig::new(500_000);
+    cfg.set_data_bitrate(2_000_000)
+        .set_resistance(true)
+        .add_other(SAMPLE_POINT, Box::new(875u32))
+        .add_other(CTRL_MODE, Box::new(CanCtrlMode::BERR_REPORTING | CanCtrlMode::ONE_SHOT));
+    let config = CanLinkConfig::try_from(&cfg)?;
+    assert_eq!(config.bit_timing(), Some(CanBitTiming::new(500_000, Some(875))));
+    assert_eq!(config.data_bit_timing(), Some(CanBitTiming::new(2_000_000, None)));
+    assert_eq!(config.ctrl_flags(), CanCtrlMode::FD | CanCtrlMode::BERR_REPORTING | CanCtrlMode::ONE_SHOT);
+    assert_eq!(config.termination(), Some(TERMINATION_ENABLED));
+    assert_eq!(config.restart_ms(), None);
+
+    let mut config = CanLinkConfig::new();
+    assert!(config.is_empty());
+    config.set_ctrl_mode(CanCtrlMode::LISTEN_ONLY, false);
+    assert_eq!((config.ctrl_mask(), config.ctrl_flags()), (CanCtrlMode::LISTEN_ONLY, CanCtrlMode::empty()));
+    assert!(CanLinkConfig::try_from(&ChannelConfig::default())?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_interface() -> anyhow::Result<()> {
+    let iface = CanInterface::open("lo")?;
+    assert_eq!(iface.name(), "lo");
+    assert_eq!(iface.kind()?, None);
+    assert!(iface.is_up()?);
+    assert!(CanInterface::open("can-not-exist").is_err());
+
+    let status = iface.status()?;
+    assert_eq!((status.index, status.name.as_str()), (iface.index(), "lo"));
+    assert!(status.up);
+    assert_eq!(status.state, None);
+    assert_eq!(status.berr_counter, None);
+    assert_eq!(status.device_stats, None);
+    assert!(status.link_stats.is_some());
+    assert!(!status.is_bus_off());
+
+    Ok(())
+}
+
+#[test]
+fn test_status_decode() -> anyhow::Result<()> {
+    assert_eq!(CanState::try_from(3)?, CanState::BusOff);
+    assert!(CanState::try_from(6).is_err());
+    assert_eq!(CanState::ErrorPassive.to_string(), "ERROR-PASSIVE");
+
+    let data = [96u16.to_ne_bytes(), 128u16.to_ne_bytes()].concat();
+    assert_eq!(CanBerrCounter::decode(&data), Some(CanBerrCounter { tx_errors: 96, rx_errors: 128 }));
+    assert_eq!(CanBerrCounter::decode(&data[..2]), None);
+
+    let data = (1..=6u32).flat_map(|v| v.to_ne_bytes()).collect::<Vec<_>>();
+    assert_eq!(CanDeviceStats::decode(&data), Some(CanDeviceStats {
+        bus_error: 1, error_warning: 2, error_passive: 3, bus_off: 4, arbitration_lost: 5, restarts: 6,
+    }));
+    assert_eq!(CanDeviceStats::decode(&data[..20]), None);
+
+    let data = (0..24u64).flat_map(|v| v.to_ne_bytes()).collect::<Vec<_>>();
+    let stats = LinkStats::decode(&data).unwrap();
+    assert_eq!((stats.rx_packets, stats.tx_packets, stats.rx_dropped, stats.tx_dropped), (0, 1, 6, 7));
+    assert_eq!((stats.rx_over_errors, stats.rx_fifo_errors, stats.tx_fifo_errors), (11, 14, 18));
+    assert_eq!(LinkStats::decode(&data[..64]), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_link_monitor() -> anyhow::Result<()> {
+    let monitor = LinkMonitor::open()?;
+    assert!(monitor.recv(Some(10))?.iter().all(|e| !e.name().is_empty()));
+
+    let handle = monitor.spawn(|_| {});
+    assert!(handle.is_running());
+    handle.stop();
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "vcan")]
+
+use rs_can::{CanDevice, CanError, CanFrame};
+use socketcan_rs::{netlink::{CanInterface, VirtualKind, VirtualLink, VirtualLinkConfig, CANFD_MTU, CAN_MTU}, CanMessage, SocketCan};
+
+fn receive(sock: &SocketCan, iface: &str) -> Result<Vec<CanMessage>, CanError> {
+    loop {
+        match sock.receive(iface.to_string(), Some(100)) {
+            Ok(frames) => if !frames.is_empty() {
+                return Ok(frames);
+            },
+            Err(CanError::TimeoutError(_)) => {},
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[test]
+fn test_config() {
+    let config = VirtualLinkConfig::new();
+    assert_eq!((config.mtu(), config.netns(), config.up()), (None, &None, true));
+
+    let mut config = VirtualLinkConfig::new();
+    config.set_mtu(CANFD_MTU)
+        .set_netns("/proc/self/ns/net")
+        .set_up(false);
+    assert_eq!(config.mtu(), Some(CANFD_MTU));
+    assert_eq!(config.netns().as_deref(), Some("/proc/self/ns/net".as_ref()));
+    assert!(!config.up());
+    assert_eq!(VirtualKind::Vxcan.as_str(), "vxcan");
+}
+
+#[test]
+fn test_vcan() -> anyhow::Result<()> {
+    let name = "vcan-fixture";
+    let mut config = VirtualLinkConfig::new();
+    config.set_mtu(CANFD_MTU);
+    let link = VirtualLink::vcan(name, &config)?;
+    assert_eq!((link.kind(), link.name(), link.peer()), (VirtualKind::Vcan, name, None));
+    assert!(VirtualLink::vcan(name, &config).is_err());
+
+    let iface = CanInterface::open(name)?;
+    assert_eq!(iface.kind()?.as_deref(), Some("vcan"));
+    assert!(iface.is_up()?);
+    link.set_mtu(CAN_MTU)?;
+    assert!(iface.is_up()?);
+
+    drop(link);
+    assert!(CanInterface::open(name).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_vxcan() -> anyhow::Result<()> {
+    let (name, peer) = ("vxcan-fixture0", "vxcan-fixture1");
+    let mut config = VirtualLinkConfig::new();
+    // the current namespace is entered by a spawned thread
+    config.set_netns("/proc/self/ns/net");
+    let link = VirtualLink::vxcan(name, peer, &config)?;
+    assert_eq!(link.peer(), Some(peer));
+
+    let mut sock = SocketCan::new();
+    sock.init_channel(name, false)?;
+    sock.init_channel(peer, false)?;
+    let mut message = CanMessage::new(0x123, &[0x01, 0x02, 0x03]).unwrap();
+    message.set_channel(name.to_string());
+    sock.transmit(message, None)?;
+    let frames = receive(&sock, peer)?;
+    assert_eq!(frames[0].data(), [0x01, 0x02, 0x03]);
+    sock.shutdown();
+
+    link.delete()?;
+    assert!(CanInterface::open(name).is_err());
+    assert!(CanInterface::open(peer).is_err());
+
+    Ok(())
+}
@@ -15,6 +15,10 @@ mod linux;
 #[cfg(target_os = "linux")]
 pub use linux::ZCanDriver;
 
+/// The [`ZCanDriver`] which runs on the blocking threads of tokio.
+#[cfg(feature = "async")]
+pub type AsyncZCanDriver = rs_can::BlockingAdapter<ZCanDriver>;
+
 impl CanDevice for ZCanDriver {
     type Channel = u8;
     type Frame = CanMessage;
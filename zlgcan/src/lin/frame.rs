@@ -1,5 +1,5 @@
 use std::ffi::{c_uchar, c_ulong, c_ushort};
-use rs_can::CanError;
+use rs_can::{CanDirect, CanError, trace::LinMessage};
 use super::constant::{ZLinCheckSumMode, ZLinDataType};
 
 #[repr(C)]
@@ -110,6 +110,41 @@ impl ZLinFrame {
     }
 }
 
+/// Convert the data or error frame to [`LinMessage`] for trace files, the event frame is not supported.
+impl TryFrom<&ZLinFrame> for LinMessage {
+    type Error = CanError;
+    fn try_from(frame: &ZLinFrame) -> Result<Self, Self::Error> {
+        let direct = |dir: u8| if dir == 1 { CanDirect::Transmit } else { CanDirect::Receive };
+        match ZLinDataType::try_from(frame.data_type)? {
+            ZLinDataType::TypeData => {
+                let data = unsafe { frame.data.data };
+                let len = (data.rx_data.len as usize).min(data.rx_data.data.len());
+                Ok(Self {
+                    timestamp: data.rx_data.timestamp as u64 / 1000,
+                    channel: frame.chl as u32,
+                    id: data.pid & 0x3F,
+                    data: data.rx_data.data[..len].to_vec(),
+                    checksum: data.rx_data.chk_sum,
+                    direct: direct(data.rx_data.dir),
+                })
+            },
+            ZLinDataType::TypeError => {
+                let err = unsafe { frame.data.err };
+                let len = (err.len as usize).min(err.data.len());
+                Ok(Self {
+                    timestamp: err.timestamp as u64 / 1000,
+                    channel: frame.chl as u32,
+                    id: err.pid & 0x3F,
+                    data: err.data[..len].to_vec(),
+                    checksum: err.chk_sum,
+                    direct: direct(err.dir),
+                })
+            },
+            ZLinDataType::TypeEvent => Err(CanError::NotSupportedError),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
@@ -166,3 +201,55 @@ impl ZLinPublishEx {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use rs_can::{trace::{mdf::{MdfReader, MdfWriter}, LinMessage, TraceObject}, vcan::CanMessage, CanDirect};
+    use crate::lin::constant::ZLinDataType;
+    use super::{LinErrData, ZLinData, ZLinFrame, ZLinFrameDataUnion, ZLinRxData};
+
+    #[test]
+    fn lin_message_round_trip() -> anyhow::Result<()> {
+        let rx_data = ZLinRxData {
+            timestamp: 1_700_000_000_000_000,
+            len: 3,
+            dir: 1,
+            chk_sum: 0x5A,
+            data: [0x01, 0x02, 0x03, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            ..Default::default()
+        };
+        let frame = ZLinFrame::new(1, ZLinDataType::TypeData, ZLinFrameDataUnion::from_data(ZLinData { pid: 0xE2, rx_data, ..Default::default() }));
+        let message = LinMessage::try_from(&frame)?;
+        assert_eq!(message, LinMessage {
+            timestamp: 1_700_000_000_000,
+            channel: 1,
+            id: 0x22,
+            data: vec![0x01, 0x02, 0x03],
+            checksum: 0x5A,
+            direct: CanDirect::Transmit,
+        });
+
+        let err = LinErrData { timestamp: 1_700_000_000_001_000, pid: 0x10, len: 20, chk_sum: 0x33, ..Default::default() };
+        let frame = ZLinFrame::new(0, ZLinDataType::TypeError, ZLinFrameDataUnion::from_error(err));
+        let error = LinMessage::try_from(&frame)?;
+        assert_eq!((error.id, error.data.len(), error.direct), (0x10, 8, CanDirect::Receive));
+        assert!(LinMessage::try_from(&ZLinFrame::default_event()).is_err());
+
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut writer = MdfWriter::new(&mut buffer)?;
+            writer.write_lin(&message)?;
+            writer.write_lin(&error)?;
+        }
+        buffer.set_position(0);
+        let objects = MdfReader::<_, CanMessage>::new(buffer)?
+            .collect::<Result<Vec<_>, _>>()?;
+        match objects.as_slice() {
+            [TraceObject::Lin(v1), TraceObject::Lin(v2)] => assert_eq!((v1, v2), (&message, &error)),
+            _ => panic!("unexpected objects: {:?}", objects),
+        }
+
+        Ok(())
+    }
+}